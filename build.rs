@@ -0,0 +1,34 @@
+//! Embed the `git describe` output of the sources into sparrow as `SPARROW_GIT_DESCRIBE`, unless
+//! it is already given in the environment, e.g. by packaging that builds without a git checkout
+
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=SPARROW_GIT_DESCRIBE");
+    if let Ok(git_describe) = std::env::var("SPARROW_GIT_DESCRIBE") {
+        println!("cargo:rustc-env=SPARROW_GIT_DESCRIBE={git_describe}");
+        return;
+    }
+
+    // only describe our own checkout, not a repository the sources happen to be unpacked in
+    if !Path::new(".git").exists() {
+        return;
+    }
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let git_describe = Command::new("git")
+        .args(["describe", "--tags", "--always", "--dirty"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(git_describe) = git_describe {
+        println!(
+            "cargo:rustc-env=SPARROW_GIT_DESCRIBE={}",
+            git_describe.trim()
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use crate::host::rsync::SyncStats;
+use crate::host::{Host, RunID, RunOutputSyncOptions, SyncError};
+use crate::utils::format_size;
+use camino::Utf8Path as Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct SyncOutcome {
+    run_id: RunID,
+    result: Result<Option<SyncStats>, SyncError>,
+    duration: Duration,
+}
+
+/// Outcome of syncing several runs at once, one row per run
+pub struct BatchSyncReport {
+    outcomes: Vec<SyncOutcome>,
+}
+
+impl BatchSyncReport {
+    /// Sync `run_ids` from `host` with the options `options` gives for each run and at most
+    /// `parallelism` concurrent downloads, printing a line whenever the sync of a run finished
+    pub fn sync<O: Fn(&RunID) -> RunOutputSyncOptions + Sync>(
+        host: &dyn Host,
+        run_ids: Vec<RunID>,
+        local_base_path: &Path,
+        options: O,
+        parallelism: usize,
+    ) -> Self {
+        let run_count = run_ids.len();
+        let pending = Mutex::new(run_ids.into_iter());
+        let outcomes = Mutex::new(Vec::with_capacity(run_count));
+
+        std::thread::scope(|scope| {
+            for _ in 0..parallelism.clamp(1, run_count.max(1)) {
+                scope.spawn(|| loop {
+                    let Some(run_id) = pending.lock().unwrap().next() else {
+                        break;
+                    };
+
+                    let start = Instant::now();
+                    let result = host.sync(&run_id, local_base_path, &options(&run_id));
+                    let duration = start.elapsed();
+
+                    let mut outcomes = outcomes.lock().unwrap();
+                    println!(
+                        "[{}/{run_count}] {} {run_id}",
+                        outcomes.len() + 1,
+                        if result.is_ok() { "synced" } else { "failed" },
+                    );
+                    outcomes.push(SyncOutcome {
+                        run_id,
+                        result,
+                        duration,
+                    });
+                });
+            }
+        });
+
+        let mut outcomes = outcomes.into_inner().unwrap();
+        outcomes.sort_by(|lhs, rhs| lhs.run_id.name.cmp(&rhs.run_id.name));
+
+        Self { outcomes }
+    }
+
+    pub fn synced_runs(&self) -> impl Iterator<Item = &RunID> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.result.is_ok())
+            .map(|outcome| &outcome.run_id)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &SyncError> {
+        self.outcomes
+            .iter()
+            .filter_map(|outcome| outcome.result.as_ref().err())
+    }
+
+    pub fn failure_count(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.result.is_err())
+            .count()
+    }
+}
+
+impl std::fmt::Display for BatchSyncReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let run_width = self
+            .outcomes
+            .iter()
+            .map(|outcome| outcome.run_id.to_string().len())
+            .chain(std::iter::once("run".len()))
+            .max()
+            .unwrap_or(0);
+
+        writeln!(
+            f,
+            "{:<run_width$}  {:<6}  {:>11}  {:>8}",
+            "run", "status", "transferred", "time"
+        )?;
+        for outcome in &self.outcomes {
+            let (status, transferred) = match &outcome.result {
+                Ok(Some(stats)) => ("synced", format_size(stats.transferred_file_size)),
+                Ok(None) => ("synced", "-".to_owned()),
+                Err(_) => ("failed", "-".to_owned()),
+            };
+            writeln!(
+                f,
+                "{:<run_width$}  {status:<6}  {transferred:>11}  {:>7.1}s",
+                outcome.run_id.to_string(),
+                outcome.duration.as_secs_f64(),
+            )?;
+        }
+
+        for outcome in &self.outcomes {
+            if let Err(err) = &outcome.result {
+                writeln!(f, "{}: {err}", outcome.run_id)?;
+            }
+        }
+
+        Ok(())
+    }
+}
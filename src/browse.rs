@@ -0,0 +1,140 @@
+use crate::host::rsync::SyncOptions;
+use crate::host::{DirListingEntry, Host, RunID};
+use crate::utils::select_interactively;
+use anyhow::{Context, Result};
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+
+/// Files up to this size are printed in full when previewed, larger files are truncated
+const PREVIEW_MAX_SIZE: u64 = 64 * 1024;
+
+enum BrowseEntry {
+    Quit,
+    Parent,
+    Entry(DirListingEntry),
+}
+
+impl std::fmt::Display for BrowseEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrowseEntry::Quit => write!(f, "[quit]"),
+            BrowseEntry::Parent => write!(f, "../"),
+            BrowseEntry::Entry(entry) => write!(f, "{entry}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum FileAction {
+    Preview,
+    Download,
+    Back,
+}
+
+impl std::fmt::Display for FileAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileAction::Preview => write!(f, "preview"),
+            FileAction::Download => write!(f, "download"),
+            FileAction::Back => write!(f, "back"),
+        }
+    }
+}
+
+/// Interactively navigate the output directory of `run_id` on `host`, previewing and
+/// downloading individual files into the local run output directory below `local_base_path`
+pub fn browse_run(host: &dyn Host, run_id: &RunID, local_base_path: &Path) -> Result<()> {
+    let run_dir_path = run_id.path(host.output_base_dir_path());
+    let mut relative_dir_path = PathBuf::new();
+    loop {
+        let dir_path = run_dir_path.join(&relative_dir_path);
+        let entries = host
+            .list_dir(&dir_path)
+            .context(format!("failed to list {dir_path} on {}", host.id()))?;
+
+        let mut options = vec![BrowseEntry::Quit];
+        if relative_dir_path.parent().is_some() {
+            options.push(BrowseEntry::Parent);
+        }
+        options.extend(entries.into_iter().map(BrowseEntry::Entry));
+
+        let prompt = format!("{run_id}/{relative_dir_path}: ");
+        match select_interactively(&options, &prompt).context("failed to select an entry")? {
+            BrowseEntry::Quit => return Ok(()),
+            BrowseEntry::Parent => {
+                relative_dir_path.pop();
+            }
+            BrowseEntry::Entry(entry) if entry.is_dir => relative_dir_path.push(&entry.name),
+            BrowseEntry::Entry(entry) => {
+                let relative_file_path = relative_dir_path.join(&entry.name);
+                handle_file(host, run_id, &relative_file_path, local_base_path)?;
+            }
+        }
+    }
+}
+
+fn handle_file(
+    host: &dyn Host,
+    run_id: &RunID,
+    relative_file_path: &Path,
+    local_base_path: &Path,
+) -> Result<()> {
+    let file_path = run_id
+        .path(host.output_base_dir_path())
+        .join(relative_file_path);
+
+    let mut actions = vec![FileAction::Preview];
+    if !host.is_local() {
+        actions.push(FileAction::Download);
+    }
+    actions.push(FileAction::Back);
+
+    loop {
+        let action = *select_interactively(&actions, &format!("{relative_file_path}: "))
+            .context("failed to select an action")?;
+        match action {
+            FileAction::Preview => preview_file(host, &file_path)?,
+            FileAction::Download => {
+                let local_file_path = run_id.path(local_base_path).join(relative_file_path);
+                let local_parent_path = local_file_path
+                    .parent()
+                    .expect("expected downloaded file path to have a parent");
+                std::fs::create_dir_all(local_parent_path)
+                    .context(format!("failed to create {local_parent_path}"))?;
+
+                host.try_get(
+                    &file_path,
+                    local_parent_path,
+                    SyncOptions::default().progress(),
+                )
+                .context(format!("failed to download {relative_file_path}"))?;
+                println!("Downloaded {relative_file_path} to {local_file_path}");
+            }
+            FileAction::Back => return Ok(()),
+        }
+    }
+}
+
+fn preview_file(host: &dyn Host, file_path: &Path) -> Result<()> {
+    // read one more byte than shown, to know whether the file got truncated
+    let content = host
+        .read_file_head(file_path, PREVIEW_MAX_SIZE + 1)
+        .context(format!("failed to read {file_path} on {}", host.id()))?;
+    let is_truncated = content.len() as u64 > PREVIEW_MAX_SIZE;
+    let content = &content[..content.len().min(PREVIEW_MAX_SIZE as usize)];
+
+    let Ok(content) = std::str::from_utf8(content) else {
+        println!("{file_path} does not look like a text file, not previewing it");
+        return Ok(());
+    };
+
+    println!("------ {file_path} ------");
+    print!("{content}");
+    if !content.is_empty() && !content.ends_with('\n') {
+        println!();
+    }
+    if is_truncated {
+        println!("------ truncated after {PREVIEW_MAX_SIZE} bytes ------");
+    }
+
+    Ok(())
+}
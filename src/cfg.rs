@@ -1,17 +1,59 @@
 use camino::Utf8PathBuf as PathBuf;
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::time::Duration;
 use url::Url;
 
 #[derive(Deserialize)]
 pub struct GlobalConfig {
     pub run_group: String,
+    pub default_host: Option<String>,
+    pub resources: Option<HashMap<String, ResourceProfileConfig>>,
     pub payload: PayloadMappingConfig,
     pub remote_hosts: HashMap<String, RemoteHostConfig>,
+    #[serde(default)]
+    pub cloud_hosts: HashMap<String, CloudHostConfig>,
     pub local_host: LocalHostConfig,
     pub runner: Option<RunnerConfig>,
     pub run_output: RunOutputConfig,
+    pub notifications: Option<NotificationsConfig>,
+    pub review: Option<ReviewConfig>,
+    pub config_upload: Option<ConfigUploadConfig>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfigUploadConfig {
+    pub delete: Option<ConfigUploadDelete>,
+}
+
+/// What happens to files in the config directory of a run output which the uploaded config
+/// directory does not have, written as `true`, `false` or `backup` in the configuration
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(try_from = "String")]
+pub enum ConfigUploadDelete {
+    /// Delete them, so that the config directory matches the uploaded one
+    #[default]
+    Delete,
+    /// Keep them next to the uploaded files
+    Keep,
+    /// Move them, as well as replaced files, into `reproduce_info/config_backup/<time>`
+    Backup,
+}
+
+impl TryFrom<String> for ConfigUploadDelete {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "true" => Ok(ConfigUploadDelete::Delete),
+            "false" => Ok(ConfigUploadDelete::Keep),
+            "backup" => Ok(ConfigUploadDelete::Backup),
+            _ => Err(format!(
+                "invalid config upload delete mode `{value}', expected true, false or backup"
+            )),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -19,13 +61,71 @@ pub struct LocalCodeSourceConfig {
     pub path: PathBuf,
     pub gitignore_exclude_additions: Option<Vec<String>>,
     pub gitignore_exclude_subtractions: Option<Vec<String>>,
-    pub no_config_exclude: bool
+    pub copy_excludes_from: Option<Vec<ExcludeSource>>,
+    pub no_config_exclude: bool,
+    pub snapshot: Option<LocalSnapshotMode>,
+}
+
+/// What to upload of a local code source, where the git modes export a tree of the repository,
+/// which cannot change while it is uploaded, unlike the working tree
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LocalSnapshotMode {
+    /// The working tree as it is, including untracked and uncommitted changes
+    #[default]
+    Worktree,
+    /// The tree of the checked out commit
+    Head,
+    /// The tree of the index, i.e. the checked out commit with the staged changes
+    Staged,
+}
+
+/// Where to read excludes from when copying a local code source, written as `gitignore`,
+/// `sparrowignore`, `builtin` or `file:<path>` in the configuration
+#[derive(Deserialize, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub enum ExcludeSource {
+    Gitignore,
+    Sparrowignore,
+    Builtin,
+    File(PathBuf),
+}
+
+impl TryFrom<String> for ExcludeSource {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "gitignore" => Ok(ExcludeSource::Gitignore),
+            "sparrowignore" => Ok(ExcludeSource::Sparrowignore),
+            "builtin" => Ok(ExcludeSource::Builtin),
+            _ => match value.strip_prefix("file:") {
+                Some(path) if !path.is_empty() => Ok(ExcludeSource::File(PathBuf::from(path))),
+                _ => Err(format!(
+                    "invalid exclude source `{value}', expected `gitignore', `sparrowignore', \
+                    `builtin' or `file:<path>'"
+                )),
+            },
+        }
+    }
 }
 
 #[derive(Deserialize)]
 pub struct RemoteCodeSourceConfig {
     pub url: Url,
     pub revision: String,
+    pub submodules: Option<SubmoduleMode>,
+}
+
+/// Which submodules to check out when unpacking a revision of a remote code source
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SubmoduleMode {
+    None,
+    /// Only the submodules of the repository itself, but not their submodules
+    #[default]
+    Top,
+    Recursive,
 }
 
 #[derive(Deserialize)]
@@ -33,12 +133,60 @@ pub struct CodeMappingConfig {
     pub local: LocalCodeSourceConfig,
     pub remote: RemoteCodeSourceConfig,
     pub target: PathBuf,
+    pub preserve_permissions: Option<bool>,
+    pub chmod: Option<String>,
 }
 
 #[derive(Deserialize)]
+#[serde(try_from = "ConfigSourceEntrypoints")]
 pub struct ConfigSourceConfig {
     pub dir: PathBuf,
-    pub entrypoint: PathBuf,
+    /// Entrypoints which are reviewed and checked, where the first one is used to expand
+    /// `{config.<key>}` placeholders in run names
+    pub entrypoints: Vec<PathBuf>,
+}
+
+/// The config source as written in the configuration, with either a single `entrypoint` or a
+/// list of `entrypoints`
+#[derive(Deserialize)]
+struct ConfigSourceEntrypoints {
+    dir: PathBuf,
+    entrypoint: Option<PathBuf>,
+    entrypoints: Option<Vec<PathBuf>>,
+}
+
+impl TryFrom<ConfigSourceEntrypoints> for ConfigSourceConfig {
+    type Error = String;
+
+    fn try_from(value: ConfigSourceEntrypoints) -> Result<Self, Self::Error> {
+        let entrypoints = match (value.entrypoint, value.entrypoints) {
+            (Some(entrypoint), None) => vec![entrypoint],
+            (None, Some(entrypoints)) if !entrypoints.is_empty() => entrypoints,
+            (Some(_), Some(_)) => {
+                return Err(String::from(
+                    "payload.config requires either entrypoint or entrypoints, but got both",
+                ))
+            }
+            (None, _) => {
+                return Err(String::from(
+                    "payload.config requires an entrypoint or a non-empty list of entrypoints",
+                ))
+            }
+        };
+        if let Some(entrypoint) = entrypoints
+            .iter()
+            .find(|entrypoint| !entrypoint.is_relative())
+        {
+            return Err(format!(
+                "payload.config entrypoints are required to be relative, but got `{entrypoint}'"
+            ));
+        }
+
+        Ok(ConfigSourceConfig {
+            dir: value.dir,
+            entrypoints,
+        })
+    }
 }
 
 #[derive(Deserialize, Clone)]
@@ -46,6 +194,8 @@ pub struct AuxiliaryMappingConfig {
     pub path: PathBuf,
     pub target: PathBuf,
     pub excludes: Option<Vec<String>>,
+    pub preserve_permissions: Option<bool>,
+    pub chmod: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -53,6 +203,31 @@ pub struct PayloadMappingConfig {
     pub code: HashMap<String, CodeMappingConfig>,
     pub config: ConfigSourceConfig,
     pub auxiliary: Option<Vec<AuxiliaryMappingConfig>>,
+    pub bundles: Option<HashMap<String, PayloadBundleConfig>>,
+    /// Local commands which are run in order in the prepared payload directory before uploading
+    /// it, like compiling protobufs or building a wheel
+    pub build_steps: Option<Vec<String>>,
+    /// Dependency lock of the local python environment to record with every run
+    pub environment_lock: Option<EnvironmentLockSource>,
+}
+
+/// Where to capture the dependency lock of the local python environment from
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvironmentLockSource {
+    /// `uv.lock` or `poetry.lock` if either exists, the output of `pip freeze` otherwise
+    Auto,
+    Uv,
+    Poetry,
+    Pip,
+}
+
+/// Code and auxiliary mappings which are only uploaded for runs selecting the bundle via
+/// `--payload`, in addition to the ones defined directly under `payload`
+#[derive(Deserialize)]
+pub struct PayloadBundleConfig {
+    pub code: Option<HashMap<String, CodeMappingConfig>>,
+    pub auxiliary: Option<Vec<AuxiliaryMappingConfig>>,
 }
 
 #[derive(Deserialize)]
@@ -68,37 +243,318 @@ pub struct QuickRunConfig {
     pub node_local_storage_path: PathBuf,
 }
 
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExecutionMode {
+    #[default]
+    Tmux,
+    Nohup,
+    SystemdRun,
+    Screen,
+}
+
+/// Outcomes of the run script after which its tmux session stays open with a shell, to inspect
+/// the output, instead of ending with the run script
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TmuxKeepOpen {
+    #[default]
+    Always,
+    Failure,
+    Never,
+}
+
+/// How runs are executed on a host that executes them in tmux sessions but has no tmux installed
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TmuxFallback {
+    /// Refuse to launch runs on the host, explaining how to install tmux or choose another mode
+    #[default]
+    Error,
+    Nohup,
+    /// Execute runs in screen sessions if screen is installed and with nohup otherwise
+    Screen,
+}
+
+#[derive(Deserialize, Default)]
+pub struct TmuxConfig {
+    pub keep_open_on: Option<TmuxKeepOpen>,
+    pub fallback: Option<TmuxFallback>,
+}
+
 #[derive(Deserialize)]
 pub struct RemoteHostConfig {
     pub hostname: String,
+    pub aliases: Option<Vec<String>>,
     pub script_run_command_template: Option<String>,
     pub run_output_base_dir: PathBuf,
     pub temporary_dir: PathBuf,
+    pub temporary_run_dir_template: Option<String>,
+    pub deduplicate_auxiliary: Option<bool>,
+    pub execution: Option<ExecutionMode>,
+    pub tmux: Option<TmuxConfig>,
+    pub resources: Option<HashMap<String, ResourceProfileConfig>>,
     pub quick_run: QuickRunConfig,
+    /// Submissions are refused while this many runs are running on the host, and queued runs
+    /// wait for one of them to finish
+    pub max_concurrent_runs: Option<usize>,
+    pub modules: Option<HashMap<String, String>>,
+    pub auth: Option<HostAuthConfig>,
+    /// Template wrapping every command sparrow runs on the host, where `{}` is replaced by the
+    /// quoted command, e.g. `bash -l -c {}` for hosts that only set up modules and paths in login
+    /// shells
+    pub remote_command_wrapper: Option<String>,
+}
+
+/// Local commands that make ssh able to authenticate to a remote host, like `kinit` for
+/// clusters that require a Kerberos ticket
+#[derive(Deserialize)]
+pub struct HostAuthConfig {
+    /// Command run before connecting, which may prompt for passwords or second factors
+    pub pre_command: String,
+    /// Command that succeeds while the authentication is still valid, like `klist -s`, so that
+    /// `pre_command` only runs when needed; without it, `pre_command` runs on every connection
+    pub validity_check: Option<String>,
+}
+
+/// A named set of resources exposed as `resources` to templates, where profiles of a remote
+/// host take precedence over the global profiles of the same name
+#[derive(Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Default)]
+pub struct ResourceProfileConfig {
+    pub gpus: Option<u16>,
+    pub cpus: Option<u16>,
+    pub mem: Option<String>,
+    pub time: Option<String>,
+    pub partition: Option<String>,
+}
+
+/// Resources given to `run` on the command line, which take precedence over the same fields of
+/// the selected resource profile
+#[derive(Args, Default)]
+pub struct ResourceOverrides {
+    #[arg(
+        long,
+        help = "number of gpus of the run, overriding the resource profile"
+    )]
+    pub gpus: Option<u16>,
+
+    #[arg(
+        long,
+        help = "number of cpus of the run, overriding the resource profile"
+    )]
+    pub cpus: Option<u16>,
+
+    #[arg(
+        long,
+        help = "memory of the run, like 16G, overriding the resource profile"
+    )]
+    pub mem: Option<String>,
+
+    #[arg(
+        long,
+        help = "time limit of the run, like 2:00:00, overriding the resource profile"
+    )]
+    pub time: Option<String>,
+}
+
+/// Code mappings to leave out of the payload of a run, e.g. a large framework that did not change
+#[derive(Args, Default)]
+pub struct CodeSelectionArgs {
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "ID",
+        conflicts_with = "skip_code",
+        help = "comma separated list of the ids of the only code mappings to upload"
+    )]
+    pub only_code: Vec<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "ID",
+        help = "comma separated list of the ids of code mappings not to upload"
+    )]
+    pub skip_code: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct LocalLimitArgs {
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_size,
+        help = "memory the run may use at most, like 16G, killing it when it uses more\n\
+            (local host only)"
+    )]
+    pub local_mem: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "number of cpus the run may use at most (local host only)"
+    )]
+    pub local_cpus: Option<u16>,
+}
+
+/// A virtual machine provisioned on demand by `provision_command`, which has to print the ssh
+/// destination of the machine as its last line of output, and torn down by `teardown_command`,
+/// which finds the destination in `SPARROW_CLOUD_HOST`
+#[derive(Deserialize)]
+pub struct CloudHostConfig {
+    pub provision_command: String,
+    pub teardown_command: String,
+    pub script_run_command_template: Option<String>,
+    pub run_output_base_dir: PathBuf,
+    pub temporary_dir: PathBuf,
+    pub temporary_run_dir_template: Option<String>,
+    pub deduplicate_auxiliary: Option<bool>,
+    pub execution: Option<ExecutionMode>,
+    pub tmux: Option<TmuxConfig>,
+    pub modules: Option<HashMap<String, String>>,
+    pub remote_command_wrapper: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct LocalHostConfig {
     pub run_output_base_dir: PathBuf,
     pub script_run_command_template: Option<String>,
+    pub container_engine: Option<String>,
+    pub modules: Option<HashMap<String, String>>,
 }
 
 #[derive(Deserialize, Default)]
 pub struct RunnerConfig {
     pub config: Option<HashMap<String, String>>,
     pub environment_variable_transfer_requests: Option<Vec<String>>,
+    pub validate_run_script: Option<bool>,
+    pub prologue: Option<bool>,
+    pub env_files: Option<Vec<PathBuf>>,
 }
 
 #[derive(Deserialize)]
 pub struct RunOutputSyncOptions {
     pub result_excludes: Vec<String>,
     pub reproduce_excludes: Vec<String>,
+    pub metadata: Option<MetadataSyncConfig>,
+    /// Excludes of the run groups matching a glob like `rl-*`, by glob
+    pub groups: Option<HashMap<String, GroupSyncOptionsConfig>>,
+}
+
+/// Excludes replacing the global ones for the runs of some run groups, where unset excludes fall
+/// back to the global ones
+#[derive(Deserialize)]
+pub struct GroupSyncOptionsConfig {
+    pub result_excludes: Option<Vec<String>>,
+    pub reproduce_excludes: Option<Vec<String>>,
+}
+
+/// Which files `run-output-sync --content metadata` pulls, which have to match one of `patterns`
+/// and be no larger than `max_size`, given in the size format of rsync like `500K` or `2M`
+#[derive(Deserialize)]
+pub struct MetadataSyncConfig {
+    pub max_size: Option<String>,
+    pub patterns: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct NamedResultConfig {
+    pub path: PathBuf,
+    pub open_with: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ResultsConfig {
+    Paths(Vec<PathBuf>),
+    Named(HashMap<String, NamedResultConfig>),
 }
 
 #[derive(Deserialize)]
 pub struct RunOutputConfig {
     pub sync_options: RunOutputSyncOptions,
-    pub results: Vec<PathBuf>,
+    pub results: ResultsConfig,
+    pub metrics: Option<PathBuf>,
+    pub keep_payload: Option<bool>,
+    pub destinations: Option<HashMap<String, SyncDestinationConfig>>,
+    /// How long deleted runs are kept in the trash, e.g. `30d`
+    pub trash_retention: Option<String>,
+    pub permissions: Option<OutputPermissionsConfig>,
+}
+
+/// Permissions of the directories sparrow creates for a run output, e.g. to share the outputs of
+/// a project directory with a group
+#[derive(Deserialize, Clone)]
+pub struct OutputPermissionsConfig {
+    pub mode: Option<DirectoryMode>,
+    pub group: Option<String>,
+}
+
+/// Octal mode of a directory like `2775`, as understood by chmod
+#[derive(Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct DirectoryMode(pub String);
+
+impl TryFrom<String> for DirectoryMode {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if !(3..=4).contains(&value.len()) || !value.chars().all(|c| ('0'..='7').contains(&c)) {
+            return Err(format!(
+                "invalid directory mode `{value}', expected three or four octal digits"
+            ));
+        }
+        Ok(DirectoryMode(value))
+    }
+}
+
+/// Further place to push run outputs to after syncing them, e.g. institute mandated storage
+#[derive(Deserialize)]
+pub struct SyncDestinationConfig {
+    #[serde(default)]
+    pub backend: TransferBackend,
+    pub target: String,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransferBackend {
+    #[default]
+    Rsync,
+    Rclone,
+}
+
+#[derive(Deserialize)]
+pub struct NotificationsConfig {
+    pub desktop: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct ReviewConfig {
+    pub mode: Option<ReviewMode>,
+}
+
+/// How the config directory is opened for review before submission
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewMode {
+    /// Run `$EDITOR` in the current terminal, which also works over ssh and inside tmux
+    Inline,
+    /// Run `$EDITOR` in a new window of `$TERMINAL`, which is what configurations without a
+    /// review mode do
+    #[default]
+    Terminal,
+    /// Skip the review
+    None,
+}
+
+/// Per-user part of a shared team configuration in `.sparrow/users/<name>.yaml`, which is merged
+/// into the configuration like `.sparrow/private.yaml`
+#[derive(Deserialize)]
+pub struct UserOverlayConfig {
+    /// Values for `{<name>}` placeholders in `.sparrow/team.yaml`, e.g. account names or home
+    /// directories
+    pub variables: Option<HashMap<String, String>>,
 }
 
 #[derive(Parser)]
@@ -107,147 +563,1144 @@ pub struct Cli {
     #[arg(long)]
     pub print_completion: bool,
 
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        help = "use the configuration of .sparrow/users/<NAME>.yaml instead of the one of $USER"
+    )]
+    pub as_user: Option<String>,
+
     #[command(subcommand)]
     pub command: Option<RunnerCommandConfig>,
 }
 
-#[derive(Deserialize, ValueEnum, Clone, Debug, PartialEq)]
-pub enum RunOutputSyncContent {
-    Results,
-    NecessaryForReproduction,
-}
 #[derive(Subcommand)]
-pub enum RunnerCommandConfig {
-    Run {
-        #[arg(short = 'n', long)]
-        run_name: String,
+pub enum QueueCommandConfig {
+    Add {
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            required = true,
+            help = "arguments of `sparrow run' for the queued run"
+        )]
+        args: Vec<String>,
+    },
+    Start {
+        #[arg(short = 'j', long, default_value_t = 1)]
+        max_parallel: usize,
 
-        #[arg(short = 'g', long)]
-        run_group: Option<String>,
+        #[arg(
+            short = 'i',
+            long,
+            default_value = "1m",
+            value_parser = humantime::parse_duration,
+            help = "time between checks for finished runs"
+        )]
+        interval: Duration,
+
+        #[arg(
+            short = 'r',
+            long,
+            help = "also write the outcome of the submitted runs to this .md or JUnit .xml file"
+        )]
+        report: Option<PathBuf>,
+    },
+    List,
+}
 
-        #[arg(short = 'c', long, group = "config_source")]
-        config_dir: Option<PathBuf>,
+#[derive(Subcommand)]
+pub enum SweepCommandConfig {
+    #[command(about = "plan the runs of .sparrow/sweeps/<NAME>.yaml and submit them")]
+    Launch {
+        #[arg(help = "name of the sweep spec in .sparrow/sweeps")]
+        name: String,
 
-        #[arg(long, group = "config_source")]
-        use_previous_config: bool,
+        #[arg(short = 'p', long, help = "id of the host to submit the runs to")]
+        host: Option<String>,
 
         #[arg(
-            short = 'v',
+            short = 'q',
             long,
-            value_delimiter = ',',
-            help = "a comma seperated list of source ids from which we want to ignore the \
-                revision and use the current version in the local directory"
+            help = "add the runs to the queue instead of submitting them"
         )]
-        ignore_revisions: Vec<String>,
+        queue: bool,
 
         #[arg(
-            short = 'p',
+            short = 'y',
             long,
-            default_value = "local",
-            help = "host where to run, can be 'local' or the id of any of the\n\
-                remotes defined in the configuration"
+            help = "do not ask for confirmation of the planned runs"
         )]
-        host: String,
-
-        #[arg(short = 'q', long)]
-        enforce_quick: bool,
+        yes: bool,
+    },
+}
 
-        #[arg(long)]
-        no_config_review: bool,
+#[derive(Deserialize, ValueEnum, Clone, Debug, PartialEq)]
+pub enum RunOutputSyncContent {
+    Results,
+    NecessaryForReproduction,
+    /// Only small files like metrics, configs and logs
+    Metadata,
+}
 
-        #[arg(trailing_var_arg = true)]
-        remainder: Vec<String>,
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum RunSignal {
+    /// Ask the run to save its state and exit
+    Stop,
+    /// Ask the run to save its state and go on
+    Checkpoint,
+}
 
-        #[arg(long)]
-        only_print_run_script: bool,
+#[derive(Subcommand)]
+pub enum HostCommandConfig {
+    #[command(about = "show the state of a host before deciding where to submit")]
+    Status {
+        #[arg(
+            help = "'local' or the id or alias of any of the remotes defined in the configuration"
+        )]
+        host: String,
     },
-    RemotePrepareQuickRun {
+    #[command(about = "kill the tmux or screen sessions of runs that recorded a successful exit")]
+    CleanSessions {
+        #[arg(help = "the id or alias of any of the remotes defined in the configuration")]
+        host: String,
+    },
+    #[command(about = "remove the files of the content store of a host that no run links anymore")]
+    PruneStore {
+        #[arg(help = "the id or alias of any of the remotes defined in the configuration")]
+        host: String,
+
         #[arg(
-            short = 'p',
             long,
-            help = "host where to run, can be 'local' or the id of any of the\n\
-                remotes defined in the configuration"
+            default_value = "1d",
+            value_parser = humantime::parse_duration,
+            help = "only remove files that were last linked or unlinked at least this long ago,\n\
+                which keeps data pushed with push-data for runs that are yet to come"
+        )]
+        min_age: Duration,
+    },
+    #[command(about = "run a shell command on a host, like ssh but by the id of the host")]
+    Exec {
+        #[arg(
+            help = "'local' or the id or alias of any of the remotes defined in the configuration"
         )]
         host: String,
 
-        #[arg(short = 't', long)]
-        time: Option<String>,
+        #[arg(
+            long,
+            value_name = "LOCATION",
+            num_args = 0..=1,
+            default_missing_value = "output",
+            help = "select a run and execute the command in its output (the default) or in its\n\
+                run directory"
+        )]
+        in_run: Option<RunLocation>,
 
-        #[arg(short = 'c', long)]
-        cpu_count: Option<u16>,
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            required = true,
+            help = "command to execute, which is interpreted by the shell of the host"
+        )]
+        command: Vec<String>,
+    },
+}
 
-        #[arg(short = 'g', long)]
-        gpu_count: Option<u16>,
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum RunLocation {
+    Output,
+    Dir,
+}
 
-        #[arg(short = 's', long)]
-        constraint: Option<String>,
-    },
-    RemoteClearQuickRun {
+#[derive(Subcommand)]
+pub enum TrashCommandConfig {
+    #[command(about = "list the deleted runs in the trash of a host")]
+    List {
         #[arg(
             short = 'p',
             long,
-            help = "host where to run, can be 'local' or the id of any of the\n\
-                remotes defined in the configuration"
+            help = "'local' or the id of any of the remotes defined in the configuration"
         )]
         host: String,
     },
-    ListRuns {
+    #[command(about = "move a deleted run back from the trash of a host")]
+    Restore {
         #[arg(
             short = 'p',
             long,
-            default_value = "local",
-            help = "host from which to list runs, can be the id of any of the\n\
-                remotes defined in the configuration"
+            help = "'local' or the id of any of the remotes defined in the configuration"
         )]
         host: String,
-
-        #[arg(short = 'r', long)]
-        running: bool,
     },
-    RunAttach {
+    #[command(about = "permanently delete runs which are in the trash longer than the retention")]
+    Empty {
         #[arg(
             short = 'p',
             long,
-            help = "host to attach to, can be the id of any of the remotes defined\n\
-                in the configuration"
+            help = "'local' or the id of any of the remotes defined in the configuration"
         )]
         host: String,
 
-        #[arg(short = 'q', long)]
-        quick: bool,
+        #[arg(
+            long,
+            help = "delete all runs in the trash regardless of the retention"
+        )]
+        all: bool,
     },
-    RunOutputSync {
+}
+
+#[derive(Subcommand)]
+pub enum TemplateCommandConfig {
+    #[command(
+        about = "check the run script template and .sparrow/templates for syntax errors,\n\
+            undefined and unused variables"
+    )]
+    Check,
+}
+
+#[derive(Subcommand)]
+pub enum RunScriptCommandConfig {
+    #[command(about = "print the exact run script a run was submitted with")]
+    Show {
         #[arg(
             short = 'p',
             long,
-            help = "host from which to sync from, can be the id of any of the remotes\n\
+            help = "host on which the run is, can be 'local' or the id of any of the remotes\n\
                 defined in the configuration"
         )]
         host: String,
+    },
+}
 
-        #[arg(short = 'c', long, value_enum, default_value = "results")]
-        content: RunOutputSyncContent,
+#[derive(Subcommand)]
+pub enum ConfigCommandConfig {
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommandConfig,
+    },
+}
 
-        #[arg(short = 'r', long)]
-        show_results: bool,
+#[derive(Subcommand)]
+pub enum SnapshotCommandConfig {
+    Save {
+        name: String,
 
-        #[arg(short = 'f', long, help = "ignore .from_remote marker file")]
-        force: bool,
+        #[arg(long)]
+        no_review: bool,
     },
-    RunLog {
+    List,
+    #[command(about = "copy a snapshot back into the payload config directory")]
+    Use {
+        name: String,
+    },
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    #[arg(
+        short = 'n',
+        long,
+        required_unless_present = "reuse_run_dir",
+        help = "name of the run, may contain `{config.<key>}' placeholders, which are\n\
+            expanded from the first config entrypoint, and `{date}'"
+    )]
+    pub run_name: Option<String>,
+
+    #[arg(short = 'g', long)]
+    pub run_group: Option<String>,
+
+    #[arg(short = 'c', long, group = "config_source")]
+    pub config_dir: Option<PathBuf>,
+
+    #[arg(long, group = "config_source")]
+    pub use_previous_config: bool,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        group = "config_source",
+        help = "use the config snapshot NAME, which was reviewed when it was saved"
+    )]
+    pub config_snapshot: Option<String>,
+
+    #[arg(
+        short = 'v',
+        long,
+        value_delimiter = ',',
+        help = "a comma seperated list of source ids from which we want to ignore the \
+            revision and use the current version in the local directory"
+    )]
+    pub ignore_revisions: Vec<String>,
+
+    #[arg(
+        short = 'p',
+        long,
+        help = "host where to run, can be 'local' or the id or alias of any of the\n\
+            remotes defined in the configuration; defaults to default_host\n\
+            from the configuration or 'local' if that is not set"
+    )]
+    pub host: Option<String>,
+
+    #[arg(short = 'q', long)]
+    pub enforce_quick: bool,
+
+    #[arg(long)]
+    pub no_config_review: bool,
+
+    #[arg(trailing_var_arg = true)]
+    pub remainder: Vec<String>,
+
+    #[arg(
+        short = 'R',
+        long,
+        help = "name of the resource profile from the configuration to expose to\n\
+            the run script template; defaults to the profile `default' if there is one"
+    )]
+    pub resources: Option<String>,
+
+    #[command(flatten)]
+    pub resource_overrides: ResourceOverrides,
+
+    #[arg(
+        long = "payload",
+        value_name = "BUNDLE",
+        help = "payload bundle from the configuration to upload in addition to the\n\
+            default payload, can be given multiple times"
+    )]
+    pub payload_bundles: Vec<String>,
+
+    #[command(flatten)]
+    pub code_selection: CodeSelectionArgs,
+
+    #[arg(
+        short = 't',
+        long = "tag",
+        value_name = "KEY=VALUE",
+        value_parser = parse_key_value,
+        help = "tag to attach to the run, can be given multiple times"
+    )]
+    pub tags: Vec<(String, String)>,
+
+    #[arg(long)]
+    pub only_print_run_script: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "enforce_quick", "use_previous_config", "only_print_run_script", "detach",
+            "container", "local_mem", "local_cpus", "parent", "unpin_first"
+        ],
+        help = "prepare the payload, render the run script and review the config into\n\
+            .sparrow/staged without connecting to the host, to be uploaded and launched\n\
+            later with `sparrow push-staged'"
+    )]
+    pub stage_only: bool,
+
+    #[arg(
+        long,
+        help = "do not attach to the tmux session of remote runs after launching"
+    )]
+    pub detach: bool,
+
+    #[arg(long, help = "do not lock the project against concurrent submissions")]
+    pub no_lock: bool,
+
+    #[arg(
+        long,
+        value_name = "IMAGE",
+        help = "execute the run script inside a container of IMAGE (local host only)"
+    )]
+    pub container: Option<String>,
+
+    #[command(flatten)]
+    pub local_limits: LocalLimitArgs,
+
+    #[arg(
+        long,
+        value_name = "GROUP/NAME",
+        help = "run on the same host whose output, config and code versions to expose to\n\
+            the run script template as `parent', e.g. the training run of an evaluation"
+    )]
+    pub parent: Option<String>,
+
+    #[arg(
+        long,
+        requires = "parent",
+        help = "link the output of the parent run into the run directory as `parent'"
+    )]
+    pub link_parent: bool,
+
+    #[arg(long, help = "unpin the run first if it exists already and is pinned")]
+    pub unpin_first: bool,
+
+    #[arg(
+        short = 'y',
+        long,
+        help = "submit without showing a summary of the submission and asking for confirmation"
+    )]
+    pub yes: bool,
+
+    #[arg(
+        long,
+        value_name = "GROUP/NAME",
+        conflicts_with_all = [
+            "run_name", "run_group", "config_source", "ignore_revisions", "resources",
+            "gpus", "cpus", "mem", "time", "payload_bundles", "only_code", "skip_code",
+            "tags", "container", "local_mem", "local_cpus", "parent", "sweep_index",
+            "unpin_first"
+        ],
+        help = "execute the run script again in the existing run directory of the given run,\n\
+            without uploading code or config"
+    )]
+    pub reuse_run_dir: Option<String>,
+
+    #[arg(
+        long,
+        requires = "reuse_run_dir",
+        help = "re-render the run script and upload it to the reused run directory"
+    )]
+    pub update_run_script: bool,
+
+    #[arg(
+        long,
+        requires = "sweep_total",
+        help = "index of the run within its sweep or job array, starting at 0"
+    )]
+    pub sweep_index: Option<usize>,
+
+    #[arg(
+        long,
+        requires = "sweep_index",
+        help = "total number of runs in the sweep or job array of the run"
+    )]
+    pub sweep_total: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        value_parser = parse_key_value,
+        requires = "sweep_index",
+        help = "sweep parameter of the run, can be given multiple times"
+    )]
+    pub sweep_param: Vec<(String, String)>,
+}
+
+#[derive(Subcommand)]
+pub enum RunnerCommandConfig {
+    // boxed, since the arguments of `run` outweigh those of all other commands
+    #[command(about = "run the payload on a host")]
+    Run(Box<RunArgs>),
+    Reproduce {
         #[arg(
             short = 'p',
             long,
-            help = "host from which to show log output, can be the id of any of the\n\
-                remotes defined in the configuration"
+            help = "host where the run to reproduce is stored and where to run its\n\
+                reproduction, can be 'local' or the id or alias of any of the remotes\n\
+                defined in the configuration; defaults to default_host from the\n\
+                configuration or 'local' if that is not set"
         )]
-        host: String,
+        host: Option<String>,
 
         #[arg(short = 'q', long)]
-        quick_run: bool,
+        enforce_quick: bool,
+
+        #[arg(long, help = "do not lock the project against concurrent submissions")]
+        no_lock: bool,
+
+        #[arg(trailing_var_arg = true)]
+        remainder: Vec<String>,
+    },
+    CloudTeardown {
+        #[arg(short = 'p', long, help = "id of the cloud host to tear down")]
+        host: String,
+    },
+    Queue {
+        #[command(subcommand)]
+        command: QueueCommandConfig,
+    },
+    Sweep {
+        #[command(subcommand)]
+        command: SweepCommandConfig,
+    },
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommandConfig,
+    },
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommandConfig,
+    },
+    RunScript {
+        #[command(subcommand)]
+        command: RunScriptCommandConfig,
+    },
+    Host {
+        #[command(subcommand)]
+        command: HostCommandConfig,
+    },
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommandConfig,
+    },
+    Render {
+        #[arg(help = "jinja template to render to stdout")]
+        template: PathBuf,
+
+        #[arg(
+            short = 'r',
+            long,
+            value_name = "GROUP/NAME",
+            help = "render against the recorded context of this run instead of the context\n\
+                of a new run"
+        )]
+        run: Option<String>,
+
+        #[arg(
+            short = 'n',
+            long,
+            default_value = "unnamed",
+            conflicts_with = "run",
+            help = "name of the new run whose context to render against"
+        )]
+        run_name: String,
+
+        #[arg(
+            short = 'R',
+            long,
+            conflicts_with = "run",
+            help = "name of the resource profile from the configuration to expose to\n\
+                the template of a new run"
+        )]
+        resources: Option<String>,
+
+        #[arg(
+            short = 'p',
+            long,
+            help = "host of the run, can be 'local' or the id or alias of any of the remotes\n\
+                defined in the configuration; defaults to default_host from the\n\
+                configuration or 'local' if that is not set"
+        )]
+        host: Option<String>,
+    },
+    Context {
+        #[arg(
+            short = 'n',
+            long,
+            default_value = "unnamed",
+            help = "name of the new run whose template context to print"
+        )]
+        run_name: String,
+
+        #[arg(
+            short = 'R',
+            long,
+            help = "name of the resource profile from the configuration to expose to\n\
+                the template context"
+        )]
+        resources: Option<String>,
+
+        #[arg(
+            short = 'p',
+            long,
+            help = "host of the run, can be 'local' or the id or alias of any of the remotes\n\
+                defined in the configuration; defaults to default_host from the\n\
+                configuration or 'local' if that is not set"
+        )]
+        host: Option<String>,
+
+        #[arg(
+            long,
+            conflicts_with_all = ["run_name", "resources", "host"],
+            help = "print the JSON schema of the template context instead of an example"
+        )]
+        schema: bool,
+    },
+    RemotePrepareQuickRun {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host where to run, can be 'local' or the id of any of the\n\
+                remotes defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(short = 't', long)]
+        time: Option<String>,
+
+        #[arg(short = 'c', long)]
+        cpu_count: Option<u16>,
+
+        #[arg(short = 'g', long)]
+        gpu_count: Option<u16>,
+
+        #[arg(short = 's', long)]
+        constraint: Option<String>,
+
+        #[arg(
+            short = 'e',
+            long,
+            help = "report the expected queue wait and idle alternatives before allocating"
+        )]
+        estimate: bool,
+    },
+    RemoteQuickRunEstimate {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host where to estimate, can be the id of any of the\n\
+                remotes defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(short = 't', long)]
+        time: Option<String>,
+
+        #[arg(short = 'c', long)]
+        cpu_count: Option<u16>,
+
+        #[arg(short = 'g', long)]
+        gpu_count: Option<u16>,
+
+        #[arg(short = 's', long)]
+        constraint: Option<String>,
+    },
+    RemoteClearQuickRun {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host where to run, can be 'local' or the id of any of the\n\
+                remotes defined in the configuration"
+        )]
+        host: String,
+    },
+    ListRuns {
+        #[arg(
+            short = 'p',
+            long,
+            default_value = "local",
+            help = "host from which to list runs, can be the id of any of the\n\
+                remotes defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(short = 'r', long)]
+        running: bool,
+
+        #[arg(
+            short = 't',
+            long = "tag",
+            value_name = "KEY=VALUE",
+            value_parser = parse_key_value,
+            help = "only list runs with this tag, can be given multiple times"
+        )]
+        tags: Vec<(String, String)>,
+
+        #[arg(short = 'l', long, help = "show the notes of each run below it")]
+        long: bool,
+
+        #[arg(
+            long,
+            help = "show the submission time, queue wait, runtime and partition of each run and\n\
+                the mean queue wait per partition"
+        )]
+        timings: bool,
+    },
+    RunAttach {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host to attach to, can be the id of any of the remotes defined\n\
+                in the configuration"
+        )]
+        host: String,
+
+        #[arg(short = 'q', long)]
+        quick: bool,
+    },
+    RunOutputSync {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host from which to sync from, can be the id of any of the remotes\n\
+                defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(short = 'c', long, value_enum, default_value = "results")]
+        content: RunOutputSyncContent,
+
+        #[arg(short = 'r', long)]
+        show_results: bool,
+
+        #[arg(
+            short = 'a',
+            long,
+            requires = "group",
+            conflicts_with_all = ["show_results", "watch"],
+            help = "sync all runs of --group which carry the given tags instead of selecting one"
+        )]
+        all: bool,
+
+        #[arg(
+            short = 'g',
+            long,
+            requires = "all",
+            help = "group of the runs to sync with --all"
+        )]
+        group: Option<String>,
+
+        #[arg(
+            short = 'm',
+            long,
+            conflicts_with_all = ["all", "show_results", "watch"],
+            help = "select several runs to sync interactively instead of one"
+        )]
+        multi: bool,
+
+        #[arg(
+            short = 'j',
+            long,
+            default_value_t = 4,
+            help = "maximal number of concurrent downloads with --all or --multi"
+        )]
+        jobs: usize,
+
+        #[arg(short = 'f', long, help = "ignore .from_remote marker file")]
+        force: bool,
+
+        #[arg(
+            short = 'w',
+            long,
+            help = "keep syncing while the run is still running, retrying failed transfers"
+        )]
+        watch: bool,
+
+        #[arg(
+            short = 't',
+            long = "tag",
+            value_name = "KEY=VALUE",
+            value_parser = parse_key_value,
+            help = "only offer runs with this tag for selection, can be given multiple times"
+        )]
+        tags: Vec<(String, String)>,
+
+        #[arg(
+            short = 'i',
+            long,
+            default_value = "10m",
+            value_parser = humantime::parse_duration,
+            help = "time between syncs when watching"
+        )]
+        interval: Duration,
+
+        #[arg(
+            short = 'd',
+            long = "dest",
+            value_name = "NAME",
+            help = "also push the synced run output to this destination from\n\
+                `run_output.destinations'"
+        )]
+        destination: Option<String>,
+
+        #[arg(
+            long,
+            help = "tear down the cloud host after syncing, refused while any of its runs has not \
+                finished"
+        )]
+        teardown: bool,
+    },
+    #[command(about = "copy the output of a run from one remote host to another")]
+    RunOutputTransfer {
+        #[arg(
+            long,
+            help = "host from which to copy the run, can be the id of any of the remotes\n\
+                defined in the configuration"
+        )]
+        from: String,
+
+        #[arg(
+            long,
+            help = "host to which to copy the run, can be the id of any of the remotes\n\
+                defined in the configuration"
+        )]
+        to: String,
+
+        #[arg(
+            short = 'm',
+            long = "move",
+            help = "move the run output on the source host into its trash once it is copied"
+        )]
+        move_output: bool,
+
+        #[arg(
+            long,
+            help = "relay the run output through the local machine instead of trying to copy\n\
+                it directly from the source host first"
+        )]
+        relay: bool,
+    },
+    RunBrowse {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host on which to browse run outputs, can be 'local' or the id of any of\n\
+                the remotes defined in the configuration"
+        )]
+        host: String,
+    },
+    PushStaged {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host to push staged runs to, can be the id of any of the remotes defined in\n\
+                the configuration"
+        )]
+        host: String,
+
+        #[arg(
+            short = 'r',
+            long,
+            value_name = "GROUP/NAME",
+            help = "staged run to push instead of selecting staged runs interactively"
+        )]
+        run: Option<String>,
+
+        #[arg(long, help = "do not attach to the pushed run after launching it")]
+        detach: bool,
+    },
+    #[command(
+        about = "upload the auxiliary mappings into the content store of a host, so that later\n\
+            runs link them instead of uploading them"
+    )]
+    PushData {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host to push the data to, can be the id of any of the remotes defined in the\n\
+                configuration"
+        )]
+        host: String,
+
+        #[arg(
+            long = "payload",
+            value_name = "BUNDLE",
+            help = "payload bundle from the configuration whose auxiliary mappings to push in\n\
+                addition to the default ones, can be given multiple times"
+        )]
+        payload_bundles: Vec<String>,
+    },
+    Lineage {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host whose runs to trace, can be 'local' or the id of any of the remotes\n\
+                defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(
+            short = 'r',
+            long,
+            value_name = "GROUP/NAME",
+            help = "run whose lineage to show instead of selecting one interactively"
+        )]
+        run: Option<String>,
+
+        #[arg(long, help = "print the lineage as graphviz graph instead of a tree")]
+        dot: bool,
+    },
+    RunLog {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host from which to show log output, can be the id of any of the\n\
+                remotes defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(short = 'q', long)]
+        quick_run: bool,
 
         #[arg(short = 'f', long)]
         follow: bool,
+
+        #[arg(
+            short = 'm',
+            long,
+            conflicts_with = "follow",
+            help = "continuously copy all log files of the run into its local run output\n\
+                until the run finished, instead of showing one of them"
+        )]
+        mirror: bool,
+
+        #[arg(
+            short = 'i',
+            long,
+            requires = "mirror",
+            default_value = "5s",
+            value_parser = humantime::parse_duration,
+            help = "time between copies when mirroring"
+        )]
+        interval: Duration,
+    },
+    #[command(about = "report the progress of a snakemake based run from its snakemake log")]
+    RunProgress {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host on which the run is, can be 'local' or the id of any of the remotes\n\
+                defined in the configuration"
+        )]
+        host: String,
+    },
+    RunPin {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host on which the run is, can be 'local' or the id of any of the remotes\n\
+                defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(long, help = "remove the pin from the run instead")]
+        unpin: bool,
     },
-    ShowResults {},
+    #[command(about = "append a timestamped note to reproduce_info/notes.md of a run")]
+    RunNote {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host on which the run is, can be 'local' or the id of any of the remotes\n\
+                defined in the configuration"
+        )]
+        host: String,
+
+        note: String,
+    },
+    #[command(about = "rename a run or move it to another run group")]
+    RunRename {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host on which the run is, can be 'local' or the id of any of the remotes\n\
+                defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(
+            short = 'n',
+            long,
+            required_unless_present = "group",
+            help = "new name of the run, defaults to its current name"
+        )]
+        name: Option<String>,
+
+        #[arg(
+            short = 'g',
+            long,
+            help = "run group to move the run to, defaults to its current group"
+        )]
+        group: Option<String>,
+    },
+    #[command(about = "print the runs recently submitted or selected on each host")]
+    Recent {
+        #[arg(
+            short = 'p',
+            long,
+            help = "only print the runs of this host, can be 'local' or the id of any of the\n\
+                remotes defined in the configuration"
+        )]
+        host: Option<String>,
+
+        #[arg(
+            short = 'n',
+            long,
+            default_value_t = 10,
+            help = "number of runs to print per host"
+        )]
+        count: usize,
+    },
+    #[command(about = "ask a cooperative run to checkpoint or stop gracefully")]
+    RunSignal {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host on which the run is, can be 'local' or the id of any of the remotes\n\
+                defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(short = 's', long)]
+        signal: RunSignal,
+
+        #[arg(
+            long,
+            help = "send the POSIX signal of the request to the process group of the run\n\
+                instead of writing it to the control file in the run output"
+        )]
+        posix: bool,
+    },
+    #[command(about = "move run outputs into the trash of their host")]
+    RunDelete {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host on which the runs are, can be 'local' or the id of any of the remotes\n\
+                defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(
+            short = 't',
+            long = "tag",
+            value_name = "KEY=VALUE",
+            value_parser = parse_key_value,
+            help = "only offer runs with this tag for selection, can be given multiple times"
+        )]
+        tags: Vec<(String, String)>,
+    },
+    RunExport {
+        #[arg(
+            short = 'p',
+            long,
+            default_value = "local",
+            help = "host from which to export, can be 'local' or the id of any of the\n\
+                remotes defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(
+            short = 'o',
+            long,
+            help = "destination of the bundle, defaults to <run-group>-<run-name> in the\n\
+                current directory"
+        )]
+        output: Option<PathBuf>,
+
+        #[arg(short = 'a', long, help = "pack the bundle into a .tar.gz archive")]
+        archive: bool,
+    },
+    SweepReport {
+        #[arg(
+            short = 'p',
+            long,
+            default_value = "local",
+            help = "host from which to collect metrics, can be 'local' or the id of any of\n\
+                the remotes defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(short = 'g', long)]
+        group: Option<String>,
+
+        #[arg(
+            short = 'm',
+            long,
+            help = "metrics file relative to the run output directory, defaults to\n\
+                run_output.metrics from the configuration"
+        )]
+        metrics: Option<PathBuf>,
+
+        #[arg(short = 's', long)]
+        sort_by: Option<String>,
+
+        #[arg(short = 'd', long)]
+        descending: bool,
+
+        #[arg(
+            short = 'o',
+            long,
+            help = "additionally write the report to a .csv or .md file"
+        )]
+        output: Option<PathBuf>,
+    },
+    Usage {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host whose slurm accounting to query, can be the id of any of the remotes\n\
+                defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(
+            short = 's',
+            long,
+            default_value = "30d",
+            value_parser = humantime::parse_duration,
+            help = "only account jobs that started within this time"
+        )]
+        since: Duration,
+    },
+    RunDu {
+        #[arg(
+            short = 'p',
+            long,
+            help = "host whose run outputs to measure, can be 'local' or the id of any of the\n\
+                remotes defined in the configuration"
+        )]
+        host: String,
+
+        #[arg(short = 'g', long, help = "only measure the runs of this group")]
+        group: Option<String>,
+
+        #[arg(
+            long,
+            help = "measure all runs again instead of using sizes cached within the last hour"
+        )]
+        refresh: bool,
+    },
+    ShowResults {
+        #[arg(
+            short = 'r',
+            long,
+            value_name = "HOST",
+            help = "show results of a run on the given remote host, downloading only the\n\
+                selected results into a local cache directory first"
+        )]
+        remote: Option<String>,
+
+        #[arg(
+            short = 'd',
+            long,
+            help = "open the directories containing the selected results"
+        )]
+        directory: bool,
+    },
+}
+
+/// Parse a size like `16G` with an optional binary unit `K`, `M`, `G` or `T` into bytes
+fn parse_size(size: &str) -> Result<u64, String> {
+    let (number, unit_factor) = match size.trim().char_indices().last() {
+        Some((index, unit)) if unit.is_ascii_alphabetic() => {
+            let exponent = match unit.to_ascii_uppercase() {
+                'K' => 1,
+                'M' => 2,
+                'G' => 3,
+                'T' => 4,
+                _ => {
+                    return Err(format!(
+                        "unknown unit `{unit}' in `{size}', expected K, M, G or T"
+                    ))
+                }
+            };
+            (&size.trim()[..index], 1024u64.pow(exponent))
+        }
+        _ => (size.trim(), 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(unit_factor))
+        .filter(|bytes| *bytes > 0)
+        .ok_or(format!("expected `{size}' to be a positive size like 16G"))
+}
+
+fn parse_key_value(pair: &str) -> Result<(String, String), String> {
+    match pair.split_once('=') {
+        Some((key, value)) if !key.is_empty() && !key.contains(' ') => {
+            Ok((key.to_owned(), value.to_owned()))
+        }
+        _ => Err(format!("expected `{pair}' to be given as KEY=VALUE")),
+    }
 }
@@ -0,0 +1,76 @@
+use crate::cfg::RunSignal;
+use crate::host::rsync::SyncOptions;
+use crate::host::{Host, RunID, CONTROL_FILE_NAME};
+use crate::utils::Utf8Path;
+use anyhow::{anyhow, Context, Result};
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use std::collections::BTreeMap;
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+const RUN_SIGNALS: [RunSignal; 2] = [RunSignal::Stop, RunSignal::Checkpoint];
+
+impl RunSignal {
+    /// Name of the signal as written to the control file
+    fn name(self) -> &'static str {
+        match self {
+            RunSignal::Stop => "stop",
+            RunSignal::Checkpoint => "checkpoint",
+        }
+    }
+
+    /// POSIX signal sent for the signal, where `TERM` is also what schedulers send before
+    /// killing a job, so that runs handling it stop gracefully in both cases
+    fn posix_signal(self) -> &'static str {
+        match self {
+            RunSignal::Stop => "TERM",
+            RunSignal::Checkpoint => "USR1",
+        }
+    }
+}
+
+/// Control part of the template context
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct ControlContext {
+    /// Control file of the run output, to which `sparrow run-signal` writes signal names
+    #[schemars(with = "String")]
+    file_path: PathBuf,
+    /// POSIX signal sent by `sparrow run-signal --posix` for each signal name
+    signals: BTreeMap<&'static str, &'static str>,
+}
+
+/// Control part of the template context, with the path of the control file of the run output at
+/// `output_path` and the POSIX signal of each signal name
+pub fn template_context(output_path: &Path) -> ControlContext {
+    ControlContext {
+        file_path: output_path.join(CONTROL_FILE_NAME),
+        signals: RUN_SIGNALS
+            .iter()
+            .map(|signal| (signal.name(), signal.posix_signal()))
+            .collect(),
+    }
+}
+
+/// Ask `run_id` to act on `signal` by writing its name to the control file of the run, which
+/// cooperative run code polls, or by sending its POSIX signal to the process group of the run
+pub fn signal_run(host: &dyn Host, run_id: &RunID, signal: RunSignal, posix: bool) -> Result<()> {
+    if posix {
+        let process_group = host.recorded_process_group(run_id).ok_or(anyhow!(
+            "found no recorded process group of {run_id}, it might not have started yet"
+        ))?;
+        return host.signal_process_group(process_group, signal.posix_signal());
+    }
+
+    let mut control_file =
+        NamedTempFile::new().context("failed to create temporary control file")?;
+    control_file
+        .write_all(format!("{}\n", signal.name()).as_bytes())
+        .context("failed to write control file")?;
+    host.put(
+        control_file.utf8_path(),
+        &host.control_file_destination_path(run_id),
+        SyncOptions::default(),
+    );
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use crate::host::manifest::Manifest;
+use crate::host::{prepare_auxiliary_mappings, Host};
+use crate::payload::AuxiliaryMapping;
+use crate::utils::{format_size, Utf8Path};
+use anyhow::{bail, Context, Result};
+use tempfile::TempDir;
+
+/// Upload the files of `auxiliary_mappings` into the content store of `host` without submitting a
+/// run, so that runs submitted later link them from the store instead of uploading them
+pub fn push_data(host: &dyn Host, auxiliary_mappings: &[AuxiliaryMapping]) -> Result<()> {
+    if auxiliary_mappings.is_empty() {
+        bail!("found no auxiliary mappings to push in the payload configuration");
+    }
+    // check for the store before copying what may be a lot of data
+    let Some(store_path) = host.content_store_path() else {
+        bail!(
+            "{} has no content store to push data to, set `deduplicate_auxiliary: true' in its \
+            configuration",
+            host.id()
+        );
+    };
+
+    println!("Collecting auxiliary mappings from...");
+    for auxiliary_mapping in auxiliary_mappings {
+        println!(
+            "    {}: {}",
+            auxiliary_mapping.target_path, auxiliary_mapping.source_path
+        );
+    }
+    let data_dir = TempDir::new().context("failed to create temporary data directory")?;
+    prepare_auxiliary_mappings(auxiliary_mappings, data_dir.utf8_path());
+    let manifest = Manifest::build(data_dir.utf8_path())
+        .context("failed to build the manifest of the auxiliary mappings")?;
+    let files = manifest.files.iter().collect::<Vec<_>>();
+
+    println!(
+        "Pushing {} files ({}) to {store_path} on {}...",
+        files.len(),
+        format_size(files.iter().map(|file| file.size).sum()),
+        host.id()
+    );
+    let stats = host
+        .push_to_content_store(data_dir.utf8_path(), &files)
+        .context(format!("failed to push data to {}", host.id()))?;
+    if let Some(stats) = stats {
+        println!(
+            "Transferred {}, the rest was stored already",
+            format_size(stats.transferred_file_size)
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+/// Exit codes of sparrow, so that wrapper scripts can branch on the kind of failure; failures
+/// are tagged with their exit status as context or root error and `main` exits with the
+/// outermost tag, or with 1 if a failure is not tagged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitStatus {
+    Failure = 1,
+    Config = 2,
+    Connection = 3,
+    Submission = 4,
+    Aborted = 5,
+    SyncConflict = 6,
+}
+
+impl ExitStatus {
+    pub fn of(err: &anyhow::Error) -> ExitStatus {
+        err.downcast_ref::<ExitStatus>()
+            .copied()
+            .unwrap_or(ExitStatus::Failure)
+    }
+
+    pub fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
+
+impl std::fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExitStatus::Failure => write!(f, "failed"),
+            ExitStatus::Config => write!(f, "invalid configuration"),
+            ExitStatus::Connection => write!(f, "failed to connect"),
+            ExitStatus::Submission => write!(f, "failed to submit"),
+            ExitStatus::Aborted => write!(f, "aborted"),
+            ExitStatus::SyncConflict => write!(f, "sync conflict"),
+        }
+    }
+}
+
+impl std::error::Error for ExitStatus {}
+
+pub trait WithExitStatus<T> {
+    /// Tag the error with `status`, unless it was already tagged more specifically
+    fn exit_status(self, status: ExitStatus) -> anyhow::Result<T>;
+}
+
+impl<T> WithExitStatus<T> for anyhow::Result<T> {
+    fn exit_status(self, status: ExitStatus) -> anyhow::Result<T> {
+        self.map_err(|err| {
+            if err.downcast_ref::<ExitStatus>().is_some() {
+                err
+            } else {
+                err.context(status)
+            }
+        })
+    }
+}
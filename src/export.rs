@@ -0,0 +1,103 @@
+use crate::host::rsync::SyncOptions;
+use crate::host::{Host, RunID};
+use crate::utils::Utf8Path;
+use anyhow::{bail, Context, Result};
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use tempfile::TempDir;
+
+/// Assemble a portable bundle of a run, consisting of the given results and the complete
+/// `reproduce_info` directory, either as a plain directory or as a `.tar.gz` archive
+pub fn export_run(
+    host: &dyn Host,
+    run_id: &RunID,
+    result_paths: &Vec<&Path>,
+    destination_path: &Path,
+    archive: bool,
+) -> Result<()> {
+    if destination_path.exists() {
+        bail!("refusing to export to {destination_path}, since it already exists");
+    }
+
+    let staging_dir = TempDir::new().context("failed to create temporary staging directory")?;
+    let bundle_path = if archive {
+        staging_dir.utf8_path().join(run_id.name.as_str())
+    } else {
+        destination_path.to_owned()
+    };
+    std::fs::create_dir_all(&bundle_path).context(format!("failed to create {bundle_path}"))?;
+
+    println!("Collecting reproduce info of {run_id}...");
+    host.try_get(
+        &host.reproduce_info_path(run_id),
+        &bundle_path,
+        SyncOptions::default(),
+    )
+    .context(format!("failed to collect the reproduce info of {run_id}"))?;
+
+    let run_path = run_id.path(host.output_base_dir_path());
+    for result_path in result_paths {
+        println!("Collecting {result_path}...");
+        let bundle_result_path = bundle_path.join(result_path);
+        let bundle_result_parent_path = bundle_result_path
+            .parent()
+            .expect("expected result path to have a parent");
+        std::fs::create_dir_all(bundle_result_parent_path)
+            .context(format!("failed to create {bundle_result_parent_path}"))?;
+
+        host.try_get(
+            &run_path.join(result_path),
+            bundle_result_parent_path,
+            SyncOptions::default(),
+        )
+        .context(format!("failed to collect {result_path} of {run_id}"))?;
+    }
+
+    if archive {
+        pack_archive(&bundle_path, destination_path).context(format!(
+            "failed to pack {bundle_path} into {destination_path}"
+        ))?;
+    }
+
+    println!("Exported {run_id} to {destination_path}");
+    Ok(())
+}
+
+pub fn default_export_path(run_id: &RunID, archive: bool) -> PathBuf {
+    let name = format!("{}-{}", run_id.group, run_id.name);
+    if archive {
+        PathBuf::from(format!("{name}.tar.gz"))
+    } else {
+        PathBuf::from(name)
+    }
+}
+
+fn pack_archive(bundle_path: &Path, archive_path: &Path) -> Result<()> {
+    let archive_path = camino::absolute_utf8(archive_path).context(format!(
+        "failed to convert {archive_path} to an absolute path"
+    ))?;
+    let bundle_parent_path = bundle_path
+        .parent()
+        .expect("expected bundle path to have a parent");
+    let bundle_name = bundle_path
+        .file_name()
+        .expect("expected bundle path to have a file name");
+
+    let mut tar_command = std::process::Command::new("tar");
+    tar_command
+        .arg("--create")
+        .arg("--gzip")
+        .arg("--file")
+        .arg(&archive_path)
+        .arg("--directory")
+        .arg(bundle_parent_path)
+        .arg(bundle_name);
+
+    let status = tar_command
+        .status()
+        .context(format!("failed to run `{tar_command:?}'"))?;
+    if !status.success() {
+        bail!("`{tar_command:?}' exited unsuccessfully");
+    }
+
+    Ok(())
+}
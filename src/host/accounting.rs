@@ -0,0 +1,50 @@
+use camino::Utf8PathBuf as PathBuf;
+use std::time::Duration;
+
+/// Fields requested from `sacct`, in the order in which [`AccountedJob::parse`] expects them
+pub const SACCT_FORMAT: &str = "WorkDir,ElapsedRaw,AllocCPUS,AllocTRES,ConsumedEnergyRaw";
+
+/// A slurm job as recorded by the accounting of the cluster
+pub struct AccountedJob {
+    pub work_dir: PathBuf,
+    pub elapsed: Duration,
+    pub cpu_count: u64,
+    pub gpu_count: u64,
+    /// Consumed energy in joules, if the cluster gathers it
+    pub energy: Option<u64>,
+}
+
+impl AccountedJob {
+    /// Parse the output of `sacct --parsable2 --noheader --format` [`SACCT_FORMAT`], skipping
+    /// lines that do not describe a job
+    pub fn parse(output: &str) -> Vec<AccountedJob> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let fields = line.split('|').collect::<Vec<_>>();
+                let [work_dir, elapsed, cpu_count, tres, energy] = fields[..] else {
+                    return None;
+                };
+
+                Some(AccountedJob {
+                    work_dir: PathBuf::from(work_dir),
+                    elapsed: Duration::from_secs(elapsed.parse().ok()?),
+                    cpu_count: cpu_count.parse().unwrap_or(0),
+                    gpu_count: Self::parse_gpu_count(tres),
+                    // sacct reports zero if energy is not gathered
+                    energy: energy.parse().ok().filter(|&energy| energy > 0),
+                })
+            })
+            .collect()
+    }
+
+    /// Sum the gpus in trackable resources like `cpu=4,gres/gpu=1,gres/gpu:a100=1,mem=16G`,
+    /// where typed gpus are skipped since they are also counted untyped
+    fn parse_gpu_count(tres: &str) -> u64 {
+        tres.split(',')
+            .filter_map(|resource| resource.split_once('='))
+            .filter(|(name, _)| *name == "gres/gpu")
+            .filter_map(|(_, count)| count.parse::<u64>().ok())
+            .sum()
+    }
+}
@@ -0,0 +1,43 @@
+use crate::cfg::HostAuthConfig;
+use anyhow::{bail, Context, Result};
+use std::process::{ExitStatus, Stdio};
+
+fn run_locally(command: &str, interactive: bool) -> Result<ExitStatus> {
+    let mut cmd = std::process::Command::new(std::env::var("SHELL").unwrap_or(String::from("sh")));
+    cmd.arg("-c").arg(command);
+    if !interactive {
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+    }
+    cmd.status().context(format!("failed to run `{command}`"))
+}
+
+/// Make sure that ssh can authenticate to the host `host_id` before connecting to it, by running
+/// `pre_command` of `auth` locally, e.g. `kinit`, unless `validity_check` of `auth` succeeds
+/// already; `pre_command` gets the terminal, so that it can prompt for passwords or second
+/// factors
+pub fn authenticate(host_id: &str, auth: &HostAuthConfig) -> Result<()> {
+    if let Some(validity_check) = &auth.validity_check {
+        if run_locally(validity_check, false)?.success() {
+            return Ok(());
+        }
+    }
+
+    println!("Authenticating for {host_id}...");
+    let status = run_locally(&auth.pre_command, true)?;
+    if !status.success() {
+        bail!("`{}` failed with {status}", auth.pre_command);
+    }
+
+    if let Some(validity_check) = &auth.validity_check {
+        if !run_locally(validity_check, false)?.success() {
+            bail!(
+                "`{validity_check}` still fails after `{}` succeeded",
+                auth.pre_command
+            );
+        }
+    }
+
+    Ok(())
+}
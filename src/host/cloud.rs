@@ -0,0 +1,403 @@
+use super::accounting::AccountedJob;
+use super::disk_usage::DiskUsage;
+use super::facts::HostFacts;
+use super::local::LocalHost;
+use super::maintenance::Maintenance;
+use super::manifest::ManifestEntry;
+use super::rsync::{SyncOptions, SyncStats};
+use super::slurm_cluster::{QuickRunPreparationOptions, SlurmClusterHost, SlurmClusterHostOptions};
+use super::status::HostStatus;
+use super::{
+    DirListingEntry, Host, QuickRunEstimate, QuickRunPrepOptions, RunDirectory, RunID,
+    RunOutputSyncOptions, RunTag, SyncError,
+};
+use crate::cfg::{CloudHostConfig, OutputPermissionsConfig};
+use anyhow::{anyhow, bail, Context, Result};
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use std::collections::{BTreeMap, HashMap};
+
+const CLOUD_STATE_DIR_PATH: &str = ".sparrow/cloud";
+const SSH_WAIT_ATTEMPT_COUNT_MAX: u32 = 30;
+const SSH_WAIT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Environment variable holding the ssh destination of the virtual machine for the teardown
+/// command
+const CLOUD_HOST_VARIABLE_NAME: &str = "SPARROW_CLOUD_HOST";
+
+/// A virtual machine that is provisioned on demand, on which runs are executed like on the login
+/// node of a slurm cluster, but without any slurm functionality
+pub struct CloudHost {
+    inner: SlurmClusterHost,
+    teardown_command: String,
+}
+
+impl CloudHost {
+    /// Connect to the virtual machine of `id`, provisioning it first if it is not running yet and
+    /// `provision` is set, which is only worth it to submit runs
+    pub fn new(id: &str, config: &CloudHostConfig, provision: bool) -> Result<Self> {
+        let destination = match provisioned_destination(id)? {
+            Some(destination) => destination,
+            None if provision => provision_machine(id, &config.provision_command)
+                .context(format!("failed to provision {id}"))?,
+            None => bail!(
+                "{id} is not provisioned, it is provisioned by submitting a run to it with \
+                `sparrow run --host {id}'"
+            ),
+        };
+        wait_for_ssh(&destination).context(format!("failed to reach {id} via ssh"))?;
+
+        Ok(Self {
+            inner: SlurmClusterHost::new(
+                id,
+                &destination,
+                SlurmClusterHostOptions {
+                    script_run_command_template: config
+                        .script_run_command_template
+                        .clone()
+                        .unwrap_or(String::from("bash {}")),
+                    output_base_dir_path: config.run_output_base_dir.clone(),
+                    temporary_dir_path: config.temporary_dir.clone(),
+                    run_dir_name_template: config.temporary_run_dir_template.clone(),
+                    deduplicate_auxiliary: config.deduplicate_auxiliary.unwrap_or(false),
+                    execution: config.execution.unwrap_or_default(),
+                    tmux_keep_open_on: config
+                        .tmux
+                        .as_ref()
+                        .and_then(|tmux| tmux.keep_open_on)
+                        .unwrap_or_default(),
+                    tmux_fallback: config
+                        .tmux
+                        .as_ref()
+                        .and_then(|tmux| tmux.fallback)
+                        .unwrap_or_default(),
+                    modules: config
+                        .modules
+                        .clone()
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect(),
+                    remote_command_wrapper: config.remote_command_wrapper.clone(),
+                },
+                // never used, since quick runs are not supported on cloud hosts
+                QuickRunPreparationOptions {
+                    slurm_account: String::new(),
+                    slurm_service_quality: None,
+                    node_local_storage_path: PathBuf::new(),
+                },
+                false,
+            )?,
+            teardown_command: config.teardown_command.clone(),
+        })
+    }
+}
+
+fn state_file_path(id: &str) -> PathBuf {
+    PathBuf::from(CLOUD_STATE_DIR_PATH).join(id)
+}
+
+fn provisioned_destination(id: &str) -> Result<Option<String>> {
+    let state_file_path = state_file_path(id);
+    if !state_file_path.exists() {
+        return Ok(None);
+    }
+
+    let destination = std::fs::read_to_string(&state_file_path)
+        .context(format!("failed to read {state_file_path}"))?;
+    Ok(Some(destination.trim().to_owned()))
+}
+
+/// Run the provision command, which is expected to print the ssh destination of the new virtual
+/// machine as the last line of its output
+fn provision_machine(id: &str, provision_command: &str) -> Result<String> {
+    println!("Provisioning {id}...");
+    let output = std::process::Command::new(std::env::var("SHELL").unwrap())
+        .arg("-c")
+        .arg(provision_command)
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .context(format!("failed to run `{provision_command}`"))?;
+    if !output.status.success() {
+        bail!("`{provision_command}` failed");
+    }
+
+    let output = String::from_utf8(output.stdout).context(format!(
+        "found non-valid utf8 in output of `{provision_command}`"
+    ))?;
+    let destination = output
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty())
+        .ok_or(anyhow!(
+            "`{provision_command}` did not print an ssh destination"
+        ))?
+        .to_owned();
+
+    std::fs::create_dir_all(CLOUD_STATE_DIR_PATH)
+        .context(format!("failed to create {CLOUD_STATE_DIR_PATH}"))?;
+    let state_file_path = state_file_path(id);
+    std::fs::write(&state_file_path, format!("{destination}\n"))
+        .context(format!("failed to write {state_file_path}"))?;
+
+    Ok(destination)
+}
+
+fn wait_for_ssh(destination: &str) -> Result<()> {
+    for attempt in 1..=SSH_WAIT_ATTEMPT_COUNT_MAX {
+        let status = std::process::Command::new("ssh")
+            .args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"])
+            .arg(destination)
+            .arg("true")
+            .stdin(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .context("failed to run ssh")?;
+        if status.success() {
+            return Ok(());
+        }
+
+        if attempt == 1 {
+            println!("Waiting for {destination} to accept ssh connections...");
+        }
+        std::thread::sleep(SSH_WAIT_INTERVAL);
+    }
+
+    bail!(
+        "{destination} did not accept ssh connections after {SSH_WAIT_ATTEMPT_COUNT_MAX} attempts"
+    )
+}
+
+/// Tear down the virtual machine of `id` with `teardown_command`, if it is provisioned
+pub fn teardown(id: &str, teardown_command: &str) -> Result<()> {
+    let Some(destination) = provisioned_destination(id)? else {
+        println!("{id} is not provisioned, nothing to tear down");
+        return Ok(());
+    };
+
+    println!("Tearing down {id} ({destination})...");
+    let status = std::process::Command::new(std::env::var("SHELL").unwrap())
+        .arg("-c")
+        .arg(teardown_command)
+        .env(CLOUD_HOST_VARIABLE_NAME, &destination)
+        .status()
+        .context(format!("failed to run `{teardown_command}`"))?;
+    if !status.success() {
+        bail!("`{teardown_command}` failed");
+    }
+
+    let state_file_path = state_file_path(id);
+    std::fs::remove_file(&state_file_path).context(format!("failed to remove {state_file_path}"))
+}
+
+impl Host for CloudHost {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+    fn hostname(&self) -> &str {
+        self.inner.hostname()
+    }
+    fn script_run_command(&self, script_path: &str) -> String {
+        self.inner.script_run_command(script_path)
+    }
+    fn detached_run_command(
+        &self,
+        run_cmd: &str,
+        run_id: &RunID,
+        environment_variable_names: &[String],
+        attach: bool,
+    ) -> String {
+        self.inner
+            .detached_run_command(run_cmd, run_id, environment_variable_names, attach)
+    }
+    fn output_base_dir_path(&self) -> &Path {
+        self.inner.output_base_dir_path()
+    }
+    fn is_local(&self) -> bool {
+        false
+    }
+    fn is_configured_for_quick_run(&self) -> bool {
+        false
+    }
+    fn modules(&self) -> &BTreeMap<String, String> {
+        self.inner.modules()
+    }
+    fn check_execution(&self) -> Result<()> {
+        self.inner.check_execution()
+    }
+    fn prune_content_store(&self, min_age: std::time::Duration) -> Result<Vec<u64>> {
+        self.inner.prune_content_store(min_age)
+    }
+    fn accounted_jobs(&self, _since: std::time::Duration) -> Result<Vec<AccountedJob>> {
+        bail!("slurm accounting is not available on cloud hosts")
+    }
+    fn status(&self) -> Result<HostStatus> {
+        self.inner.status()
+    }
+    fn disk_usage(&self, run_ids: &[RunID]) -> Result<DiskUsage> {
+        self.inner.disk_usage(run_ids)
+    }
+    fn maintenance(&self) -> Result<Maintenance> {
+        bail!("slurm reservations are not available on cloud hosts")
+    }
+    fn facts(&self) -> Result<HostFacts> {
+        self.inner.facts()
+    }
+
+    fn upload_run_dir(
+        &self,
+        prep_dir: tempfile::TempDir,
+        run_id: &RunID,
+        deduplicated_files: &[&ManifestEntry],
+    ) -> (RunDirectory, Option<SyncStats>) {
+        self.inner
+            .upload_run_dir(prep_dir, run_id, deduplicated_files)
+    }
+    fn content_store_path(&self) -> Option<PathBuf> {
+        self.inner.content_store_path()
+    }
+    fn push_to_content_store(
+        &self,
+        dir_path: &Path,
+        files: &[&ManifestEntry],
+    ) -> Result<Option<SyncStats>> {
+        self.inner.push_to_content_store(dir_path, files)
+    }
+    fn download_config_dir(&self, local: &LocalHost, run_id: &RunID) -> Result<PathBuf> {
+        self.inner.download_config_dir(local, run_id)
+    }
+
+    fn try_put(&self, local_path: &Path, host_path: &Path, options: SyncOptions) -> Result<()> {
+        self.inner.try_put(local_path, host_path, options)
+    }
+    fn try_get(&self, host_path: &Path, local_path: &Path, options: SyncOptions) -> Result<()> {
+        self.inner.try_get(host_path, local_path, options)
+    }
+    fn create_dir(&self, path: &Path) {
+        self.inner.create_dir(path)
+    }
+    fn create_dir_all(&self, path: &Path) {
+        self.inner.create_dir_all(path)
+    }
+    fn exists(&self, path: &Path) -> Result<bool> {
+        self.inner.exists(path)
+    }
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        self.inner.remove_dir_all(path)
+    }
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        self.inner.remove_file(path)
+    }
+    fn move_path(&self, source_path: &Path, destination_path: &Path) -> Result<()> {
+        self.inner.move_path(source_path, destination_path)
+    }
+    fn set_permissions(
+        &self,
+        path: &Path,
+        permissions: &OutputPermissionsConfig,
+        recursive: bool,
+    ) -> Result<()> {
+        self.inner.set_permissions(path, permissions, recursive)
+    }
+    fn copy_dir(
+        &self,
+        source_path: &Path,
+        destination_path: &Path,
+        excludes: &[String],
+    ) -> Result<()> {
+        self.inner.copy_dir(source_path, destination_path, excludes)
+    }
+
+    fn copy_dir_to_host(
+        &self,
+        source_path: &Path,
+        hostname: &str,
+        destination_path: &Path,
+    ) -> Result<()> {
+        self.inner
+            .copy_dir_to_host(source_path, hostname, destination_path)
+    }
+
+    fn prepare_quick_run(&self, _options: &QuickRunPrepOptions) -> Result<()> {
+        bail!("quick runs are not supported on cloud hosts")
+    }
+    fn estimate_quick_run(&self, _options: &QuickRunPrepOptions) -> Result<QuickRunEstimate> {
+        bail!("quick runs are not supported on cloud hosts")
+    }
+    fn quick_run_is_prepared(&self) -> Result<bool> {
+        Ok(false)
+    }
+    fn clear_preparation(&self) {}
+
+    fn release(&self) -> Result<()> {
+        // sessions are kept open after runs exit, so only a recorded exit code tells they finished
+        let runs = self
+            .runs()
+            .context(format!("failed to obtain runs from {}", self.id()))?;
+        if let Some(run_id) = runs
+            .iter()
+            .find(|run_id| self.recorded_exit_code(run_id).is_none())
+        {
+            bail!(
+                "refusing to tear down {}, since {run_id} has not finished yet",
+                self.id()
+            );
+        }
+
+        teardown(self.id(), &self.teardown_command)
+    }
+
+    fn runs(&self) -> Result<Vec<RunID>> {
+        self.inner.runs()
+    }
+    fn run_tags(&self) -> Result<HashMap<RunID, Vec<RunTag>>> {
+        self.inner.run_tags()
+    }
+    fn run_parents(&self) -> Result<HashMap<RunID, RunID>> {
+        self.inner.run_parents()
+    }
+    fn running_runs(&self) -> Vec<RunID> {
+        self.inner.running_runs()
+    }
+    fn clean_finished_sessions(&self) -> Result<Vec<RunID>> {
+        self.inner.clean_finished_sessions()
+    }
+    fn rename_session(&self, run_id: &RunID, new_run_id: &RunID) -> Result<()> {
+        self.inner.rename_session(run_id, new_run_id)
+    }
+    fn execute(
+        &self,
+        command: &str,
+        working_dir_path: Option<&Path>,
+    ) -> Result<std::process::ExitStatus> {
+        self.inner.execute(command, working_dir_path)
+    }
+    fn signal_process_group(&self, process_group: i32, signal: &str) -> Result<()> {
+        self.inner.signal_process_group(process_group, signal)
+    }
+    fn log_file_paths(&self, run_id: &RunID) -> Vec<PathBuf> {
+        self.inner.log_file_paths(run_id)
+    }
+    fn attach(&self, run_id: &RunID) {
+        self.inner.attach(run_id)
+    }
+    fn sync(
+        &self,
+        run_id: &RunID,
+        local_base_path: &Path,
+        options: &RunOutputSyncOptions,
+    ) -> Result<Option<SyncStats>, SyncError> {
+        self.inner.sync(run_id, local_base_path, options)
+    }
+    fn tail_log(&self, run_id: &RunID, log_file_path: &Path, follow: bool) {
+        self.inner.tail_log(run_id, log_file_path, follow)
+    }
+
+    fn list_dir(&self, dir_path: &Path) -> Result<Vec<DirListingEntry>> {
+        self.inner.list_dir(dir_path)
+    }
+    fn file_sizes(&self, dir_path: &Path) -> Result<BTreeMap<PathBuf, u64>> {
+        self.inner.file_sizes(dir_path)
+    }
+    fn read_file_head(&self, file_path: &Path, max_size: u64) -> Result<Vec<u8>> {
+        self.inner.read_file_head(file_path, max_size)
+    }
+}
@@ -1,130 +1,395 @@
 use std::iter;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use super::rsync::{rsync, SyncOptions, SyncPayload};
-use camino::Utf8Path as Path;
+use super::rsync::{rsync, SyncOptions, SyncPayload, SyncStats};
+use crate::utils::shell_quote;
+use anyhow::{bail, Context, Result};
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
 use openssh::{Session, SessionBuilder};
 
+/// Interval after which ssh asks the remote for a response if nothing was received, which keeps
+/// quiet commands, like salloc waiting in the queue, from being dropped by firewalls and lets ssh
+/// notice a connection that broke
+const SERVER_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
 pub struct Connection {
     pub async_runtime: tokio::runtime::Runtime,
-    pub session: Session,
+    /// Session to the remote, which is replaced by a new one if it breaks
+    session: Mutex<Arc<Session>>,
+    hostname: String,
+    /// Template wrapping every command run on the remote, where `{}` is replaced by the quoted
+    /// command, e.g. `bash -l -c {}` to run it in a login shell
+    command_wrapper: Option<String>,
 }
 
 impl Connection {
-    pub fn new(hostname: &str) -> Result<Self, openssh::Error> {
+    pub fn new(hostname: &str, command_wrapper: Option<String>) -> Result<Self, openssh::Error> {
         let async_runtime = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
             .expect("expected tokio runtime to build successfully");
 
-        let session_builder = SessionBuilder::default();
-        let (builder, destination) = session_builder.resolve(hostname);
-        let session = async_runtime.block_on(builder.connect(destination))?;
+        let session = connect(&async_runtime, hostname)?;
 
         return Ok(Self {
             async_runtime,
-            session,
+            session: Mutex::new(Arc::new(session)),
+            hostname: hostname.to_owned(),
+            command_wrapper,
         });
     }
 
-    fn control_socket_path(&self) -> &Path {
-        return Path::from_path(self.session.control_socket())
-            .expect("control socket path is not a valid utf8 string");
+    fn session(&self) -> Arc<Session> {
+        Arc::clone(
+            &self
+                .session
+                .lock()
+                .expect("expected session lock to not be poisoned"),
+        )
+    }
+
+    fn control_socket_path(&self) -> PathBuf {
+        return Path::from_path(self.session().control_socket())
+            .expect("control socket path is not a valid utf8 string")
+            .to_owned();
     }
 
-    pub fn upload(&self, local_path: &Path, remote_path: &Path, options: SyncOptions) {
+    /// Whether `err` of a command was caused by a broken session, which is confirmed by checking
+    /// the session, since ssh reports it like a remote command exiting with 255
+    fn is_broken(&self, err: &openssh::Error) -> bool {
+        matches!(
+            err,
+            openssh::Error::Disconnected
+                | openssh::Error::RemoteProcessTerminated
+                | openssh::Error::Master(_)
+        ) && self.block_on(self.session().check()).is_err()
+    }
+
+    /// Replace the broken session by a new one, which commands that are still running on the
+    /// broken session do not notice
+    fn reconnect(&self) -> Result<(), openssh::Error> {
+        eprintln!(
+            "warning: lost the connection to {}, reconnecting...",
+            self.hostname
+        );
+        let session = connect(&self.async_runtime, &self.hostname)?;
+        *self
+            .session
+            .lock()
+            .expect("expected session lock to not be poisoned") = Arc::new(session);
+        Ok(())
+    }
+
+    /// Upload `local_path` to `remote_path`, returning the transfer statistics if requested by
+    /// `options`
+    pub fn upload(
+        &self,
+        local_path: &Path,
+        remote_path: &Path,
+        options: SyncOptions,
+    ) -> Option<SyncStats> {
+        self.try_upload(local_path, remote_path, options)
+            .expect("rsync should not fail")
+    }
+
+    /// Like [`Connection::upload`], but leaving it to the caller to handle a failed upload
+    pub fn try_upload(
+        &self,
+        local_path: &Path,
+        remote_path: &Path,
+        options: SyncOptions,
+    ) -> std::io::Result<Option<SyncStats>> {
         rsync(
             SyncPayload::LocalToRemote {
-                control_path: self.control_socket_path(),
+                control_path: &self.control_socket_path(),
                 sources: &vec![local_path],
                 destination: remote_path,
             },
             options,
         )
-        .expect("rsync should not fail");
     }
 
-    #[allow(unused)]
-    pub fn download(&self, remote_path: &Path, local_path: &Path, options: SyncOptions) {
+    /// Download `remote_path` to `local_path`, returning the transfer statistics if requested by
+    /// `options`
+    pub fn download(
+        &self,
+        remote_path: &Path,
+        local_path: &Path,
+        options: SyncOptions,
+    ) -> std::io::Result<Option<SyncStats>> {
         rsync(
             SyncPayload::RemoteToLocal {
-                control_path: self.control_socket_path(),
+                control_path: &self.control_socket_path(),
                 source: remote_path,
                 destination: local_path,
             },
             options,
         )
-        .expect("rsync should not fail");
     }
 
     pub fn command(&self, program: &str) -> Command {
         Command::from_session(self, program)
     }
 
+    /// Wrap the shell `command` into the command wrapper of the remote, if there is one
+    pub fn wrap_command(&self, command: &str) -> String {
+        match &self.command_wrapper {
+            Some(command_wrapper) => command_wrapper.replace("{}", &shell_quote(command)),
+            None => command.to_owned(),
+        }
+    }
+
+    /// Expand `~`, `$USER` and any other environment variables in `path` by the shell of the
+    /// remote, so that one configuration resolves to per-user paths; paths without either are
+    /// returned as they are
+    pub fn expand_path(&self, path: &Path) -> Result<PathBuf> {
+        if !path.as_str().starts_with('~') && !path.as_str().contains('$') {
+            return Ok(path.to_owned());
+        }
+
+        let expand_command = format!("printf '%s' {}", path_expression(path.as_str()));
+        let output = self
+            .command("bash")
+            .arg("-c")
+            .arg(&expand_command)
+            .idempotent()
+            .output()
+            .context(format!("failed to run `{expand_command}`"))?;
+        if !output.status.success() {
+            bail!("`{expand_command}` failed");
+        }
+
+        let expanded_path = String::from_utf8(output.stdout).context(format!(
+            "found non-valid utf8 in output of `{expand_command}`"
+        ))?;
+        if expanded_path.is_empty() {
+            bail!("{path} expanded to an empty path");
+        }
+        Ok(PathBuf::from(expanded_path))
+    }
+
+    /// Offset of the clock of the remote from the local clock in whole seconds, comparing the
+    /// output of `date +%s` to the local time in the middle of its round trip
+    pub fn clock_skew(&self) -> Result<i64> {
+        let unix_seconds = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64()
+        };
+
+        let start_time = SystemTime::now();
+        let output = self
+            .command("date")
+            .arg("+%s")
+            .idempotent()
+            .output()
+            .context("failed to run `date +%s`")?;
+        let local_time = (unix_seconds(start_time) + unix_seconds(SystemTime::now())) / 2.0;
+        if !output.status.success() {
+            bail!("`date +%s` failed");
+        }
+
+        let remote_time = String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse::<i64>()
+            .context("found no unix time in the output of `date +%s`")?;
+        // `date +%s` truncates, so the remote time is half a second later on average
+        Ok((remote_time as f64 + 0.5 - local_time).round() as i64)
+    }
+
     pub fn block_on<F: std::future::Future>(&self, future: F) -> F::Output {
         self.async_runtime.block_on(future)
     }
 }
 
+/// Shell expression of `path`, where only a leading `~` and the environment variables `$NAME` and
+/// `${NAME}` are expanded and everything else is quoted, so that the path is neither split,
+/// globbed nor executed
+fn path_expression(path: &str) -> String {
+    let (mut expression, path) = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => (String::from("\"$HOME\""), rest),
+        _ => (String::new(), path),
+    };
+
+    let mut literal = String::new();
+    let mut rest = path;
+    while let Some(start) = rest.find('$') {
+        let after_dollar = &rest[start + 1..];
+        let name = match after_dollar.strip_prefix('{') {
+            Some(braced) => braced.find('}').map(|end| &braced[..end]),
+            None => Some(
+                &after_dollar[..after_dollar
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(after_dollar.len())],
+            ),
+        }
+        .filter(|name| {
+            name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+
+        literal += &rest[..start];
+        let Some(name) = name else {
+            literal.push('$');
+            rest = after_dollar;
+            continue;
+        };
+        if !literal.is_empty() {
+            expression += &shell_quote(&literal);
+            literal.clear();
+        }
+        expression += &format!("\"${{{name}}}\"");
+        let reference_length = if after_dollar.starts_with('{') {
+            name.len() + 2
+        } else {
+            name.len()
+        };
+        rest = &after_dollar[reference_length..];
+    }
+    literal += rest;
+    if !literal.is_empty() {
+        expression += &shell_quote(&literal);
+    }
+
+    expression
+}
+
+fn connect(
+    async_runtime: &tokio::runtime::Runtime,
+    hostname: &str,
+) -> Result<Session, openssh::Error> {
+    let mut session_builder = SessionBuilder::default();
+    session_builder.server_alive_interval(SERVER_ALIVE_INTERVAL);
+    let (builder, destination) = session_builder.resolve(hostname);
+    async_runtime.block_on(builder.connect(destination))
+}
+
+/// Command on the remote, which is only built once it runs, so that it can be wrapped into the
+/// command wrapper of the remote as a whole
 pub struct Command<'c> {
-    async_runtime: &'c tokio::runtime::Runtime,
-    pub command: openssh::OwningCommand<&'c openssh::Session>,
+    connection: &'c Connection,
     program: String,
     args: Vec<String>,
+    /// Constructors of the standard streams, which are called for every execution, since a
+    /// stream can only be handed to a single one
+    stdin: Option<fn() -> openssh::Stdio>,
+    stdout: Option<fn() -> openssh::Stdio>,
+    stderr: Option<fn() -> openssh::Stdio>,
+    /// Whether the command can safely run again after the session broke while it was running
+    idempotent: bool,
 }
 
 impl<'c> Command<'c> {
     pub fn from_session(connection: &'c Connection, program: &str) -> Self {
         Self {
-            async_runtime: &connection.async_runtime,
-            command: connection.session.command(program),
+            connection,
             program: program.to_owned(),
             args: Vec::new(),
+            stdin: None,
+            stdout: None,
+            stderr: None,
+            idempotent: false,
         }
     }
 
     pub fn arg<A: AsRef<str>>(&mut self, arg: A) -> &mut Self {
         self.args.push(arg.as_ref().to_owned());
-        self.command.arg(arg);
         self
     }
 
     pub fn args<I, A>(&mut self, args: I) -> &mut Self
     where
-        I: IntoIterator<Item = A> + Clone,
+        I: IntoIterator<Item = A>,
         A: AsRef<str>,
     {
-        self.command.args(args.clone());
         self.args
             .extend(args.into_iter().map(|arg| arg.as_ref().to_owned()));
         self
     }
 
-    pub fn stdout(&mut self, cfg: openssh::Stdio) -> &mut Self {
-        self.command.stdout(cfg);
+    pub fn stdout(&mut self, cfg: fn() -> openssh::Stdio) -> &mut Self {
+        self.stdout = Some(cfg);
         self
     }
 
-    pub fn stdin(&mut self, cfg: openssh::Stdio) -> &mut Self {
-        self.command.stdin(cfg);
+    pub fn stdin(&mut self, cfg: fn() -> openssh::Stdio) -> &mut Self {
+        self.stdin = Some(cfg);
         self
     }
 
     #[allow(unused)]
-    pub fn stderr(&mut self, cfg: openssh::Stdio) -> &mut Self {
-        self.command.stderr(cfg);
+    pub fn stderr(&mut self, cfg: fn() -> openssh::Stdio) -> &mut Self {
+        self.stderr = Some(cfg);
+        self
+    }
+
+    /// Mark the command as free of side effects, like listing files or querying the status of the
+    /// remote, so that it is run again on a new session instead of failing if the session breaks
+    pub fn idempotent(&mut self) -> &mut Self {
+        self.idempotent = true;
         self
     }
 
     pub fn output(&mut self) -> Result<std::process::Output, openssh::Error> {
-        self.async_runtime.block_on(self.command.output())
+        let mut command = self.build();
+        match self.connection.block_on(command.output()) {
+            Err(err) if self.idempotent && self.connection.is_broken(&err) => {
+                self.connection.reconnect()?;
+                let mut command = self.build();
+                self.connection.block_on(command.output())
+            }
+            result => result,
+        }
     }
 
     pub fn status(&mut self) -> Result<std::process::ExitStatus, openssh::Error> {
-        self.async_runtime.block_on(self.command.status())
+        let mut command = self.build();
+        match self.connection.block_on(command.status()) {
+            Err(err) if self.idempotent && self.connection.is_broken(&err) => {
+                self.connection.reconnect()?;
+                let mut command = self.build();
+                self.connection.block_on(command.status())
+            }
+            result => result,
+        }
+    }
+
+    pub fn spawn(&mut self) -> Result<openssh::Child<Arc<Session>>, openssh::Error> {
+        let mut command = self.build();
+        self.connection.block_on(command.spawn())
     }
 
-    pub fn spawn(&mut self) -> Result<openssh::Child<&openssh::Session>, openssh::Error> {
-        self.async_runtime.block_on(self.command.spawn())
+    /// Build the ssh command with new standard streams, so that a command that is run again after
+    /// the session broke gets the same streams as the first time
+    fn build(&self) -> openssh::OwningCommand<Arc<Session>> {
+        let session = self.connection.session();
+        let mut command = match &self.connection.command_wrapper {
+            Some(_) => {
+                let command = iter::once(&self.program)
+                    .chain(&self.args)
+                    .map(|arg| shell_quote(arg))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                Session::to_raw_command(session, self.connection.wrap_command(&command))
+            }
+            None => {
+                let mut command = Session::to_command(session, self.program.as_str());
+                command.args(&self.args);
+                command
+            }
+        };
+        if let Some(stdin) = self.stdin {
+            command.stdin(stdin());
+        }
+        if let Some(stdout) = self.stdout {
+            command.stdout(stdout());
+        }
+        if let Some(stderr) = self.stderr {
+            command.stderr(stderr());
+        }
+
+        command
     }
 }
 
@@ -134,7 +399,9 @@ impl std::fmt::Debug for Command<'_> {
         let command = Iterator::chain(
             iter::once(&self.program).map(quote),
             self.args.iter().map(quote),
-        ).collect::<Vec<_>>().join(" ");
+        )
+        .collect::<Vec<_>>()
+        .join(" ");
 
         write!(f, "{command}")
     }
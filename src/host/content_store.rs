@@ -0,0 +1,69 @@
+use super::manifest::ManifestEntry;
+use crate::utils::{shell_quote, Utf8Path};
+use anyhow::{Context, Result};
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Name of the content-addressed store below the temporary directory of a host, in which files
+/// of auxiliary mappings are kept once per checksum under `objects/<sha256>` and hard-linked
+/// into run directories; the link count of an object is thus its reference count, so objects
+/// with a single link are not used by any run directory anymore
+pub const CONTENT_STORE_DIR_NAME: &str = "sparrow-cas";
+pub const LINK_SCRIPT_FILE_NAME: &str = ".sparrow_cas_links.sh";
+
+pub fn objects_path(store_path: &Path) -> PathBuf {
+    store_path.join("objects")
+}
+
+/// Build a command which removes the objects of the store at `store_path` that are not linked
+/// into any run directory and whose link count last changed at least `min_age` ago, so that
+/// objects pushed ahead of a run are kept for a while, and prints the size of each removed object
+pub fn prune_command(store_path: &Path, min_age: Duration) -> String {
+    let objects_path = shell_quote(objects_path(store_path).as_str());
+    format!(
+        "test ! -d {objects_path} \
+        || find {objects_path} -type f -links 1 -cmin +{} -printf '%s\\n' -delete",
+        min_age.as_secs() / 60
+    )
+}
+
+/// Collect the given files of the prepared run directory at `prep_dir_path` into a new
+/// directory, named by their checksums, and remove them from the run directory
+pub fn extract_objects(prep_dir_path: &Path, files: &[&ManifestEntry]) -> Result<TempDir> {
+    let objects_dir = TempDir::new().context("failed to create temporary objects directory")?;
+    for file in files {
+        let file_path = prep_dir_path.join(&file.path);
+        let object_path = objects_dir.utf8_path().join(&file.sha256);
+        if !object_path.exists() {
+            std::fs::rename(&file_path, &object_path)
+                .or_else(|_| std::fs::copy(&file_path, &object_path).map(|_| ()))
+                .context(format!("failed to move {file_path} to {object_path}"))?;
+        }
+        if file_path.exists() {
+            std::fs::remove_file(&file_path).context(format!("failed to remove {file_path}"))?;
+        }
+    }
+
+    Ok(objects_dir)
+}
+
+/// Build a script which restores the given files in a run directory, when executed in it, by
+/// hard-linking them from the store at `store_path`
+pub fn link_script(store_path: &Path, files: &[&ManifestEntry]) -> String {
+    let objects_path = objects_path(store_path);
+    files.iter().fold(String::from("set -e\n"), |script, file| {
+        let parent_path = file.path.parent().unwrap_or(Path::new(""));
+        let mkdir_command = if parent_path.as_str().is_empty() {
+            String::new()
+        } else {
+            format!("mkdir -p {} && ", shell_quote(parent_path.as_str()))
+        };
+        script
+            + &format!(
+                "{mkdir_command}ln -f {} {}\n",
+                shell_quote(objects_path.join(&file.sha256).as_str()),
+                shell_quote(file.path.as_str())
+            )
+    })
+}
@@ -0,0 +1,94 @@
+use super::RunID;
+
+/// Script printing the disk usage of the runs given as `<group>/<name>` after the run output base
+/// directory as first argument, as `<KiB>\t<group>/<name>` lines of `du`, which runs in parallel
+/// over batches of runs, followed by a `quota <size> <used> <available>` line in KiB of the quota
+/// of the user on the file system the run outputs are stored on, as reported by `lfs quota` on
+/// lustre or by `quota`, or a `filesystem <size> <used> <available>` line of `df` if neither
+/// reports a quota
+pub const DISK_USAGE_SCRIPT: &str = r#"
+cd "$1" || exit 1
+shift
+if [ $# -gt 0 ]; then
+    printf '%s\0' "$@" | xargs -0 -n 16 -P 8 du -sk -- 2>/dev/null
+fi
+
+# quota lines start with the file system, which may be on its own line, followed by the used KiB,
+# marked with `*` when over quota, and the soft and hard limit, which are 0 if there is none
+quota_usage() {
+    awk '{ gsub(/\*/, ""); if ($1 !~ /^[0-9]+$/) { $1 = ""; $0 = $0 } }
+        NF >= 3 && $1 ~ /^[0-9]+$/ {
+            limit = $2 > 0 ? $2 : $3
+            if (limit > 0) { print "quota", limit, $1, (limit > $1 ? limit - $1 : 0); exit }
+        }'
+}
+mount_path=$(df -Pk . 2>/dev/null | awk 'NR == 2 { print $6 }')
+usage=
+if command -v lfs > /dev/null 2>&1; then
+    usage=$(lfs quota -q -u "$(id -un)" "$mount_path" 2>/dev/null | quota_usage)
+fi
+if [ -z "$usage" ] && command -v quota > /dev/null 2>&1; then
+    usage=$(quota -w -f "$mount_path" 2>/dev/null | quota_usage)
+fi
+if [ -z "$usage" ]; then
+    usage=$(df -Pk . 2>/dev/null | awk 'NR == 2 { print "filesystem", $2, $3, $4 }')
+fi
+printf '%s\n' "$usage"
+"#;
+
+/// Size, used and available bytes of a file system, or of the quota of the user on it
+#[derive(Clone, Copy)]
+pub struct FilesystemUsage {
+    pub size: u64,
+    pub used: u64,
+    pub available: u64,
+    pub is_quota: bool,
+}
+
+/// Disk usage of runs in bytes, where runs whose output could not be measured are missing, and
+/// usage of the file system the run outputs are stored on
+pub struct DiskUsage {
+    pub run_sizes: Vec<(RunID, u64)>,
+    pub filesystem: Option<FilesystemUsage>,
+}
+
+impl DiskUsage {
+    pub fn parse(output: &str) -> Self {
+        let mut usage = DiskUsage {
+            run_sizes: Vec::new(),
+            filesystem: None,
+        };
+        for line in output.lines() {
+            let (usage_line, is_quota) = match line.split_once(' ') {
+                Some(("filesystem", usage_line)) => (Some(usage_line), false),
+                Some(("quota", usage_line)) => (Some(usage_line), true),
+                _ => (None, false),
+            };
+            if let Some(usage_line) = usage_line {
+                let sizes = usage_line
+                    .split_whitespace()
+                    .filter_map(|size| size.parse::<u64>().ok())
+                    .collect::<Vec<_>>();
+                if let [size, used, available] = sizes[..] {
+                    usage.filesystem = Some(FilesystemUsage {
+                        size: size * 1024,
+                        used: used * 1024,
+                        available: available * 1024,
+                        is_quota,
+                    });
+                }
+                continue;
+            }
+
+            let Some((size, run)) = line.split_once('\t') else {
+                continue;
+            };
+            let (Ok(size), Ok(run_id)) = (size.parse::<u64>(), RunID::parse(run)) else {
+                continue;
+            };
+            usage.run_sizes.push((run_id, size * 1024));
+        }
+
+        usage
+    }
+}
@@ -0,0 +1,103 @@
+use super::local::cache_dir_path;
+use anyhow::{Context, Result};
+use camino::Utf8PathBuf as PathBuf;
+use std::time::Duration;
+
+/// Facts are detected again once their cached version is older than this
+const FACTS_CACHE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Script printing one `<fact>=<value>` line per fact, where facts that cannot be detected on a
+/// host are left empty
+pub const FACTS_DETECTION_SCRIPT: &str = r#"
+echo "gpu_count=$(nvidia-smi --list-gpus 2>/dev/null | wc -l)"
+echo "cuda_version=$(nvidia-smi 2>/dev/null | sed -n 's/.*CUDA Version: *\([0-9.]*\).*/\1/p')"
+echo "partitions=$(sinfo --noheader --format=%R 2>/dev/null | paste -sd, -)"
+echo "glibc_version=$(ldd --version 2>/dev/null | head -n 1 | awk '{ print $NF }')"
+echo "scratch_path=${SCRATCH:-${SCRATCH_DIR:-${TMPDIR:-}}}"
+"#;
+
+/// Facts about a host exposed as `host.facts` to templates, so that templates can adapt to a
+/// host without hard-coding host ids
+#[derive(serde::Serialize, serde::Deserialize, schemars::JsonSchema, Default)]
+pub struct HostFacts {
+    pub gpu_count: Option<u16>,
+    pub cuda_version: Option<String>,
+    pub partitions: Vec<String>,
+    pub glibc_version: Option<String>,
+    #[schemars(with = "Option<String>")]
+    pub scratch_path: Option<PathBuf>,
+}
+
+impl HostFacts {
+    fn cache_path(hostname: &str) -> PathBuf {
+        cache_dir_path()
+            .join("facts")
+            .join(format!("{hostname}.json"))
+    }
+
+    /// Load the facts of `hostname` from the local cache, running `detect` to obtain the output
+    /// of [`FACTS_DETECTION_SCRIPT`] when they are missing or outdated
+    pub fn cached<F: FnOnce() -> Result<String>>(hostname: &str, detect: F) -> Result<Self> {
+        let cache_path = Self::cache_path(hostname);
+
+        let cache_age = std::fs::metadata(&cache_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modification_time| modification_time.elapsed().ok());
+        if cache_age.is_some_and(|cache_age| cache_age < FACTS_CACHE_MAX_AGE) {
+            let cached_facts = std::fs::read_to_string(&cache_path)
+                .context(format!("failed to read {cache_path}"))?;
+            if let Ok(facts) = serde_json::from_str(&cached_facts) {
+                return Ok(facts);
+            }
+        }
+
+        let facts =
+            Self::parse(&detect().context(format!("failed to detect facts of {hostname}"))?);
+
+        let cache_parent_path = cache_path
+            .parent()
+            .expect("expected facts cache path to have a parent");
+        std::fs::create_dir_all(cache_parent_path)
+            .context(format!("failed to create {cache_parent_path}"))?;
+        std::fs::write(
+            &cache_path,
+            serde_json::to_string_pretty(&facts).expect("expected facts to be serializable"),
+        )
+        .context(format!("failed to write {cache_path}"))?;
+
+        Ok(facts)
+    }
+
+    /// Load the facts of `hostname` from the local cache regardless of their age, for hosts that
+    /// cannot be reached to detect them again
+    pub fn last_cached(hostname: &str) -> Option<Self> {
+        std::fs::read_to_string(Self::cache_path(hostname))
+            .ok()
+            .and_then(|cached_facts| serde_json::from_str(&cached_facts).ok())
+    }
+
+    fn parse(output: &str) -> Self {
+        let mut facts = HostFacts::default();
+        for (fact, value) in output
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(fact, value)| (fact.trim(), value.trim()))
+            .filter(|(_, value)| !value.is_empty())
+        {
+            match fact {
+                // without gpus, nvidia-smi is usually missing, so zero gpus are not a fact
+                "gpu_count" => facts.gpu_count = value.parse().ok().filter(|&count| count > 0),
+                "cuda_version" => facts.cuda_version = Some(value.to_owned()),
+                "partitions" => {
+                    facts.partitions = value.split(',').map(String::from).collect();
+                }
+                "glibc_version" => facts.glibc_version = Some(value.to_owned()),
+                "scratch_path" => facts.scratch_path = Some(PathBuf::from(value)),
+                _ => {}
+            }
+        }
+
+        facts
+    }
+}
@@ -1,19 +1,37 @@
-use super::rsync::{copy_directory, SyncOptions};
-use super::{Host, QuickRunPrepOptions, RunDirectory, RunID, RunOutputSyncOptions};
+use super::accounting::AccountedJob;
+use super::disk_usage::{DiskUsage, DISK_USAGE_SCRIPT};
+use super::facts::{HostFacts, FACTS_DETECTION_SCRIPT};
+use super::maintenance::Maintenance;
+use super::manifest::ManifestEntry;
+use super::rsync::{copy_directory, try_copy_directory, SyncOptions, SyncStats};
+use super::status::{HostStatus, STATUS_SCRIPT};
+use super::{
+    parse_tags, permissions_script_args, DirListingEntry, Host, QuickRunEstimate,
+    QuickRunPrepOptions, RunDirectory, RunID, RunOutputSyncOptions, RunTag, SyncError,
+    PERMISSIONS_SCRIPT,
+};
+use crate::cfg::OutputPermissionsConfig;
 use crate::utils::{AsUtf8Path, Utf8Str};
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use std::collections::{BTreeMap, HashMap};
 
 pub struct LocalHost {
     output_base_dir_path: PathBuf,
     script_run_command_template: String,
+    modules: BTreeMap<String, String>,
 }
 
 impl LocalHost {
-    pub fn new(output_base_dir_path: &Path, script_run_command_template: String) -> Self {
+    pub fn new(
+        output_base_dir_path: &Path,
+        script_run_command_template: String,
+        modules: BTreeMap<String, String>,
+    ) -> Self {
         return Self {
             output_base_dir_path: PathBuf::from(output_base_dir_path),
             script_run_command_template,
+            modules,
         };
     }
 }
@@ -26,7 +44,17 @@ impl Host for LocalHost {
         "localhost"
     }
     fn script_run_command(&self, script_path: &str) -> String {
-        return self.script_run_command_template.replace("{}", script_path)
+        return self.script_run_command_template.replace("{}", script_path);
+    }
+    fn detached_run_command(
+        &self,
+        run_cmd: &str,
+        _run_id: &RunID,
+        _environment_variable_names: &[String],
+        _attach: bool,
+    ) -> String {
+        // local runs are executed in the foreground
+        run_cmd.to_owned()
     }
     fn output_base_dir_path(&self) -> &Path {
         &self.output_base_dir_path.as_path()
@@ -39,18 +67,79 @@ impl Host for LocalHost {
     fn is_configured_for_quick_run(&self) -> bool {
         true
     }
+    fn modules(&self) -> &BTreeMap<String, String> {
+        &self.modules
+    }
 
-    fn upload_run_dir(&self, prep_dir: tempfile::TempDir) -> RunDirectory {
-        return RunDirectory::Local(prep_dir);
+    fn accounted_jobs(&self, _since: std::time::Duration) -> Result<Vec<AccountedJob>> {
+        bail!("slurm accounting is not available on the local host")
+    }
+    fn status(&self) -> Result<HostStatus> {
+        let output = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(STATUS_SCRIPT)
+            .arg("sparrow")
+            .arg(&self.output_base_dir_path)
+            .output()
+            .context("failed to run the status script")?;
+        Ok(HostStatus::parse(
+            &String::from_utf8_lossy(&output.stdout),
+            None,
+        ))
+    }
+    fn disk_usage(&self, run_ids: &[RunID]) -> Result<DiskUsage> {
+        let output = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(DISK_USAGE_SCRIPT)
+            .arg("sparrow")
+            .arg(&self.output_base_dir_path)
+            .args(run_ids.iter().map(|run_id| run_id.to_string()))
+            .output()
+            .context("failed to run the disk usage script")?;
+        if !output.status.success() {
+            bail!("failed to enter {}", self.output_base_dir_path);
+        }
+        Ok(DiskUsage::parse(&String::from_utf8_lossy(&output.stdout)))
+    }
+    fn maintenance(&self) -> Result<Maintenance> {
+        bail!("slurm reservations are not available on the local host")
+    }
+    fn facts(&self) -> Result<HostFacts> {
+        HostFacts::cached(self.hostname(), || {
+            let output = std::process::Command::new("bash")
+                .arg("-c")
+                .arg(FACTS_DETECTION_SCRIPT)
+                .output()
+                .context("failed to run the facts detection script")?;
+            String::from_utf8(output.stdout)
+                .context("found non-valid utf8 in output of the facts detection script")
+        })
+    }
+
+    fn upload_run_dir(
+        &self,
+        prep_dir: tempfile::TempDir,
+        _run_id: &RunID,
+        _deduplicated_files: &[&ManifestEntry],
+    ) -> (RunDirectory, Option<SyncStats>) {
+        return (RunDirectory::Local(prep_dir), None);
     }
     fn download_config_dir(&self, _local: &LocalHost, run_id: &RunID) -> Result<PathBuf> {
         Ok(self.config_dir_destination_path(run_id))
     }
 
-    fn put(&self, local_path: &Path, host_path: &Path, options: SyncOptions) {
+    fn try_put(&self, local_path: &Path, host_path: &Path, options: SyncOptions) -> Result<()> {
         if local_path != host_path {
-            copy_directory(local_path, host_path, options);
+            try_copy_directory(local_path, host_path, options)?;
+        }
+        Ok(())
+    }
+
+    fn try_get(&self, host_path: &Path, local_path: &Path, options: SyncOptions) -> Result<()> {
+        if host_path != local_path {
+            try_copy_directory(host_path, local_path, options)?;
         }
+        Ok(())
     }
 
     fn create_dir(&self, path: &Path) {
@@ -61,7 +150,78 @@ impl Host for LocalHost {
         std::fs::create_dir_all(path).expect(&format!("expected creation of {path} to work"));
     }
 
-    fn prepare_quick_run(&self, _options: &QuickRunPrepOptions) -> Result<()> { Ok(()) }
+    fn exists(&self, path: &Path) -> Result<bool> {
+        std::fs::exists(path).context(format!("failed to check for existence of {path}"))
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        match std::fs::remove_dir_all(path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(err).context(format!("failed to remove {path}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        match std::fs::remove_file(path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(err).context(format!("failed to remove {path}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn move_path(&self, source_path: &Path, destination_path: &Path) -> Result<()> {
+        std::fs::rename(source_path, destination_path).context(format!(
+            "failed to move {source_path} to {destination_path}"
+        ))
+    }
+
+    fn set_permissions(
+        &self,
+        path: &Path,
+        permissions: &OutputPermissionsConfig,
+        recursive: bool,
+    ) -> Result<()> {
+        let status = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(PERMISSIONS_SCRIPT)
+            .arg("sparrow")
+            .args(permissions_script_args(path, permissions, recursive))
+            .status()
+            .context("failed to run the permissions script")?;
+        if !status.success() {
+            bail!("failed to change the permissions of {path}");
+        }
+
+        Ok(())
+    }
+
+    fn copy_dir(
+        &self,
+        source_path: &Path,
+        destination_path: &Path,
+        excludes: &[String],
+    ) -> Result<()> {
+        std::fs::create_dir_all(destination_path)
+            .context(format!("failed to create {destination_path}"))?;
+        copy_directory(
+            source_path,
+            destination_path,
+            SyncOptions::default()
+                .copy_contents()
+                .exclude(&excludes.to_vec()),
+        );
+        Ok(())
+    }
+
+    fn prepare_quick_run(&self, _options: &QuickRunPrepOptions) -> Result<()> {
+        Ok(())
+    }
+    fn estimate_quick_run(&self, _options: &QuickRunPrepOptions) -> Result<QuickRunEstimate> {
+        bail!("cannot estimate quick run allocation on local host");
+    }
     fn quick_run_is_prepared(&self) -> Result<bool> {
         Ok(true)
     }
@@ -76,19 +236,31 @@ impl Host for LocalHost {
         for group_dir in std::fs::read_dir(self.output_base_dir_path.as_path())
             .context(format!("failed to read {}", self.output_base_dir_path))?
         {
-            let group_dir = group_dir.context(format!("failed to read {}", self.output_base_dir_path))?;
+            let group_dir =
+                group_dir.context(format!("failed to read {}", self.output_base_dir_path))?;
+            // hidden directories like the trash are no run groups
+            if group_dir.file_name().utf8_str().starts_with('.') {
+                continue;
+            }
             for name_dir in std::fs::read_dir(group_dir.path())
                 .expect("expected read of run output group dir to succeed")
             {
-                let name_dir = name_dir.context(format!("failed to read {}", self.output_base_dir_path))?;
+                let name_dir =
+                    name_dir.context(format!("failed to read {}", self.output_base_dir_path))?;
 
                 assert!(group_dir
                     .file_type()
-                    .context(format!("failed to obtain file type for {}", group_dir.path().as_utf8()))?
+                    .context(format!(
+                        "failed to obtain file type for {}",
+                        group_dir.path().as_utf8()
+                    ))?
                     .is_dir());
                 assert!(name_dir
                     .file_type()
-                    .context(format!("failed to obtain file type for {}", name_dir.path().as_utf8()))?
+                    .context(format!(
+                        "failed to obtain file type for {}",
+                        name_dir.path().as_utf8()
+                    ))?
                     .is_dir());
 
                 ids.push(RunID::new(
@@ -100,9 +272,63 @@ impl Host for LocalHost {
 
         Ok(ids)
     }
+    fn run_tags(&self) -> Result<HashMap<RunID, Vec<RunTag>>> {
+        let mut run_tags = HashMap::new();
+        for run_id in self.runs()? {
+            let tags_file_path = self.tags_file_destination_path(&run_id);
+            if !tags_file_path.exists() {
+                continue;
+            }
+
+            let tags = std::fs::read_to_string(&tags_file_path)
+                .context(format!("failed to read {tags_file_path}"))?;
+            run_tags.insert(
+                run_id,
+                parse_tags(&tags).context(format!("failed to parse {tags_file_path}"))?,
+            );
+        }
+
+        Ok(run_tags)
+    }
+    fn run_parents(&self) -> Result<HashMap<RunID, RunID>> {
+        Ok(self
+            .runs()?
+            .into_iter()
+            .filter_map(|run_id| Some((run_id.clone(), self.recorded_parent(&run_id)?)))
+            .collect())
+    }
     fn running_runs(&self) -> Vec<RunID> {
         unimplemented!();
     }
+    fn clean_finished_sessions(&self) -> Result<Vec<RunID>> {
+        bail!("local runs are executed in the foreground and have no sessions")
+    }
+    fn execute(
+        &self,
+        command: &str,
+        working_dir_path: Option<&Path>,
+    ) -> Result<std::process::ExitStatus> {
+        let mut cmd = std::process::Command::new("bash");
+        cmd.arg("-c").arg(command);
+        if let Some(working_dir_path) = working_dir_path {
+            cmd.current_dir(working_dir_path);
+        }
+        cmd.status().context(format!("failed to run `{command}`"))
+    }
+    fn signal_process_group(&self, process_group: i32, signal: &str) -> Result<()> {
+        let status = std::process::Command::new("kill")
+            .arg("-s")
+            .arg(signal)
+            .arg("--")
+            .arg(format!("-{process_group}"))
+            .status()
+            .context(format!("failed to signal process group {process_group}"))?;
+        if !status.success() {
+            bail!("failed to send {signal} to process group {process_group}");
+        }
+
+        Ok(())
+    }
     fn log_file_paths(&self, run_id: &RunID) -> Vec<PathBuf> {
         let log_path = run_id.path(&self.output_base_dir_path).join("logs");
         walkdir::WalkDir::new(log_path)
@@ -127,16 +353,93 @@ impl Host for LocalHost {
         _run_id: &RunID,
         _local_base_path: &Path,
         _options: &RunOutputSyncOptions,
-    ) -> Result<(), String> {
-        Ok(())
+    ) -> Result<Option<SyncStats>, SyncError> {
+        Ok(None)
     }
     fn tail_log(&self, _run_id: &RunID, _log_file_path: &Path, _follow: bool) {
         unimplemented!();
     }
+
+    fn list_dir(&self, dir_path: &Path) -> Result<Vec<DirListingEntry>> {
+        let mut entries = std::fs::read_dir(dir_path)
+            .context(format!("failed to read {dir_path}"))?
+            .map(|entry| {
+                let entry = entry.context(format!("failed to read {dir_path}"))?;
+                // follow symlinks, so that linked directories can be browsed as well
+                let metadata = std::fs::metadata(entry.path()).context(format!(
+                    "failed to obtain metadata for {}",
+                    entry.path().as_utf8()
+                ))?;
+                Ok(DirListingEntry {
+                    name: entry.file_name().utf8_str().to_owned(),
+                    is_dir: metadata.is_dir(),
+                    size: metadata.len(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+
+        Ok(entries)
+    }
+
+    fn file_sizes(&self, dir_path: &Path) -> Result<BTreeMap<PathBuf, u64>> {
+        walkdir::WalkDir::new(dir_path)
+            .into_iter()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .map_or(true, |entry| entry.file_type().is_file())
+            })
+            .map(|entry| {
+                let entry = entry.context(format!("failed to walk {dir_path}"))?;
+                let path = entry.path().as_utf8();
+                let size = entry
+                    .metadata()
+                    .context(format!("failed to obtain metadata for {path}"))?
+                    .len();
+                Ok((path.strip_prefix(dir_path)?.to_owned(), size))
+            })
+            .collect()
+    }
+
+    fn read_file_head(&self, file_path: &Path, max_size: u64) -> Result<Vec<u8>> {
+        use std::io::Read;
+
+        let mut content = Vec::new();
+        std::fs::File::open(file_path)
+            .context(format!("failed to open {file_path}"))?
+            .take(max_size)
+            .read_to_end(&mut content)
+            .context(format!("failed to read {file_path}"))?;
+
+        Ok(content)
+    }
 }
 
-pub fn show_result(run_id: &RunID, base_path: &Path, path: &Path) {
+pub fn cache_dir_path() -> PathBuf {
+    let cache_base_path = std::env::var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            PathBuf::from(std::env::var("HOME").expect("expected HOME variable to be set"))
+                .join(".cache")
+        });
+    cache_base_path.join("sparrow")
+}
+
+pub fn result_cache_dir_path(host_id: &str) -> PathBuf {
+    cache_dir_path().join("results").join(host_id)
+}
+
+pub fn show_result(run_id: &RunID, base_path: &Path, path: &Path, open_with: Option<&str>) {
     let result_path = run_id.path(base_path).join(path);
-    open::that_detached(&result_path)
-        .expect(&format!("failed to open `{result_path}' with the system default application"));
+    let open_result = match open_with {
+        Some(open_with) => open::with_detached(&result_path, open_with),
+        None => open::that_detached(&result_path),
+    };
+    if let Err(err) = open_result {
+        panic!(
+            "failed to open `{result_path}' with {}: {err}",
+            open_with.unwrap_or("the system default application")
+        );
+    }
 }
@@ -0,0 +1,160 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Script printing the current time of the host in unix seconds and its utc offset, followed by
+/// the reservations of the cluster, one per line, since slurm reports reservation times in the
+/// local time of the cluster without an offset
+pub const MAINTENANCE_SCRIPT: &str = r#"
+echo "now $(date +%s) $(date +%z)"
+scontrol show reservation --oneliner 2>/dev/null
+"#;
+
+/// A reservation with the `MAINT` flag, during which the reserved nodes do not run jobs, with
+/// times in unix seconds of the clock of the host
+pub struct MaintenanceWindow {
+    pub name: String,
+    pub start: u64,
+    pub end: u64,
+    pub nodes: String,
+    /// Partition the reservation is restricted to, if any
+    pub partition: Option<String>,
+}
+
+impl MaintenanceWindow {
+    /// Whether a job started at `now` with the time limit `walltime` would run into this window,
+    /// where a job without a known time limit runs into every window that has not ended yet
+    pub fn overlaps(&self, now: u64, walltime: Option<Duration>) -> bool {
+        let job_end = walltime.map_or(u64::MAX, |walltime| now.saturating_add(walltime.as_secs()));
+        self.start < job_end && self.end > now
+    }
+}
+
+impl std::fmt::Display for MaintenanceWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format_time = |time: u64| {
+            humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(time)).to_string()
+        };
+        write!(
+            f,
+            "{} from {} to {} on {}",
+            self.name,
+            format_time(self.start),
+            format_time(self.end),
+            self.nodes
+        )?;
+        if let Some(partition) = &self.partition {
+            write!(f, " in partition {partition}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Maintenance windows of a cluster together with the time of the host in unix seconds
+pub struct Maintenance {
+    pub now: u64,
+    pub windows: Vec<MaintenanceWindow>,
+}
+
+impl Maintenance {
+    /// Parse the output of [`MAINTENANCE_SCRIPT`], skipping reservations without the `MAINT` flag
+    pub fn parse(output: &str) -> Self {
+        let mut now = None;
+        let mut utc_offset = 0;
+        let mut windows = Vec::new();
+        for line in output.lines() {
+            if let Some(clock) = line.strip_prefix("now ") {
+                let mut fields = clock.split_whitespace();
+                now = fields.next().and_then(|now| now.parse().ok());
+                utc_offset = fields.next().and_then(parse_utc_offset).unwrap_or(0);
+                continue;
+            }
+
+            let fields = line
+                .split_whitespace()
+                .filter_map(|field| field.split_once('='))
+                .collect::<Vec<_>>();
+            let field = |key: &str| {
+                fields
+                    .iter()
+                    .find(|(field_key, _)| *field_key == key)
+                    .map(|(_, value)| *value)
+                    .filter(|value| *value != "(null)")
+            };
+            let is_maintenance =
+                field("Flags").is_some_and(|flags| flags.split(',').any(|flag| flag == "MAINT"));
+            if !is_maintenance {
+                continue;
+            }
+            let Some(name) = field("ReservationName") else {
+                continue;
+            };
+            let (Some(start), Some(end)) = (
+                field("StartTime").and_then(|time| parse_local_time(time, utc_offset)),
+                field("EndTime").and_then(|time| parse_local_time(time, utc_offset)),
+            ) else {
+                continue;
+            };
+
+            windows.push(MaintenanceWindow {
+                name: name.to_owned(),
+                start,
+                end,
+                nodes: field("Nodes").unwrap_or("all nodes").to_owned(),
+                partition: field("PartitionName").map(String::from),
+            });
+        }
+        windows.sort_by_key(|window| window.start);
+
+        Maintenance {
+            now: now.unwrap_or_else(|| {
+                UNIX_EPOCH
+                    .elapsed()
+                    .expect("expected the system time to be after the unix epoch")
+                    .as_secs()
+            }),
+            windows,
+        }
+    }
+}
+
+/// Parse an offset like `+0200` of `date +%z` into seconds
+fn parse_utc_offset(offset: &str) -> Option<i64> {
+    let (sign, digits) = match offset.split_at_checked(1)? {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+    let hours = digits.get(..2)?.parse::<i64>().ok()?;
+    let minutes = digits.get(2..4)?.parse::<i64>().ok()?;
+    Some(sign * (hours * 60 + minutes) * 60)
+}
+
+/// Parse a time like `2024-05-01T08:00:00` in the local time of the cluster into unix seconds
+fn parse_local_time(time: &str, utc_offset: i64) -> Option<u64> {
+    let time = humantime::parse_rfc3339_weak(time).ok()?;
+    let seconds = time.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    u64::try_from(seconds - utc_offset).ok()
+}
+
+/// Parse a slurm time limit like `30`, `2:00:00` or `1-12:00` into a duration, where a plain
+/// number is in minutes
+pub fn parse_slurm_time_limit(time: &str) -> Option<Duration> {
+    let (days, time) = match time.trim().split_once('-') {
+        Some((days, time)) => (Some(days.parse::<u64>().ok()?), time),
+        None => (None, time.trim()),
+    };
+    let parts = time
+        .split(':')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let seconds = match (days.is_some(), &parts[..]) {
+        // with days, the parts are hours, hours:minutes or hours:minutes:seconds
+        (true, &[hours]) => hours * 3600,
+        (true, &[hours, minutes]) => hours * 3600 + minutes * 60,
+        (true, &[hours, minutes, seconds]) => hours * 3600 + minutes * 60 + seconds,
+        (false, &[minutes]) => minutes * 60,
+        (false, &[minutes, seconds]) => minutes * 60 + seconds,
+        (false, &[hours, minutes, seconds]) => hours * 3600 + minutes * 60 + seconds,
+        _ => return None,
+    };
+    Some(Duration::from_secs(days.unwrap_or(0) * 24 * 3600 + seconds))
+}
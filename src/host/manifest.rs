@@ -0,0 +1,69 @@
+use crate::utils::Utf8Path;
+use anyhow::{Context, Result};
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use sha2::{Digest, Sha256};
+
+pub const MANIFEST_FILE_NAME: &str = "MANIFEST.json";
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ManifestEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// List every regular file below `dir_path` with its size and sha256 checksum, with paths
+    /// relative to `dir_path`
+    pub fn build(dir_path: &Path) -> Result<Self> {
+        let mut files = walkdir::WalkDir::new(dir_path)
+            .into_iter()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .map_or(true, |entry| entry.file_type().is_file())
+            })
+            .map(|entry| {
+                let entry = entry.context(format!("failed to walk {dir_path}"))?;
+                let path = entry.utf8_path();
+
+                let mut file =
+                    std::fs::File::open(path).context(format!("failed to open {path}"))?;
+                let mut hasher = Sha256::new();
+                let size = std::io::copy(&mut file, &mut hasher)
+                    .context(format!("failed to read {path}"))?;
+
+                Ok(ManifestEntry {
+                    path: path
+                        .strip_prefix(dir_path)
+                        .expect("expected walked path to be below the walked directory")
+                        .to_owned(),
+                    size,
+                    sha256: hasher
+                        .finalize()
+                        .iter()
+                        .map(|byte| format!("{byte:02x}"))
+                        .collect(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        files.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
+
+        Ok(Manifest { files })
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).context(format!("failed to read {path}"))?;
+        serde_json::from_str(&content).context(format!("failed to parse {path}"))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let file = std::fs::File::create(path).context(format!("failed to create {path}"))?;
+        serde_json::to_writer_pretty(file, self).context(format!("failed to write {path}"))
+    }
+}
@@ -1,31 +1,126 @@
+pub mod accounting;
+pub mod auth;
+pub mod cloud;
 pub mod connection;
+pub mod content_store;
+pub mod disk_usage;
+pub mod facts;
 pub mod local;
+pub mod maintenance;
+pub mod manifest;
 pub mod rsync;
 pub mod slurm_cluster;
+pub mod status;
+pub mod timings;
+pub mod upload_summary;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::io::Write;
+use std::sync::Mutex;
 
-use super::utils::Utf8Path;
-use crate::cfg::{LocalHostConfig, QuickRunConfig, RemoteHostConfig};
-use crate::payload::{AuxiliaryMapping, CodeMapping, CodeSource, ConfigSource};
-use anyhow::{bail, Result};
+use super::utils::{ask, shell_quote, suggest_closest, Utf8Path};
+use crate::cfg::{
+    ConfigUploadDelete, LocalHostConfig, LocalSnapshotMode, OutputPermissionsConfig,
+    QuickRunConfig, RemoteHostConfig, ReviewConfig, ReviewMode, SubmoduleMode,
+};
+use crate::exit::{ExitStatus, WithExitStatus};
+use crate::payload::{
+    rendered_entrypoint_path, AuxiliaryMapping, CodeMapping, CodeSnapshot, CodeSource,
+    ConfigSource, PayloadBuild, TargetPermissions, CONFIG_TEMPLATE_EXTENSION,
+};
+use crate::run::default::render_template;
+use crate::GlobalConfig;
+use accounting::AccountedJob;
+use anyhow::{anyhow, bail, Context, Result};
 use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use cloud::CloudHost;
+use config::FileFormat;
+use disk_usage::DiskUsage;
+use facts::HostFacts;
 use git2::Repository;
 use local::LocalHost;
-use rsync::{copy_directory, SyncOptions};
-use slurm_cluster::{QuickRunPreparationOptions, SlurmClusterHost};
+use maintenance::Maintenance;
+use manifest::{Manifest, ManifestEntry, MANIFEST_FILE_NAME};
+use rsync::{copy_directory, try_copy_directory, SyncOptions, SyncStats};
+use slurm_cluster::{QuickRunPreparationOptions, SlurmClusterHost, SlurmClusterHostOptions};
+use status::HostStatus;
 use tempfile::NamedTempFile;
 use tempfile::TempDir;
+use timings::{RunTimings, MAX_TIMINGS_FILE_SIZE, TIMINGS_FILE_NAME};
+use upload_summary::{UploadSummary, UPLOAD_SUMMARY_FILE_NAME};
 use url::Url;
 
-pub trait Host {
+/// File in the run directory with the environment of the run, which the run script sources
+pub const ENVIRONMENT_FILE_NAME: &str = ".sparrow_env";
+/// File in the reproduce info of a run output to which the run script writes its exit code
+pub const EXIT_CODE_FILE_NAME: &str = "exit_code";
+const MAX_EXIT_CODE_FILE_SIZE: u64 = 64;
+/// File in the run output to which `sparrow run-signal` writes signals for the run
+pub const CONTROL_FILE_NAME: &str = ".sparrow_control";
+/// File in the reproduce info of a run output to which the run script writes its process group
+pub const PROCESS_GROUP_FILE_NAME: &str = "process_group";
+const MAX_PROCESS_GROUP_FILE_SIZE: u64 = 64;
+const MAX_RUN_DIR_FILE_SIZE: u64 = 4096;
+
+/// Script changing the group of `$1` to `$3` and the mode of its directories to `$2`, while
+/// making its files group-readable, descending at most `$4` levels, where empty modes or groups
+/// are left unchanged
+pub const PERMISSIONS_SCRIPT: &str = r#"
+set -e
+if [ -n "$3" ]; then find "$1" -maxdepth "$4" -exec chgrp "$3" {} +; fi
+if [ -n "$2" ]; then
+    find "$1" -maxdepth "$4" -type d -exec chmod "$2" {} +
+    find "$1" -maxdepth "$4" -type f -exec chmod g+r {} +
+fi
+"#;
+
+/// Arguments of [`PERMISSIONS_SCRIPT`] to apply `permissions` to `path`
+pub fn permissions_script_args(
+    path: &Path,
+    permissions: &OutputPermissionsConfig,
+    recursive: bool,
+) -> [String; 4] {
+    [
+        path.to_string(),
+        permissions
+            .mode
+            .as_ref()
+            .map(|mode| mode.0.clone())
+            .unwrap_or_default(),
+        permissions.group.clone().unwrap_or_default(),
+        String::from(if recursive { "1000" } else { "0" }),
+    ]
+}
+
+pub trait Host: Sync {
     fn id(&self) -> &str;
     fn hostname(&self) -> &str;
     fn script_run_command(&self, script_path: &str) -> String;
+    fn detached_run_command(
+        &self,
+        run_cmd: &str,
+        run_id: &RunID,
+        environment_variable_names: &[String],
+        attach: bool,
+    ) -> String;
     fn output_base_dir_path(&self) -> &Path;
     fn is_local(&self) -> bool;
     fn is_configured_for_quick_run(&self) -> bool;
+    /// Environment modules of the host by the name under which templates refer to them
+    fn modules(&self) -> &BTreeMap<String, String>;
+    /// Fail with instructions if runs cannot be launched on the host, like when the tmux they
+    /// are executed in is not installed
+    fn check_execution(&self) -> Result<()> {
+        Ok(())
+    }
+    fn facts(&self) -> Result<HostFacts>;
+    fn status(&self) -> Result<HostStatus>;
+    /// Jobs of the user that started within `since`, from the slurm accounting of the host
+    fn accounted_jobs(&self, since: std::time::Duration) -> Result<Vec<AccountedJob>>;
+    /// Disk usage of the outputs of `run_ids`, measured in parallel, and of their file system
+    fn disk_usage(&self, run_ids: &[RunID]) -> Result<DiskUsage>;
+    /// Scheduled maintenance reservations of the slurm cluster of the host
+    fn maintenance(&self) -> Result<Maintenance>;
 
     fn info(&self) -> HostInfo {
         HostInfo {
@@ -34,126 +129,389 @@ pub trait Host {
             run_output_base_dir_path: self.output_base_dir_path().to_owned(),
             is_local: self.is_local(),
             is_configured_for_quick_run: self.is_configured_for_quick_run(),
+            modules: self.modules().clone(),
+            facts: self.facts().unwrap_or_else(|err| {
+                eprintln!(
+                    "warning: exposing no facts of {} to templates: {err:#}",
+                    self.id()
+                );
+                HostFacts::default()
+            }),
         }
     }
 
     fn prepare_run_directory(
         &self,
-        code_mappings: &Vec<CodeMapping>,
-        auxiliary_mappings: &Vec<AuxiliaryMapping>,
+        code_mappings: &[CodeMapping],
+        auxiliary_mappings: &[AuxiliaryMapping],
+        payload_build: &PayloadBuild,
         run_script: NamedTempFile,
-    ) -> RunDirectory {
-        let payload_prep_dir = TempDir::new().expect("failed to create temporary directory");
+        environment_file: Option<NamedTempFile>,
+        run_id: &RunID,
+    ) -> Result<(RunDirectory, UploadSummary)> {
+        // the preparation directory is removed when it is dropped on any of the error paths below
+        let payload_prep_dir =
+            TempDir::new().context("failed to create temporary payload directory")?;
+        let manifest = prepare_payload_directory(
+            code_mappings,
+            auxiliary_mappings,
+            payload_build,
+            run_script.utf8_path(),
+            payload_prep_dir.utf8_path(),
+        )?;
 
-        for code_mapping in code_mappings {
-            prepare_code(code_mapping, payload_prep_dir.utf8_path());
-        }
+        let mappings = payload_mapping_targets(code_mappings, auxiliary_mappings);
+        let auxiliary_target_paths = auxiliary_mappings
+            .iter()
+            .map(|auxiliary_mapping| auxiliary_mapping.target_path.clone())
+            .collect::<Vec<_>>();
+        self.upload_prepared_run_directory(
+            payload_prep_dir,
+            &manifest,
+            &mappings,
+            &auxiliary_target_paths,
+            environment_file,
+            run_id,
+        )
+    }
 
-        for auxiliary_mapping in auxiliary_mappings {
-            copy_directory(
-                &auxiliary_mapping.source_path,
-                &payload_prep_dir
-                    .utf8_path()
-                    .join(&auxiliary_mapping.target_path),
-                SyncOptions::default()
-                    .copy_contents()
-                    .exclude(&auxiliary_mapping.copy_excludes),
-            );
+    /// Upload the payload prepared in `payload_prep_dir`, which is described by `manifest`, as
+    /// run directory together with `environment_file`, where `mappings` are the names and target
+    /// paths of the mappings in the payload and files below `auxiliary_target_paths` are taken
+    /// from the content store of the host if it deduplicates them
+    fn upload_prepared_run_directory(
+        &self,
+        payload_prep_dir: TempDir,
+        manifest: &Manifest,
+        mappings: &[(String, PathBuf)],
+        auxiliary_target_paths: &[PathBuf],
+        environment_file: Option<NamedTempFile>,
+        run_id: &RunID,
+    ) -> Result<(RunDirectory, UploadSummary)> {
+        // the environment may contain secrets, so keep it out of the manifest
+        if let Some(environment_file) = environment_file {
+            let environment_dest_path = payload_prep_dir.utf8_path().join(ENVIRONMENT_FILE_NAME);
+            std::fs::copy(&environment_file, &environment_dest_path).context(format!(
+                "failed to copy {} to {environment_dest_path}",
+                environment_file.utf8_path()
+            ))?;
         }
 
-        let run_script_dest_path = payload_prep_dir.utf8_path().join("run.sh");
-        std::fs::copy(&run_script, &run_script_dest_path).expect(&format!(
-            "expected copy from {} to {} to work",
-            run_script.utf8_path(),
-            run_script_dest_path
-        ));
+        let deduplicated_files = if self.content_store_path().is_some() {
+            manifest
+                .files
+                .iter()
+                .filter(|entry| {
+                    auxiliary_target_paths
+                        .iter()
+                        .any(|target_path| entry.path.starts_with(target_path))
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mappings = mappings
+            .iter()
+            .map(|(name, target_path)| (name.clone(), target_path.as_path()))
+            .collect::<Vec<_>>();
+        let upload_start = std::time::Instant::now();
+        let (run_dir, stats) = self.upload_run_dir(payload_prep_dir, run_id, &deduplicated_files);
+        let upload_summary = UploadSummary::new(
+            manifest,
+            &mappings,
+            stats,
+            upload_start.elapsed(),
+            run_dir.path(),
+        );
 
-        return self.upload_run_dir(payload_prep_dir);
+        Ok((run_dir, upload_summary))
     }
 
-    fn upload_run_dir(&self, prep_dir_path: TempDir) -> RunDirectory;
+    /// Move the prepared run directory to the host, returning the transfer statistics if it had
+    /// to be uploaded; `deduplicated_files` are to be taken from the content store of the host
+    fn upload_run_dir(
+        &self,
+        prep_dir_path: TempDir,
+        run_id: &RunID,
+        deduplicated_files: &[&ManifestEntry],
+    ) -> (RunDirectory, Option<SyncStats>);
+    /// Content-addressed store for files of auxiliary mappings, if the host deduplicates them
+    fn content_store_path(&self) -> Option<PathBuf> {
+        None
+    }
+    /// Upload `files` of the directory at `dir_path` into the content store of the host
+    /// independent of any run, skipping files that are already stored, so that later runs link
+    /// them instead of uploading them again; callers check for a [`Host::content_store_path`]
+    /// first, since hosts without one push nothing
+    fn push_to_content_store(
+        &self,
+        _dir_path: &Path,
+        _files: &[&ManifestEntry],
+    ) -> Result<Option<SyncStats>> {
+        Ok(None)
+    }
+    /// Remove the objects of the content store that no run directory links anymore and whose
+    /// link count last changed at least `min_age` ago, returning the sizes of the removed objects;
+    /// callers check for a [`Host::content_store_path`] first, since hosts without one have
+    /// nothing to prune
+    fn prune_content_store(&self, _min_age: std::time::Duration) -> Result<Vec<u64>> {
+        Ok(Vec::new())
+    }
     fn download_config_dir(&self, local: &LocalHost, run_id: &RunID) -> Result<PathBuf>;
 
+    /// Upload the config reviewed into `review_dir_path` and the code versions as those of
+    /// `run_id`
     fn prepare_config_directory(
         &self,
-        config_mapping: &ConfigSource,
+        review_dir_path: &Path,
         run_id: &RunID,
         code_versions: HashMap<String, String>,
-        review: bool,
-    ) {
-        let review_dir = TempDir::new().expect("expected temporary directory creation to work");
-
-        copy_directory(
-            &config_mapping.dir_path,
-            &review_dir.utf8_path(),
-            SyncOptions::default().copy_contents().resolve_symlinks(),
-        );
-
-        if review {
-            let entry_path = review_dir.utf8_path().join(&config_mapping.entrypoint_path);
-            review_config(review_dir.utf8_path(), &entry_path);
-        }
-
-        self.create_dir_all(&self.config_dir_destination_path(run_id));
+        delete: ConfigUploadDelete,
+    ) -> Result<()> {
+        self.upload_config_directory(review_dir_path, run_id, delete);
 
         let mut versions_file =
             NamedTempFile::new().expect("expecte temporary file creation to work");
         versions_file
-            .write_all(
-                code_versions
-                    .iter()
-                    .fold(String::new(), |output, (code_source_id, version)| {
-                        output + &format!("{} = {}\n", code_source_id, version)
-                    })
-                    .as_bytes(),
-            )
+            .write_all(format_code_versions(&code_versions).as_bytes())
             .expect("expected writing to temporary file to work");
-
-        self.put(
-            review_dir.utf8_path(),
-            &self.config_dir_destination_path(run_id),
-            SyncOptions::default().copy_contents().delete(),
-        );
-
         self.put(
             versions_file.utf8_path(),
             &self.code_versions_file_destination_path(run_id),
             SyncOptions::default(),
-        )
+        );
+
+        Ok(())
     }
 
-    fn config_dir_destination_path(&self, run_id: &RunID) -> PathBuf {
+    /// Upload the reviewed config directory at `review_dir_path` as config of `run_id`, where
+    /// `delete` decides what happens to config files of a previous submission that are gone
+    fn upload_config_directory(
+        &self,
+        review_dir_path: &Path,
+        run_id: &RunID,
+        delete: ConfigUploadDelete,
+    ) {
+        self.create_dir_all(&self.config_dir_destination_path(run_id));
+
+        let upload_options = match delete {
+            ConfigUploadDelete::Delete => SyncOptions::default().copy_contents().delete(),
+            ConfigUploadDelete::Keep => SyncOptions::default().copy_contents(),
+            ConfigUploadDelete::Backup => {
+                SyncOptions::default().copy_contents().delete().backup_dir(
+                    &self.reproduce_info_path(run_id).join("config_backup").join(
+                        humantime::format_rfc3339_seconds(std::time::SystemTime::now()).to_string(),
+                    ),
+                )
+            }
+        };
+        self.put(
+            review_dir_path,
+            &self.config_dir_destination_path(run_id),
+            upload_options,
+        );
+    }
+
+    fn reproduce_info_path(&self, run_id: &RunID) -> PathBuf {
         run_id
             .path(self.output_base_dir_path())
-            .join("reproduce_info/config")
+            .join("reproduce_info")
+    }
+    fn config_dir_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id).join("config")
     }
     fn code_versions_file_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id).join("code_versions.txt")
+    }
+    fn run_context_file_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id).join("run_context.json")
+    }
+    fn tags_file_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id).join("tags.txt")
+    }
+    fn submodule_diffs_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id).join("submodule_diffs")
+    }
+    fn notes_file_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id).join("notes.md")
+    }
+    fn parent_file_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id).join("parent.txt")
+    }
+    /// Parent run of `run_id` as recorded on submission, or `None` if it has no parent
+    fn recorded_parent(&self, run_id: &RunID) -> Option<RunID> {
+        self.read_file_head(
+            &self.parent_file_destination_path(run_id),
+            MAX_RUN_DIR_FILE_SIZE,
+        )
+        .ok()
+        .and_then(|content| RunID::parse(String::from_utf8_lossy(&content).trim()).ok())
+    }
+    fn run_dir_file_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id).join("run_dir.txt")
+    }
+    /// Run directory of `run_id` as recorded on submission
+    fn recorded_run_dir_path(&self, run_id: &RunID) -> Result<PathBuf> {
+        let content = self
+            .read_file_head(
+                &self.run_dir_file_destination_path(run_id),
+                MAX_RUN_DIR_FILE_SIZE,
+            )
+            .context(format!("found no recorded run directory of {run_id}"))?;
+        Ok(PathBuf::from(String::from_utf8_lossy(&content).trim()))
+    }
+    fn exit_code_file_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id).join(EXIT_CODE_FILE_NAME)
+    }
+    /// Exit code recorded by the run script of `run_id`, or `None` if it has not exited yet
+    fn recorded_exit_code(&self, run_id: &RunID) -> Option<i32> {
+        self.read_file_head(
+            &self.exit_code_file_destination_path(run_id),
+            MAX_EXIT_CODE_FILE_SIZE,
+        )
+        .ok()
+        .and_then(|content| String::from_utf8_lossy(&content).trim().parse().ok())
+    }
+    fn timings_file_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id).join(TIMINGS_FILE_NAME)
+    }
+    /// Submission, start and end times of `run_id`, or `None` for runs submitted by older versions
+    /// of sparrow
+    fn recorded_timings(&self, run_id: &RunID) -> Option<RunTimings> {
+        self.read_file_head(
+            &self.timings_file_destination_path(run_id),
+            MAX_TIMINGS_FILE_SIZE,
+        )
+        .ok()
+        .map(|content| RunTimings::parse(&String::from_utf8_lossy(&content)))
+    }
+    fn control_file_destination_path(&self, run_id: &RunID) -> PathBuf {
         run_id
             .path(self.output_base_dir_path())
-            .join("reproduce_info/code_versions.txt")
+            .join(CONTROL_FILE_NAME)
+    }
+    /// Process group recorded by the run script of `run_id`, or `None` if it has not started yet
+    fn recorded_process_group(&self, run_id: &RunID) -> Option<i32> {
+        self.read_file_head(
+            &self
+                .reproduce_info_path(run_id)
+                .join(PROCESS_GROUP_FILE_NAME),
+            MAX_PROCESS_GROUP_FILE_SIZE,
+        )
+        .ok()
+        .and_then(|content| String::from_utf8_lossy(&content).trim().parse().ok())
+    }
+    fn payload_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id).join("payload")
+    }
+    fn upload_summary_file_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id)
+            .join(UPLOAD_SUMMARY_FILE_NAME)
+    }
+    fn run_script_file_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id).join("run_script.sh")
+    }
+    fn run_script_fingerprints_file_destination_path(&self, run_id: &RunID) -> PathBuf {
+        self.reproduce_info_path(run_id)
+            .join("run_script_fingerprints.txt")
     }
 
-    fn put(&self, local_path: &Path, host_path: &Path, options: SyncOptions);
+    fn put(&self, local_path: &Path, host_path: &Path, options: SyncOptions) {
+        self.try_put(local_path, host_path, options)
+            .expect("rsync should not fail");
+    }
+    /// Like [`Host::put`], but leaving it to the caller to handle a failed transfer
+    fn try_put(&self, local_path: &Path, host_path: &Path, options: SyncOptions) -> Result<()>;
+    /// Download `host_path` to `local_path`, which fails if it does not exist on the host
+    fn try_get(&self, host_path: &Path, local_path: &Path, options: SyncOptions) -> Result<()>;
     #[allow(unused)]
     fn create_dir(&self, path: &Path);
     fn create_dir_all(&self, path: &Path);
+    fn exists(&self, path: &Path) -> Result<bool>;
+    fn remove_dir_all(&self, path: &Path) -> Result<()>;
+    fn remove_file(&self, path: &Path) -> Result<()>;
+    fn move_path(&self, source_path: &Path, destination_path: &Path) -> Result<()>;
+    /// Apply `permissions` to `path` and, if `recursive`, to everything below it
+    fn set_permissions(
+        &self,
+        path: &Path,
+        permissions: &OutputPermissionsConfig,
+        recursive: bool,
+    ) -> Result<()>;
+    /// Copy the contents of the directory `source_path` into `destination_path` on the host,
+    /// leaving out what matches one of the rsync patterns `excludes`
+    fn copy_dir(
+        &self,
+        source_path: &Path,
+        destination_path: &Path,
+        excludes: &[String],
+    ) -> Result<()>;
+    /// Copy the contents of the directory `source_path` into `destination_path` on the host that
+    /// this host reaches via ssh as `hostname`, without passing through the local machine
+    fn copy_dir_to_host(
+        &self,
+        _source_path: &Path,
+        _hostname: &str,
+        _destination_path: &Path,
+    ) -> Result<()> {
+        bail!("cannot copy directly from {} to other hosts", self.id())
+    }
 
     fn prepare_quick_run(&self, options: &QuickRunPrepOptions) -> Result<()>;
+    fn estimate_quick_run(&self, options: &QuickRunPrepOptions) -> Result<QuickRunEstimate>;
     #[allow(unused)]
     fn quick_run_is_prepared(&self) -> Result<bool>;
     fn clear_preparation(&self);
 
+    /// Give back the resources held for this host once all of its runs have finished
+    fn release(&self) -> Result<()> {
+        bail!(
+            "{} is not a cloud host, so there is nothing to tear down",
+            self.id()
+        )
+    }
+
     fn runs(&self) -> Result<Vec<RunID>>;
+    /// Tags of all runs that have any, read from their tags files
+    fn run_tags(&self) -> Result<HashMap<RunID, Vec<RunTag>>>;
+    /// Parent runs by run, for all runs that recorded a parent
+    fn run_parents(&self) -> Result<HashMap<RunID, RunID>>;
     fn running_runs(&self) -> Vec<RunID>;
+    /// Run the shell `command` on the host with the standard streams of sparrow, in
+    /// `working_dir_path` if given, returning its exit status
+    fn execute(
+        &self,
+        command: &str,
+        working_dir_path: Option<&Path>,
+    ) -> Result<std::process::ExitStatus>;
+    /// Kill the sessions that were kept open after their run recorded a successful exit, which
+    /// would count as running otherwise, returning the runs of the killed sessions
+    fn clean_finished_sessions(&self) -> Result<Vec<RunID>>;
+    /// Rename the session a run was executed in, if it is still open, and its registration after
+    /// the run was renamed from `run_id` to `new_run_id`; hosts without sessions have nothing to
+    /// rename
+    fn rename_session(&self, _run_id: &RunID, _new_run_id: &RunID) -> Result<()> {
+        Ok(())
+    }
+    /// Send the POSIX `signal`, like `TERM`, to all processes of `process_group` on the host
+    fn signal_process_group(&self, process_group: i32, signal: &str) -> Result<()>;
     fn log_file_paths(&self, run_id: &RunID) -> Vec<PathBuf>;
     fn attach(&self, run_id: &RunID);
+    /// Sync the output of `run_id` to below `local_base_path`, returning the transfer statistics
+    /// unless the progress is shown instead
     fn sync(
         &self,
         run_id: &RunID,
         local_base_path: &Path,
         options: &RunOutputSyncOptions,
-    ) -> Result<(), String>;
+    ) -> Result<Option<SyncStats>, SyncError>;
     fn tail_log(&self, run_id: &RunID, log_file_path: &Path, follow: bool);
+
+    fn list_dir(&self, dir_path: &Path) -> Result<Vec<DirListingEntry>>;
+    /// Sizes of all regular files below `dir_path` by their path relative to it
+    fn file_sizes(&self, dir_path: &Path) -> Result<BTreeMap<PathBuf, u64>>;
+    fn read_file_head(&self, file_path: &Path, max_size: u64) -> Result<Vec<u8>>;
 }
 
 pub enum RunDirectory {
@@ -200,12 +558,94 @@ impl QuickRunPrepOptions {
     }
 }
 
+pub struct IdleResources {
+    pub partition: String,
+    pub features: Vec<String>,
+    pub idle_node_count: u32,
+    pub generic_resources: String,
+}
+
+pub struct QuickRunEstimate {
+    pub expected_start: Option<String>,
+    pub expected_partition: Option<String>,
+    pub idle_alternatives: Vec<IdleResources>,
+}
+
+impl std::fmt::Display for QuickRunEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.expected_start, &self.expected_partition) {
+            (Some(start), Some(partition)) => {
+                writeln!(f, "expected start: {start} (partition {partition})")?
+            }
+            (Some(start), None) => writeln!(f, "expected start: {start}")?,
+            (None, _) => writeln!(f, "expected start: unknown")?,
+        }
+
+        if self.idle_alternatives.is_empty() {
+            return writeln!(f, "no idle nodes matching the requested resources");
+        }
+
+        writeln!(f, "idle nodes:")?;
+        for resources in &self.idle_alternatives {
+            write!(
+                f,
+                "    partition {}: {} idle node(s), gres {}",
+                resources.partition, resources.idle_node_count, resources.generic_resources
+            )?;
+            if !resources.features.is_empty() {
+                write!(f, ", constraints {}", resources.features.join(","))?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct DirListingEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+impl std::fmt::Display for DirListingEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_dir {
+            write!(f, "{}/", self.name)
+        } else {
+            write!(f, "{} ({} B)", self.name, self.size)
+        }
+    }
+}
+
+/// Why syncing the output of a run failed
+pub enum SyncError {
+    /// The local run output was not synced from the host, so syncing would overwrite it
+    Conflict(String),
+    /// Transferring the run output failed, e.g. because the connection dropped
+    Transfer(String),
+}
+
+impl std::fmt::Display for SyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncError::Conflict(message) | SyncError::Transfer(message) => write!(f, "{message}"),
+        }
+    }
+}
+
 pub struct RunOutputSyncOptions {
     pub excludes: Vec<String>,
+    pub includes: Vec<String>,
+    /// Skip files larger than this, which also leaves out directories that end up empty
+    pub max_size: Option<String>,
     pub ignore_from_remote_marker: bool,
+    pub show_progress: bool,
 }
 
-#[derive(serde::Serialize, Clone)]
+#[derive(
+    serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone, PartialEq, Eq, Hash,
+)]
 pub struct RunID {
     pub name: String,
     pub group: String,
@@ -219,6 +659,14 @@ impl RunID {
         }
     }
 
+    /// Parse a run id given as `<group>/<name>`
+    pub fn parse(run: &str) -> Result<Self> {
+        let (group, name) = run.split_once('/').ok_or(anyhow!(
+            "expected run `{run}' to be given as <group>/<name>"
+        ))?;
+        Ok(Self::new(name, group))
+    }
+
     pub fn path<P: Into<PathBuf>>(&self, base_path: P) -> PathBuf {
         base_path
             .into()
@@ -233,13 +681,59 @@ impl std::fmt::Display for RunID {
     }
 }
 
-#[derive(serde::Serialize)]
+pub type RunTag = (String, String);
+
+pub fn format_tags(tags: &[RunTag]) -> String {
+    tags.iter()
+        .map(|(key, value)| format!("{key} = {value}\n"))
+        .collect()
+}
+
+pub fn parse_tags(content: &str) -> Result<Vec<RunTag>> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            line.split_once(" = ")
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                .ok_or(anyhow!("found malformed tag line `{line}'"))
+        })
+        .collect()
+}
+
+/// Keep only the runs that carry all of the given tags
+pub fn filter_runs_by_tags(
+    host: &dyn Host,
+    run_ids: Vec<RunID>,
+    tags: &[RunTag],
+) -> Result<Vec<RunID>> {
+    if tags.is_empty() {
+        return Ok(run_ids);
+    }
+
+    let run_tags = host
+        .run_tags()
+        .context(format!("failed to obtain run tags from {}", host.id()))?;
+    Ok(run_ids
+        .into_iter()
+        .filter(|run_id| {
+            run_tags
+                .get(run_id)
+                .is_some_and(|run_tags| tags.iter().all(|tag| run_tags.contains(tag)))
+        })
+        .collect())
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
 pub struct HostInfo {
     pub id: String,
     pub hostname: String,
+    #[schemars(with = "String")]
     pub run_output_base_dir_path: PathBuf,
     pub is_local: bool,
     pub is_configured_for_quick_run: bool,
+    pub modules: BTreeMap<String, String>,
+    pub facts: HostFacts,
 }
 
 pub fn build_local_host(local_config: &LocalHostConfig) -> LocalHost {
@@ -249,91 +743,615 @@ pub fn build_local_host(local_config: &LocalHostConfig) -> LocalHost {
             .script_run_command_template
             .clone()
             .unwrap_or(String::from("bash {}")),
+        local_config
+            .modules
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .collect(),
     )
 }
 
+/// What a host is built for, which decides whether a cloud host is provisioned for it
+#[derive(Clone, Copy, PartialEq)]
+pub enum HostPurpose {
+    /// Inspecting or managing existing runs, which fails for cloud hosts that are not provisioned
+    Inspection,
+    /// Submitting runs, which provisions cloud hosts that are not provisioned yet
+    Submission,
+}
+
+impl GlobalConfig {
+    /// Build the host `host_id` for inspecting or managing the runs on it
+    pub fn build_host(&self, host_id: &str) -> Result<Box<dyn Host>> {
+        build_host(host_id, self, HostPurpose::Inspection, false)
+    }
+}
+
 pub fn build_host(
     host_id: &str,
-    local_config: &LocalHostConfig,
-    remote_configs: &HashMap<String, RemoteHostConfig>,
+    config: &GlobalConfig,
+    purpose: HostPurpose,
     configure_for_quick_run: bool,
 ) -> Result<Box<dyn Host>> {
+    let remote_configs = &config.remote_hosts;
     if host_id == "local" && configure_for_quick_run {
         bail!("Cannot use --enforce-quick with the local host");
     }
+    if config.cloud_hosts.contains_key(host_id) && configure_for_quick_run {
+        bail!("Cannot use --enforce-quick with the cloud host {host_id}");
+    }
 
     if host_id == "local" {
-        Ok(Box::new(build_local_host(local_config)))
-    } else if remote_configs.contains_key(host_id) {
+        Ok(Box::new(build_local_host(&config.local_host)))
+    } else if let Some(cloud_config) = config.cloud_hosts.get(host_id) {
+        Ok(Box::new(
+            CloudHost::new(host_id, cloud_config, purpose == HostPurpose::Submission)
+                .exit_status(ExitStatus::Connection)?,
+        ))
+    } else {
+        let host_id = resolve_host_id(host_id, remote_configs).exit_status(ExitStatus::Config)?;
+        if let Some(auth) = &remote_configs[host_id].auth {
+            auth::authenticate(host_id, auth)
+                .context(format!("failed to authenticate for {host_id}"))
+                .exit_status(ExitStatus::Connection)?;
+        }
+        let remote_config = &remote_configs[host_id];
         Ok(Box::new(SlurmClusterHost::new(
             &host_id,
-            remote_configs[host_id].hostname.as_str(),
-            remote_configs[host_id]
-                .script_run_command_template
-                .clone()
-                .unwrap_or(String::from("bash {}")),
-            remote_configs[host_id].run_output_base_dir.as_path(),
-            remote_configs[host_id].temporary_dir.as_path(),
+            remote_config.hostname.as_str(),
+            SlurmClusterHostOptions {
+                script_run_command_template: remote_config
+                    .script_run_command_template
+                    .clone()
+                    .unwrap_or(String::from("bash {}")),
+                output_base_dir_path: remote_config.run_output_base_dir.clone(),
+                temporary_dir_path: remote_config.temporary_dir.clone(),
+                run_dir_name_template: remote_config.temporary_run_dir_template.clone(),
+                deduplicate_auxiliary: remote_config.deduplicate_auxiliary.unwrap_or(false),
+                execution: remote_config.execution.unwrap_or_default(),
+                tmux_keep_open_on: remote_config
+                    .tmux
+                    .as_ref()
+                    .and_then(|tmux| tmux.keep_open_on)
+                    .unwrap_or_default(),
+                tmux_fallback: remote_config
+                    .tmux
+                    .as_ref()
+                    .and_then(|tmux| tmux.fallback)
+                    .unwrap_or_default(),
+                modules: remote_config
+                    .modules
+                    .clone()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect(),
+                remote_command_wrapper: remote_config.remote_command_wrapper.clone(),
+            },
             QuickRunPreparationOptions {
-                slurm_account: remote_configs[host_id].quick_run.account.clone(),
-                slurm_service_quality: remote_configs[host_id].quick_run.service_quality.clone(),
-                node_local_storage_path: remote_configs[host_id]
-                    .quick_run
-                    .node_local_storage_path
-                    .clone(),
+                slurm_account: remote_config.quick_run.account.clone(),
+                slurm_service_quality: remote_config.quick_run.service_quality.clone(),
+                node_local_storage_path: remote_config.quick_run.node_local_storage_path.clone(),
             },
             configure_for_quick_run,
-        )))
-    } else {
-        bail!("Host id `{host_id}` not found in remote hosts configuration");
+        )?))
+    }
+}
+
+pub fn resolve_host_id<'c>(
+    host_id: &str,
+    remote_configs: &'c HashMap<String, RemoteHostConfig>,
+) -> Result<&'c str> {
+    if let Some((id, _)) = remote_configs.get_key_value(host_id) {
+        return Ok(id);
+    }
+    if remote_configs.is_empty() {
+        bail!(
+            "found no host `{host_id}'{}, since there are no remote_hosts in the configuration; \
+            add it there or use `local'",
+            suggest_closest(host_id, ["local"])
+        );
+    }
+
+    let alias_matches = remote_configs
+        .iter()
+        .filter(|(_, config)| {
+            config
+                .aliases
+                .as_ref()
+                .is_some_and(|aliases| aliases.iter().any(|alias| alias == host_id))
+        })
+        .map(|(id, _)| id.as_str())
+        .collect::<Vec<_>>();
+
+    match alias_matches.as_slice() {
+        [id] => Ok(id),
+        [] => {
+            let mut ids = remote_configs
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            ids.sort();
+            let aliases = remote_configs
+                .values()
+                .flat_map(|config| config.aliases.iter().flatten())
+                .map(String::as_str);
+            bail!(
+                "found no host `{host_id}' in the remote_hosts of the configuration{}, \
+                available hosts are local, {}",
+                suggest_closest(
+                    host_id,
+                    ["local"]
+                        .into_iter()
+                        .chain(ids.iter().copied())
+                        .chain(aliases)
+                ),
+                ids.join(", ")
+            )
+        }
+        ids => bail!(
+            "Host alias `{host_id}` is ambiguous, it is used by {}",
+            ids.join(", ")
+        ),
+    }
+}
+
+/// Find the run `run`, given as `<group>/<name>`, among `run_ids`, suggesting the closest run
+/// name of its group if it does not exist
+pub fn resolve_run_id(run: &str, run_ids: &[RunID]) -> Result<RunID> {
+    let run_id = RunID::parse(run)?;
+    if run_ids.contains(&run_id) {
+        return Ok(run_id);
+    }
+
+    ensure_run_group_exists(&run_id.group, run_ids)?;
+    let names = run_ids
+        .iter()
+        .filter(|other| other.group == run_id.group)
+        .map(|other| other.name.as_str());
+    bail!(
+        "found no run `{}' in group `{}'{}",
+        run_id.name,
+        run_id.group,
+        suggest_closest(&run_id.name, names)
+    )
+}
+
+/// Fail if none of `run_ids` is in `group`, suggesting the closest existing group
+pub fn ensure_run_group_exists(group: &str, run_ids: &[RunID]) -> Result<()> {
+    if run_ids.iter().any(|run_id| run_id.group == group) {
+        return Ok(());
+    }
+    if run_ids.is_empty() {
+        bail!("found no run group `{group}', since there are no runs yet");
+    }
+
+    let groups = run_ids
+        .iter()
+        .map(|run_id| run_id.group.as_str())
+        .collect::<BTreeSet<_>>();
+    bail!(
+        "found no run group `{group}'{}, existing groups are {}",
+        suggest_closest(group, groups.iter().copied()),
+        groups.into_iter().collect::<Vec<_>>().join(", ")
+    )
+}
+
+/// Prepare the code mappings in `prep_dir` in parallel, where a failing mapping does not stop
+/// the others, so that the failures of all of them are reported together
+fn prepare_code_mappings(code_mappings: &[CodeMapping], prep_dir: &Path) -> Result<()> {
+    let mapping_count = code_mappings.len();
+    let finished_count = Mutex::new(0);
+    let failures = std::thread::scope(|scope| {
+        let preparations = code_mappings
+            .iter()
+            .map(|code_mapping| {
+                let finished_count = &finished_count;
+                scope.spawn(move || {
+                    let result = prepare_code(code_mapping, prep_dir);
+                    let mut finished_count = finished_count.lock().unwrap();
+                    *finished_count += 1;
+                    println!(
+                        "[{finished_count}/{mapping_count}] {} {}",
+                        if result.is_ok() { "prepared" } else { "failed" },
+                        code_mapping.id
+                    );
+                    result
+                })
+            })
+            .collect::<Vec<_>>();
+
+        preparations
+            .into_iter()
+            .zip(code_mappings)
+            .filter_map(|(preparation, code_mapping)| {
+                let result = preparation.join().unwrap_or_else(|panic| {
+                    let message = panic
+                        .downcast_ref::<String>()
+                        .map(String::as_str)
+                        .or(panic.downcast_ref::<&str>().copied())
+                        .unwrap_or("unknown panic");
+                    Err(anyhow!("panicked: {message}"))
+                });
+                let err = result.err()?;
+                Some(format!(
+                    "    {} from {}: {err:#}",
+                    code_mapping.id, code_mapping.source
+                ))
+            })
+            .collect::<Vec<_>>()
+    });
+
+    if !failures.is_empty() {
+        bail!(
+            "failed to prepare {} of {mapping_count} code mappings:\n{}",
+            failures.len(),
+            failures.join("\n")
+        );
     }
+    Ok(())
 }
 
-fn prepare_code(code_mapping: &CodeMapping, prep_dir: &Path) {
+fn prepare_code(code_mapping: &CodeMapping, prep_dir: &Path) -> Result<()> {
     assert!(code_mapping.target_path.is_relative());
 
+    let target_path = prep_dir.join(code_mapping.target_path.as_path());
+    let copy_to_target = |source_path: &Path, options: SyncOptions| {
+        try_copy_directory(
+            source_path,
+            &target_path,
+            with_target_permissions(options, &code_mapping.target_permissions),
+        )
+        .map_err(anyhow::Error::from)
+    };
+
     match &code_mapping.source {
         CodeSource::Local {
             path,
             copy_excludes,
+            snapshot: Some(snapshot),
         } => {
-            copy_directory(
-                path.as_path(),
-                &prep_dir.join(code_mapping.target_path.as_path()),
+            let snapshot_dir = TempDir::new().context("failed to create temporary directory")?;
+            let exported_path = export_snapshot(path, snapshot, snapshot_dir.utf8_path())?;
+            copy_to_target(
+                &exported_path,
                 SyncOptions::default()
                     .copy_contents()
-                    .exclude(&copy_excludes),
-            );
+                    .exclude(copy_excludes),
+            )
         }
-        CodeSource::Remote { url, git_revision } => {
+        CodeSource::Local {
+            path,
+            copy_excludes,
+            snapshot: None,
+        } => copy_to_target(
+            path.as_path(),
+            SyncOptions::default()
+                .copy_contents()
+                .exclude(copy_excludes),
+        ),
+        CodeSource::Remote {
+            url,
+            git_revision,
+            submodules,
+        } => {
+            let ssh_key_path = format!("{}/.ssh/id_ed25519", std::env::var("HOME").unwrap());
+            if code_mapping.target_permissions.is_default() {
+                return unpack_revision(
+                    url,
+                    git_revision.as_str(),
+                    &target_path,
+                    Path::new(&ssh_key_path),
+                    *submodules,
+                );
+            }
+
+            // permissions are adjusted by rsync, so unpack somewhere else first
+            let unpack_dir = TempDir::new().context("failed to create temporary directory")?;
             unpack_revision(
-                &url,
+                url,
                 git_revision.as_str(),
-                &prep_dir.join(code_mapping.target_path.as_path()),
-                Path::new(&format!(
-                    "{}/.ssh/id_ed25519",
-                    std::env::var("HOME").unwrap()
-                )),
+                unpack_dir.utf8_path(),
+                Path::new(&ssh_key_path),
+                *submodules,
+            )?;
+            copy_to_target(
+                unpack_dir.utf8_path(),
+                SyncOptions::default().copy_contents(),
+            )
+        }
+    }
+}
+
+fn with_target_permissions(options: SyncOptions, permissions: &TargetPermissions) -> SyncOptions {
+    let options = match permissions.preserve {
+        Some(preserve) => options.preserve_permissions(preserve),
+        None => options,
+    };
+    match &permissions.chmod {
+        Some(chmod) => options.chmod(chmod),
+        None => options,
+    }
+}
+
+/// Copy the code and auxiliary mappings into `prep_dir_path`, run the build steps and add the
+/// run script, returning the manifest of the payload, which is also written into it
+pub fn prepare_payload_directory(
+    code_mappings: &[CodeMapping],
+    auxiliary_mappings: &[AuxiliaryMapping],
+    payload_build: &PayloadBuild,
+    run_script_path: &Path,
+    prep_dir_path: &Path,
+) -> Result<Manifest> {
+    prepare_code_mappings(code_mappings, prep_dir_path)?;
+    prepare_auxiliary_mappings(auxiliary_mappings, prep_dir_path);
+
+    payload_build
+        .run(prep_dir_path)
+        .context("failed to build the payload")?;
+
+    let run_script_dest_path = prep_dir_path.join("run.sh");
+    std::fs::copy(run_script_path, &run_script_dest_path).context(format!(
+        "failed to copy {run_script_path} to {run_script_dest_path}"
+    ))?;
+
+    // build the manifest last, so that it covers the complete payload
+    let manifest =
+        Manifest::build(prep_dir_path).context("failed to build the run directory manifest")?;
+    manifest
+        .write(&prep_dir_path.join(MANIFEST_FILE_NAME))
+        .context("failed to write the run directory manifest")?;
+
+    Ok(manifest)
+}
+
+/// Copy the auxiliary mappings to their target paths below `prep_dir_path`
+pub fn prepare_auxiliary_mappings(auxiliary_mappings: &[AuxiliaryMapping], prep_dir_path: &Path) {
+    for auxiliary_mapping in auxiliary_mappings {
+        copy_directory(
+            &auxiliary_mapping.source_path,
+            &prep_dir_path.join(&auxiliary_mapping.target_path),
+            with_target_permissions(
+                SyncOptions::default()
+                    .copy_contents()
+                    .exclude(&auxiliary_mapping.copy_excludes),
+                &auxiliary_mapping.target_permissions,
+            ),
+        );
+    }
+}
+
+/// Names and target paths of the code and auxiliary mappings of a payload
+pub fn payload_mapping_targets(
+    code_mappings: &[CodeMapping],
+    auxiliary_mappings: &[AuxiliaryMapping],
+) -> Vec<(String, PathBuf)> {
+    code_mappings
+        .iter()
+        .map(|code_mapping| (code_mapping.id.clone(), code_mapping.target_path.clone()))
+        .chain(auxiliary_mappings.iter().map(|auxiliary_mapping| {
+            (
+                auxiliary_mapping.target_path.to_string(),
+                auxiliary_mapping.target_path.clone(),
+            )
+        }))
+        .collect()
+}
+
+/// Copy the config directory of `config_mapping` into `review_dir_path`, render its config
+/// templates against `template_context` and review it, checking the syntax of its entrypoints
+pub fn review_config_directory(
+    config_mapping: &ConfigSource,
+    review_dir_path: &Path,
+    review_mode: ReviewMode,
+    question: Option<&str>,
+    template_context: minijinja::Value,
+) -> Result<()> {
+    copy_directory(
+        &config_mapping.dir_path,
+        review_dir_path,
+        SyncOptions::default().copy_contents().resolve_symlinks(),
+    );
+
+    for entrypoint_path in &config_mapping.entrypoint_paths {
+        if entrypoint_path.extension() != Some(CONFIG_TEMPLATE_EXTENSION) {
+            continue;
+        }
+        render_config_template(
+            &review_dir_path.join(entrypoint_path),
+            template_context.clone(),
+        )
+        .context(format!(
+            "failed to render the config template {entrypoint_path}"
+        ))
+        .exit_status(ExitStatus::Config)?;
+    }
+
+    let entrypoint_paths = config_mapping.rendered_entrypoint_paths();
+    review_config(review_dir_path, &entrypoint_paths, review_mode, question)?;
+    for entrypoint_path in &entrypoint_paths {
+        check_config_syntax(&review_dir_path.join(entrypoint_path))
+            .context(format!(
+                "found a syntax error in the config entrypoint {entrypoint_path}"
+            ))
+            .exit_status(ExitStatus::Config)?;
+    }
+
+    Ok(())
+}
+
+/// Content of the code versions file in the reproduce info of a run
+pub fn format_code_versions(code_versions: &HashMap<String, String>) -> String {
+    code_versions
+        .iter()
+        .fold(String::new(), |output, (code_source_id, version)| {
+            output + &format!("{} = {}\n", code_source_id, version)
+        })
+}
+
+/// The review mode of the configuration, which defaults to opening a new terminal, or no review
+/// if it was skipped with `no_review`
+pub fn configured_review_mode(review: Option<&ReviewConfig>, no_review: bool) -> ReviewMode {
+    if no_review {
+        return ReviewMode::None;
+    }
+    review.and_then(|review| review.mode).unwrap_or_default()
+}
+
+/// Open all entrypoints below `dir_path` in one editor session, which shows them as buffers or
+/// tabs depending on the editor, and block until the editor exits; afterwards, `question` asks
+/// whether to go on with the reviewed config, edit it again or abort, where aborting fails with
+/// [`ExitStatus::Aborted`], unless it is `None`, e.g. because `--yes` was passed
+pub fn review_config(
+    dir_path: &Path,
+    entrypoint_paths: &[PathBuf],
+    mode: ReviewMode,
+    question: Option<&str>,
+) -> Result<()> {
+    if mode == ReviewMode::None {
+        return Ok(());
+    }
+
+    loop {
+        let status = open_config_editor(dir_path, entrypoint_paths, mode)?;
+        // closing the terminal window or a crashing editor leaves the config in whatever state
+        // was last saved, which is most likely not what was meant to be submitted
+        if !status.success() {
+            eprintln!(
+                "warning: the config review exited with {status}, changes may not have been saved"
             );
         }
+
+        let Some(question) = question else {
+            return Ok(());
+        };
+        match ask(&format!("{question} [y/N/re-edit]"))?.as_str() {
+            "y" | "yes" => return Ok(()),
+            "r" | "e" | "re-edit" | "edit" => continue,
+            _ => {
+                return Err(anyhow::Error::new(ExitStatus::Aborted)
+                    .context("aborted after reviewing the config"))
+            }
+        }
     }
 }
 
-fn review_config(dir_path: &Path, entrypoint_path: &Path) {
-    let terminal_name = std::env::var("TERMINAL").expect("expected TERMINAL variable to be set");
+fn open_config_editor(
+    dir_path: &Path,
+    entrypoint_paths: &[PathBuf],
+    mode: ReviewMode,
+) -> Result<std::process::ExitStatus> {
+    let mut cmd = match mode {
+        ReviewMode::None => unreachable!("expected the config review not to be skipped"),
+        ReviewMode::Inline => std::process::Command::new("bash"),
+        ReviewMode::Terminal => {
+            let terminal_name =
+                std::env::var("TERMINAL").expect("expected TERMINAL variable to be set");
+            let mut cmd = std::process::Command::new(terminal_name);
+            cmd.arg("-e").arg("bash");
+            cmd
+        }
+    };
+
     let editor_name = std::env::var("EDITOR").expect("expected EDITOR variable to be set");
-    let mut cmd = std::process::Command::new(terminal_name);
+    let cmd = cmd.arg("-c").arg(format!(
+        "cd {dir_path} && {editor_name} {}",
+        entrypoint_paths
+            .iter()
+            .map(|path| shell_quote(path.as_str()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    ));
+
+    cmd.status().context(format!("failed to run `{cmd:?}`"))
+}
+
+/// Render the config template at `template_path` into the file without its extension and remove
+/// the template, so that only the config of the run is reviewed and uploaded; config directories
+/// that only contain the rendered file, like the recorded config of a previous run, are used as
+/// they are
+fn render_config_template(template_path: &Path, context: minijinja::Value) -> Result<()> {
+    let rendered_path = rendered_entrypoint_path(template_path);
+    if !template_path.exists() && rendered_path.exists() {
+        return Ok(());
+    }
+
+    let template_content = std::fs::read_to_string(template_path)
+        .context(format!("failed to read {template_path}"))?;
+    // jinja drops the trailing newline of templates, which config files are expected to end with
+    let rendered_content = render_template(&template_content, context)? + "\n";
+    std::fs::write(&rendered_path, rendered_content)
+        .context(format!("failed to write {rendered_path}"))?;
+    std::fs::remove_file(template_path).context(format!("failed to remove {template_path}"))
+}
+
+/// Parse the config entrypoint according to its extension, so that syntax errors are reported
+/// with their position before submission; entrypoints of unknown formats are not checked
+pub fn check_config_syntax(entrypoint_path: &Path) -> Result<()> {
+    let format = match entrypoint_path.extension() {
+        Some("yaml" | "yml") => FileFormat::Yaml,
+        Some("json") => FileFormat::Json,
+        Some("toml") => FileFormat::Toml,
+        _ => return Ok(()),
+    };
 
-    let cmd = cmd.arg("-e")
-        .arg("bash")
-        .arg("-c")
-        .arg(format!("cd {dir_path} && {editor_name} {entrypoint_path}"));
+    // parse from a string, since the path of the review copy means nothing to the user
+    let content = std::fs::read_to_string(entrypoint_path)
+        .context(format!("failed to read {entrypoint_path}"))?;
+    config::Config::builder()
+        .add_source(config::File::from_str(&content, format))
+        .build()?;
 
-    cmd.status()
-        .expect(&format!("expected {cmd:?} to run successfully"));
+    Ok(())
 }
 
-fn unpack_revision(url: &Url, git_revision: &str, destination_path: &Path, ssh_key_path: &Path) {
+/// Export `snapshot` of the local code source at `source_path` into `destination_path`, returning
+/// the path of the exported code source
+fn export_snapshot(
+    source_path: &Path,
+    snapshot: &CodeSnapshot,
+    destination_path: &Path,
+) -> Result<PathBuf> {
+    let repo = Repository::discover(source_path)
+        .context(format!("failed to find a git repository at {source_path}"))?;
+    let tree_id = &snapshot.tree_id;
+
+    let mut checkout = git2::build::CheckoutBuilder::new();
+    checkout
+        .target_dir(destination_path.as_std_path())
+        .update_index(false)
+        .force();
+    match snapshot.mode {
+        // the tree of the index was only built in memory, so the index is checked out instead,
+        // which places the code source at its path in the repository
+        LocalSnapshotMode::Staged => {
+            if !snapshot.repository_path.as_str().is_empty() {
+                checkout.path(snapshot.repository_path.as_str());
+            }
+            let mut index = repo.index().context("failed to read the index")?;
+            repo.checkout_index(Some(&mut index), Some(&mut checkout))
+                .context(format!("failed to export the index of {source_path}"))?;
+            Ok(destination_path.join(&snapshot.repository_path))
+        }
+        LocalSnapshotMode::Worktree => unreachable!("the working tree is not a snapshot"),
+        LocalSnapshotMode::Head => {
+            let tree = git2::Oid::from_str(tree_id)
+                .and_then(|tree_id| repo.find_tree(tree_id))
+                .context(format!("failed to find tree {tree_id} in {source_path}"))?;
+            repo.checkout_tree(tree.as_object(), Some(&mut checkout))
+                .context(format!("failed to export tree {tree_id}"))?;
+            Ok(destination_path.to_owned())
+        }
+    }
+}
+
+fn unpack_revision(
+    url: &Url,
+    git_revision: &str,
+    destination_path: &Path,
+    ssh_key_path: &Path,
+    submodules: SubmoduleMode,
+) -> Result<()> {
     // build lambda for fetch options
     let get_fetch_options = || {
         let mut callbacks = git2::RemoteCallbacks::new();
@@ -346,34 +1364,56 @@ fn unpack_revision(url: &Url, git_revision: &str, destination_path: &Path, ssh_k
         return fetch_options;
     };
 
-    let repo =
-        Repository::init(destination_path).expect("expected repository initialization to work");
-    let mut origin = repo.remote("origin", url.as_str()).expect(&format!(
-        "expected remote creation of origin under `{url}' to work"
-    ));
+    let repo = Repository::init(destination_path).context(format!(
+        "failed to initialize a repository at {destination_path}"
+    ))?;
+    let mut origin = repo
+        .remote("origin", url.as_str())
+        .context(format!("failed to add origin under `{url}'"))?;
     origin
         .fetch(&[git_revision], Some(&mut get_fetch_options()), None)
-        .expect(&format!(
-            "expected fetch of {git_revision} from origin under `{url}' to work"
-        ));
+        .context(format!("failed to fetch {git_revision} from `{url}'"))?;
 
     let (object, _) = repo
         .revparse_ext(git_revision)
-        .expect(&format!("expected parsing of `{git_revision}' to work"));
+        .context(format!("failed to parse `{git_revision}'"))?;
     repo.checkout_tree(&object, None)
-        .expect(&format!("expected checkout of `{git_revision}' to work"));
-    repo.set_head_detached(object.id())
-        .expect(&format!("expected checkout of `{git_revision}' to work"));
+        .and_then(|()| repo.set_head_detached(object.id()))
+        .context(format!("failed to check out `{git_revision}'"))?;
 
-    let mut submodules = repo
-        .submodules()
-        .expect("expected submodules to be accessible");
+    if submodules != SubmoduleMode::None {
+        update_submodules(
+            &repo,
+            &get_fetch_options,
+            submodules == SubmoduleMode::Recursive,
+        )?;
+    }
 
-    let mut submodule_update_opts = git2::SubmoduleUpdateOptions::new();
-    submodule_update_opts.fetch(get_fetch_options());
-    submodules.iter_mut().for_each(|submodule| {
+    Ok(())
+}
+
+fn update_submodules<'f>(
+    repo: &Repository,
+    get_fetch_options: &dyn Fn() -> git2::FetchOptions<'f>,
+    recursive: bool,
+) -> Result<()> {
+    let mut submodules = repo.submodules().context("failed to look up submodules")?;
+
+    for submodule in submodules.iter_mut() {
+        let submodule_path = submodule.path().display().to_string();
+        let mut submodule_update_opts = git2::SubmoduleUpdateOptions::new();
+        submodule_update_opts.fetch(get_fetch_options());
         submodule
             .update(true, Some(&mut submodule_update_opts))
-            .expect(&format!("expected update of submodule to work"));
-    });
+            .context(format!("failed to update submodule {submodule_path}"))?;
+
+        if recursive {
+            let submodule_repo = submodule
+                .open()
+                .context(format!("failed to open submodule {submodule_path}"))?;
+            update_submodules(&submodule_repo, get_fetch_options, recursive)?;
+        }
+    }
+
+    Ok(())
 }
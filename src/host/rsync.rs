@@ -1,3 +1,4 @@
+use crate::cfg::TransferBackend;
 use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
 use std::process::Command;
 use std::str::FromStr;
@@ -18,6 +19,11 @@ pub enum SyncPayload<'a> {
         sources: &'a Vec<&'a Path>,
         destination: &'a Path,
     },
+    /// Destination in any form rsync understands, e.g. `host:path` or `rsync://host/module/path`
+    LocalToDestination {
+        sources: &'a Vec<&'a Path>,
+        destination: &'a str,
+    },
 }
 
 #[derive(Debug)]
@@ -26,10 +32,19 @@ pub struct SyncOptions {
     verbose: bool,
     delete: bool,
     excludes: Vec<String>,
+    includes: Vec<String>,
     infos: Vec<String>,
     copy_contents: bool,
     progress: bool,
     resolve_symlinks: bool,
+    preserve_permissions: Option<bool>,
+    chmod: Option<String>,
+    stats: bool,
+    ignore_existing: bool,
+    max_size: Option<String>,
+    prune_empty_dirs: bool,
+    backup_dir: Option<PathBuf>,
+    make_parents: bool,
 }
 impl SyncOptions {
     pub fn default() -> SyncOptions {
@@ -38,14 +53,22 @@ impl SyncOptions {
             verbose: false,
             delete: false,
             excludes: Vec::new(),
+            includes: Vec::new(),
             infos: Vec::new(),
             copy_contents: false,
             progress: false,
             resolve_symlinks: false,
+            preserve_permissions: None,
+            chmod: None,
+            stats: false,
+            ignore_existing: false,
+            max_size: None,
+            prune_empty_dirs: false,
+            backup_dir: None,
+            make_parents: false,
         }
     }
 
-    #[allow(unused)]
     pub fn quiet(mut self) -> SyncOptions {
         self.quiet = true;
         self
@@ -68,6 +91,12 @@ impl SyncOptions {
         self
     }
 
+    /// Only copy paths matching one of the given patterns, excludes still take precedence
+    pub fn only_include(mut self, includes: &[String]) -> SyncOptions {
+        self.includes.extend_from_slice(includes);
+        self
+    }
+
     #[allow(unused)]
     pub fn info(mut self, infos: &Vec<&str>) -> SyncOptions {
         self.infos.extend(
@@ -94,6 +123,104 @@ impl SyncOptions {
         self.resolve_symlinks = true;
         self
     }
+
+    pub fn preserve_permissions(mut self, preserve: bool) -> SyncOptions {
+        self.preserve_permissions = Some(preserve);
+        self
+    }
+
+    pub fn chmod(mut self, chmod: &str) -> SyncOptions {
+        self.chmod = Some(chmod.to_owned());
+        self
+    }
+
+    /// Skip files which already exist at the destination, regardless of their content
+    pub fn ignore_existing(mut self) -> SyncOptions {
+        self.ignore_existing = true;
+        self
+    }
+
+    /// Capture the transfer statistics of rsync instead of printing its output
+    pub fn stats(mut self) -> SyncOptions {
+        self.stats = true;
+        self
+    }
+
+    /// Skip files larger than `max_size`, given in the size format of rsync like `2M`
+    pub fn max_size(mut self, max_size: &str) -> SyncOptions {
+        self.max_size = Some(max_size.to_owned());
+        self
+    }
+
+    /// Do not create directories which would end up empty, e.g. since all their files were
+    /// filtered out
+    pub fn prune_empty_dirs(mut self) -> SyncOptions {
+        self.prune_empty_dirs = true;
+        self
+    }
+
+    /// Move files which are deleted or replaced at the destination into `backup_dir_path` on the
+    /// destination side instead of discarding them
+    pub fn backup_dir(mut self, backup_dir_path: &Path) -> SyncOptions {
+        self.backup_dir = Some(backup_dir_path.to_owned());
+        self
+    }
+
+    /// Create the missing parent directories of the destination instead of failing on them
+    pub fn make_parents(mut self) -> SyncOptions {
+        self.make_parents = true;
+        self
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SyncStats {
+    pub transferred_file_size: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+impl std::ops::Add for SyncStats {
+    type Output = SyncStats;
+
+    fn add(self, other: SyncStats) -> SyncStats {
+        SyncStats {
+            transferred_file_size: self.transferred_file_size + other.transferred_file_size,
+            bytes_sent: self.bytes_sent + other.bytes_sent,
+            bytes_received: self.bytes_received + other.bytes_received,
+        }
+    }
+}
+
+impl SyncStats {
+    /// Parse the output of `rsync --stats`, ignoring statistics that are missing
+    fn parse(output: &str) -> SyncStats {
+        let mut stats = SyncStats::default();
+        for line in output.lines() {
+            let Some((key, value)) = line.split_once(": ") else {
+                continue;
+            };
+            let value = value
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
+                .replace(',', "")
+                .parse::<u64>()
+                .ok();
+            let Some(value) = value else {
+                continue;
+            };
+
+            match key {
+                "Total transferred file size" => stats.transferred_file_size = value,
+                "Total bytes sent" => stats.bytes_sent = value,
+                "Total bytes received" => stats.bytes_received = value,
+                _ => {}
+            }
+        }
+
+        stats
+    }
 }
 
 fn ensure_trailing_slash(path: &Path) -> PathBuf {
@@ -104,8 +231,12 @@ fn ensure_trimmed_trailing_slash(path: &Path) -> &Path {
     return Path::new(path.as_str().trim_end_matches("/"));
 }
 
-pub fn rsync<'a>(payload: SyncPayload<'a>, options: SyncOptions) -> std::io::Result<()> {
+pub fn rsync<'a>(
+    payload: SyncPayload<'a>,
+    options: SyncOptions,
+) -> std::io::Result<Option<SyncStats>> {
     let mut cmd = Command::new("rsync");
+    let capture_stats = options.stats;
 
     cmd.args(["--archive", "--checksum"]);
 
@@ -125,10 +256,50 @@ pub fn rsync<'a>(payload: SyncPayload<'a>, options: SyncOptions) -> std::io::Res
         cmd.arg("--progress");
     }
 
+    if options.ignore_existing {
+        cmd.arg("--ignore-existing");
+    }
+
     if options.resolve_symlinks {
         cmd.arg("--copy-links");
     }
 
+    match options.preserve_permissions {
+        Some(true) => {
+            cmd.arg("--perms");
+        }
+        Some(false) => {
+            cmd.arg("--no-perms");
+        }
+        None => {}
+    }
+
+    if options.stats {
+        // keep the number format of the statistics independent of the locale
+        cmd.arg("--stats").env("LC_ALL", "C");
+    }
+
+    if let Some(chmod) = &options.chmod {
+        cmd.arg(format!("--chmod={chmod}"));
+    }
+
+    if let Some(max_size) = &options.max_size {
+        cmd.arg(format!("--max-size={max_size}"));
+    }
+
+    if options.prune_empty_dirs {
+        cmd.arg("--prune-empty-dirs");
+    }
+
+    if let Some(backup_dir) = &options.backup_dir {
+        cmd.arg("--backup")
+            .arg(format!("--backup-dir={backup_dir}"));
+    }
+
+    if options.make_parents {
+        cmd.arg("--mkpath");
+    }
+
     if options.infos.len() > 0 {
         let infos = options.infos.join(",");
         cmd.arg(format!("--info={infos}"));
@@ -140,6 +311,13 @@ pub fn rsync<'a>(payload: SyncPayload<'a>, options: SyncOptions) -> std::io::Res
         }
     }
 
+    if !options.includes.is_empty() {
+        for include in &options.includes {
+            cmd.arg(format!("--include={include}"));
+        }
+        cmd.arg("--exclude=*");
+    }
+
     let ensure_correct_source = move |source| {
         if options.copy_contents {
             ensure_trailing_slash(source)
@@ -181,14 +359,52 @@ pub fn rsync<'a>(payload: SyncPayload<'a>, options: SyncOptions) -> std::io::Res
             }
             cmd.arg(destination);
         }
+        SyncPayload::LocalToDestination {
+            sources,
+            destination,
+        } => {
+            for source in sources {
+                cmd.arg(ensure_correct_source(source));
+            }
+            cmd.arg(destination);
+        }
+    }
+
+    if capture_stats {
+        let output = cmd.stderr(std::process::Stdio::inherit()).output()?;
+        check_rsync_status(output.status)?;
+        return Ok(Some(SyncStats::parse(&String::from_utf8_lossy(
+            &output.stdout,
+        ))));
     }
 
-    cmd.status()?;
+    check_rsync_status(cmd.status()?)?;
 
-    Ok(())
+    Ok(None)
+}
+
+/// Fail unless rsync exited successfully, where files that vanished while they were copied, e.g.
+/// temporary files of a run that is still running, do not count as a failure
+fn check_rsync_status(status: std::process::ExitStatus) -> std::io::Result<()> {
+    const VANISHED_SOURCE_FILES_EXIT_CODE: i32 = 24;
+
+    if status.success() || status.code() == Some(VANISHED_SOURCE_FILES_EXIT_CODE) {
+        return Ok(());
+    }
+    Err(std::io::Error::other(format!("rsync failed with {status}")))
 }
 
 pub fn copy_directory(source: &Path, destination: &Path, options: SyncOptions) {
+    try_copy_directory(source, destination, options).expect("rsync should not fail");
+}
+
+/// Like [`copy_directory`], but leaving it to the caller to handle a failure to run rsync or an
+/// unsuccessful exit of it, whose error names the source and destination
+pub fn try_copy_directory(
+    source: &Path,
+    destination: &Path,
+    options: SyncOptions,
+) -> std::io::Result<()> {
     rsync(
         SyncPayload::LocalToLocal {
             sources: &vec![source],
@@ -196,5 +412,115 @@ pub fn copy_directory(source: &Path, destination: &Path, options: SyncOptions) {
         },
         options,
     )
-    .expect("rsync should not fail");
+    .map(|_| ())
+    .map_err(|err| {
+        std::io::Error::new(
+            err.kind(),
+            format!("failed to copy {source} to {destination}: {err}"),
+        )
+    })
+}
+
+/// Pushes a local directory to a destination whose form depends on the backend
+pub trait Transfer {
+    fn push(&self, source: &Path, destination: &str, options: SyncOptions) -> std::io::Result<()>;
+}
+
+pub fn build_transfer(backend: TransferBackend) -> Box<dyn Transfer> {
+    match backend {
+        TransferBackend::Rsync => Box::new(RsyncTransfer),
+        TransferBackend::Rclone => Box::new(RcloneTransfer),
+    }
+}
+
+/// Transfer to a local path, `host:path` or an rsync daemon given as `rsync://host/module/path`
+pub struct RsyncTransfer;
+
+impl Transfer for RsyncTransfer {
+    fn push(&self, source: &Path, destination: &str, options: SyncOptions) -> std::io::Result<()> {
+        rsync(
+            SyncPayload::LocalToDestination {
+                sources: &vec![source],
+                destination,
+            },
+            options,
+        )?;
+        Ok(())
+    }
+}
+
+/// Transfer to an rclone remote given as `remote:path`, which needs to be configured in rclone
+pub struct RcloneTransfer;
+
+impl RcloneTransfer {
+    /// rclone filters mostly follow rsync patterns, but match recursively with `**` instead of
+    /// `***`
+    fn filter_pattern(pattern: &str) -> String {
+        match pattern.strip_suffix("***") {
+            Some(prefix) => format!("{prefix}**"),
+            None => pattern.to_owned(),
+        }
+    }
+}
+
+impl Transfer for RcloneTransfer {
+    fn push(&self, source: &Path, destination: &str, options: SyncOptions) -> std::io::Result<()> {
+        let mut cmd = Command::new("rclone");
+
+        // like rsync --delete, `sync` removes files at the destination that are missing locally
+        cmd.arg(if options.delete { "sync" } else { "copy" });
+
+        if options.quiet {
+            cmd.arg("--quiet");
+        }
+
+        if options.verbose {
+            cmd.arg("--verbose");
+        }
+
+        if options.progress {
+            cmd.arg("--progress");
+        }
+
+        if options.ignore_existing {
+            cmd.arg("--ignore-existing");
+        }
+
+        if options.resolve_symlinks {
+            cmd.arg("--copy-links");
+        }
+
+        for exclude in &options.excludes {
+            cmd.arg(format!("--filter=- {}", Self::filter_pattern(exclude)));
+        }
+
+        if !options.includes.is_empty() {
+            for include in &options.includes {
+                cmd.arg(format!("--filter=+ {}", Self::filter_pattern(include)));
+            }
+            cmd.arg("--filter=- **");
+        }
+
+        // rclone creates missing parent directories of the destination by itself, so
+        // `make_parents` needs no counterpart
+        // rclone always copies the contents of the source directory
+        let destination = if options.copy_contents {
+            destination.to_owned()
+        } else {
+            let name = ensure_trimmed_trailing_slash(source)
+                .file_name()
+                .unwrap_or("");
+            format!("{}/{name}", destination.trim_end_matches('/'))
+        };
+        cmd.arg(source).arg(&destination);
+
+        let status = cmd.status()?;
+        if !status.success() {
+            return Err(std::io::Error::other(format!(
+                "rclone failed to push {source} to {destination} with {status}"
+            )));
+        }
+
+        Ok(())
+    }
 }
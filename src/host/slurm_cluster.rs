@@ -1,73 +1,470 @@
+use super::accounting::{AccountedJob, SACCT_FORMAT};
 use super::connection::Connection;
+use super::content_store;
+use super::disk_usage::{DiskUsage, DISK_USAGE_SCRIPT};
+use super::facts::{HostFacts, FACTS_DETECTION_SCRIPT};
 use super::local::LocalHost;
-use super::rsync::SyncOptions;
-use super::{Host, QuickRunPrepOptions, RunDirectory, RunID, RunOutputSyncOptions};
-use crate::utils::Utf8Path;
-use anyhow::{anyhow, Context, Result};
+use super::maintenance::{Maintenance, MAINTENANCE_SCRIPT};
+use super::manifest::ManifestEntry;
+use super::rsync::{SyncOptions, SyncStats};
+use super::status::{HostStatus, STATUS_SCRIPT};
+use super::{
+    parse_tags, permissions_script_args, DirListingEntry, Host, HostInfo, IdleResources,
+    QuickRunEstimate, QuickRunPrepOptions, RunDirectory, RunID, RunOutputSyncOptions, RunTag,
+    SyncError, PERMISSIONS_SCRIPT,
+};
+use crate::cfg::{ExecutionMode, OutputPermissionsConfig, TmuxFallback, TmuxKeepOpen};
+use crate::exit::{ExitStatus, WithExitStatus};
+use crate::run::default::render_template;
+use crate::utils::{keep_open_wrap, shell_quote, tmux_wrap, Utf8Path};
+use anyhow::{anyhow, bail, Context, Result};
 use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
 use core::str;
+use std::collections::{BTreeMap, HashMap};
 use std::os::unix::process::CommandExt;
+use std::sync::OnceLock;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
+/// Failure of a slurm submission, where transient failures (e.g. slurmctld timeouts) are worth
+/// retrying while permanent ones (e.g. an invalid account) are not
+enum SubmissionError {
+    Transient(anyhow::Error),
+    Permanent(anyhow::Error),
+}
+
+impl From<anyhow::Error> for SubmissionError {
+    fn from(err: anyhow::Error) -> Self {
+        SubmissionError::Permanent(err)
+    }
+}
+
+fn is_transient_submission_failure(output: &str) -> bool {
+    const TRANSIENT_FAILURE_MESSAGES: &[&str] = &[
+        "timed out",
+        "Connection reset",
+        "Connection refused",
+        "Unable to contact slurm controller",
+        "Resource temporarily unavailable",
+        "Zero Bytes were transmitted or received",
+    ];
+
+    let output = output.to_lowercase();
+    TRANSIENT_FAILURE_MESSAGES
+        .iter()
+        .any(|message| output.contains(&message.to_lowercase()))
+}
+
 pub struct QuickRunPreparationOptions {
     pub slurm_account: String,
     pub slurm_service_quality: Option<String>,
     pub node_local_storage_path: PathBuf,
 }
 
+/// Template of the preparation the towel job of a quick run executes on the allocated node before
+/// it goes to sleep, which replaces the copying of the fast access containers
+pub const QUICK_RUN_TEMPLATE_PATH: &str = ".sparrow/quick_run.sh.j2";
+
+/// Context [`QUICK_RUN_TEMPLATE_PATH`] is rendered with
+#[derive(serde::Serialize)]
+struct QuickRunTemplateContext<'o> {
+    quick_run: QuickRunTemplateOptions<'o>,
+    host: HostInfo,
+}
+
+#[derive(serde::Serialize)]
+struct QuickRunTemplateOptions<'o> {
+    account: &'o str,
+    service_quality: Option<&'o str>,
+    constraint: Option<&'o str>,
+    partitions: Option<&'o [String]>,
+    time: &'o str,
+    cpu_count: u16,
+    gpu_count: u16,
+    fast_access_container_paths: &'o [PathBuf],
+    node_local_storage_path: &'o Path,
+}
+
+/// How a slurm cluster host executes runs and where it puts them, as configured for the host
+pub struct SlurmClusterHostOptions {
+    pub script_run_command_template: String,
+    pub output_base_dir_path: PathBuf,
+    pub temporary_dir_path: PathBuf,
+    pub run_dir_name_template: Option<String>,
+    pub deduplicate_auxiliary: bool,
+    pub execution: ExecutionMode,
+    pub tmux_keep_open_on: TmuxKeepOpen,
+    pub tmux_fallback: TmuxFallback,
+    pub modules: BTreeMap<String, String>,
+    pub remote_command_wrapper: Option<String>,
+}
+
 pub struct SlurmClusterHost {
     id: String,
     script_run_command_template: String,
     output_base_dir_path: PathBuf,
     temporary_dir_path: PathBuf,
+    run_dir_name_template: String,
+    deduplicate_auxiliary: bool,
+    execution: ExecutionMode,
+    tmux_keep_open_on: TmuxKeepOpen,
+    tmux_fallback: TmuxFallback,
+    /// Execution mode replacing tmux, which is only looked up once an operation depends on it,
+    /// or `None` if tmux is not installed and there is no fallback
+    tmux_replacement: OnceLock<Option<ExecutionMode>>,
+    modules: BTreeMap<String, String>,
 
     hostname: String,
     connection: Connection,
+    clock_skew: Option<i64>,
     quick_run_preparation: QuickRunPreparationOptions,
 }
 
 impl SlurmClusterHost {
     const QUICK_RUN_TOWEL_JOB_NAME: &str = "quick-run-towel";
+    const DEFAULT_RUN_DIR_NAME_TEMPLATE: &str = "run.{rand}";
+    /// Clock skew in seconds from which on modification times of the host and the local machine
+    /// are not comparable anymore
+    const CLOCK_SKEW_WARNING_THRESHOLD: i64 = 5;
 
     pub fn new(
         id: &str,
         hostname: &str,
-        script_run_command_template: String,
-        output_base_dir_path: &Path,
-        temporary_dir_path: &Path,
+        options: SlurmClusterHostOptions,
         quick_run_preparation: QuickRunPreparationOptions,
         allow_quick_runs: bool,
-    ) -> Self {
+    ) -> Result<Self> {
+        let SlurmClusterHostOptions {
+            script_run_command_template,
+            output_base_dir_path,
+            temporary_dir_path,
+            run_dir_name_template,
+            deduplicate_auxiliary,
+            execution,
+            tmux_keep_open_on,
+            tmux_fallback,
+            modules,
+            remote_command_wrapper,
+        } = options;
         let hostname = if allow_quick_runs {
             &format!("{hostname}-quick")
         } else {
             hostname
         };
 
-        let connection = match Connection::new(hostname) {
+        let connection = match Connection::new(hostname, remote_command_wrapper) {
             Ok(connection) => connection,
             Err(e) => {
                 eprintln!("Failed to connect to host {}: {:?}", hostname, e);
                 if allow_quick_runs {
                     eprintln!("Did you forget to prepare the remote?")
                 }
-                std::process::exit(1);
+                ExitStatus::Connection.exit();
             }
         };
+        let expand_path = |path: &Path| {
+            connection
+                .expand_path(path)
+                .context(format!("failed to expand {path} on {hostname}"))
+                .exit_status(ExitStatus::Config)
+        };
+        let output_base_dir_path = expand_path(&output_base_dir_path)?;
+        let temporary_dir_path = expand_path(&temporary_dir_path)?;
+
+        let clock_skew = match connection.clock_skew() {
+            Ok(clock_skew) => Some(clock_skew),
+            Err(err) => {
+                eprintln!("warning: failed to compare the clock of {hostname}: {err:#}");
+                None
+            }
+        };
+        match clock_skew {
+            Some(clock_skew) if clock_skew.abs() > Self::CLOCK_SKEW_WARNING_THRESHOLD => {
+                eprintln!(
+                    "warning: the clock of {hostname} is {}s {} the local clock, so modification \
+                    times of run outputs are not comparable between them; syncs are unaffected \
+                    since they compare checksums",
+                    clock_skew.abs(),
+                    if clock_skew > 0 { "ahead of" } else { "behind" }
+                )
+            }
+            _ => {}
+        }
 
-        return Self {
+        Ok(Self {
             id: id.to_owned(),
             hostname: hostname.to_owned(),
             script_run_command_template,
-            output_base_dir_path: output_base_dir_path.to_owned(),
-            temporary_dir_path: temporary_dir_path.to_owned(),
+            output_base_dir_path,
+            temporary_dir_path,
+            run_dir_name_template: run_dir_name_template
+                .unwrap_or(String::from(Self::DEFAULT_RUN_DIR_NAME_TEMPLATE)),
+            deduplicate_auxiliary,
+            execution,
+            tmux_keep_open_on,
+            tmux_fallback,
+            tmux_replacement: OnceLock::new(),
+            modules,
             connection,
+            clock_skew,
             quick_run_preparation,
+        })
+    }
+}
+
+impl SlurmClusterHost {
+    /// Execution mode of runs on the host, where tmux is replaced as `tmux.fallback` says if it
+    /// is not installed; without a fallback, tmux is kept and [`Host::check_execution`] refuses
+    /// to launch runs
+    fn execution(&self) -> ExecutionMode {
+        if self.execution != ExecutionMode::Tmux {
+            return self.execution;
+        }
+
+        self.tmux_replacement().unwrap_or(ExecutionMode::Tmux)
+    }
+
+    /// Execution mode replacing tmux as `tmux.fallback` says, which looks up tmux and screen in a
+    /// single round trip, or `None` if tmux is not installed and there is no fallback
+    fn tmux_replacement(&self) -> Option<ExecutionMode> {
+        *self
+            .tmux_replacement
+            .get_or_init(|| self.find_tmux_replacement())
+    }
+
+    fn find_tmux_replacement(&self) -> Option<ExecutionMode> {
+        let installed_programs = self
+            .connection
+            .command("bash")
+            .arg("-c")
+            .arg(
+                "for program in tmux screen; do \
+                    command -v $program > /dev/null && echo $program; \
+                done; true",
+            )
+            .idempotent()
+            .output();
+        let installed_programs = match installed_programs {
+            Ok(output) if output.status.success() => {
+                String::from_utf8_lossy(&output.stdout).into_owned()
+            }
+            Ok(output) => {
+                eprintln!(
+                    "warning: failed to check for tmux on {}: {}",
+                    self.hostname,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                return Some(ExecutionMode::Tmux);
+            }
+            Err(err) => {
+                eprintln!(
+                    "warning: failed to check for tmux on {}: {err}",
+                    self.hostname
+                );
+                return Some(ExecutionMode::Tmux);
+            }
         };
+        let is_installed = |program: &str| installed_programs.lines().any(|line| line == program);
+        if is_installed("tmux") {
+            return Some(ExecutionMode::Tmux);
+        }
+
+        match self.tmux_fallback {
+            TmuxFallback::Error => None,
+            TmuxFallback::Screen if is_installed("screen") => Some(ExecutionMode::Screen),
+            TmuxFallback::Screen | TmuxFallback::Nohup => Some(ExecutionMode::Nohup),
+        }
+    }
+
+    /// Name of the systemd unit or screen session of `run_id`, which may not contain slashes
+    fn session_name(run_id: &RunID) -> String {
+        format!("sparrow-{}-{}", run_id.group, run_id.name).replace(
+            |c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '_',
+            "_",
+        )
     }
 }
 
 impl SlurmClusterHost {
+    const RUN_LOG_FILE_NAME: &str = "run.log";
+    const RUN_PID_FILE_NAME: &str = "run.pid";
+    const RUN_UNIT_FILE_NAME: &str = "run.unit";
+
+    /// Directory in which runs that are not executed in tmux sessions are registered by
+    /// linking `<group>/<name>` to their run directory
+    fn run_registry_path(&self) -> PathBuf {
+        self.temporary_dir_path.join(".sparrow_runs")
+    }
+
+    /// Name a new run directory after `run_dir_name_template`, where `{group}`, `{name}` and
+    /// `{rand}` are replaced by the run group, the run name and four random characters
+    fn run_dir_path(&self, run_id: &RunID) -> PathBuf {
+        let name = self
+            .run_dir_name_template
+            .replace("{group}", &run_id.group)
+            .replace("{name}", &run_id.name)
+            .replace("{rand}", &tmpname("", "", 4));
+        let run_dir_path = self.temporary_dir_path.join(name);
+
+        // templates without `{rand}' name the directories of repeated runs the same
+        match self.exists(&run_dir_path) {
+            Ok(false) => run_dir_path,
+            _ => PathBuf::from(tmpname(&format!("{run_dir_path}."), "", 4)),
+        }
+    }
+
+    /// Upload the given files of the prepared run directory to the content store, where files
+    /// that are already stored are skipped, and replace them by a script that links them
+    fn upload_to_content_store(
+        &self,
+        prep_dir_path: &Path,
+        files: &[&ManifestEntry],
+    ) -> Result<Option<SyncStats>> {
+        let Some(store_path) = self.content_store_path() else {
+            return Ok(Some(SyncStats::default()));
+        };
+        if files.is_empty() {
+            return Ok(Some(SyncStats::default()));
+        }
+
+        let objects_dir = content_store::extract_objects(prep_dir_path, files)
+            .context("failed to collect auxiliary files for the content store")?;
+        std::fs::write(
+            prep_dir_path.join(content_store::LINK_SCRIPT_FILE_NAME),
+            content_store::link_script(&store_path, files),
+        )
+        .context("failed to write the content store link script")?;
+
+        let objects_path = content_store::objects_path(&store_path);
+        self.create_dir_all(&objects_path);
+        // stored objects are shared between runs, so they must never be modified in place
+        Ok(self.connection.upload(
+            objects_dir.utf8_path(),
+            &objects_path,
+            SyncOptions::default()
+                .copy_contents()
+                .ignore_existing()
+                .chmod("a-w")
+                .stats(),
+        ))
+    }
+
+    fn link_from_content_store(&self, run_dir_path: &Path) -> Result<()> {
+        let link_command = format!(
+            "cd {} && bash {script} && rm {script}",
+            shell_quote(run_dir_path.as_str()),
+            script = content_store::LINK_SCRIPT_FILE_NAME,
+        );
+        let status = self
+            .connection
+            .command("bash")
+            .arg("-c")
+            .arg(&link_command)
+            .status()
+            .context(format!(
+                "failed to run `{link_command}` on {}",
+                self.hostname
+            ))?;
+        if !status.success() {
+            return Err(anyhow!("`{link_command}` failed on {}", self.hostname));
+        }
+
+        Ok(())
+    }
+
+    fn register_run_command(&self, run_id: &RunID) -> String {
+        let registry_path = self.run_registry_path();
+        let group_path = registry_path.join(&run_id.group);
+        format!(
+            "mkdir -p {} && ln -sfn \"$PWD\" {}",
+            shell_quote(group_path.as_str()),
+            shell_quote(group_path.join(&run_id.name).as_str()),
+        )
+    }
+
+    /// Lines of the file `file_name` in the reproduce info of all runs, read in one go, together
+    /// with the run they belong to
+    fn reproduce_info_lines(&self, file_name: &str) -> Result<Vec<(RunID, String)>> {
+        // print all files in one go as `<path>:<line>`
+        let mut find_command = self.connection.command("find");
+        find_command
+            .arg(self.output_base_dir_path.as_str())
+            .arg("-mindepth")
+            .arg("4")
+            .arg("-maxdepth")
+            .arg("4")
+            .arg("-path")
+            .arg(format!("*/reproduce_info/{file_name}"))
+            .arg("-exec")
+            .arg("grep")
+            .arg("-H")
+            .arg("")
+            .arg("{}")
+            .arg("+")
+            .idempotent();
+        let find_command_string = format!("{find_command:?}");
+
+        let find_output = find_command
+            .stderr(openssh::Stdio::inherit)
+            .output()
+            .context(format!("failed to run `{find_command_string}`"))?;
+        let find_output = String::from_utf8(find_output.stdout).context(format!(
+            "found non-valid utf8 in output of `{find_command_string}`"
+        ))?;
+
+        find_output
+            .lines()
+            .map(|line| {
+                let (file_path, file_line) = line.split_once(':').ok_or(anyhow!(
+                    "found malformed line `{line}' in output of `{find_command_string}`"
+                ))?;
+                let run_path = Path::new(file_path)
+                    .parent()
+                    .and_then(|reproduce_info_path| reproduce_info_path.parent())
+                    .expect("expected reproduce info file to be located in a run directory");
+                let run_id = RunID::new(
+                    run_path.file_name().unwrap(),
+                    run_path.parent().unwrap().file_name().unwrap(),
+                );
+                Ok((run_id, file_line.to_owned()))
+            })
+            .collect()
+    }
+    fn registered_running_runs(&self) -> Vec<RunID> {
+        let registry_path = self.run_registry_path();
+        let check_command = format!(
+            "for entry in {registry_path}/*/*; do \
+                if [ -f \"$entry/{pid_file}\" ] \
+                    && kill -0 \"$(cat \"$entry/{pid_file}\")\" 2>/dev/null; then \
+                    echo \"${{entry#{registry_path}/}}\"; \
+                elif [ -f \"$entry/{unit_file}\" ] \
+                    && systemctl --user is-active --quiet \"$(cat \"$entry/{unit_file}\")\"; then \
+                    echo \"${{entry#{registry_path}/}}\"; \
+                fi; \
+            done",
+            pid_file = Self::RUN_PID_FILE_NAME,
+            unit_file = Self::RUN_UNIT_FILE_NAME,
+        );
+
+        let output = self
+            .connection
+            .command("bash")
+            .arg("-c")
+            .arg(check_command)
+            .idempotent()
+            .output()
+            .expect("expected run registry check to succeed");
+        if !output.status.success() {
+            return Vec::new();
+        }
+
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.split_once("/"))
+            .map(|(group, name)| RunID::new(name, group))
+            .collect()
+    }
+
     pub fn allocate_quick_run_node(
         &self,
         constraint: &Option<String>,
@@ -77,10 +474,32 @@ impl SlurmClusterHost {
         gpu_count: u16,
         fast_access_container_paths: &Vec<PathBuf>,
     ) -> Result<()> {
-        let submission_script = Self::build_quick_run_towel_job_script(
-            fast_access_container_paths,
-            &self.quick_run_preparation.node_local_storage_path,
-        );
+        let preparation = if std::path::Path::new(QUICK_RUN_TEMPLATE_PATH).exists() {
+            let template_content = std::fs::read_to_string(QUICK_RUN_TEMPLATE_PATH)
+                .context(format!("failed to read {QUICK_RUN_TEMPLATE_PATH}"))?;
+            let context = QuickRunTemplateContext {
+                quick_run: QuickRunTemplateOptions {
+                    account: &self.quick_run_preparation.slurm_account,
+                    service_quality: self.quick_run_preparation.slurm_service_quality.as_deref(),
+                    constraint: constraint.as_deref(),
+                    partitions: partitions.as_deref(),
+                    time,
+                    cpu_count,
+                    gpu_count,
+                    fast_access_container_paths,
+                    node_local_storage_path: &self.quick_run_preparation.node_local_storage_path,
+                },
+                host: self.info(),
+            };
+            render_template(&template_content, minijinja::Value::from_serialize(context))
+                .context(format!("failed to render {QUICK_RUN_TEMPLATE_PATH}"))?
+        } else {
+            Self::build_container_copy_loop(
+                fast_access_container_paths,
+                &self.quick_run_preparation.node_local_storage_path,
+            )
+        };
+        let submission_script = Self::build_quick_run_towel_job_script(&preparation);
 
         let submission_options = Self::quick_run_towel_job_submission_options(
             self.quick_run_preparation.slurm_account.clone(),
@@ -92,8 +511,10 @@ impl SlurmClusterHost {
             gpu_count,
         );
 
-        self.submit_quick_run_towel_job(&submission_script, &submission_options)
-            .context("failed to submit quick run towel job")?;
+        self.submit_with_retries(|| {
+            self.submit_quick_run_towel_job(&submission_script, &submission_options)
+        })
+        .context("failed to submit quick run towel job")?;
 
         Ok(())
     }
@@ -124,8 +545,9 @@ impl SlurmClusterHost {
             .command("bash")
             .arg("-c")
             .arg(check_command_inner)
-            .stdout(openssh::Stdio::piped())
-            .stderr(openssh::Stdio::piped())
+            .idempotent()
+            .stdout(openssh::Stdio::piped)
+            .stderr(openssh::Stdio::piped)
             .output()
             .expect("expected squeue to succeed");
         if !output.status.success() {
@@ -148,18 +570,154 @@ impl SlurmClusterHost {
         return Ok(job_status == "R");
     }
 
-    fn submit_quick_run_towel_job(&self, script: &str, options: &Vec<String>) -> Result<()> {
-        let mut submission_command = self.connection.command("salloc");
+    pub fn estimate_quick_run_node_allocation(
+        &self,
+        constraint: &Option<String>,
+        partitions: &Option<Vec<String>>,
+        time: &str,
+        cpu_count: u16,
+        gpu_count: u16,
+    ) -> Result<QuickRunEstimate> {
+        let submission_options = Self::quick_run_towel_job_submission_options(
+            self.quick_run_preparation.slurm_account.clone(),
+            self.quick_run_preparation.slurm_service_quality.clone(),
+            constraint,
+            partitions,
+            time,
+            cpu_count,
+            gpu_count,
+        );
+
+        let (expected_start, expected_partition) = self
+            .query_expected_quick_run_start(&submission_options)
+            .context("failed to query the expected start of a quick run towel job")?;
+        let idle_alternatives = self
+            .query_idle_resources(gpu_count > 0)
+            .context("failed to query idle resources")?;
+
+        Ok(QuickRunEstimate {
+            expected_start,
+            expected_partition,
+            idle_alternatives,
+        })
+    }
+
+    fn query_expected_quick_run_start(
+        &self,
+        options: &Vec<String>,
+    ) -> Result<(Option<String>, Option<String>)> {
+        // sbatch reports the estimated start on stderr without submitting anything, e.g.
+        // `sbatch: Job 1234 to start at 2024-01-01T12:00:00 using 4 processors on nodes n1 in
+        // partition gpu`
+        let mut test_command = self.connection.command("sbatch");
+        test_command
+            .arg("--test-only")
+            .args(options)
+            .arg("--wrap")
+            .arg("sleep 1d")
+            .idempotent();
+        let test_command_string = format!("{test_command:?}");
+
+        let output = test_command
+            .stdout(openssh::Stdio::piped)
+            .stderr(openssh::Stdio::piped)
+            .output()
+            .context(format!(
+                "failed to run `{test_command_string}' on {}",
+                self.id
+            ))?;
+
+        let report = String::from_utf8(output.stderr).context(format!(
+            "failed to convert the output of `{test_command_string}' (run on {id}) to utf8",
+            id = self.id
+        ))?;
+        if !output.status.success() {
+            eprintln!("{report}");
+            return Err(anyhow!("failed to run `{test_command_string}'"));
+        }
+
+        let word_after = |marker: &str| {
+            report
+                .split_once(marker)
+                .and_then(|(_, rest)| rest.split_whitespace().next())
+                .map(String::from)
+        };
+
+        Ok((word_after(" to start at "), word_after(" in partition ")))
+    }
+
+    fn query_idle_resources(&self, require_gpus: bool) -> Result<Vec<IdleResources>> {
+        let mut sinfo_command = self.connection.command("sinfo");
+        sinfo_command
+            .arg("--noheader")
+            .arg("--states=idle")
+            .arg("--format=%R %f %D %G")
+            .idempotent();
+        let sinfo_command_string = format!("{sinfo_command:?}");
+
+        let output = sinfo_command
+            .stdout(openssh::Stdio::piped)
+            .stderr(openssh::Stdio::inherit)
+            .output()
+            .context(format!(
+                "failed to run `{sinfo_command_string}' on {}",
+                self.id
+            ))?;
+        if !output.status.success() {
+            return Err(anyhow!("failed to run `{sinfo_command_string}'"));
+        }
+
+        let output = String::from_utf8(output.stdout).context(format!(
+            "failed to convert the output of `{sinfo_command_string}' (run on {id}) to utf8",
+            id = self.id
+        ))?;
+
+        Ok(output
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let partition = fields.next()?.to_owned();
+                let features = fields.next()?;
+                let idle_node_count = fields.next()?.parse().ok()?;
+                let generic_resources = fields.next()?.to_owned();
+
+                Some(IdleResources {
+                    partition,
+                    features: if features == "(null)" {
+                        Vec::new()
+                    } else {
+                        features.split(",").map(String::from).collect()
+                    },
+                    idle_node_count,
+                    generic_resources,
+                })
+            })
+            .filter(|resources| !require_gpus || resources.generic_resources.contains("gpu"))
+            .collect())
+    }
+
+    fn submit_quick_run_towel_job(
+        &self,
+        script: &str,
+        options: &[String],
+    ) -> Result<(), SubmissionError> {
         let submission_commmand_string =
             format!("salloc {} -- bash -c \"bash -\"", options.join(" "));
+
+        // merge stderr into stdout, so that slurm errors can be told apart after a failure
+        let mut submission_command = self.connection.command("bash");
         let mut submission_command = submission_command
-            .args(options)
-            .arg("--")
-            .arg("bash")
             .arg("-c")
-            .arg(&format!("bash -"))
-            .stdin(openssh::Stdio::piped())
-            .stdout(openssh::Stdio::piped())
+            .arg(format!(
+                "exec salloc {} -- bash -c 'bash -' 2>&1",
+                options
+                    .iter()
+                    .map(|option| shell_quote(option))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ))
+            .stdin(openssh::Stdio::piped)
+            .stdout(openssh::Stdio::piped)
             .spawn()
             .context(format!(
                 "failed to execute `{submission_commmand_string}' on {hostname}",
@@ -181,71 +739,113 @@ impl SlurmClusterHost {
 
         const OUTPUT_CHUNK_COUNT_MAX: u16 = 10_000;
         const OUTPUT_CHUNK_SIZE: usize = 1_000;
-        let mut output = [0u8; OUTPUT_CHUNK_SIZE];
-        let output_chunks = (0..OUTPUT_CHUNK_COUNT_MAX)
-            .into_iter()
-            .map(|_| {
-                let output_length =
-                    self.connection
-                        .block_on(stdout.read(&mut output))
-                        .context(format!(
-                            "failed to read stdout of `{submission_commmand_string}'`"
-                        ))?;
-                let output =
-                    String::from_utf8(output[..output_length].to_vec()).context(format!(
-                        "failed to convert some output of `{submission_commmand_string}' to utf8"
-                    ))?;
-                if !output.is_empty() {
-                    println!("{output}");
-                }
+        let mut output_chunk = [0u8; OUTPUT_CHUNK_SIZE];
+        let mut output = String::new();
+        for _ in 0..OUTPUT_CHUNK_COUNT_MAX {
+            let output_length = self
+                .connection
+                .block_on(stdout.read(&mut output_chunk))
+                .context(format!(
+                    "failed to read stdout of `{submission_commmand_string}'`"
+                ))?;
+            if output_length == 0 {
+                // salloc exited before the towel job went to sleep
+                let err = anyhow!(
+                    "`{submission_commmand_string}' failed:\n{}",
+                    output.trim_end()
+                );
+                return Err(if is_transient_submission_failure(&output) {
+                    SubmissionError::Transient(err)
+                } else {
+                    SubmissionError::Permanent(err)
+                });
+            }
 
-                Ok(output)
-            })
-            .take_while(|output_chunk| {
-                output_chunk
-                    .as_ref()
-                    .map_or(false, |chunk| chunk != "Going to sleep...")
-            })
-            .collect::<Result<Vec<_>>>()?;
-        if output_chunks.len() as u16 == OUTPUT_CHUNK_COUNT_MAX {
-            return Err(anyhow!(
-                "failed to read the `Going to sleep...' line using {chunk_count} \
-                output chunks of size {chunk_size} indicating the success of `{command}'",
-                chunk_count = OUTPUT_CHUNK_COUNT_MAX,
-                chunk_size = OUTPUT_CHUNK_SIZE,
-                command = submission_commmand_string
-            ));
+            let output_chunk = String::from_utf8(output_chunk[..output_length].to_vec()).context(
+                format!("failed to convert some output of `{submission_commmand_string}' to utf8"),
+            )?;
+            print!("{output_chunk}");
+            output += &output_chunk;
+
+            if output.ends_with("Going to sleep...") {
+                println!();
+                self.connection
+                    .block_on(submission_command.disconnect())
+                    .context(format!(
+                        "failed to disconnect from `{submission_commmand_string}'"
+                    ))?;
+                return Ok(());
+            }
         }
 
-        self.connection
-            .block_on(submission_command.disconnect())
-            .context(format!(
-                "failed to disconnect from `{submission_commmand_string}'"
-            ))?;
+        Err(SubmissionError::Permanent(anyhow!(
+            "failed to read the `Going to sleep...' line using {chunk_count} \
+            output chunks of size {chunk_size} indicating the success of `{command}'",
+            chunk_count = OUTPUT_CHUNK_COUNT_MAX,
+            chunk_size = OUTPUT_CHUNK_SIZE,
+            command = submission_commmand_string
+        )))
+    }
 
-        Ok(())
+    /// Run `submit` until it succeeds, retrying transient failures with exponential backoff
+    /// while giving up immediately on permanent ones
+    fn submit_with_retries<T>(
+        &self,
+        mut submit: impl FnMut() -> Result<T, SubmissionError>,
+    ) -> Result<T> {
+        const SUBMISSION_ATTEMPT_COUNT_MAX: u32 = 5;
+        const SUBMISSION_RETRY_DELAY_INITIAL: std::time::Duration =
+            std::time::Duration::from_secs(10);
+
+        let mut retry_delay = SUBMISSION_RETRY_DELAY_INITIAL;
+        for attempt in 1.. {
+            match submit() {
+                Ok(result) => return Ok(result),
+                Err(SubmissionError::Permanent(err)) => return Err(err),
+                Err(SubmissionError::Transient(err)) if attempt == SUBMISSION_ATTEMPT_COUNT_MAX => {
+                    return Err(err.context(format!(
+                        "giving up after {SUBMISSION_ATTEMPT_COUNT_MAX} attempts"
+                    )));
+                }
+                Err(SubmissionError::Transient(err)) => {
+                    eprintln!(
+                        "warning: submission to {} failed transiently ({err:#}), retrying in {}...",
+                        self.hostname,
+                        humantime::format_duration(retry_delay)
+                    );
+                    std::thread::sleep(retry_delay);
+                    retry_delay *= 2;
+                }
+            }
+        }
+
+        unreachable!("expected the submission loop to return");
     }
 
-    fn build_quick_run_towel_job_script(
+    fn build_container_copy_loop(
         fast_access_container_paths: &Vec<PathBuf>,
         node_local_storage_path: &Path,
     ) -> String {
-        let container_copy_loop = if fast_access_container_paths.is_empty() {
-            ""
-        } else {
-            let fast_access_container_paths = fast_access_container_paths
-                .iter()
-                .map(|p| p.as_str())
-                .collect::<Vec<&str>>()
-                .join(" ");
-            &format!(
-                "\
-                for container_file in {fast_access_container_paths}; do\n\
-                    rsync --progress $container_file {node_local_storage_path}/\n\
-                done",
-            )
-        };
+        if fast_access_container_paths.is_empty() {
+            return String::new();
+        }
+
+        let fast_access_container_paths = fast_access_container_paths
+            .iter()
+            .map(|p| p.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ");
+        format!(
+            "\
+            for container_file in {fast_access_container_paths}; do\n\
+                rsync --progress $container_file {node_local_storage_path}/\n\
+            done",
+        )
+    }
 
+    /// Towel job executing `preparation` on the allocated node and then keeping the allocation
+    /// alive, where sparrow waits for the `Going to sleep...' line to tell that the node is ready
+    fn build_quick_run_towel_job_script(preparation: &str) -> String {
         format!(
             concat!(
                 "#!/bin/bash\n",
@@ -253,7 +853,7 @@ impl SlurmClusterHost {
                 "printf \"Going to sleep...\"\n",
                 "sleep 1d",
             ),
-            container_copy_loop
+            preparation
         )
     }
 
@@ -302,6 +902,62 @@ impl Host for SlurmClusterHost {
     fn script_run_command(&self, script_path: &str) -> String {
         return self.script_run_command_template.replace("{}", script_path);
     }
+    fn detached_run_command(
+        &self,
+        run_cmd: &str,
+        run_id: &RunID,
+        environment_variable_names: &[String],
+        attach: bool,
+    ) -> String {
+        match self.execution() {
+            ExecutionMode::Tmux => tmux_wrap(
+                run_cmd,
+                &format!("{run_id}"),
+                attach,
+                self.tmux_keep_open_on,
+            ),
+            ExecutionMode::Nohup => format!(
+                "{{ nohup bash -c {run_cmd} > {log_file} 2>&1 < /dev/null & }} \
+                && echo $! > {pid_file} && {register_command}",
+                run_cmd = shell_quote(run_cmd),
+                log_file = Self::RUN_LOG_FILE_NAME,
+                pid_file = Self::RUN_PID_FILE_NAME,
+                register_command = self.register_run_command(run_id),
+            ),
+            ExecutionMode::SystemdRun => {
+                let unit_name = Self::session_name(run_id);
+                let setenv_options = environment_variable_names
+                    .iter()
+                    .map(|name| format!("--setenv={name} "))
+                    .collect::<String>();
+                format!(
+                    "systemd-run --user --quiet --collect --unit={unit_name} {setenv_options}\
+                    --working-directory=\"$PWD\" \
+                    --property=StandardOutput=file:\"$PWD/{log_file}\" \
+                    --property=StandardError=file:\"$PWD/{log_file}\" \
+                    bash -c {run_cmd} \
+                    && echo {unit_name} > {unit_file} && {register_command}",
+                    run_cmd = shell_quote(run_cmd),
+                    log_file = Self::RUN_LOG_FILE_NAME,
+                    unit_file = Self::RUN_UNIT_FILE_NAME,
+                    register_command = self.register_run_command(run_id),
+                )
+            }
+            // the run is registered beforehand, since attaching blocks until the session ends
+            ExecutionMode::Screen => format!(
+                "{register_command} && exec screen{detach_flags} -S {session_name} \
+                bash -c {session_cmd}",
+                register_command = self.register_run_command(run_id),
+                detach_flags = if attach { "" } else { " -dm" },
+                session_name = Self::session_name(run_id),
+                session_cmd = shell_quote(&format!(
+                    "echo $$ > {pid_file}; {}",
+                    keep_open_wrap(run_cmd, self.tmux_keep_open_on),
+                    pid_file = Self::RUN_PID_FILE_NAME,
+                )),
+            ),
+        }
+    }
     fn output_base_dir_path(&self) -> &Path {
         &self.output_base_dir_path.as_path()
     }
@@ -311,54 +967,423 @@ impl Host for SlurmClusterHost {
     fn is_configured_for_quick_run(&self) -> bool {
         self.hostname.ends_with("-quick")
     }
-
-    fn upload_run_dir(&self, prep_dir: tempfile::TempDir) -> RunDirectory {
-        let run_dir_path = self.temporary_dir_path.join(tmpname("run.", "", 4));
-        self.connection.upload(
-            &prep_dir.utf8_path(),
-            &run_dir_path,
-            SyncOptions::default().copy_contents(),
-        );
-        return RunDirectory::Remote(run_dir_path);
+    fn modules(&self) -> &BTreeMap<String, String> {
+        &self.modules
     }
-    fn download_config_dir(&self, local: &LocalHost, run_id: &RunID) -> Result<PathBuf> {
-        let destination_path = local.config_dir_destination_path(run_id);
-        local.create_dir_all(&destination_path);
-        self.connection.download(
-            &self.config_dir_destination_path(run_id),
-            &destination_path,
-            SyncOptions::default().copy_contents(),
-        );
+    fn check_execution(&self) -> Result<()> {
+        if self.execution == ExecutionMode::Tmux && self.tmux_replacement().is_none() {
+            bail!(
+                "tmux is not installed on {}, but runs are executed in tmux sessions there; \
+                install tmux, set `execution' of the host to `nohup', `systemd-run' or `screen', \
+                or set `tmux.fallback' of the host to `nohup' or `screen'",
+                self.hostname
+            );
+        }
 
-        Ok(destination_path)
+        Ok(())
     }
 
-    fn put(&self, local_path: &Path, host_path: &Path, options: SyncOptions) {
-        self.connection.upload(local_path, host_path, options);
-    }
+    fn accounted_jobs(&self, since: std::time::Duration) -> Result<Vec<AccountedJob>> {
+        let sacct_command = format!(
+            "sacct --noheader --parsable2 --allocations --user \"$USER\" \
+                --starttime now-{} --format {SACCT_FORMAT}",
+            since.as_secs()
+        );
+        let output = self
+            .connection
+            .command("bash")
+            .arg("-c")
+            .arg(&sacct_command)
+            .idempotent()
+            .stderr(openssh::Stdio::inherit)
+            .output()
+            .context(format!(
+                "failed to run `{sacct_command}' on {}",
+                self.hostname
+            ))?;
+        if !output.status.success() {
+            return Err(anyhow!("`{sacct_command}' failed on {}", self.hostname));
+        }
 
-    fn create_dir(&self, path: &Path) {
-        self.connection
-            .command("mkdir")
-            .arg(path)
-            .status()
-            .expect(&format!("expected mkdir {path} to succeed"));
+        Ok(AccountedJob::parse(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
     }
-
-    fn create_dir_all(&self, path: &Path) {
+    fn status(&self) -> Result<HostStatus> {
+        let latency_start = std::time::Instant::now();
         self.connection
-            .command("mkdir")
-            .arg("-p")
-            .arg(path)
+            .command("true")
+            .idempotent()
             .status()
-            .expect(&format!("expected mkdir {path} to succeed"));
-    }
+            .context(format!("failed to reach {}", self.hostname))?;
+        let latency = latency_start.elapsed();
 
-    fn prepare_quick_run(&self, options: &QuickRunPrepOptions) -> Result<()> {
-        match &options {
-            QuickRunPrepOptions::SlurmCluster {
-                constraint,
-                partitions,
+        let output = self
+            .connection
+            .command("bash")
+            .arg("-c")
+            .arg(STATUS_SCRIPT)
+            .arg("sparrow")
+            .arg(self.temporary_dir_path.as_str())
+            .idempotent()
+            .output()
+            .context(format!(
+                "failed to run the status script on {}",
+                self.hostname
+            ))?;
+        Ok(HostStatus {
+            clock_skew: self.clock_skew,
+            ..HostStatus::parse(&String::from_utf8_lossy(&output.stdout), Some(latency))
+        })
+    }
+    fn disk_usage(&self, run_ids: &[RunID]) -> Result<DiskUsage> {
+        let output = self
+            .connection
+            .command("bash")
+            .arg("-c")
+            .arg(DISK_USAGE_SCRIPT)
+            .arg("sparrow")
+            .arg(self.output_base_dir_path.as_str())
+            .args(run_ids.iter().map(|run_id| run_id.to_string()))
+            .idempotent()
+            .output()
+            .context(format!(
+                "failed to run the disk usage script on {}",
+                self.hostname
+            ))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "failed to enter {} on {}",
+                self.output_base_dir_path,
+                self.hostname
+            ));
+        }
+        Ok(DiskUsage::parse(&String::from_utf8_lossy(&output.stdout)))
+    }
+    fn maintenance(&self) -> Result<Maintenance> {
+        let output = self
+            .connection
+            .command("bash")
+            .arg("-c")
+            .arg(MAINTENANCE_SCRIPT)
+            .idempotent()
+            .output()
+            .context(format!("failed to query reservations on {}", self.hostname))?;
+        Ok(Maintenance::parse(&String::from_utf8_lossy(&output.stdout)))
+    }
+    fn facts(&self) -> Result<HostFacts> {
+        HostFacts::cached(&self.hostname, || {
+            let output = self
+                .connection
+                .command("bash")
+                .arg("-c")
+                .arg(FACTS_DETECTION_SCRIPT)
+                .idempotent()
+                .output()
+                .context(format!(
+                    "failed to run the facts detection script on {}",
+                    self.hostname
+                ))?;
+            String::from_utf8(output.stdout)
+                .context("found non-valid utf8 in output of the facts detection script")
+        })
+    }
+
+    fn upload_run_dir(
+        &self,
+        prep_dir: tempfile::TempDir,
+        run_id: &RunID,
+        deduplicated_files: &[&ManifestEntry],
+    ) -> (RunDirectory, Option<SyncStats>) {
+        let run_dir_path = self.run_dir_path(run_id);
+        let upload_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let store_stats = self
+                .upload_to_content_store(prep_dir.utf8_path(), deduplicated_files)
+                .expect("expected upload to the content store to work");
+            let stats = self.connection.upload(
+                prep_dir.utf8_path(),
+                &run_dir_path,
+                SyncOptions::default().copy_contents().stats(),
+            );
+            if !deduplicated_files.is_empty() {
+                self.link_from_content_store(&run_dir_path)
+                    .expect("expected linking from the content store to work");
+            }
+            stats
+                .zip(store_stats)
+                .map(|(stats, store_stats)| stats + store_stats)
+        }));
+        let stats = match upload_result {
+            Ok(stats) => stats,
+            Err(panic) => {
+                // do not leave partially uploaded run directories behind
+                if let Err(err) = self.remove_dir_all(&run_dir_path) {
+                    eprintln!(
+                        "warning: failed to clean up {run_dir_path} on {}: {err:#}",
+                        self.id
+                    );
+                }
+                std::panic::resume_unwind(panic);
+            }
+        };
+        return (RunDirectory::Remote(run_dir_path), stats);
+    }
+    fn content_store_path(&self) -> Option<PathBuf> {
+        self.deduplicate_auxiliary.then(|| {
+            self.temporary_dir_path
+                .join(content_store::CONTENT_STORE_DIR_NAME)
+        })
+    }
+    fn push_to_content_store(
+        &self,
+        dir_path: &Path,
+        files: &[&ManifestEntry],
+    ) -> Result<Option<SyncStats>> {
+        self.upload_to_content_store(dir_path, files)
+    }
+    fn prune_content_store(&self, min_age: std::time::Duration) -> Result<Vec<u64>> {
+        let Some(store_path) = self.content_store_path() else {
+            return Ok(Vec::new());
+        };
+
+        let prune_command = content_store::prune_command(&store_path, min_age);
+        let output = self
+            .connection
+            .command("bash")
+            .arg("-c")
+            .arg(&prune_command)
+            .stderr(openssh::Stdio::inherit)
+            .output()
+            .context(format!(
+                "failed to run `{prune_command}` on {}",
+                self.hostname
+            ))?;
+        if !output.status.success() {
+            bail!("`{prune_command}` failed on {}", self.hostname);
+        }
+
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|size| {
+                size.parse()
+                    .context(format!("found malformed object size `{size}'"))
+            })
+            .collect()
+    }
+    fn download_config_dir(&self, local: &LocalHost, run_id: &RunID) -> Result<PathBuf> {
+        let destination_path = local.config_dir_destination_path(run_id);
+        local.create_dir_all(&destination_path);
+        self.connection
+            .download(
+                &self.config_dir_destination_path(run_id),
+                &destination_path,
+                SyncOptions::default().copy_contents(),
+            )
+            .context(format!(
+                "failed to download the config directory of {run_id}"
+            ))?;
+
+        Ok(destination_path)
+    }
+
+    fn try_put(&self, local_path: &Path, host_path: &Path, options: SyncOptions) -> Result<()> {
+        self.connection
+            .try_upload(local_path, host_path, options)
+            .context(format!(
+                "failed to upload {local_path} to {host_path} on {}",
+                self.hostname
+            ))?;
+        Ok(())
+    }
+
+    fn try_get(&self, host_path: &Path, local_path: &Path, options: SyncOptions) -> Result<()> {
+        self.connection
+            .download(host_path, local_path, options)
+            .context(format!(
+                "failed to download {host_path} from {} to {local_path}",
+                self.hostname
+            ))?;
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &Path) {
+        self.connection
+            .command("mkdir")
+            .arg(path)
+            .status()
+            .expect(&format!("expected mkdir {path} to succeed"));
+    }
+
+    fn create_dir_all(&self, path: &Path) {
+        self.connection
+            .command("mkdir")
+            .arg("-p")
+            .arg(path)
+            .status()
+            .expect(&format!("expected mkdir {path} to succeed"));
+    }
+
+    fn exists(&self, path: &Path) -> Result<bool> {
+        let status = self
+            .connection
+            .command("test")
+            .arg("-e")
+            .arg(path)
+            .idempotent()
+            .status()
+            .context(format!(
+                "failed to check for existence of {path} on {}",
+                self.hostname
+            ))?;
+        Ok(status.success())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        let status = self
+            .connection
+            .command("rm")
+            .arg("-rf")
+            .arg(path)
+            .status()
+            .context(format!(
+                "failed to run `rm -rf {path}` on {}",
+                self.hostname
+            ))?;
+        if !status.success() {
+            return Err(anyhow!("`rm -rf {path}` failed on {}", self.hostname));
+        }
+
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        let status = self
+            .connection
+            .command("rm")
+            .arg("-f")
+            .arg(path)
+            .status()
+            .context(format!("failed to run `rm -f {path}` on {}", self.hostname))?;
+        if !status.success() {
+            return Err(anyhow!("`rm -f {path}` failed on {}", self.hostname));
+        }
+
+        Ok(())
+    }
+
+    fn move_path(&self, source_path: &Path, destination_path: &Path) -> Result<()> {
+        let status = self
+            .connection
+            .command("mv")
+            .arg("-T")
+            .arg(source_path)
+            .arg(destination_path)
+            .status()
+            .context(format!(
+                "failed to run `mv -T {source_path} {destination_path}` on {}",
+                self.hostname
+            ))?;
+        if !status.success() {
+            return Err(anyhow!(
+                "`mv -T {source_path} {destination_path}` failed on {}",
+                self.hostname
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn set_permissions(
+        &self,
+        path: &Path,
+        permissions: &OutputPermissionsConfig,
+        recursive: bool,
+    ) -> Result<()> {
+        let status = self
+            .connection
+            .command("bash")
+            .arg("-c")
+            .arg(PERMISSIONS_SCRIPT)
+            .arg("sparrow")
+            .args(permissions_script_args(path, permissions, recursive))
+            .status()
+            .context(format!(
+                "failed to run the permissions script on {}",
+                self.hostname
+            ))?;
+        if !status.success() {
+            return Err(anyhow!(
+                "failed to change the permissions of {path} on {}",
+                self.hostname
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn copy_dir(
+        &self,
+        source_path: &Path,
+        destination_path: &Path,
+        excludes: &[String],
+    ) -> Result<()> {
+        let copy_command = format!(
+            "mkdir -p {destination} && rsync --archive{excludes} {source}/ {destination}",
+            excludes = excludes
+                .iter()
+                .map(|exclude| format!(" --exclude={}", shell_quote(exclude)))
+                .collect::<String>(),
+            source = shell_quote(source_path.as_str()),
+            destination = shell_quote(destination_path.as_str()),
+        );
+        let status = self
+            .connection
+            .command("bash")
+            .arg("-c")
+            .arg(&copy_command)
+            .status()
+            .context(format!(
+                "failed to run `{copy_command}` on {}",
+                self.hostname
+            ))?;
+        if !status.success() {
+            return Err(anyhow!("`{copy_command}` failed on {}", self.hostname));
+        }
+
+        Ok(())
+    }
+
+    fn copy_dir_to_host(
+        &self,
+        source_path: &Path,
+        hostname: &str,
+        destination_path: &Path,
+    ) -> Result<()> {
+        // nobody could answer a password prompt of the nested ssh connection
+        let status = self
+            .connection
+            .command("rsync")
+            .arg("--archive")
+            .arg("--rsh=ssh -o BatchMode=yes")
+            .arg(format!("{source_path}/"))
+            .arg(format!("{hostname}:{destination_path}/"))
+            .status()
+            .context(format!("failed to run rsync on {}", self.hostname))?;
+        if !status.success() {
+            return Err(anyhow!(
+                "rsync from {} to {hostname} failed with {status}",
+                self.hostname
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn prepare_quick_run(&self, options: &QuickRunPrepOptions) -> Result<()> {
+        match &options {
+            QuickRunPrepOptions::SlurmCluster {
+                constraint,
+                partitions,
                 time,
                 cpu_count,
                 gpu_count,
@@ -377,6 +1402,20 @@ impl Host for SlurmClusterHost {
 
         Ok(())
     }
+    fn estimate_quick_run(&self, options: &QuickRunPrepOptions) -> Result<QuickRunEstimate> {
+        match &options {
+            QuickRunPrepOptions::SlurmCluster {
+                constraint,
+                partitions,
+                time,
+                cpu_count,
+                gpu_count,
+                ..
+            } => self.estimate_quick_run_node_allocation(
+                constraint, partitions, time, *cpu_count, *gpu_count,
+            ),
+        }
+    }
     fn quick_run_is_prepared(&self) -> Result<bool> {
         self.has_allocated_quick_run_node()
     }
@@ -394,11 +1433,12 @@ impl Host for SlurmClusterHost {
             .arg("-maxdepth")
             .arg("2")
             .arg("-type")
-            .arg("d");
+            .arg("d")
+            .idempotent();
         let find_command_string = format!("{find_command:?}");
 
         let find_output = find_command
-            .stderr(openssh::Stdio::inherit())
+            .stderr(openssh::Stdio::inherit)
             .output()
             .context(format!("failed to run `{find_command_string}`"))?;
 
@@ -412,13 +1452,42 @@ impl Host for SlurmClusterHost {
                 let group = path.parent().unwrap().file_name().unwrap();
                 RunID::new(name, group)
             })
+            // hidden directories like the trash are no run groups
+            .filter(|run_id| !run_id.group.starts_with('.'))
+            .collect())
+    }
+    fn run_tags(&self) -> Result<HashMap<RunID, Vec<RunTag>>> {
+        let mut run_tags = HashMap::<RunID, String>::new();
+        for (run_id, tag_line) in self.reproduce_info_lines("tags.txt")? {
+            *run_tags.entry(run_id).or_default() += &format!("{tag_line}\n");
+        }
+
+        run_tags
+            .into_iter()
+            .map(|(run_id, tags)| {
+                let tags =
+                    parse_tags(&tags).context(format!("failed to parse tags of {run_id}"))?;
+                Ok((run_id, tags))
+            })
+            .collect()
+    }
+    fn run_parents(&self) -> Result<HashMap<RunID, RunID>> {
+        Ok(self
+            .reproduce_info_lines("parent.txt")?
+            .into_iter()
+            .filter_map(|(run_id, parent)| Some((run_id, RunID::parse(parent.trim()).ok()?)))
             .collect())
     }
     fn running_runs(&self) -> Vec<RunID> {
+        if self.execution() != ExecutionMode::Tmux {
+            return self.registered_running_runs();
+        }
+
         let tmux_output = self
             .connection
             .command("tmux")
             .arg("list-sessions")
+            .idempotent()
             .output()
             .expect("expected run output find to succeed");
 
@@ -440,6 +1509,150 @@ impl Host for SlurmClusterHost {
             })
             .collect()
     }
+    fn clean_finished_sessions(&self) -> Result<Vec<RunID>> {
+        let execution = self.execution();
+        if !matches!(execution, ExecutionMode::Tmux | ExecutionMode::Screen) {
+            return Ok(Vec::new());
+        }
+
+        let mut cleaned_runs = Vec::new();
+        for run_id in self.running_runs() {
+            if self.recorded_exit_code(&run_id) != Some(0) {
+                continue;
+            }
+
+            let mut kill_command = match execution {
+                ExecutionMode::Screen => {
+                    let mut kill_command = self.connection.command("screen");
+                    kill_command
+                        .arg("-S")
+                        .arg(Self::session_name(&run_id))
+                        .arg("-X")
+                        .arg("quit");
+                    kill_command
+                }
+                _ => {
+                    let mut kill_command = self.connection.command("tmux");
+                    kill_command
+                        .arg("kill-session")
+                        .arg("-t")
+                        .arg(run_id.to_string());
+                    kill_command
+                }
+            };
+            let status = kill_command.status().context(format!(
+                "failed to kill the session of {run_id} on {}",
+                self.hostname
+            ))?;
+            if !status.success() {
+                return Err(anyhow!(
+                    "failed to kill the session of {run_id} on {}",
+                    self.hostname
+                ));
+            }
+            cleaned_runs.push(run_id);
+        }
+
+        Ok(cleaned_runs)
+    }
+    fn rename_session(&self, run_id: &RunID, new_run_id: &RunID) -> Result<()> {
+        let execution = self.execution();
+        let rename_command = match execution {
+            ExecutionMode::Tmux => format!(
+                "if tmux has-session -t {run} 2>/dev/null; then \
+                    tmux rename-session -t {run} {new_run}; \
+                fi",
+                run = shell_quote(&run_id.to_string()),
+                new_run = shell_quote(&new_run_id.to_string()),
+            ),
+            ExecutionMode::Nohup | ExecutionMode::SystemdRun | ExecutionMode::Screen => {
+                let registry_path = self.run_registry_path();
+                let new_entry_path = new_run_id.path(&registry_path);
+                let mut rename_command = format!(
+                    "if [ -L {entry} ]; then mkdir -p {new_group} && mv {entry} {new_entry}; fi",
+                    entry = shell_quote(run_id.path(&registry_path).as_str()),
+                    new_group = shell_quote(
+                        new_entry_path
+                            .parent()
+                            .expect("expected registry entry to have a parent")
+                            .as_str()
+                    ),
+                    new_entry = shell_quote(new_entry_path.as_str()),
+                );
+                // screen sessions are listed as `<pid>.<name>`
+                if execution == ExecutionMode::Screen {
+                    rename_command += &format!(
+                        " && if screen -ls | grep -q '[.]{session}[[:space:]]'; then \
+                            screen -S {session} -X sessionname {new_session}; \
+                        fi",
+                        session = Self::session_name(run_id),
+                        new_session = Self::session_name(new_run_id),
+                    );
+                }
+                rename_command
+            }
+        };
+
+        let status = self
+            .connection
+            .command("bash")
+            .arg("-c")
+            .arg(&rename_command)
+            .status()
+            .context(format!(
+                "failed to rename the session of {run_id} on {}",
+                self.hostname
+            ))?;
+        if !status.success() {
+            return Err(anyhow!(
+                "failed to rename the session of {run_id} on {}",
+                self.hostname
+            ));
+        }
+
+        Ok(())
+    }
+    fn execute(
+        &self,
+        command: &str,
+        working_dir_path: Option<&Path>,
+    ) -> Result<std::process::ExitStatus> {
+        let command = match working_dir_path {
+            Some(working_dir_path) => {
+                format!("cd {} && {command}", shell_quote(working_dir_path.as_str()))
+            }
+            None => command.to_owned(),
+        };
+        self.connection
+            .command("bash")
+            .arg("-c")
+            .arg(&command)
+            .status()
+            .context(format!("failed to run `{command}` on {}", self.hostname))
+    }
+    fn signal_process_group(&self, process_group: i32, signal: &str) -> Result<()> {
+        let status = self
+            .connection
+            .command("kill")
+            .arg("-s")
+            .arg(signal)
+            .arg("--")
+            .arg(format!("-{process_group}"))
+            .status()
+            .context(format!(
+                "failed to signal process group {process_group} on {}",
+                self.hostname
+            ))?;
+        if !status.success() {
+            return Err(anyhow!(
+                "failed to send {signal} to process group {process_group} on {}, runs submitted \
+                to a scheduler execute on other nodes and only see the control file",
+                self.hostname
+            ));
+        }
+
+        Ok(())
+    }
     fn log_file_paths(&self, run_id: &RunID) -> Vec<PathBuf> {
         let log_path = run_id.path(&self.output_base_dir_path);
 
@@ -451,6 +1664,7 @@ impl Host for SlurmClusterHost {
             .arg("f")
             .arg("-name")
             .arg("*.log")
+            .idempotent()
             .output()
             .expect("expected log find to succeed");
 
@@ -471,11 +1685,30 @@ impl Host for SlurmClusterHost {
             .collect()
     }
     fn attach(&self, run_id: &RunID) {
+        let attach_command = match self.execution() {
+            ExecutionMode::Tmux => {
+                format!(
+                    "exec tmux attach-session -t {}",
+                    shell_quote(&run_id.to_string())
+                )
+            }
+            ExecutionMode::Screen => format!("exec screen -r {}", Self::session_name(run_id)),
+            ExecutionMode::Nohup | ExecutionMode::SystemdRun => format!(
+                "exec tail -f {}",
+                shell_quote(
+                    run_id
+                        .path(self.run_registry_path())
+                        .join(Self::RUN_LOG_FILE_NAME)
+                        .as_str()
+                )
+            ),
+        };
         let err = std::process::Command::new(std::env::var("SHELL").unwrap())
             .arg("-c")
-            .arg(&format!(
-                "ssh -tt {} 'exec tmux attach-session -t {run_id}'",
-                self.hostname
+            .arg(format!(
+                "ssh -tt {} {}",
+                shell_quote(&self.hostname),
+                shell_quote(&self.connection.wrap_command(&attach_command))
             ))
             .exec();
         panic!("expected exec to never fail: {err}");
@@ -485,7 +1718,7 @@ impl Host for SlurmClusterHost {
         run_id: &RunID,
         local_base_path: &Path,
         options: &RunOutputSyncOptions,
-    ) -> Result<(), String> {
+    ) -> Result<Option<SyncStats>, SyncError> {
         let local_dest_path = run_id.path(local_base_path);
         let from_remote_marker_path = local_dest_path.join(".from_remote");
 
@@ -493,10 +1726,10 @@ impl Host for SlurmClusterHost {
             && !from_remote_marker_path.exists()
             && !options.ignore_from_remote_marker
         {
-            return Err(format!(
+            return Err(SyncError::Conflict(format!(
                 "{local_dest_path} does exist but the `.from_remote' \
                 marker does not exist, refusing to sync"
-            ));
+            )));
         }
 
         if !local_dest_path.exists() {
@@ -505,20 +1738,32 @@ impl Host for SlurmClusterHost {
             ));
         }
 
-        self.connection.download(
-            &run_id.path(&self.output_base_dir_path),
-            &local_dest_path,
-            SyncOptions::default()
-                .copy_contents()
-                .exclude(&options.excludes)
-                .progress(),
-        );
+        let sync_options = SyncOptions::default()
+            .copy_contents()
+            .exclude(&options.excludes)
+            .only_include(&options.includes);
+        let sync_options = match &options.max_size {
+            Some(max_size) => sync_options.max_size(max_size).prune_empty_dirs(),
+            None => sync_options,
+        };
+        let stats = self
+            .connection
+            .download(
+                &run_id.path(&self.output_base_dir_path),
+                &local_dest_path,
+                if options.show_progress {
+                    sync_options.progress()
+                } else {
+                    sync_options.stats()
+                },
+            )
+            .map_err(|err| SyncError::Transfer(format!("failed to download {run_id}: {err}")))?;
 
         std::fs::File::create(&from_remote_marker_path).expect(&format!(
             "expected creation of {from_remote_marker_path} to work"
         ));
 
-        Ok(())
+        Ok(stats)
     }
     fn tail_log(&self, run_id: &RunID, log_file_path: &Path, follow: bool) {
         let log_file_path = run_id.path(&self.output_base_dir_path).join(log_file_path);
@@ -526,12 +1771,124 @@ impl Host for SlurmClusterHost {
         let err = std::process::Command::new(std::env::var("SHELL").unwrap())
             .arg("-c")
             .arg(&format!(
-                "ssh -tt {} 'exec {cmd} {log_file_path}'",
-                self.hostname
+                "ssh -tt {} {}",
+                shell_quote(&self.hostname),
+                shell_quote(&format!(
+                    "exec {cmd} {}",
+                    shell_quote(log_file_path.as_str())
+                ))
             ))
             .exec();
         panic!("expected exec to never fail: {err}");
     }
+
+    fn list_dir(&self, dir_path: &Path) -> Result<Vec<DirListingEntry>> {
+        let mut find_command = self.connection.command("find");
+        find_command
+            .arg(dir_path)
+            .arg("-mindepth")
+            .arg("1")
+            .arg("-maxdepth")
+            .arg("1")
+            .arg("-printf")
+            .arg("%Y %s %f\\n")
+            .idempotent();
+        let find_command_string = format!("{find_command:?}");
+
+        let find_output = find_command
+            .stderr(openssh::Stdio::inherit)
+            .output()
+            .context(format!("failed to run `{find_command_string}`"))?;
+        if !find_output.status.success() {
+            return Err(anyhow!("`{find_command_string}` failed"));
+        }
+
+        let find_output = String::from_utf8(find_output.stdout).context(format!(
+            "found non-valid utf8 in output of `{find_command_string}`"
+        ))?;
+
+        let mut entries = find_output
+            .lines()
+            .map(|line| {
+                let mut fields = line.splitn(3, ' ');
+                let (Some(file_type), Some(size), Some(name)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    return Err(anyhow!(
+                        "found malformed line `{line}' in output of `{find_command_string}`"
+                    ));
+                };
+                Ok(DirListingEntry {
+                    name: name.to_owned(),
+                    is_dir: file_type == "d",
+                    size: size.parse().context(format!(
+                        "found malformed size in output of `{find_command_string}`"
+                    ))?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+
+        Ok(entries)
+    }
+
+    fn file_sizes(&self, dir_path: &Path) -> Result<BTreeMap<PathBuf, u64>> {
+        let mut find_command = self.connection.command("find");
+        find_command
+            .arg(dir_path)
+            .arg("-type")
+            .arg("f")
+            .arg("-printf")
+            .arg("%s %P\\0")
+            .idempotent();
+        let find_command_string = format!("{find_command:?}");
+
+        let find_output = find_command
+            .stderr(openssh::Stdio::inherit)
+            .output()
+            .context(format!("failed to run `{find_command_string}`"))?;
+        if !find_output.status.success() {
+            return Err(anyhow!("`{find_command_string}` failed"));
+        }
+
+        let find_output = String::from_utf8(find_output.stdout).context(format!(
+            "found non-valid utf8 in output of `{find_command_string}`"
+        ))?;
+        find_output
+            .split_terminator('\0')
+            .map(|entry| {
+                let Some((size, path)) = entry.split_once(' ') else {
+                    return Err(anyhow!(
+                        "found malformed entry `{entry}' in output of `{find_command_string}`"
+                    ));
+                };
+                let size = size.parse().context(format!(
+                    "found malformed size in output of `{find_command_string}`"
+                ))?;
+                Ok((PathBuf::from(path), size))
+            })
+            .collect()
+    }
+
+    fn read_file_head(&self, file_path: &Path, max_size: u64) -> Result<Vec<u8>> {
+        let mut head_command = self.connection.command("head");
+        head_command
+            .arg("-c")
+            .arg(max_size.to_string())
+            .arg(file_path)
+            .idempotent();
+        let head_command_string = format!("{head_command:?}");
+
+        let head_output = head_command
+            .stderr(openssh::Stdio::inherit)
+            .output()
+            .context(format!("failed to run `{head_command_string}`"))?;
+        if !head_output.status.success() {
+            return Err(anyhow!("`{head_command_string}` failed"));
+        }
+
+        Ok(head_output.stdout)
+    }
 }
 
 fn tmpname(prefix: &str, suffix: &str, rand_len: u8) -> String {
@@ -0,0 +1,130 @@
+use super::local::cache_dir_path;
+use anyhow::{Context, Result};
+use camino::Utf8PathBuf as PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// Script printing one `<key>=<value>` line per status value of the host, given the directory
+/// whose file system usage to report as its first argument; unavailable values are left empty
+pub const STATUS_SCRIPT: &str = r#"
+echo "load=$(cut -d ' ' -f 1-3 /proc/loadavg 2>/dev/null)"
+echo "scratch_usage=$(df -P "$1" 2>/dev/null \
+    | awk 'NR == 2 { printf "%s of %.0f GiB", $5, $2 / 1048576 }')"
+if command -v squeue > /dev/null; then
+    echo "pending_jobs=$(squeue --noheader --user "$USER" --states PENDING | wc -l)"
+    echo "running_jobs=$(squeue --noheader --user "$USER" --states RUNNING | wc -l)"
+fi
+"#;
+
+/// Current state of a host, shown by `sparrow host status` before deciding where to submit
+#[derive(Default)]
+pub struct HostStatus {
+    pub latency: Option<Duration>,
+    /// Offset of the clock of the host from the local clock in seconds
+    pub clock_skew: Option<i64>,
+    pub load: Option<String>,
+    pub scratch_usage: Option<String>,
+    pub pending_jobs: Option<usize>,
+    pub running_jobs: Option<usize>,
+}
+
+impl HostStatus {
+    pub fn parse(output: &str, latency: Option<Duration>) -> Self {
+        let mut status = HostStatus {
+            latency,
+            ..Default::default()
+        };
+        for (key, value) in output
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .filter(|(_, value)| !value.is_empty())
+        {
+            match key {
+                "load" => status.load = Some(value.to_owned()),
+                "scratch_usage" => status.scratch_usage = Some(value.to_owned()),
+                "pending_jobs" => status.pending_jobs = value.parse().ok(),
+                "running_jobs" => status.running_jobs = value.parse().ok(),
+                _ => {}
+            }
+        }
+
+        status
+    }
+}
+
+impl std::fmt::Display for HostStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unknown = String::from("unknown");
+        let latency = self
+            .latency
+            .map(|latency| format!("{} ms", latency.as_millis()))
+            .unwrap_or(String::from("-"));
+        let clock_skew = self
+            .clock_skew
+            .map(|clock_skew| format!("{clock_skew:+} s"))
+            .unwrap_or(String::from("-"));
+        let jobs = match (self.pending_jobs, self.running_jobs) {
+            (Some(pending), Some(running)) => format!("{pending} pending, {running} running"),
+            _ => String::from("-"),
+        };
+
+        writeln!(f, "latency:       {latency}")?;
+        writeln!(f, "clock skew:    {clock_skew}")?;
+        writeln!(
+            f,
+            "load:          {}",
+            self.load.as_ref().unwrap_or(&unknown)
+        )?;
+        writeln!(
+            f,
+            "scratch usage: {}",
+            self.scratch_usage.as_ref().unwrap_or(&unknown)
+        )?;
+        writeln!(f, "jobs:          {jobs}")
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Operation {
+    pub description: String,
+    pub time: String,
+}
+
+fn operation_file_path(host_id: &str) -> PathBuf {
+    cache_dir_path()
+        .join("operations")
+        .join(format!("{host_id}.json"))
+}
+
+/// Remember `description` as the last successful operation on the host `host_id`, which is only
+/// informational, so failures are reported as warnings
+pub fn record_operation(host_id: &str, description: &str) {
+    let operation = Operation {
+        description: description.to_owned(),
+        time: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+    };
+    let operation_path = operation_file_path(host_id);
+    let result = operation_path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| {
+            std::fs::write(
+                &operation_path,
+                serde_json::to_string(&operation).expect("expected operation to be serializable"),
+            )
+        });
+    if let Err(err) = result {
+        eprintln!("warning: failed to record operation in {operation_path}: {err}");
+    }
+}
+
+pub fn last_operation(host_id: &str) -> Result<Option<Operation>> {
+    let operation_path = operation_file_path(host_id);
+    if !operation_path.exists() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read_to_string(&operation_path)
+        .context(format!("failed to read {operation_path}"))?;
+    serde_json::from_str(&content).context(format!("failed to parse {operation_path}"))
+}
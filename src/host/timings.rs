@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, UNIX_EPOCH};
+
+pub const TIMINGS_FILE_NAME: &str = "timings.txt";
+pub const MAX_TIMINGS_FILE_SIZE: u64 = 4096;
+
+/// Times of the last execution of a run in unix seconds, all taken by the clock of its host,
+/// where the submission time is recorded by sparrow and the start and end times and the slurm
+/// partition by the run script
+#[derive(Default)]
+pub struct RunTimings {
+    pub submitted: Option<u64>,
+    pub started: Option<u64>,
+    pub ended: Option<u64>,
+    pub partition: Option<String>,
+}
+
+impl RunTimings {
+    /// Parse the `<key>=<value>` lines of a timings file, where later lines take precedence, so
+    /// that a run script executed again in the same output overrides the times of the previous
+    /// execution
+    pub fn parse(content: &str) -> Self {
+        let mut timings = RunTimings::default();
+        for (key, value) in content
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .filter(|(_, value)| !value.is_empty())
+        {
+            match key {
+                "submitted" => timings.submitted = value.parse().ok(),
+                "started" => timings.started = value.parse().ok(),
+                "ended" => timings.ended = value.parse().ok(),
+                "partition" => timings.partition = Some(value.to_owned()),
+                _ => {}
+            }
+        }
+
+        timings
+    }
+
+    /// Time between submission and start, which is the queue wait on slurm clusters
+    pub fn queue_wait(&self) -> Option<Duration> {
+        match (self.submitted, self.started) {
+            (Some(submitted), Some(started)) => {
+                Some(Duration::from_secs(started.saturating_sub(submitted)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Time between start and end, which is unknown while the run is running
+    pub fn runtime(&self) -> Option<Duration> {
+        match (self.started, self.ended) {
+            (Some(started), Some(ended)) if ended >= started => {
+                Some(Duration::from_secs(ended - started))
+            }
+            _ => None,
+        }
+    }
+}
+
+pub const TIMINGS_HEADER: [&str; 4] = ["submitted", "queue wait", "runtime", "partition"];
+
+/// Columns of `timings` below [`TIMINGS_HEADER`] in `list-runs --timings`
+pub fn timings_columns(timings: &RunTimings) -> [String; 4] {
+    let format_duration = |duration: Option<Duration>| {
+        duration
+            .map(|duration| humantime::format_duration(duration).to_string())
+            .unwrap_or(String::from("-"))
+    };
+
+    [
+        timings
+            .submitted
+            .map(|submitted| {
+                humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(submitted))
+                    .to_string()
+            })
+            .unwrap_or(String::from("-")),
+        format_duration(timings.queue_wait()),
+        format_duration(timings.runtime()),
+        timings.partition.clone().unwrap_or(String::from("-")),
+    ]
+}
+
+/// Mean queue wait and number of started runs by partition, where runs outside of slurm are
+/// counted under `-`
+pub fn mean_queue_waits<'t, I: IntoIterator<Item = &'t RunTimings>>(
+    timings: I,
+) -> BTreeMap<String, (Duration, usize)> {
+    let mut waits = BTreeMap::<String, (Duration, usize)>::new();
+    for timings in timings {
+        let Some(queue_wait) = timings.queue_wait() else {
+            continue;
+        };
+        let partition = timings.partition.clone().unwrap_or(String::from("-"));
+        let (total_wait, count) = waits.entry(partition).or_default();
+        *total_wait += queue_wait;
+        *count += 1;
+    }
+
+    waits
+        .into_iter()
+        .map(|(partition, (total_wait, count))| {
+            let mean_wait = Duration::from_secs(total_wait.as_secs() / count as u64);
+            (partition, (mean_wait, count))
+        })
+        .collect()
+}
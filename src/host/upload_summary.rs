@@ -0,0 +1,117 @@
+use super::manifest::Manifest;
+use super::rsync::SyncStats;
+use crate::utils::format_size;
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use std::time::Duration;
+
+pub const UPLOAD_SUMMARY_FILE_NAME: &str = "run_meta.yaml";
+
+/// Sizes of the mappings in a run directory and how long its upload took
+pub struct UploadSummary {
+    pub mapping_sizes: Vec<(String, u64)>,
+    pub total_size: u64,
+    /// Size of the files that were actually transferred, which leaves out unchanged files and is
+    /// unknown for local run directories
+    pub transferred_size: Option<u64>,
+    /// Bytes sent over the network, which is unknown for local run directories
+    pub bytes_sent: Option<u64>,
+    pub duration: Duration,
+    pub run_dir_path: PathBuf,
+}
+
+impl UploadSummary {
+    /// Summarize the upload of the run directory at `run_dir_path`, whose payload is described
+    /// by `manifest` and where each mapping is given by its name and target path
+    pub fn new(
+        manifest: &Manifest,
+        mappings: &[(String, &Path)],
+        stats: Option<SyncStats>,
+        duration: Duration,
+        run_dir_path: &Path,
+    ) -> Self {
+        let mapping_sizes = mappings
+            .iter()
+            .map(|(name, target_path)| {
+                let size = manifest
+                    .files
+                    .iter()
+                    .filter(|entry| entry.path.starts_with(target_path))
+                    .map(|entry| entry.size)
+                    .sum();
+                (name.clone(), size)
+            })
+            .collect();
+
+        UploadSummary {
+            mapping_sizes,
+            total_size: manifest.files.iter().map(|entry| entry.size).sum(),
+            transferred_size: stats.map(|stats| stats.transferred_file_size),
+            bytes_sent: stats.map(|stats| stats.bytes_sent),
+            duration,
+            run_dir_path: run_dir_path.to_owned(),
+        }
+    }
+
+    fn bandwidth(&self) -> Option<f64> {
+        let seconds = self.duration.as_secs_f64();
+        self.bytes_sent
+            .filter(|_| seconds > 0.0)
+            .map(|bytes_sent| bytes_sent as f64 / seconds)
+    }
+
+    pub fn to_yaml(&self) -> String {
+        let quote = |value: &str| {
+            serde_json::to_string(value).expect("expected strings to be serializable")
+        };
+
+        let mut yaml = format!(
+            "run_dir: {}\nupload:\n  duration_seconds: {:.3}\n  total_bytes: {}\n",
+            quote(self.run_dir_path.as_str()),
+            self.duration.as_secs_f64(),
+            self.total_size,
+        );
+        if let Some(transferred_size) = self.transferred_size {
+            yaml += &format!("  transferred_bytes: {transferred_size}\n");
+        }
+        if let Some(bytes_sent) = self.bytes_sent {
+            yaml += &format!("  bytes_sent: {bytes_sent}\n");
+        }
+        if let Some(bandwidth) = self.bandwidth() {
+            yaml += &format!("  bytes_per_second: {bandwidth:.0}\n");
+        }
+        yaml += "  mappings:\n";
+        for (name, size) in &self.mapping_sizes {
+            yaml += &format!("    {}: {size}\n", quote(name));
+        }
+
+        yaml
+    }
+}
+
+impl std::fmt::Display for UploadSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Upload summary:")?;
+        for (name, size) in &self.mapping_sizes {
+            writeln!(f, "    {name}: {}", format_size(*size))?;
+        }
+        write!(
+            f,
+            "    total: {} in {}",
+            format_size(self.total_size),
+            humantime::format_duration(Duration::from_millis(self.duration.as_millis() as u64))
+        )?;
+        if let Some(transferred_size) = self.transferred_size {
+            write!(f, ", {} transferred", format_size(transferred_size))?;
+        }
+        if let (Some(bytes_sent), Some(bandwidth)) = (self.bytes_sent, self.bandwidth()) {
+            write!(
+                f,
+                ", {} sent at {}/s",
+                format_size(bytes_sent),
+                format_size(bandwidth as u64)
+            )?;
+        }
+        writeln!(f)?;
+        write!(f, "    run directory: {}", self.run_dir_path)
+    }
+}
@@ -0,0 +1,244 @@
+use crate::host::{Host, RunID};
+use crate::run::read_recorded_code_versions;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// State of a run in a lineage, as far as it can be told from its host
+enum LineageStatus {
+    Running,
+    Succeeded,
+    Failed(i32),
+    /// The run has not recorded an exit code, e.g. because it was killed or is still running on
+    /// the local host
+    Unknown,
+    /// The run was recorded as parent but its output no longer exists
+    Deleted,
+}
+
+impl std::fmt::Display for LineageStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LineageStatus::Running => write!(f, "running"),
+            LineageStatus::Succeeded => write!(f, "succeeded"),
+            LineageStatus::Failed(exit_code) => write!(f, "failed with exit code {exit_code}"),
+            LineageStatus::Unknown => write!(f, "unknown"),
+            LineageStatus::Deleted => write!(f, "deleted"),
+        }
+    }
+}
+
+struct LineageNode {
+    status: LineageStatus,
+    code_versions: BTreeMap<String, String>,
+}
+
+impl LineageNode {
+    fn describe(&self) -> String {
+        let code_versions = self
+            .code_versions
+            .iter()
+            .map(|(code_source_id, version)| format!("{code_source_id}={version}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if code_versions.is_empty() {
+            return self.status.to_string();
+        }
+        format!("{}, {code_versions}", self.status)
+    }
+}
+
+/// Ancestors and descendants of a run, as recorded by `--parent` on submission
+pub struct Lineage {
+    selected: RunID,
+    /// Ancestors of the selected run, the most distant first
+    ancestors: Vec<RunID>,
+    /// Children of the selected run and its descendants by run, sorted by name
+    children: HashMap<RunID, Vec<RunID>>,
+    nodes: HashMap<RunID, LineageNode>,
+}
+
+impl Lineage {
+    /// Collect the lineage of `run_id` on `host` together with the status and code versions of
+    /// each run in it
+    pub fn collect(host: &dyn Host, run_id: &RunID) -> Result<Self> {
+        let runs = host
+            .runs()
+            .context(format!("failed to obtain runs from {}", host.id()))?
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let parents = host
+            .run_parents()
+            .context(format!("failed to obtain run parents from {}", host.id()))?;
+
+        // parents are only ever recorded for existing runs, but guard against edited records
+        let mut ancestors = Vec::new();
+        let mut visited = HashSet::from([run_id.clone()]);
+        let mut current = run_id;
+        while let Some(parent) = parents.get(current) {
+            if !visited.insert(parent.clone()) {
+                break;
+            }
+            ancestors.push(parent.clone());
+            current = parent;
+        }
+        ancestors.reverse();
+
+        let mut all_children = HashMap::<&RunID, Vec<RunID>>::new();
+        for (child, parent) in &parents {
+            all_children.entry(parent).or_default().push(child.clone());
+        }
+        let mut children = HashMap::new();
+        let mut pending = vec![run_id.clone()];
+        while let Some(current) = pending.pop() {
+            let mut current_children = all_children
+                .get(&current)
+                .into_iter()
+                .flatten()
+                .filter(|child| visited.insert((*child).clone()))
+                .cloned()
+                .collect::<Vec<_>>();
+            current_children.sort_by_key(|child| child.to_string());
+            pending.extend(current_children.iter().cloned());
+            children.insert(current, current_children);
+        }
+
+        let running_runs = if host.is_local() {
+            Vec::new()
+        } else {
+            host.running_runs()
+        };
+        let mut nodes = HashMap::new();
+        for node_id in visited {
+            let node = if !runs.contains(&node_id) {
+                LineageNode {
+                    status: LineageStatus::Deleted,
+                    code_versions: BTreeMap::new(),
+                }
+            } else {
+                // a recorded exit code takes precedence, since sessions kept open after the run
+                // exited still count as running
+                let status = match host.recorded_exit_code(&node_id) {
+                    Some(0) => LineageStatus::Succeeded,
+                    Some(exit_code) => LineageStatus::Failed(exit_code),
+                    None if running_runs.contains(&node_id) => LineageStatus::Running,
+                    None => LineageStatus::Unknown,
+                };
+                LineageNode {
+                    status,
+                    code_versions: read_recorded_code_versions(host, &node_id)
+                        .context(format!("failed to read code versions of {node_id}"))?,
+                }
+            };
+            nodes.insert(node_id, node);
+        }
+
+        Ok(Lineage {
+            selected: run_id.clone(),
+            ancestors,
+            children,
+            nodes,
+        })
+    }
+
+    fn describe(&self, run_id: &RunID) -> String {
+        self.nodes
+            .get(run_id)
+            .map(LineageNode::describe)
+            .unwrap_or_default()
+    }
+
+    fn write_descendants(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        run_id: &RunID,
+        prefix: &str,
+    ) -> std::fmt::Result {
+        let children = self.children.get(run_id).map(Vec::as_slice).unwrap_or(&[]);
+        for (index, child) in children.iter().enumerate() {
+            let is_last = index + 1 == children.len();
+            let (branch, indent) = if is_last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            writeln!(f, "{prefix}{branch}{child} ({})", self.describe(child))?;
+            self.write_descendants(f, child, &format!("{prefix}{indent}"))?;
+        }
+        Ok(())
+    }
+
+    /// Write the lineage as graphviz graph, with the selected run in bold
+    pub fn to_dot(&self) -> String {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut nodes = self.nodes.keys().collect::<Vec<_>>();
+        nodes.sort_by_key(|run_id| run_id.to_string());
+
+        let mut dot = String::from("digraph lineage {\n    node [shape=box];\n");
+        for run_id in &nodes {
+            let label = format!("{run_id}\n{}", self.describe(run_id).replace(", ", "\n"));
+            let style = if **run_id == self.selected {
+                ", style=bold"
+            } else {
+                ""
+            };
+            dot += &format!(
+                "    {} [label={}{style}];\n",
+                quote(&run_id.to_string()),
+                quote(&label).replace('\n', "\\n")
+            );
+        }
+        let edges = self.ancestors.windows(2).map(|pair| (&pair[0], &pair[1]));
+        let edges = edges
+            .chain(self.ancestors.last().map(|parent| (parent, &self.selected)))
+            .chain(nodes.iter().flat_map(|run_id| {
+                self.children
+                    .get(run_id)
+                    .into_iter()
+                    .flatten()
+                    .map(move |child| (*run_id, child))
+            }));
+        for (parent, child) in edges {
+            dot += &format!(
+                "    {} -> {};\n",
+                quote(&parent.to_string()),
+                quote(&child.to_string())
+            );
+        }
+        dot += "}\n";
+
+        dot
+    }
+}
+
+impl std::fmt::Display for Lineage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut prefix = String::new();
+        for (depth, ancestor) in self.ancestors.iter().enumerate() {
+            let branch = if depth == 0 { "" } else { "└── " };
+            writeln!(
+                f,
+                "{prefix}{branch}{ancestor} ({})",
+                self.describe(ancestor)
+            )?;
+            if depth > 0 {
+                prefix += "    ";
+            }
+        }
+
+        let branch = if self.ancestors.is_empty() {
+            ""
+        } else {
+            "└── "
+        };
+        writeln!(
+            f,
+            "{prefix}{branch}{} ({}) <- selected",
+            self.selected,
+            self.describe(&self.selected)
+        )?;
+        if !self.ancestors.is_empty() {
+            prefix += "    ";
+        }
+        self.write_descendants(f, &self.selected, &prefix)
+    }
+}
@@ -0,0 +1,49 @@
+use crate::host::rsync::SyncOptions;
+use crate::host::{Host, RunID};
+use anyhow::{bail, Context, Result};
+use camino::Utf8Path as Path;
+use std::time::Duration;
+
+/// Copy all log files of `run_id` to the same paths below the run output in `local_base_path`
+/// every `interval` while the run is running, with a last pass once it finished
+pub fn mirror_run_logs(
+    host: &dyn Host,
+    run_id: &RunID,
+    local_base_path: &Path,
+    interval: Duration,
+) -> Result<()> {
+    if host.is_local() {
+        bail!("the logs of local runs are on the local machine already");
+    }
+
+    let host_run_path = run_id.path(host.output_base_dir_path());
+    let local_run_path = run_id.path(local_base_path);
+    std::fs::create_dir_all(&local_run_path)
+        .context(format!("failed to create {local_run_path}"))?;
+
+    let includes = [String::from("*/"), String::from("*.log")];
+    loop {
+        // checked before syncing, so that the last pass sees the final logs; a recorded exit code
+        // means the run is done even if its session is kept open
+        let is_running =
+            host.running_runs().contains(run_id) && host.recorded_exit_code(run_id).is_none();
+        host.try_get(
+            &host_run_path,
+            &local_run_path,
+            SyncOptions::default()
+                .quiet()
+                .copy_contents()
+                .only_include(&includes)
+                .prune_empty_dirs(),
+        )
+        .context(format!("failed to mirror the logs of {run_id}"))?;
+
+        if !is_running {
+            break;
+        }
+        std::thread::sleep(interval);
+    }
+    println!("{run_id} finished, mirrored its logs to {local_run_path}");
+
+    Ok(())
+}
@@ -34,6 +34,12 @@
 //! Consult the documentation of the [`cfg`] module for details on how to write this file and note
 //! that the two files get merged into one configuration, where `.sparrow/private.yaml` has
 //! priority.
+//! Teams can share hosts and the payload layout in a committed `.sparrow/team.yaml`, which is
+//! merged after `.sparrow/config.yaml`, followed by the overlay `.sparrow/users/<name>.yaml` of
+//! `$USER` (or of `--as-user <name>`) and finally `.sparrow/private.yaml`, which is optional then.
+//! Strings in the team configuration may contain `{user}` and `{<variable>}` placeholders, which
+//! are replaced by the user name and the `variables` of the overlay, e.g. to resolve account names
+//! and home directories per user.
 //!
 //! Now we only need to define the command we want sparrow to run our code with.
 //! This is done by writing a .sparrow/run.sh.j2 file, which is a bash script template that uses the [jinja
@@ -66,6 +72,83 @@
 //! populated by sparrow to create the final run script.
 //! These expression allow for some logic with a python-like syntax, like if-statements and loops.
 //! The variables that jinja uses are defined and documented by sparrow in the [`RunInfo`] struct.
+//! Pipelines with several config files, like `train.yaml` and `eval.yaml`, can list them as
+//! `entrypoints` instead of a single `entrypoint` under `payload.config`; all of them are opened
+//! together for review and checked, the first one expands `{config.<key>}` placeholders in run
+//! names and the list is available as `payload.config_entrypoints` to the template.
+//! Entrypoints ending in `.j2`, like `config.yaml.j2`, are jinja templates, which are rendered
+//! against the template context of each run into the entrypoint without `.j2` before the review,
+//! so that e.g. runs of a sweep get config files with their `sweep.params` filled in; only the
+//! rendered file is uploaded, and `{config.<key>}` placeholders in run names cannot refer to it.
+//! Resource requirements can be collected in named profiles under `resources:` in the
+//! configuration (with `gpus`, `cpus`, `mem`, `time` and `partition`), where a remote host may
+//! define its own `resources:` to override profiles of the same name.
+//! Passing `--resources <name>` to `sparrow run` exposes the selected profile as `resources` to
+//! the template, e.g. as `{{ resources.gpus }}`; without `--resources`, a profile named `default`
+//! is used if there is one.
+//! `--gpus`, `--cpus`, `--mem` and `--time` override the corresponding fields of the profile, or
+//! define the resources of the run on their own, and all resources of a run are written as
+//! `#SBATCH` directives to the top of the run script, which slurm applies when the run script is
+//! submitted with sbatch, e.g. by `script_run_command_template: sbatch {}`.
+//! Facts detected on the host, like its GPU count, CUDA and glibc versions, slurm partitions and
+//! scratch path, are available as `host.facts`; they are cached for a day in
+//! `~/.cache/sparrow/facts`, so delete the cached file of a host to detect them again.
+//! Environment modules differ between clusters, so each host can map names to the modules it
+//! provides under `modules:`, e.g. `python: Python/3.11.5`, which are available as
+//! `host.modules`; `{{ host.module_load(['python', 'gcc']) }}` renders the corresponding
+//! `module load Python/3.11.5 ...` line and fails for names the host does not define.
+//! Runs that are part of a sweep or job array can be given `--sweep-index`, `--sweep-total` and
+//! `--sweep-param KEY=VALUE`, which are available as `sweep.index`, `sweep.total` and
+//! `sweep.params` and are also exported as `SPARROW_SWEEP_INDEX`, `SPARROW_SWEEP_TOTAL` and
+//! `SPARROW_SWEEP_PARAM_<KEY>` at the top of the run script; `sparrow reproduce` keeps the
+//! position of the reproduced run.
+//! If a run with the same group and name exists already on the host, `previous_run` holds its
+//! `code_versions`, `output_path` and `status` (`running` or `finished`), which allows warm
+//! starts like
+//! `{% if previous_run %}--resume-from {{ previous_run.output_path }}/last.ckpt{% endif %}`.
+//! Runs that build on the outputs of another run on the same host, like an evaluation of a
+//! training run, name it with `--parent <group>/<name>`; `parent` then holds its `run_id`,
+//! `output_path`, `config_dir`, `config_entrypoints` and `code_versions`, and with
+//! `--link-parent` the parent output is also linked into the run directory at `parent.link_path`.
+//! The parent is recorded in `reproduce_info/parent.txt`, which `sparrow list-runs --long` shows,
+//! and reproductions keep it.
+//! `sparrow lineage --host <host-id> [--run <group>/<name>]` shows the ancestors and descendants
+//! of a run as a tree, with the status and recorded code versions of each run; `--dot` prints it
+//! as graphviz graph instead, e.g. for `sparrow lineage ... --dot | dot -Tsvg > lineage.svg`.
+//! Without a connection to a remote host, `sparrow run --host <host-id> --stage-only ...` prepares
+//! the payload, renders the run script, reviews the config and captures the environment of the run
+//! into `.sparrow/staged/<host-id>`, using the host facts cached when the host was last reached;
+//! this requires an absolute `run_output_base_dir` for the host.
+//! Once the host is reachable again, `sparrow push-staged --host <host-id> [--run <group>/<name>]`
+//! uploads the staged runs and launches them, attaching to the run if only one is pushed unless
+//! `--detach` is given.
+//! To check what a template renders to without submitting anything, use `sparrow render
+//! <template>`, or `sparrow render <template> --run <group>/<name>` to render it against the
+//! context recorded for a previous run.
+//! `sparrow context [--host <host-id>]` prints the template context a new run of the project would
+//! get as JSON, with example values for the parts a new run only has in some cases, like `sweep`
+//! or `parent`; `sparrow context --schema` prints the JSON schema of the template context instead.
+//! The run script is composed of sections: the environment setup, an optional teardown, the status
+//! traps recording the exit code, an optional staging section and the main command from
+//! `.sparrow/run.sh.j2`, whose leading comments, like its own `#SBATCH` directives, are moved in
+//! front of all other sections, right after its shebang.
+//! Staging steps go into `.sparrow/run.pre.sh.j2` and teardown steps into
+//! `.sparrow/run.post.sh.j2`, which are rendered with the same context; the teardown runs when the
+//! run script exits, also on failure, and finds the exit code of the run in `$sparrow_exit_code`.
+//! `sparrow template check` checks the run script templates and all templates in
+//! `.sparrow/templates` for syntax errors, variables that are not part of the template context
+//! and variables that are set but never used.
+//! Setting `validate_run_script: true` under `runner` in the configuration checks the rendered
+//! run script with `shellcheck`, or with `bash -n` if shellcheck is not installed, and aborts the
+//! submission on errors before anything is uploaded.
+//! Setting `prologue: true` under `runner` adds a standard prologue right after the scheduler
+//! directives of the run script, which creates the run output, exports `SPARROW_RUN_NAME`,
+//! `SPARROW_RUN_GROUP`, `SPARROW_OUTPUT_PATH` and `SPARROW_HOST`, logs when the run starts and
+//! ends and sets `set -euo pipefail`, so that the rest of the run script fails on the first error.
+//! Variables from the `KEY=VALUE` files listed in `env_files` under `runner`, together with the
+//! ones of `environment_variable_transfer_requests`, are written to `.sparrow_env` in the run
+//! directory, which is only readable by the owner, sourced at the top of the run script and left
+//! out of the copy of the run directory that `keep_payload` keeps.
 //!
 //! To launch an experiment after `.sparrow/config.yaml`, `.sparrow/private.yaml` and `.sparrow/run.sh.j2`
 //! are created, we can run
@@ -77,6 +160,29 @@
 //! This will simply launch the command we defined in `.sparrow/run.sh.j2` on our local machine in a
 //! temporary run directory and point the command to the output directory we defined in the
 //! configuration files under `<run-group>/my_experiment` (where the run group is also defined in the config).
+//! To test in a clean environment instead, `--container <image>` executes the run script inside a
+//! container of the given image, with the run directory and the run output base directory mounted
+//! at the same paths; the container engine is `docker` unless `container_engine` is set under
+//! `local_host` in the configuration, e.g. to `podman`.
+//! So that a buggy data loader cannot freeze the machine, `--local-mem <size>` and
+//! `--local-cpus <N>` limit the memory and the cpus of a local run, by running it in a scope of
+//! the systemd user manager, which kills the run once it exceeds the memory, or by passing the
+//! limits to the container engine; without systemd, `prlimit` limits the address space and
+//! `taskset` the usable cpus instead.
+//! If a repository serves several pipelines, code and auxiliary mappings that only some runs need
+//! can be put into named bundles under `payload.bundles`, which are only uploaded for runs that
+//! select them with `--payload <bundle>`; the selected bundles are available as
+//! `payload.bundles` to the template.
+//! Commands listed under `payload.build_steps`, like compiling protobufs or building a wheel, are
+//! run in order in the prepared payload directory after the code and auxiliary files are copied
+//! and before it is uploaded, with `SPARROW_RUN_NAME`, `SPARROW_RUN_GROUP`, `SPARROW_HOST`,
+//! `SPARROW_OUTPUT_PATH` and the complete template context as json in `SPARROW_RUN_CONTEXT` in
+//! their environment; the submission fails if any of them fails.
+//! Setting `payload.environment_lock` to `uv`, `poetry` or `pip` records `uv.lock`, `poetry.lock`
+//! or the output of `pip freeze` of the local environment in the reproduce info of every run,
+//! where `auto` takes the first of them that is available; the template finds its kind and path
+//! as `payload.environment_lock.kind` and `payload.environment_lock.path`, so that the run script
+//! can build the same environment on the host, e.g. with `pip install -r`.
 //!
 //! If we want to launch the experiment on a remote host instead, we simply specify the id of the
 //! remote host, as specified in the configuration
@@ -87,6 +193,214 @@
 //!
 //! This will copy all code and configuration to the remote machine into a dedicated run directory
 //! and execute the given command in a tmux session from which one can de- and reattach.
+//! Before anything is created, sparrow shows the host, the run id and its output path, the code
+//! sources with their revisions and whether their working tree is dirty, the config directory, the
+//! position in the sweep and the estimated upload size, and only submits once this is confirmed;
+//! pass `--yes` to skip the confirmation, e.g. in scripts.
+//! Before that, sparrow prints the size of each mapping of the payload, how much of it was actually
+//! transferred, the duration and bandwidth of the upload and the path of the run directory, which
+//! are also stored in `reproduce_info/run_meta.yaml` in the output directory of the run.
+//! Both `run_meta.yaml` and the run context record the version of sparrow, its `git describe`
+//! and the layout of the metadata, which templates see as `sparrow.version`,
+//! `sparrow.git_describe` and `sparrow.layout`; reproducing a run, reusing its run directory or
+//! rendering against it warns if its metadata was written with another layout and migrates
+//! older layouts where possible; metadata that is only added keeps the layout.
+//! Run directories are created in the `temporary_dir` of the host and named `run.<random>` by
+//! default; set `temporary_run_dir_template`, e.g. to `sparrow-{group}-{name}-{rand}`, in the host
+//! configuration to make them easier to find.
+//! The reviewed config directory replaces the one of a previous submission of the same run, which
+//! deletes config files that are gone locally; set `delete: false` under `config_upload` to keep
+//! them, or `delete: backup` to move replaced and deleted files to
+//! `reproduce_info/config_backup/<time>` in the output directory instead.
+//! Code sources copied from the local directory with `--ignore-revisions` have no revision of their
+//! own, but the checked out revision of each of their submodules, nested ones included, is
+//! recorded as `<source-id>/<submodule-path>` in `reproduce_info/code_versions.txt`, with a
+//! `-dirty` suffix and the uncommitted changes in `reproduce_info/submodule_diffs` if there are
+//! any; when unpacking a remote revision, `submodules: none|top|recursive` under `remote` selects
+//! which submodules are checked out, defaulting to `top`.
+//! Since the working tree of a local code source can change while it is copied, e.g. when an
+//! editor saves a file, `snapshot: head` under `local` uploads the tree of the checked out commit
+//! instead and `snapshot: staged` the tree of the index, i.e. the commit with the staged changes;
+//! neither includes untracked files or submodules, and the id of the uploaded tree is recorded as
+//! `<source-id>:tree` in `reproduce_info/code_versions.txt`.
+//! To upload only some of the code sources, e.g. to leave out a large framework that did not
+//! change, pass their ids as `--only-code <id>,...`, or the ids of those to leave out as
+//! `--skip-code <id>,...`; the omitted ids are recorded as `payload.omitted_code` in the run
+//! context, and reproducing the run leaves them out again.
+//! For byte-exact provenance beyond the recorded git revisions, `keep_payload: true` under
+//! `run_output` copies the run directory into `reproduce_info/payload` before the run starts.
+//! With `notifications: {desktop: true}` in the configuration, long-running commands like
+//! `run-output-sync` (also with `--watch`) and `remote-prepare-quick-run` show a desktop
+//! notification when they finish, so one can switch away from the terminal in the meantime.
+//! For allocation reviews, `sparrow usage --host <host-id> [--since 30d]` queries `sacct` for
+//! jobs whose working directory lies within the output or run directory of a run and reports
+//! their CPU-hours, GPU-hours and energy per run group.
+//! Before scratch fills up, `sparrow run-du --host <host-id> [--group <group>]` measures the run
+//! outputs on a host with `du`, in parallel batches, and lists them by size per run and per group
+//! together with the usage of the quota of the user on their file system, as reported by
+//! `lfs quota` or `quota`, or of the file system itself if there is no quota; sizes are cached in
+//! `~/.cache/sparrow/disk_usage` for an hour, unless `--refresh` is passed.
+//! The config is reviewed by opening `$EDITOR` in a new `$TERMINAL` window before submission;
+//! with `review: {mode: inline}` in the configuration, `$EDITOR` runs in the current terminal
+//! instead, which works over ssh or mosh and inside tmux, and `mode: none` skips the review.
+//! Once the editor exits, sparrow asks whether to submit the reviewed config, edit it again or
+//! abort the submission, which replaces the confirmation of the summary, and warns if the editor
+//! or terminal exited abnormally, e.g. because the window was closed, since the config on disk is
+//! then not necessarily the reviewed one; with `--yes`, the review is not followed by a question.
+//! Several config variants can be prepared in advance with `sparrow config snapshot save <name>`,
+//! which copies the payload config directory to `.sparrow/config_snapshots/<name>` and reviews
+//! it once; `sparrow run --config-snapshot <name>` then submits with that snapshot without
+//! another review, `config snapshot list` lists the snapshots and `config snapshot use <name>`
+//! copies one back into the payload config directory.
+//! Runs that must not be lost, e.g. the ones going into a paper, can be pinned with `sparrow
+//! run-pin --host <host-id>`, which places a `.pinned` marker into the run output on the host and
+//! in the local copy; submitting a run under the name of a pinned run is then refused unless
+//! `--unpin-first` is given, and `run-pin --unpin` removes the pin again.
+//! `sparrow run-note --host <host-id> "tried cosine schedule"` appends a timestamped note to
+//! `reproduce_info/notes.md` of a run, which `sparrow list-runs --long` shows below each run.
+//! `sparrow run-rename --host <host-id> --name <name>` renames a selected run and `--group
+//! <group>` moves it to another run group; this moves the run output on the host and the synced
+//! local copy if there is one, renames the session the run was executed in if it is still open,
+//! updates `reproduce_info/parent.txt` of its children and the recently used runs, and adds a
+//! note with the old name. Runs that are still running are not renamed, and `parent` links in
+//! the run directories of children keep pointing to the old output path.
+//! Each submission keeps the rendered run script as `reproduce_info/run_script.sh`, which `sparrow
+//! run-script show --host <host-id>` prints for a selected run, together with checksums of the
+//! script, of the run script templates and of the templates with their context in
+//! `reproduce_info/run_script_fingerprints.txt`. Resubmitting or reproducing a run warns if the
+//! run script templates changed since the run was submitted.
+//! `sparrow run-delete --host <host-id>` does not delete runs right away, but moves them into
+//! `.sparrow_trash/<time>/` below the run output base directory of the host, from where `sparrow
+//! trash restore` moves them back; `sparrow trash list` shows the trash and `sparrow trash empty`
+//! deletes the runs that are in the trash for longer than `run_output.trash_retention` (30 days by
+//! default), which also happens on every `run-delete`.
+//! On shared project directories, `run_output.permissions: {mode: "2775", group: "atlas"}` makes
+//! sparrow change the group of the run output it creates (and of a new run group directory) to
+//! `group`, set the mode of its directories to `mode` and make its files group-readable, so that
+//! teammates can read and extend each other's runs.
+//! Run outputs can additionally be pushed to storage listed under `run_output.destinations` with
+//! `run-output-sync --dest <name>`, where each destination has a `target` and a `backend`, which
+//! is either `rsync` (local paths, `host:path` or `rsync://` daemons) or `rclone` (any configured
+//! rclone remote, e.g. `onedrive:results`).
+//! To fetch a whole sweep, `run-output-sync --all --group <group>` syncs every run of the group
+//! (restricted by `--tag` if given) with up to `--jobs` concurrent downloads and summarizes the
+//! transferred size and duration per run in a table; `run-output-sync --multi` does the same for
+//! the runs selected interactively.
+//! To migrate a run between clusters, `sparrow run-output-transfer --from <host-id> --to
+//! <host-id>` copies the output of a selected run from one remote host to the other, directly via
+//! rsync on the source host if it reaches the destination host with ssh without a password, and
+//! relayed through the local machine otherwise or with `--relay`; `--move` moves the run output
+//! on the source host into its trash afterwards, once the destination has all of its files with
+//! the same sizes. Paths recorded in `reproduce_info` keep pointing to the source host.
+//! To inspect metrics and configs of many runs without downloading checkpoints, `run-output-sync
+//! --content metadata` only pulls files matching `*.json`, `*.yaml`, `*.log` and similar patterns
+//! that are at most 1 MiB large; both can be changed with `patterns` and `max_size` under
+//! `run_output.sync_options.metadata`.
+//! Run groups with their own artifact layout can replace `result_excludes` and
+//! `reproduce_excludes` under `run_output.sync_options.groups`, keyed by a glob of the group name
+//! like `rl-*`; if several globs match a group, the longest one is used.
+//! Setting `deduplicate_auxiliary: true` on a remote host stores the files of auxiliary mappings
+//! once per content in `sparrow-cas` below its `temporary_dir` and hard-links them into each run
+//! directory, so unchanged datasets are not uploaded again.
+//! Large datasets can be uploaded ahead of time with `sparrow push-data --host <host-id> [--payload
+//! <bundle>...]`, which pushes the auxiliary mappings of the payload into the store of the host
+//! without submitting a run, so that the transfer can happen overnight and later runs only link
+//! the stored files.
+//! Stored files stay until `sparrow host prune-store <host-id>` removes those that no run directory
+//! links anymore, where `--min-age`, one day by default, spares files that were linked, unlinked or
+//! pushed more recently, like data pushed for runs that are yet to come.
+//! On hosts that do not allow long-lived tmux sessions, setting `execution: nohup` or
+//! `execution: systemd-run` in the host configuration detaches the run command instead and writes
+//! its output to `run.log` in the run directory, which is what attaching then follows.
+//! With `execution: screen`, runs are executed in screen sessions named `sparrow-<group>-<name>`
+//! instead of tmux sessions.
+//! If tmux is not installed on a host that executes runs in tmux sessions, sparrow refuses to
+//! launch or attach to runs there with instructions, unless `tmux.fallback: nohup` executes runs
+//! with nohup instead or `tmux.fallback: screen` executes them in screen sessions, falling back
+//! to nohup if screen is not installed either.
+//! Tmux is only looked up by commands that launch, attach to or list runs.
+//! Tmux and screen sessions are kept open with a shell after the run script exits, which
+//! `tmux.keep_open_on: failure` limits to failed runs and `tmux.keep_open_on: never` disables;
+//! since open sessions count as running runs, `sparrow host clean-sessions <host-id>` kills the
+//! sessions of runs that recorded a successful exit.
+//! The `run_output_base_dir` and `temporary_dir` of remote hosts are expanded by the shell of the
+//! remote, so a leading `~`, `$USER` and other environment variables, like in
+//! `/scratch/$USER/runs`, let one shared configuration resolve to the private paths of every user.
+//! Remote hosts can also be referred to by any of the `aliases` listed in their configuration, and
+//! setting `default_host` in the configuration makes `sparrow run` use that host when `--host` is
+//! omitted.
+//! Before a big submission, `sparrow host status <host-id>` shows the latency, load and scratch
+//! usage of a host together with its pending and running jobs, whether a quick run is prepared and
+//! the last successful sync, preparation or submission recorded in `~/.cache/sparrow/operations`.
+//! `sparrow host exec <host-id> -- <command...>` runs a shell command on a host over the
+//! connection of sparrow, in the output of a selected run with `--in-run` or in its run directory
+//! with `--in-run dir`, and exits with the exit code of the command.
+//! The clock of a remote host is compared to the local clock on connecting, and a skew of more
+//! than five seconds is warned about, since modification times of run outputs are then not
+//! comparable between both machines; syncs always compare checksums and are not affected by it.
+//! The connection to a remote host sends a keep-alive every 15 seconds, so that long quiet
+//! commands, like a big `find` or `salloc` waiting in the queue, are not dropped by firewalls; if
+//! the connection breaks anyway, e.g. on a network blip, commands that only list or query, like
+//! listing runs or `host status`, reconnect and run again, while others fail as before.
+//! Hosts that need a local step before ssh works, like a Kerberos ticket, can set
+//! `auth: {pre_command: kinit, validity_check: klist -s}`, and sparrow runs `pre_command` in the
+//! terminal before connecting, so that it can prompt for a password or second factor, but only if
+//! `validity_check` fails or is not set.
+//! On hosts where modules or paths are only set up in login shells, or where commands have to run
+//! inside a container or proot, `remote_command_wrapper: "bash -l -c {}"` in the host
+//! configuration wraps every command sparrow runs there, including `find`, `squeue`, `mkdir` and
+//! `tmux`, with `{}` replaced by the quoted command; file transfers with rsync are not wrapped.
+//! For snakemake based runs, `sparrow run-progress --host <host-id>` reads the most recent log in
+//! `.snakemake/log` of the run directory or the run output and reports the completed and total
+//! jobs, the rules of the currently running jobs and the most recent failures.
+//! `sparrow run-log --host <host-id> --mirror` copies all `*.log` files of a running run into its
+//! local run output every `--interval` (5s by default) until the run finished, so that local
+//! tools can follow the logs while the run is still on the remote.
+//! `sparrow run-signal --host <host-id> --signal stop|checkpoint` asks a running run to stop or
+//! checkpoint gracefully by writing `stop` or `checkpoint` to `.sparrow_control` in its run output,
+//! which training code can poll at `control.file_path` of the template context; with `--posix`,
+//! the signal named in `control.signals` (`TERM` or `USR1`) is sent to the process group of the
+//! run script instead, which only reaches runs that are not executed on other nodes by a scheduler.
+//! Run scripts record their exit code in `reproduce_info/exit_code` of the run output, which
+//! `sparrow queue start` uses to report which of the runs it submitted succeeded or failed, with
+//! links to their log files, once all of them finished; `--report <file>` additionally writes this
+//! report as markdown (`.md`) or JUnit XML (`.xml`) for CI or a lab notebook.
+//! Sparrow records when a run was submitted and the run script when it started and ended, and on
+//! slurm clusters the partition, in `reproduce_info/timings.txt`; `sparrow list-runs --timings`
+//! shows the queue wait and runtime of each run and the mean queue wait per partition.
+//! `run-delete`, `run-pin` and `run-output-sync --multi` select several runs at once, which are
+//! marked with TAB in the selection.
+//! Interactive run selections offer the runs last submitted or selected on the host first, which
+//! are remembered in `~/.cache/sparrow/recent`, and `sparrow recent [--host <host-id>] [-n <N>]`
+//! prints the last runs of each host for copy-pasting them into other commands.
+//! A run is never submitted to a remote host on which a run with the same group and name is still
+//! running, since both would share the same tmux session and output.
+//! Before submitting to a slurm cluster, `sparrow run` looks up reservations with the `MAINT` flag
+//! in `scontrol show reservation` and warns if the run would not finish before one of them
+//! starts, judging by its time limit or else the runtime of its previous execution; unless `--yes`
+//! is given, it then asks whether to submit anyway, to abort or to submit to another remote host.
+//! Setting `max_concurrent_runs` on a remote host makes `sparrow run` refuse submissions while
+//! that many runs are running on the host, while `sparrow queue start` holds back queued runs for
+//! the host until one of its runs finished.
+//! Parameter sweeps are described in `.sparrow/sweeps/<name>.yaml` by a `list` of points, a `grid`
+//! of values, `random` samples with a fixed `seed` and repeated `seeds`, together with the run
+//! `name`, like `lr{lr}-s{seed}`, and further `args` of `sparrow run`; `sparrow sweep launch <name>
+//! --host <host-id>` shows the planned runs and submits them once confirmed, or adds them to the
+//! queue with `--queue`.
+//! When debugging a run with a heavy payload, `sparrow run --host <host-id> --reuse-run-dir
+//! <group>/<name>` executes the run script again in the existing run directory of that run without
+//! uploading anything, and `--update-run-script` additionally re-renders `.sparrow/run.sh.j2`
+//! against the recorded context of the run and replaces its run script.
+//!
+//! For bursty workloads, virtual machines can be rented on demand by listing them under
+//! `cloud_hosts` in the configuration, with a `provision_command` printing the ssh destination of
+//! a new machine and a `teardown_command` removing it again.
+//! A cloud host is provisioned when a run is submitted to it, after which runs are executed on it
+//! like on a remote host, while commands inspecting its runs fail until then;
+//! `sparrow cloud-teardown --host <host-id>` removes the machine, which `sparrow run-output-sync
+//! --teardown` also does after syncing, unless any run on the machine has not recorded its exit
+//! code yet; since the outputs on the machine are lost then, it should only be given to a sync
+//! of everything that is still needed.
 //!
 //! It is often useful to run the experiment on a remote host, but on a pre-allocated node, instead
 //! of the login node.
@@ -97,6 +411,14 @@
 //! sparrow remote-prepare-quick-run --host <host-id>
 //! ```
 //!
+//! By default, the job allocating the node copies the `fast_access_container_requests` of the
+//! host to its `node_local_storage_path` before it goes to sleep. If `.sparrow/quick_run.sh.j2`
+//! exists, it is rendered and executed on the node instead, e.g. to pre-warm caches, mount
+//! squashfs images or start a jupyter server in the background, with the quick run options as
+//! `quick_run` (`account`, `service_quality`, `constraint`, `partitions`, `time`, `cpu_count`,
+//! `gpu_count`, `fast_access_container_paths` and `node_local_storage_path`) and the host as
+//! `host`, like in the run script template.
+//!
 //! And subsequently execute the run command with the `--enforce-quick` flag and the run will
 //! automatically use the pre-allocated node.
 //! Note that for this to work, sparrow assumes that the pre-allocated node is accessible via ssh
@@ -115,25 +437,108 @@
 //! automatically, the compute nodes do not. So we add the key manually in our home directory which
 //! is shared with the compute nodes automatically via the network file system.
 //!
+//! To get an idea of how long the allocation will take, `sparrow remote-quick-run-estimate --host
+//! <host-id>` reports the expected start of the allocation and lists partitions with idle nodes.
+//! The same report can be printed right before allocating by passing `--estimate`.
+//!
+//! # Exit Codes
+//!
+//! To let wrapper scripts and CI branch on the kind of failure, sparrow exits with
+//!
+//! | code | failure                                                           |
+//! | ---- | ----------------------------------------------------------------- |
+//! | 1    | any failure without a more specific code                          |
+//! | 2    | invalid configuration, e.g. unknown hosts or resource profiles    |
+//! | 3    | failed connection to a host                                       |
+//! | 4    | failed submission of a run                                        |
+//! | 5    | aborted interactive selection                                     |
+//! | 6    | sync conflict, when a local run output was not synced from remote |
+//!
 //! [`cfg`]: crate::cfg
 //! [`RunInfo`]: crate::runner::RunInfo
 
+mod batch_sync;
+mod browse;
 mod cfg;
+mod control;
+mod data;
+mod exit;
+mod export;
 mod host;
+mod lineage;
+mod logs;
+mod notes;
 mod payload;
+mod pin;
+mod progress;
+mod queue;
+mod recent;
+mod rename;
+mod report;
+mod results;
 mod run;
+mod snapshot;
+mod storage;
+mod sweep;
+mod team;
+mod transfer;
+mod trash;
+mod usage;
 mod utils;
+mod version;
 
-use crate::utils::select_interactively;
+use crate::utils::{
+    format_size, notify_desktop, select_interactively, select_interactively_multiple,
+};
 use anyhow::{anyhow, bail, Context, Result};
+use batch_sync::BatchSyncReport;
+use browse::browse_run;
 use cfg::*;
 use clap::{CommandFactory, Parser};
 use clap_complete::{generate, Shell::Fish};
 use config::{Config, File, FileFormat};
-use host::{build_host, QuickRunPrepOptions};
-use run::run;
+use data::push_data;
+use exit::{ExitStatus, WithExitStatus};
+use export::{default_export_path, export_run};
+use host::rsync::SyncOptions;
+use host::status::{last_operation, record_operation};
+use host::timings::{mean_queue_waits, timings_columns, TIMINGS_HEADER};
+use host::{
+    build_host, configured_review_mode, ensure_run_group_exists, filter_runs_by_tags,
+    resolve_host_id, resolve_run_id, HostPurpose, QuickRunPrepOptions, SyncError,
+};
+use lineage::Lineage;
+use logs::mirror_run_logs;
+use payload::build_payload_mapping;
+use progress::{read_snakemake_log, SnakemakeProgress};
+use recent::{select_run, select_runs};
+use report::{SweepReport, SweepReportFormat};
+use results::{
+    build_run_results, metadata_sync_includes, result_sync_includes, sync_excludes, RunResult,
+};
+use run::check::check_templates;
+use run::context::print_context;
+use run::render::render;
+use run::reproduce::reproduce;
+use run::reuse::reuse_run_directory;
+use run::script::show_run_script;
+use run::stage::{push_staged_runs, staged_runs};
+use run::{
+    build_runner, config_upload_delete, lock_submission, run, RunOptions, SubmitOptions, SweepInfo,
+};
+use std::collections::HashSet;
+use storage::StorageReport;
+use team::{add_team_sources, has_team_config};
+use usage::UsageReport;
+
+fn main() {
+    if let Err(err) = run_command() {
+        eprintln!("Error: {err:?}");
+        ExitStatus::of(&err).exit();
+    }
+}
 
-fn main() -> Result<()> {
+fn run_command() -> Result<()> {
     let cli = Cli::parse();
 
     if cli.print_completion {
@@ -141,59 +546,358 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    let config: GlobalConfig = Config::builder()
-        .add_source(File::new(".sparrow/config", FileFormat::Yaml))
-        .add_source(File::new(".sparrow/private", FileFormat::Yaml))
+    let config_builder =
+        Config::builder().add_source(File::new(".sparrow/config", FileFormat::Yaml));
+    let config: GlobalConfig = add_team_sources(config_builder, cli.as_user.as_deref())
+        .context("failed to add team configuration")
+        .exit_status(ExitStatus::Config)?
+        .add_source(File::new(".sparrow/private", FileFormat::Yaml).required(!has_team_config()))
         .build()
         .unwrap_or_else(|err| {
             eprintln!("could not build configuration: {}", err);
-            std::process::exit(1);
+            ExitStatus::Config.exit();
         })
         .try_deserialize()
         .unwrap_or_else(|err| {
             eprintln!("could not deserialize configuration: {}", err);
-            std::process::exit(1);
+            ExitStatus::Config.exit();
         });
 
+    let desktop_notifications = config
+        .notifications
+        .as_ref()
+        .and_then(|notifications| notifications.desktop)
+        .unwrap_or(false);
+
     match cli.command {
-        Some(RunnerCommandConfig::Run {
-            run_name,
-            run_group,
-            config_dir,
-            use_previous_config,
-            ignore_revisions,
+        Some(RunnerCommandConfig::Run(run_args)) => match *run_args {
+            RunArgs {
+                host,
+                enforce_quick,
+                remainder,
+                detach,
+                reuse_run_dir: Some(reuse_run_dir),
+                update_run_script,
+                ..
+            } => reuse_run_directory(
+                reuse_run_dir,
+                host,
+                enforce_quick,
+                remainder,
+                update_run_script,
+                detach,
+                config,
+            )
+            .context("run failed")
+            .exit_status(ExitStatus::Submission),
+            RunArgs {
+                run_name,
+                run_group,
+                config_dir,
+                use_previous_config,
+                config_snapshot,
+                ignore_revisions,
+                host,
+                enforce_quick,
+                no_config_review,
+                remainder,
+                resources,
+                resource_overrides,
+                payload_bundles,
+                code_selection,
+                tags,
+                only_print_run_script,
+                stage_only,
+                detach,
+                no_lock,
+                container,
+                local_limits,
+                parent,
+                link_parent,
+                unpin_first,
+                yes,
+                reuse_run_dir: None,
+                sweep_index,
+                sweep_total,
+                sweep_param,
+                ..
+            } => run(
+                RunOptions {
+                    run_name: run_name
+                        .expect("expected a run name to be required without --reuse-run-dir"),
+                    run_group,
+                    config_dir: config_snapshot
+                        .as_deref()
+                        .map(snapshot::snapshot_path)
+                        .transpose()?
+                        .or(config_dir),
+                    use_previous_config,
+                    ignore_revisions,
+                    host,
+                    enforce_quick,
+                    // snapshots are reviewed when they are saved
+                    no_config_review: no_config_review || config_snapshot.is_some(),
+                    remainder,
+                    resources,
+                    resource_overrides,
+                    payload_bundles,
+                    code_selection,
+                    tags,
+                    sweep: sweep_index
+                        .zip(sweep_total)
+                        .map(|(index, total)| SweepInfo {
+                            index,
+                            total,
+                            params: sweep_param.into_iter().collect(),
+                        }),
+                    only_print_run_script,
+                    stage_only,
+                    detach,
+                    no_lock,
+                    container,
+                    local_limits,
+                    parent,
+                    link_parent,
+                    unpin_first,
+                    yes,
+                },
+                config,
+            )
+            .context("run failed")
+            .exit_status(ExitStatus::Submission),
+        },
+        Some(RunnerCommandConfig::Reproduce {
             host,
             enforce_quick,
-            no_config_review,
+            no_lock,
             remainder,
-            only_print_run_script,
-        }) => run(
+        }) => reproduce(host, enforce_quick, no_lock, remainder, config)
+            .context("reproduction failed")
+            .exit_status(ExitStatus::Submission),
+        Some(RunnerCommandConfig::CloudTeardown { host }) => {
+            let Some(cloud_config) = config.cloud_hosts.get(&host) else {
+                bail!("found no cloud host `{host}' in the configuration");
+            };
+            host::cloud::teardown(&host, &cloud_config.teardown_command)
+                .context(format!("failed to tear down {host}"))
+        }
+        Some(RunnerCommandConfig::Queue { command }) => match command {
+            QueueCommandConfig::Add { args } => {
+                queue::add(args, &config).context("failed to queue run")
+            }
+            QueueCommandConfig::Start {
+                max_parallel,
+                interval,
+                report,
+            } => queue::start(max_parallel, interval, report.as_deref(), config)
+                .context("failed to process queue"),
+            QueueCommandConfig::List => queue::list().context("failed to list queue"),
+        },
+        Some(RunnerCommandConfig::Sweep { command }) => match command {
+            SweepCommandConfig::Launch {
+                name,
+                host,
+                queue,
+                yes,
+            } => sweep::launch(&name, host, queue, yes, &config)
+                .context(format!("failed to launch sweep {name}")),
+        },
+        Some(RunnerCommandConfig::Trash { command }) => match command {
+            TrashCommandConfig::List { host } => {
+                let host = config.build_host(&host)?;
+                for trashed_run in trash::trashed_runs(&*host)
+                    .context(format!("failed to list the trash of {}", host.id()))?
+                {
+                    println!("{trashed_run}");
+                }
+                Ok(())
+            }
+            TrashCommandConfig::Restore { host } => {
+                let host = config.build_host(&host)?;
+                let trashed_runs = trash::trashed_runs(&*host)
+                    .context(format!("failed to list the trash of {}", host.id()))?;
+                if trashed_runs.is_empty() {
+                    bail!("found no runs in the trash of {}", host.id());
+                }
+                let trashed_run = select_interactively(&trashed_runs, "run: ")
+                    .context("failed to select a run to restore")?;
+                trash::restore_run(&*host, trashed_run)
+            }
+            TrashCommandConfig::Empty { host, all } => {
+                let host = config.build_host(&host)?;
+                let retention = if all {
+                    std::time::Duration::ZERO
+                } else {
+                    trash_retention(&config.run_output)?
+                };
+                trash::empty_trash(&*host, retention)
+                    .context(format!("failed to empty the trash of {}", host.id()))
+            }
+        },
+        Some(RunnerCommandConfig::Host { command }) => match command {
+            HostCommandConfig::Status { host } => {
+                let host = config.build_host(&host)?;
+
+                let status = host
+                    .status()
+                    .context(format!("failed to obtain the status of {}", host.id()))?;
+                let quick_run_prepared = host.quick_run_is_prepared().context(format!(
+                    "failed to check for the quick preparation of {}",
+                    host.id()
+                ))?;
+                let last_operation = match last_operation(host.id())? {
+                    Some(operation) => format!("{} at {}", operation.description, operation.time),
+                    None => String::from("-"),
+                };
+
+                print!("{status}");
+                println!(
+                    "quick run:     {}",
+                    if quick_run_prepared { "prepared" } else { "-" }
+                );
+                println!("last success:  {last_operation}");
+
+                Ok(())
+            }
+            HostCommandConfig::CleanSessions { host } => {
+                let host = config.build_host(&host)?;
+
+                let cleaned_runs = host
+                    .clean_finished_sessions()
+                    .context(format!("failed to clean the sessions of {}", host.id()))?;
+                for run_id in &cleaned_runs {
+                    println!("Killed the session of {run_id}");
+                }
+                if cleaned_runs.is_empty() {
+                    println!("Found no sessions of successfully finished runs");
+                }
+
+                Ok(())
+            }
+            HostCommandConfig::PruneStore { host, min_age } => {
+                let host = config.build_host(&host)?;
+                if host.content_store_path().is_none() {
+                    bail!(
+                        "{} has no content store to prune, since `deduplicate_auxiliary' is not \
+                        set in its configuration",
+                        host.id()
+                    );
+                }
+
+                let pruned_sizes = host.prune_content_store(min_age).context(format!(
+                    "failed to prune the content store of {}",
+                    host.id()
+                ))?;
+                println!(
+                    "Removed {} unlinked files ({}) from the content store of {}",
+                    pruned_sizes.len(),
+                    format_size(pruned_sizes.iter().sum()),
+                    host.id()
+                );
+
+                Ok(())
+            }
+            HostCommandConfig::Exec {
+                host,
+                in_run,
+                command,
+            } => {
+                let host = config.build_host(&host)?;
+
+                let working_dir_path = match in_run {
+                    Some(location) => {
+                        let run_id = select_run(
+                            host.id(),
+                            &host
+                                .runs()
+                                .context(format!("failed to obtain runs from {}", host.id()))?,
+                            "run: ",
+                        )
+                        .context("failed to select a run to execute the command in")?;
+                        Some(match location {
+                            RunLocation::Output => run_id.path(host.output_base_dir_path()),
+                            RunLocation::Dir => host.recorded_run_dir_path(&run_id)?,
+                        })
+                    }
+                    None => None,
+                };
+
+                // like ssh, the arguments are joined into one command for the shell of the host
+                let command = command.join(" ");
+                let status = host
+                    .execute(&command, working_dir_path.as_deref())
+                    .context(format!("failed to execute `{command}` on {}", host.id()))?;
+                if !status.success() {
+                    std::process::exit(status.code().unwrap_or(ExitStatus::Failure as i32));
+                }
+
+                Ok(())
+            }
+        },
+        Some(RunnerCommandConfig::Template { command }) => match command {
+            TemplateCommandConfig::Check => check_templates().context("template check failed"),
+        },
+        Some(RunnerCommandConfig::RunScript { command }) => match command {
+            RunScriptCommandConfig::Show { host } => {
+                let host = config
+                    .build_host(&host)
+                    .context("failed to build host of the run")?;
+                let run_id = select_run(
+                    host.id(),
+                    &host
+                        .runs()
+                        .context(format!("failed to obtain runs from {}", host.id()))?,
+                    "run: ",
+                )
+                .context("failed to select a run to show the run script of")?;
+
+                show_run_script(&*host, &run_id)
+                    .context(format!("failed to show the run script of {run_id}"))
+            }
+        },
+        Some(RunnerCommandConfig::Config { command }) => match command {
+            ConfigCommandConfig::Snapshot { command } => match command {
+                SnapshotCommandConfig::Save { name, no_review } => {
+                    let review_mode = configured_review_mode(config.review.as_ref(), no_review);
+                    snapshot::save(&name, review_mode, &config.payload.config)
+                        .context(format!("failed to save config snapshot `{name}'"))
+                }
+                SnapshotCommandConfig::List => {
+                    snapshot::list().context("failed to list config snapshots")
+                }
+                SnapshotCommandConfig::Use { name } => {
+                    snapshot::restore(&name, &config.payload.config)
+                        .context(format!("failed to use config snapshot `{name}'"))
+                }
+            },
+        },
+        Some(RunnerCommandConfig::Render {
+            template,
+            run,
             run_name,
-            run_group,
-            config_dir,
-            use_previous_config,
-            ignore_revisions,
+            resources,
             host,
-            enforce_quick,
-            no_config_review,
-            remainder,
-            only_print_run_script,
-            config,
-        )
-        .context("run failed"),
+        }) => render(template, run, run_name, resources, host, config).context("rendering failed"),
+        Some(RunnerCommandConfig::Context {
+            run_name,
+            resources,
+            host,
+            schema,
+        }) => print_context(run_name, resources, host, schema, config)
+            .context("failed to print the template context"),
         Some(RunnerCommandConfig::RemotePrepareQuickRun {
             host: host_id,
             time,
             gpu_count,
             cpu_count,
             constraint,
+            estimate,
         }) => {
             if host_id == "local" {
                 return Err(anyhow!("cannot prepare quick run on local host"));
             }
 
-            let host = build_host(&host_id, &config.local_host, &config.remote_hosts, false)
-                .expect("expected host building to always succeed");
+            let host = config.build_host(&host_id)?;
             if host.quick_run_is_prepared().context(format!(
                 "failed to check for the quick preparation of {}",
                 host.id()
@@ -202,30 +906,86 @@ fn main() -> Result<()> {
                 return Ok(());
             }
 
-            host.prepare_quick_run(&QuickRunPrepOptions::build(
+            let options = QuickRunPrepOptions::build(
                 time.as_deref(),
                 cpu_count,
                 gpu_count,
                 constraint,
-                &config.remote_hosts[&host_id].quick_run,
-            ))
-            .context(format!("failed to prepare {} for quick runs", host.id()))
+                &config.remote_hosts[host.id()].quick_run,
+            );
+            if estimate {
+                print!(
+                    "{}",
+                    host.estimate_quick_run(&options).context(format!(
+                        "failed to estimate quick run allocation on {}",
+                        host.id()
+                    ))?
+                );
+            }
+
+            let result = host
+                .prepare_quick_run(&options)
+                .context(format!("failed to prepare {} for quick runs", host.id()));
+            if result.is_ok() {
+                record_operation(host.id(), "prepared quick run");
+            }
+            if desktop_notifications {
+                let summary = match result {
+                    Ok(()) => "quick run prepared",
+                    Err(_) => "quick run preparation failed",
+                };
+                notify_desktop(summary, &format!("on {}", host.id()));
+            }
+            result
+        }
+        Some(RunnerCommandConfig::RemoteQuickRunEstimate {
+            host: host_id,
+            time,
+            gpu_count,
+            cpu_count,
+            constraint,
+        }) => {
+            if host_id == "local" {
+                return Err(anyhow!(
+                    "cannot estimate quick run allocation on local host"
+                ));
+            }
+
+            let host = config.build_host(&host_id)?;
+            let estimate = host
+                .estimate_quick_run(&QuickRunPrepOptions::build(
+                    time.as_deref(),
+                    cpu_count,
+                    gpu_count,
+                    constraint,
+                    &config.remote_hosts[host.id()].quick_run,
+                ))
+                .context(format!(
+                    "failed to estimate quick run allocation on {}",
+                    host.id()
+                ))?;
+            print!("{estimate}");
+
+            Ok(())
         }
         Some(RunnerCommandConfig::RemoteClearQuickRun { host }) => {
             if host == "local" {
-                eprintln!("cannot prepare quick run on local host");
-                std::process::exit(1);
+                bail!("cannot prepare quick run on local host");
             }
 
-            let host = build_host(&host, &config.local_host, &config.remote_hosts, false)
-                .expect("expected host building to always succeed");
+            let host = config.build_host(&host)?;
             host.clear_preparation();
 
             Ok(())
         }
-        Some(RunnerCommandConfig::ListRuns { host, running }) => {
-            let host = build_host(&host, &config.local_host, &config.remote_hosts, false)
-                .expect("expected host building to always succeed");
+        Some(RunnerCommandConfig::ListRuns {
+            host,
+            running,
+            tags,
+            long,
+            timings,
+        }) => {
+            let host = config.build_host(&host)?;
 
             let run_ids = if running {
                 host.running_runs()
@@ -233,18 +993,64 @@ fn main() -> Result<()> {
                 host.runs()
                     .context(format!("failed to obtain runs from {}", host.id()))?
             };
+            let run_ids = filter_runs_by_tags(&*host, run_ids, &tags)?;
 
+            let run_width = run_ids
+                .iter()
+                .map(|run_id| run_id.to_string().len())
+                .chain(std::iter::once("run".len()))
+                .max()
+                .unwrap_or(0);
+            let print_timings_row = |run: &str, columns: [String; 4]| {
+                let [submitted, queue_wait, runtime, partition] = columns;
+                println!(
+                    "{run:<run_width$}  {submitted:<20}  {queue_wait:>12}  {runtime:>12}  \
+                    {partition}"
+                );
+            };
+            let mut run_timings = Vec::new();
+            if timings {
+                print_timings_row("run", TIMINGS_HEADER.map(String::from));
+            }
             for run_id in run_ids {
-                println!("{}", run_id);
+                if timings {
+                    let recorded_timings = host.recorded_timings(&run_id).unwrap_or_default();
+                    print_timings_row(&run_id.to_string(), timings_columns(&recorded_timings));
+                    run_timings.push(recorded_timings);
+                } else {
+                    println!("{}", run_id);
+                }
+                if !long {
+                    continue;
+                }
+                if let Some(parent_id) = host.recorded_parent(&run_id) {
+                    println!("    parent: {parent_id}");
+                }
+                if let Some(notes) = notes::read_notes(&*host, &run_id)? {
+                    for line in notes.lines() {
+                        println!("    {line}");
+                    }
+                }
+            }
+
+            let mean_waits = mean_queue_waits(&run_timings);
+            if !mean_waits.is_empty() {
+                println!("\nmean queue wait per partition:");
+                for (partition, (mean_wait, count)) in mean_waits {
+                    println!(
+                        "    {partition}: {} over {count} runs",
+                        humantime::format_duration(mean_wait)
+                    );
+                }
             }
 
             Ok(())
         }
         Some(RunnerCommandConfig::RunAttach { host, quick }) => {
-            let host = build_host(&host, &config.local_host, &config.remote_hosts, quick)
-                .expect("expected host building to always succeed");
+            let host = build_host(&host, &config, HostPurpose::Inspection, quick)?;
+            host.check_execution().exit_status(ExitStatus::Config)?;
             host.attach(
-                select_interactively(&host.running_runs(), "run: ")
+                &select_run(host.id(), &host.running_runs(), "run: ")
                     .context("failed to select a run to attach to")?,
             );
 
@@ -254,59 +1060,346 @@ fn main() -> Result<()> {
             host,
             content,
             show_results,
+            all,
+            group,
+            multi,
+            jobs,
             force,
+            watch,
+            tags,
+            interval,
+            destination,
+            teardown,
         }) => {
-            let host = build_host(&host, &config.local_host, &config.remote_hosts, false)
-                .expect("expected host building to always succeed");
+            let host = config.build_host(&host)?;
+            if teardown && !config.cloud_hosts.contains_key(host.id()) {
+                bail!(
+                    "cannot tear down {}, since it is not a cloud host",
+                    host.id()
+                );
+            }
+            if watch && host.is_local() {
+                bail!("cannot watch runs on the local host, since there is nothing to sync");
+            }
+            let destination = destination
+                .map(|name| {
+                    config
+                        .run_output
+                        .destinations
+                        .as_ref()
+                        .and_then(|destinations| destinations.get(&name))
+                        .ok_or(anyhow!(
+                            "found no destination `{name}' in the configuration"
+                        ))
+                })
+                .transpose()?;
 
-            let run_id = select_interactively(
-                &host
-                    .runs()
-                    .context(format!("failed to obtain runs from {}", host.id()))?,
-                "run: ",
-            )
-            .context("failed to select a run to synchronize")?
-            .clone();
-            let sync_result = host.sync(
-                &run_id,
-                &config.local_host.run_output_base_dir,
-                &match &content {
-                    RunOutputSyncContent::Results => host::RunOutputSyncOptions {
-                        excludes: config.run_output.sync_options.result_excludes,
-                        ignore_from_remote_marker: force,
-                    },
-                    RunOutputSyncContent::NecessaryForReproduction => host::RunOutputSyncOptions {
-                        excludes: config.run_output.sync_options.reproduce_excludes,
-                        ignore_from_remote_marker: force,
-                    },
-                },
-            );
-            if let Err(err) = sync_result {
-                eprintln!("error while syncing: {}", err);
-                std::process::exit(1);
+            let run_ids = host
+                .runs()
+                .context(format!("failed to obtain runs from {}", host.id()))?;
+            if let Some(group) = &group {
+                ensure_run_group_exists(group, &run_ids)?;
             }
+            let run_ids = filter_runs_by_tags(&*host, run_ids, &tags)?;
+            let (metadata_includes, max_size) = match &content {
+                RunOutputSyncContent::Metadata => {
+                    let (includes, max_size) =
+                        metadata_sync_includes(config.run_output.sync_options.metadata.as_ref());
+                    (includes, Some(max_size))
+                }
+                _ => (Vec::new(), None),
+            };
+
+            if all || multi {
+                let (run_ids, synced) = match group {
+                    Some(group) => {
+                        let run_ids: Vec<_> = run_ids
+                            .into_iter()
+                            .filter(|run_id| run_id.group == group)
+                            .collect();
+                        if run_ids.is_empty() {
+                            bail!("found no runs of group `{group}' to sync on {}", host.id());
+                        }
+                        (run_ids, format!("group {group}"))
+                    }
+                    None => {
+                        let run_ids = select_runs(host.id(), &run_ids, "runs: ")
+                            .context("failed to select runs to synchronize")?;
+                        let synced = format!("{} runs", run_ids.len());
+                        (run_ids, synced)
+                    }
+                };
+
+                let sync_options = |run_id: &host::RunID| host::RunOutputSyncOptions {
+                    excludes: sync_excludes(
+                        &config.run_output.sync_options,
+                        &run_id.group,
+                        &content,
+                    ),
+                    includes: metadata_includes.clone(),
+                    max_size: max_size.clone(),
+                    ignore_from_remote_marker: force,
+                    show_progress: false,
+                };
+                let report = BatchSyncReport::sync(
+                    &*host,
+                    run_ids,
+                    &config.local_host.run_output_base_dir,
+                    sync_options,
+                    jobs,
+                );
+                print!("\n{report}");
 
-            let result_path = match (show_results, config.run_output.results.len()) {
-                (false, _) => {
-                    std::process::exit(0);
+                if let Some(destination) = destination {
+                    for run_id in report.synced_runs() {
+                        push_run_output(
+                            run_id,
+                            &config.local_host.run_output_base_dir,
+                            destination,
+                            &sync_options(run_id),
+                        )
+                        .context(format!("failed to push {run_id} to {}", destination.target))?;
+                    }
+                }
+
+                let failure_count = report.failure_count();
+                if failure_count == 0 {
+                    record_operation(host.id(), &format!("synced {synced}"));
+                }
+                if desktop_notifications {
+                    notify_desktop(
+                        if failure_count == 0 {
+                            "sync finished"
+                        } else {
+                            "sync failed"
+                        },
+                        &format!("{synced} from {}", host.id()),
+                    );
                 }
-                (true, 0) => {
+                if failure_count > 0 {
+                    sync_failure_status(report.errors()).exit();
+                }
+
+                if teardown {
+                    host.release()
+                        .context(format!("failed to release {}", host.id()))?;
+                }
+                return Ok(());
+            }
+
+            let run_id = select_run(host.id(), &run_ids, "run: ")
+                .context("failed to select a run to synchronize")?;
+
+            if show_results && content == RunOutputSyncContent::Metadata {
+                bail!("cannot select results to sync when only syncing metadata");
+            }
+            let results = build_run_results(&config.run_output.results);
+            let selected_results = if show_results {
+                if results.is_empty() {
                     println!(
                         "Requested results, but no results path specified in config. \
                         Consider adding 'results: [output_dir/relative/path/to/results]' \
                         to the config."
                     );
-                    std::process::exit(1);
+                    ExitStatus::Config.exit();
                 }
-                (true, 1) => config.run_output.results.first().unwrap(),
-                (true, _) => {
-                    assert!(config.run_output.results.len() > 1);
-                    select_interactively(&config.run_output.results, "result: ")
-                        .context("failed to select a result to synchronize")?
+                select_results(&results).context("failed to select results to synchronize")?
+            } else {
+                Vec::new()
+            };
+            // everything necessary for reproduction is synced anyway, results included
+            let mut includes = if content == RunOutputSyncContent::Results {
+                result_sync_includes(
+                    &selected_results
+                        .iter()
+                        .map(|result| result.path.as_path())
+                        .collect(),
+                )
+            } else {
+                Vec::new()
+            };
+            includes.extend(metadata_includes);
+
+            let sync_options = host::RunOutputSyncOptions {
+                excludes: sync_excludes(&config.run_output.sync_options, &run_id.group, &content),
+                includes,
+                max_size,
+                ignore_from_remote_marker: force,
+                show_progress: true,
+            };
+            loop {
+                // check before syncing, so that the last sync happens after the run finished; a
+                // recorded exit code means the run is done even if its session is kept open
+                let is_running = watch
+                    && host.running_runs().contains(&run_id)
+                    && host.recorded_exit_code(&run_id).is_none();
+
+                let sync_result = host.sync(
+                    &run_id,
+                    &config.local_host.run_output_base_dir,
+                    &sync_options,
+                );
+                match sync_result {
+                    Ok(_) => {
+                        if let Some(destination) = destination {
+                            push_run_output(
+                                &run_id,
+                                &config.local_host.run_output_base_dir,
+                                destination,
+                                &sync_options,
+                            )
+                            .context(format!(
+                                "failed to push {run_id} to {}",
+                                destination.target
+                            ))?;
+                        }
+                    }
+                    // the next sync may well succeed, e.g. after a dropped connection
+                    Err(SyncError::Transfer(err)) if is_running => {
+                        eprintln!("error while syncing: {err}");
+                    }
+                    Err(err) => {
+                        eprintln!("error while syncing: {err}");
+                        if desktop_notifications {
+                            notify_desktop("sync failed", &format!("{run_id} from {}", host.id()));
+                        }
+                        sync_failure_status([&err]).exit();
+                    }
+                }
+
+                if !is_running {
+                    break;
                 }
+
+                println!(
+                    "{run_id} is still running, syncing again in {}...",
+                    humantime::format_duration(interval)
+                );
+                std::thread::sleep(interval);
+            }
+            record_operation(host.id(), &format!("synced {run_id}"));
+            if desktop_notifications {
+                notify_desktop("sync finished", &format!("{run_id} from {}", host.id()));
+            }
+
+            for result in selected_results {
+                host::local::show_result(
+                    &run_id,
+                    &config.local_host.run_output_base_dir,
+                    &result.path,
+                    result.open_with.as_deref(),
+                );
+            }
+
+            if teardown {
+                host.release()
+                    .context(format!("failed to release {}", host.id()))?;
+            }
+
+            Ok(())
+        }
+        Some(RunnerCommandConfig::RunBrowse { host }) => {
+            let host = config
+                .build_host(&host)
+                .context("failed to build host to browse")?;
+
+            let run_id = select_run(
+                host.id(),
+                &host
+                    .runs()
+                    .context(format!("failed to obtain runs from {}", host.id()))?,
+                "run: ",
+            )
+            .context("failed to select a run to browse")?;
+
+            browse_run(&*host, &run_id, &config.local_host.run_output_base_dir)
+                .context(format!("failed to browse {run_id}"))
+        }
+        Some(RunnerCommandConfig::PushStaged { host, run, detach }) => {
+            let host_id =
+                resolve_host_id(&host, &config.remote_hosts).exit_status(ExitStatus::Config)?;
+            let staged_run_ids = staged_runs(host_id)
+                .context(format!("failed to obtain the runs staged for {host_id}"))?;
+            if staged_run_ids.is_empty() {
+                bail!(
+                    "found no runs staged for {host_id}, stage them with `sparrow run --stage-only'"
+                );
+            }
+            let run_ids = match run {
+                Some(run) => vec![resolve_run_id(&run, &staged_run_ids)?],
+                None if staged_run_ids.len() == 1 => staged_run_ids,
+                None => select_interactively_multiple(&staged_run_ids, "staged runs: ")
+                    .context("failed to select staged runs to push")?
+                    .into_iter()
+                    .cloned()
+                    .collect(),
+            };
+
+            let _submission_lock = lock_submission()?;
+            println!("Connect to host...");
+            let host = build_host(host_id, &config, HostPurpose::Submission, false)
+                .context("failed to build host to push staged runs to")?;
+            let runner = build_runner(&Vec::new(), config.runner, None, None);
+            push_staged_runs(
+                &*host,
+                &*runner,
+                &run_ids,
+                &config.remote_hosts,
+                &SubmitOptions {
+                    // attaching happens once the staging area is cleaned up
+                    detach: true,
+                    keep_payload: config.run_output.keep_payload.unwrap_or(false),
+                    output_permissions: config.run_output.permissions.clone(),
+                    config_upload_delete: config_upload_delete(config.config_upload.as_ref()),
+                    ..Default::default()
+                },
+            )
+            .exit_status(ExitStatus::Submission)?;
+
+            if let [run_id] = &run_ids[..] {
+                if !detach {
+                    host.attach(run_id);
+                }
+            }
+            Ok(())
+        }
+        Some(RunnerCommandConfig::PushData {
+            host,
+            payload_bundles,
+        }) => {
+            let payload_mapping =
+                build_payload_mapping(&config.payload, None, &Vec::new(), &payload_bundles)
+                    .context("failed to build payload mapping")
+                    .exit_status(ExitStatus::Config)?;
+
+            println!("Connect to host...");
+            let host = build_host(&host, &config, HostPurpose::Submission, false)
+                .context("failed to build host to push data to")?;
+            push_data(&*host, &payload_mapping.auxiliary_mappings)?;
+            record_operation(host.id(), "pushed data");
+            Ok(())
+        }
+        Some(RunnerCommandConfig::Lineage { host, run, dot }) => {
+            let host = config
+                .build_host(&host)
+                .context("failed to build host to trace runs on")?;
+
+            let run_ids = host
+                .runs()
+                .context(format!("failed to obtain runs from {}", host.id()))?;
+            let run_id = match run {
+                Some(run) => resolve_run_id(&run, &run_ids)?,
+                None => select_run(host.id(), &run_ids, "run: ")
+                    .context("failed to select a run to trace")?,
             };
 
-            host::local::show_result(&run_id, &config.local_host.run_output_base_dir, result_path);
+            let lineage = Lineage::collect(&*host, &run_id)
+                .context(format!("failed to collect the lineage of {run_id}"))?;
+            if dot {
+                print!("{}", lineage.to_dot());
+            } else {
+                print!("{lineage}");
+            }
 
             Ok(())
         }
@@ -314,13 +1407,23 @@ fn main() -> Result<()> {
             host,
             quick_run,
             follow,
+            mirror,
+            interval,
         }) => {
-            let host = build_host(&host, &config.local_host, &config.remote_hosts, quick_run)
-                .expect("expected host building to always succeed");
+            let host = build_host(&host, &config, HostPurpose::Inspection, quick_run)?;
+
+            let run_id = select_run(host.id(), &host.running_runs(), "run: ")
+                .context("failed to select a run to select a log file from")?;
+            if mirror {
+                return mirror_run_logs(
+                    &*host,
+                    &run_id,
+                    &config.local_host.run_output_base_dir,
+                    interval,
+                )
+                .context(format!("failed to mirror the logs of {run_id}"));
+            }
 
-            let run_id = select_interactively(&host.running_runs(), "run: ")
-                .context("failed to select a run to select a log file from")?
-                .clone();
             let log_file_path = select_interactively(&host.log_file_paths(&run_id), "log: ")
                 .context("failed to select a log file")?
                 .clone();
@@ -329,40 +1432,430 @@ fn main() -> Result<()> {
 
             Ok(())
         }
-        Some(RunnerCommandConfig::ShowResults {}) => {
-            let host = build_host("local", &config.local_host, &config.remote_hosts, false)
-                .expect("expected host building to always succeed");
+        Some(RunnerCommandConfig::RunProgress { host }) => {
+            let host = config
+                .build_host(&host)
+                .context("failed to build host of the run")?;
 
-            let run_id = select_interactively(
+            let run_id = select_run(
+                host.id(),
                 &host
                     .runs()
                     .context(format!("failed to obtain runs from {}", host.id()))?,
                 "run: ",
             )
-            .context("failed to select a run to select a result from")?
-            .clone();
+            .context("failed to select a run to report the progress of")?;
 
-            let result_path = match config.run_output.results.len() {
-                0 => {
-                    println!(
-                        "Requested results, but no results path specified in config. \
-                        Consider adding 'results: [output_dir/relative/path/to/results]' \
-                        to the config."
-                    );
-                    std::process::exit(1);
+            let log = read_snakemake_log(&*host, &run_id)
+                .context(format!("failed to read the snakemake log of {run_id}"))?;
+            print!("{}", SnakemakeProgress::parse(&log));
+
+            Ok(())
+        }
+        Some(RunnerCommandConfig::RunOutputTransfer {
+            from,
+            to,
+            move_output,
+            relay,
+        }) => {
+            let source = config
+                .build_host(&from)
+                .context("failed to build host to transfer from")?;
+            let destination = config
+                .build_host(&to)
+                .context("failed to build host to transfer to")?;
+            if source.is_local() || destination.is_local() {
+                bail!(
+                    "can only transfer run outputs between remote hosts, use `run-output-sync' \
+                    to sync them to the local host"
+                );
+            }
+            if source.id() == destination.id() {
+                bail!("cannot transfer run outputs from {} to itself", source.id());
+            }
+
+            let run_id = select_run(
+                source.id(),
+                &source
+                    .runs()
+                    .context(format!("failed to obtain runs from {}", source.id()))?,
+                "run: ",
+            )
+            .context("failed to select a run to transfer")?;
+            transfer::transfer_run_output(&*source, &*destination, &run_id, relay).context(
+                format!(
+                    "failed to transfer {run_id} from {} to {}",
+                    source.id(),
+                    destination.id()
+                ),
+            )?;
+            record_operation(
+                destination.id(),
+                &format!("received {run_id} from {}", source.id()),
+            );
+
+            if move_output {
+                let source_id = source.id();
+                transfer::verify_transferred_run_output(&*source, &*destination, &run_id).context(
+                    format!("refusing to move {run_id} into the trash of {source_id}"),
+                )?;
+                trash::trash_runs(&*source, &[&run_id]).context(format!(
+                    "failed to move {run_id} into the trash of {source_id}"
+                ))?;
+            }
+            Ok(())
+        }
+        Some(RunnerCommandConfig::RunPin { host, unpin }) => {
+            let host = config
+                .build_host(&host)
+                .context("failed to build host of the run to pin")?;
+            let local_host = host::build_local_host(&config.local_host);
+
+            let run_ids = select_runs(
+                host.id(),
+                &host
+                    .runs()
+                    .context(format!("failed to obtain runs from {}", host.id()))?,
+                "runs: ",
+            )
+            .context("failed to select runs to pin")?;
+
+            for run_id in &run_ids {
+                if unpin {
+                    pin::unpin_run(&*host, &local_host, run_id)
+                        .context(format!("failed to unpin {run_id}"))?;
+                } else {
+                    pin::pin_run(&*host, &local_host, run_id)
+                        .context(format!("failed to pin {run_id}"))?;
                 }
-                1 => config.run_output.results.first().unwrap(),
-                _ => {
-                    assert!(config.run_output.results.len() > 1);
-                    select_interactively(&config.run_output.results, "result: ")
-                        .context("failed to select a result to show")?
+            }
+            Ok(())
+        }
+        Some(RunnerCommandConfig::RunNote { host, note }) => {
+            let host = config
+                .build_host(&host)
+                .context("failed to build host of the run to annotate")?;
+
+            let run_id = select_run(
+                host.id(),
+                &host
+                    .runs()
+                    .context(format!("failed to obtain runs from {}", host.id()))?,
+                "run: ",
+            )
+            .context("failed to select a run to annotate")?;
+
+            notes::append_note(&*host, &run_id, &note)
+                .context(format!("failed to add a note to {run_id}"))
+        }
+        Some(RunnerCommandConfig::RunRename { host, name, group }) => {
+            let host = config
+                .build_host(&host)
+                .context("failed to build host of the run to rename")?;
+            let local_host = host::build_local_host(&config.local_host);
+
+            let run_id = select_run(
+                host.id(),
+                &host
+                    .runs()
+                    .context(format!("failed to obtain runs from {}", host.id()))?,
+                "run: ",
+            )
+            .context("failed to select a run to rename")?;
+            let new_run_id = rename::renamed_run_id(&run_id, name.as_deref(), group.as_deref())?;
+
+            rename::rename_run(&*host, &local_host, &run_id, &new_run_id)
+                .context(format!("failed to rename {run_id}"))
+        }
+        Some(RunnerCommandConfig::Recent { host, count }) => {
+            let host_ids = match host {
+                Some(host) if host == "local" || config.cloud_hosts.contains_key(&host) => {
+                    vec![host]
                 }
+                Some(host) => vec![resolve_host_id(&host, &config.remote_hosts)?.to_owned()],
+                None => recent::hosts_with_recent_runs()?,
             };
 
-            host::local::show_result(&run_id, &config.local_host.run_output_base_dir, result_path);
+            for host_id in host_ids {
+                let recent_runs = recent::recent_runs(&host_id)?;
+                if recent_runs.is_empty() {
+                    continue;
+                }
+
+                println!("{host_id}:");
+                for recent_run in recent_runs.iter().take(count) {
+                    println!("  {}  {}", recent_run.run_id, recent_run.time);
+                }
+            }
+
+            Ok(())
+        }
+        Some(RunnerCommandConfig::RunSignal {
+            host,
+            signal,
+            posix,
+        }) => {
+            let host = config
+                .build_host(&host)
+                .context("failed to build host of the run to signal")?;
+
+            // local runs execute in the foreground, so their host does not track running runs
+            let run_ids = if host.is_local() {
+                host.runs()
+                    .context(format!("failed to obtain runs from {}", host.id()))?
+            } else {
+                host.running_runs()
+            };
+            let run_id = select_run(host.id(), &run_ids, "run: ")
+                .context("failed to select a run to signal")?;
+
+            control::signal_run(&*host, &run_id, signal, posix)
+                .context(format!("failed to signal {run_id}"))
+        }
+        Some(RunnerCommandConfig::RunDelete { host, tags }) => {
+            let host = config
+                .build_host(&host)
+                .context("failed to build host of the runs to delete")?;
+
+            let run_ids = filter_runs_by_tags(
+                &*host,
+                host.runs()
+                    .context(format!("failed to obtain runs from {}", host.id()))?,
+                &tags,
+            )?;
+            let selected_run_ids = select_runs(host.id(), &run_ids, "runs: ")
+                .context("failed to select runs to delete")?;
+            trash::trash_runs(&*host, &selected_run_ids.iter().collect::<Vec<_>>())
+                .context("failed to delete runs")?;
+
+            // deleting is when the trash grows, so this is the time to drop expired runs
+            trash::empty_trash(&*host, trash_retention(&config.run_output)?)
+                .context(format!("failed to empty the trash of {}", host.id()))
+        }
+        Some(RunnerCommandConfig::RunExport {
+            host,
+            output,
+            archive,
+        }) => {
+            let host = config
+                .build_host(&host)
+                .context("failed to build host to export from")?;
+
+            let run_id = select_run(
+                host.id(),
+                &host
+                    .runs()
+                    .context(format!("failed to obtain runs from {}", host.id()))?,
+                "run: ",
+            )
+            .context("failed to select a run to export")?;
+            let results = build_run_results(&config.run_output.results);
+            let selected_results =
+                select_results(&results).context("failed to select results to export")?;
+
+            export_run(
+                &*host,
+                &run_id,
+                &selected_results
+                    .iter()
+                    .map(|result| result.path.as_path())
+                    .collect(),
+                &output.unwrap_or(default_export_path(&run_id, archive)),
+                archive,
+            )
+            .context(format!("failed to export {run_id}"))
+        }
+        Some(RunnerCommandConfig::SweepReport {
+            host,
+            group,
+            metrics,
+            sort_by,
+            descending,
+            output,
+        }) => {
+            let host = config
+                .build_host(&host)
+                .context("failed to build host to collect metrics from")?;
+
+            let Some(metrics_path) = metrics.or(config.run_output.metrics) else {
+                bail!(
+                    "no metrics file specified, consider adding \
+                    'metrics: output_dir/relative/path/to/metrics.json' to run_output in the \
+                    config or pass --metrics"
+                );
+            };
+            let output_format = output
+                .as_deref()
+                .map(SweepReportFormat::from_path)
+                .transpose()?;
+
+            let group = group.unwrap_or(config.run_group);
+            let mut report = SweepReport::collect(&*host, &group, &metrics_path)
+                .context(format!("failed to collect metrics of {group}"))?;
+            if let Some(sort_by) = sort_by {
+                report.sort_by(&sort_by, descending)?;
+            }
+
+            print!("{}", report.render(SweepReportFormat::Table));
+            if let (Some(output), Some(output_format)) = (output, output_format) {
+                std::fs::write(&output, report.render(output_format))
+                    .context(format!("failed to write report to {output}"))?;
+            }
+
+            Ok(())
+        }
+        Some(RunnerCommandConfig::Usage { host, since }) => {
+            let host = config
+                .build_host(&host)
+                .context("failed to build host to report usage of")?;
+
+            let report = UsageReport::collect(&*host, since)
+                .context(format!("failed to collect usage on {}", host.id()))?;
+            print!("{report}");
+
+            Ok(())
+        }
+        Some(RunnerCommandConfig::RunDu {
+            host,
+            group,
+            refresh,
+        }) => {
+            let host = config
+                .build_host(&host)
+                .context("failed to build host to measure run outputs on")?;
+
+            let report = StorageReport::collect(&*host, group.as_deref(), refresh)
+                .context(format!("failed to measure run outputs on {}", host.id()))?;
+            print!("{report}");
+
+            Ok(())
+        }
+        Some(RunnerCommandConfig::ShowResults { remote, directory }) => {
+            let host = config
+                .build_host(remote.as_deref().unwrap_or("local"))
+                .context("failed to build host to show results from")?;
+
+            let run_id = select_run(
+                host.id(),
+                &host
+                    .runs()
+                    .context(format!("failed to obtain runs from {}", host.id()))?,
+                "run: ",
+            )
+            .context("failed to select a run to select a result from")?;
+
+            let results = build_run_results(&config.run_output.results);
+            if results.is_empty() {
+                println!(
+                    "Requested results, but no results path specified in config. \
+                    Consider adding 'results: [output_dir/relative/path/to/results]' \
+                    to the config."
+                );
+                ExitStatus::Config.exit();
+            }
+            let selected_results =
+                select_results(&results).context("failed to select results to show")?;
+
+            // when opening directories, the configured openers don't apply, and results in the
+            // same directory are opened only once, in the order they were selected
+            let mut seen_result_paths = HashSet::new();
+            let result_paths = selected_results
+                .into_iter()
+                .map(|result| match directory {
+                    true => (result.path.parent().unwrap_or(&result.path), None),
+                    false => (result.path.as_path(), result.open_with.as_deref()),
+                })
+                .filter(|result_path| seen_result_paths.insert(*result_path))
+                .collect::<Vec<_>>();
+
+            let result_base_path = if host.is_local() {
+                config.local_host.run_output_base_dir.clone()
+            } else {
+                let cache_path = host::local::result_cache_dir_path(host.id());
+                for (result_path, _) in &result_paths {
+                    let local_path = run_id.path(&cache_path).join(result_path);
+                    let local_parent_path = local_path
+                        .parent()
+                        .expect("expected result path to have a parent");
+                    std::fs::create_dir_all(local_parent_path)
+                        .context(format!("failed to create {local_parent_path}"))?;
+
+                    println!("Downloading {result_path} from {}...", host.id());
+                    host.try_get(
+                        &run_id.path(host.output_base_dir_path()).join(result_path),
+                        local_parent_path,
+                        SyncOptions::default(),
+                    )
+                    .context(format!(
+                        "failed to download {result_path} from {}",
+                        host.id()
+                    ))?;
+                }
+
+                cache_path
+            };
+
+            for (result_path, open_with) in result_paths {
+                host::local::show_result(&run_id, &result_base_path, result_path, open_with);
+            }
 
             Ok(())
         }
         None => bail!("no command specified, use --help to see available commands"),
     }
 }
+
+/// Exit status after syncs failed with `errors`, where refusing to overwrite a local run output
+/// that was not synced from the host takes precedence over failed transfers
+fn sync_failure_status<'e>(errors: impl IntoIterator<Item = &'e SyncError>) -> ExitStatus {
+    if errors
+        .into_iter()
+        .any(|err| matches!(err, SyncError::Conflict(_)))
+    {
+        ExitStatus::SyncConflict
+    } else {
+        ExitStatus::Failure
+    }
+}
+
+fn push_run_output(
+    run_id: &host::RunID,
+    local_base_path: &camino::Utf8Path,
+    destination: &SyncDestinationConfig,
+    sync_options: &host::RunOutputSyncOptions,
+) -> Result<()> {
+    let destination_path = format!(
+        "{}/{}/{}",
+        destination.target.trim_end_matches('/'),
+        run_id.group,
+        run_id.name
+    );
+    let options = SyncOptions::default()
+        .copy_contents()
+        .make_parents()
+        .progress()
+        .exclude(&sync_options.excludes)
+        .only_include(&sync_options.includes);
+
+    host::rsync::build_transfer(destination.backend)
+        .push(&run_id.path(local_base_path), &destination_path, options)
+        .context(format!("failed to transfer to {destination_path}"))
+}
+
+fn trash_retention(run_output: &RunOutputConfig) -> Result<std::time::Duration> {
+    let retention = run_output
+        .trash_retention
+        .as_deref()
+        .unwrap_or(trash::DEFAULT_TRASH_RETENTION);
+    humantime::parse_duration(retention)
+        .context(format!("failed to parse trash retention `{retention}'"))
+        .exit_status(ExitStatus::Config)
+}
+
+fn select_results(results: &Vec<RunResult>) -> Result<Vec<&RunResult>> {
+    match results.len() {
+        0 => Ok(Vec::new()),
+        1 => Ok(vec![results.first().unwrap()]),
+        _ => select_interactively_multiple(results, "results: "),
+    }
+}
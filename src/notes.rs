@@ -0,0 +1,52 @@
+use crate::host::rsync::SyncOptions;
+use crate::host::{Host, RunID};
+use crate::utils::Utf8Path;
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+const MAX_NOTES_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Notes attached to `run_id`, or `None` if it has none yet
+pub fn read_notes(host: &dyn Host, run_id: &RunID) -> Result<Option<String>> {
+    let notes_path = host.notes_file_destination_path(run_id);
+    if !host.exists(&notes_path)? {
+        return Ok(None);
+    }
+
+    let content = host
+        .read_file_head(&notes_path, MAX_NOTES_FILE_SIZE)
+        .context(format!("failed to read {notes_path}"))?;
+    Ok(Some(String::from_utf8_lossy(&content).into_owned()))
+}
+
+/// Append `note` as a timestamped list item to the notes of `run_id` on `host`
+pub fn append_note(host: &dyn Host, run_id: &RunID, note: &str) -> Result<()> {
+    let note = note.trim();
+    if note.is_empty() {
+        bail!("refusing to add an empty note");
+    }
+
+    let mut notes = read_notes(host, run_id)?.unwrap_or_default();
+    if !notes.is_empty() && !notes.ends_with('\n') {
+        notes.push('\n');
+    }
+    notes += &format!(
+        "- {}: {}\n",
+        humantime::format_rfc3339_seconds(std::time::SystemTime::now()),
+        // continuation lines are indented to stay within the list item
+        note.replace('\n', "\n  ")
+    );
+
+    let mut notes_file = NamedTempFile::new().context("failed to create temporary notes file")?;
+    notes_file
+        .write_all(notes.as_bytes())
+        .context("failed to write run notes")?;
+    host.put(
+        notes_file.utf8_path(),
+        &host.notes_file_destination_path(run_id),
+        SyncOptions::default(),
+    );
+
+    Ok(())
+}
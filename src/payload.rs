@@ -1,7 +1,10 @@
-use crate::cfg::PayloadMappingConfig;
-use anyhow::{anyhow, Context, Result};
+use crate::cfg::{
+    EnvironmentLockSource, ExcludeSource, LocalSnapshotMode, PayloadMappingConfig, SubmoduleMode,
+};
+use crate::utils::Utf8Path;
+use anyhow::{anyhow, bail, Context, Result};
 use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use url::Url;
 
 #[derive(Clone)]
@@ -9,13 +12,27 @@ pub enum CodeSource {
     Remote {
         url: Url,
         git_revision: String,
+        submodules: SubmoduleMode,
     },
     Local {
         path: PathBuf,
         copy_excludes: Vec<String>,
+        /// Tree of the repository to upload instead of the working tree
+        snapshot: Option<CodeSnapshot>,
     },
 }
 
+/// Tree of the repository of a local code source, which is uploaded instead of its working tree
+#[derive(Clone)]
+pub struct CodeSnapshot {
+    pub mode: LocalSnapshotMode,
+    /// Id of the tree at the path of the code source; for `staged` snapshots, this tree exists only
+    /// in memory while the payload is built
+    pub tree_id: String,
+    /// Path of the code source relative to the top of its repository
+    pub repository_path: PathBuf,
+}
+
 impl CodeSource {
     pub fn git_revision(&self) -> Option<&String> {
         match self {
@@ -25,24 +42,253 @@ impl CodeSource {
     }
 }
 
+impl std::fmt::Display for CodeSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodeSource::Local { path, .. } => write!(f, "{path}"),
+            CodeSource::Remote {
+                url, git_revision, ..
+            } => write!(f, "{url}@{git_revision}"),
+        }
+    }
+}
+
+/// Checked out revision of a submodule of a local code source with its uncommitted changes
+pub struct SubmoduleState {
+    /// Path of the submodule relative to the code source
+    pub path: PathBuf,
+    pub revision: String,
+    pub diff: Option<String>,
+}
+
+/// Priority of the in-memory object database of `staged` snapshots, which has to exceed those of
+/// the loose and packed object databases of a repository so that it receives the written objects
+const MEMPACK_PRIORITY: i32 = 1000;
+
+/// Snapshot to upload of the local code source at `path` in `mode`, or `None` if the working tree
+/// is uploaded; for sources below the top of their repository, this is the subtree at their path
+fn snapshot(path: &Path, mode: LocalSnapshotMode) -> Result<Option<CodeSnapshot>> {
+    if mode == LocalSnapshotMode::Worktree {
+        return Ok(None);
+    }
+
+    let repository = git2::Repository::discover(path)
+        .context(format!("failed to find a git repository at {path}"))?;
+    let tree = match mode {
+        LocalSnapshotMode::Worktree => unreachable!(),
+        LocalSnapshotMode::Head => repository
+            .head()
+            .and_then(|head| head.peel_to_tree())
+            .context("failed to look up the tree of HEAD")?,
+        LocalSnapshotMode::Staged => {
+            // the tree of the index is built in an in-memory object database, so that taking the
+            // snapshot leaves no objects behind in the repository
+            repository
+                .odb()
+                .and_then(|odb| odb.add_new_mempack_backend(MEMPACK_PRIORITY).map(|_| ()))
+                .context("failed to set up an in-memory object database")?;
+            let tree_id = repository
+                .index()
+                .and_then(|mut index| index.write_tree())
+                .context("failed to build the tree of the index")?;
+            repository.find_tree(tree_id)?
+        }
+    };
+
+    let workdir_path = repository
+        .workdir()
+        .ok_or(anyhow!("found bare repository at {path}"))?
+        .canonicalize()?;
+    let relative_path = path
+        .as_std_path()
+        .canonicalize()
+        .context(format!("failed to resolve {path}"))?
+        .strip_prefix(&workdir_path)?
+        .to_owned();
+    let tree_id = if relative_path.as_os_str().is_empty() {
+        tree.id()
+    } else {
+        tree.get_path(&relative_path)
+            .context(format!(
+                "failed to find {path} in the tree of its repository"
+            ))?
+            .id()
+    };
+
+    Ok(Some(CodeSnapshot {
+        mode,
+        tree_id: tree_id.to_string(),
+        repository_path: PathBuf::try_from(relative_path)?,
+    }))
+}
+
+/// Dependency lock of the local python environment, captured when the payload is built
+#[derive(Clone)]
+pub struct EnvironmentLock {
+    /// Tool the lock is native to, which is one of `uv`, `poetry` or `pip`
+    pub kind: &'static str,
+    /// Name of the lock in the reproduce info of the run
+    pub file_name: &'static str,
+    pub content: Vec<u8>,
+}
+
+/// Capture the dependency lock of the python environment of the project from `source`, where
+/// lock files are looked up in the current directory
+fn capture_environment_lock(source: EnvironmentLockSource) -> Result<EnvironmentLock> {
+    let read_lock_file = |kind, file_name| -> Result<EnvironmentLock> {
+        Ok(EnvironmentLock {
+            kind,
+            file_name,
+            content: std::fs::read(file_name).context(format!("failed to read {file_name}"))?,
+        })
+    };
+
+    match source {
+        EnvironmentLockSource::Uv => read_lock_file("uv", "uv.lock"),
+        EnvironmentLockSource::Poetry => read_lock_file("poetry", "poetry.lock"),
+        EnvironmentLockSource::Auto if Path::new("uv.lock").exists() => {
+            read_lock_file("uv", "uv.lock")
+        }
+        EnvironmentLockSource::Auto if Path::new("poetry.lock").exists() => {
+            read_lock_file("poetry", "poetry.lock")
+        }
+        EnvironmentLockSource::Auto | EnvironmentLockSource::Pip => {
+            let output = std::process::Command::new("pip")
+                .arg("freeze")
+                .output()
+                .context("failed to run `pip freeze`")?;
+            if !output.status.success() {
+                bail!(
+                    "`pip freeze` failed with {}: {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+
+            Ok(EnvironmentLock {
+                kind: "pip",
+                file_name: "requirements.txt",
+                content: output.stdout,
+            })
+        }
+    }
+}
+
+/// States of all submodules of the local code source at `path` including nested ones, where
+/// submodules that were never initialized are skipped, as well as sources which are not the top
+/// of a repository
+pub fn local_submodule_states(path: &Path) -> Result<Vec<SubmoduleState>> {
+    let Ok(repository) = git2::Repository::open(path) else {
+        return Ok(Vec::new());
+    };
+
+    let mut states = Vec::new();
+    collect_submodule_states(&repository, Path::new(""), &mut states)?;
+    Ok(states)
+}
+
+fn collect_submodule_states(
+    repository: &git2::Repository,
+    prefix: &Path,
+    states: &mut Vec<SubmoduleState>,
+) -> Result<()> {
+    for submodule in repository
+        .submodules()
+        .context("failed to list submodules")?
+    {
+        let submodule_path = prefix.join(Path::from_path(submodule.path()).ok_or(anyhow!(
+            "found non-utf8 submodule path {}",
+            submodule.path().display()
+        ))?);
+        let (Some(revision), Ok(submodule_repository)) = (submodule.workdir_id(), submodule.open())
+        else {
+            continue;
+        };
+
+        states.push(SubmoduleState {
+            diff: working_tree_diff(&submodule_repository)
+                .context(format!("failed to diff submodule {submodule_path}"))?,
+            path: submodule_path.clone(),
+            revision: revision.to_string(),
+        });
+        collect_submodule_states(&submodule_repository, &submodule_path, states)?;
+    }
+
+    Ok(())
+}
+
+/// Patch of the uncommitted changes to tracked files of `repository`, if there are any
+fn working_tree_diff(repository: &git2::Repository) -> Result<Option<String>> {
+    let head_tree = repository.head()?.peel_to_tree()?;
+    let diff = repository.diff_tree_to_workdir_with_index(Some(&head_tree), None)?;
+
+    let mut patch = Vec::new();
+    diff.print(git2::DiffFormat::Patch, |_, _, line| {
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            patch.push(line.origin() as u8);
+        }
+        patch.extend_from_slice(line.content());
+        true
+    })?;
+
+    Ok((!patch.is_empty()).then(|| String::from_utf8_lossy(&patch).into_owned()))
+}
+
+#[derive(Clone, Default)]
+pub struct TargetPermissions {
+    pub preserve: Option<bool>,
+    pub chmod: Option<String>,
+}
+
+impl TargetPermissions {
+    pub fn is_default(&self) -> bool {
+        self.preserve.is_none() && self.chmod.is_none()
+    }
+}
+
 #[derive(Clone)]
 pub struct CodeMapping {
     pub id: String,
     pub source: CodeSource,
     pub target_path: PathBuf,
+    pub target_permissions: TargetPermissions,
 }
 
 #[derive(Clone)]
 pub struct ConfigSource {
-    pub entrypoint_path: PathBuf,
+    pub entrypoint_paths: Vec<PathBuf>,
     pub dir_path: PathBuf,
 }
 
+/// Extension of config entrypoints that are jinja templates, which are rendered against the
+/// template context of each run into the entrypoint without the extension
+pub const CONFIG_TEMPLATE_EXTENSION: &str = "j2";
+
+/// Path of the config entrypoint `entrypoint_path` as uploaded, which is the rendered file for
+/// config templates
+pub fn rendered_entrypoint_path(entrypoint_path: &Path) -> PathBuf {
+    if entrypoint_path.extension() == Some(CONFIG_TEMPLATE_EXTENSION) {
+        entrypoint_path.with_extension("")
+    } else {
+        entrypoint_path.to_owned()
+    }
+}
+
+impl ConfigSource {
+    pub fn rendered_entrypoint_paths(&self) -> Vec<PathBuf> {
+        self.entrypoint_paths
+            .iter()
+            .map(|entrypoint_path| rendered_entrypoint_path(entrypoint_path))
+            .collect()
+    }
+}
+
 #[derive(Clone)]
 pub struct AuxiliaryMapping {
     pub source_path: PathBuf,
     pub target_path: PathBuf,
     pub copy_excludes: Vec<String>,
+    pub target_permissions: TargetPermissions,
 }
 
 #[derive(Clone)]
@@ -50,16 +296,118 @@ pub struct PayloadMapping {
     pub code_mappings: Vec<CodeMapping>,
     pub config_source: ConfigSource,
     pub auxiliary_mappings: Vec<AuxiliaryMapping>,
+    pub bundles: Vec<String>,
+    pub build_steps: Vec<String>,
+    pub environment_lock: Option<EnvironmentLock>,
+    /// Ids of the code mappings left out of the payload on request
+    pub omitted_code: Vec<String>,
+}
+
+impl PayloadMapping {
+    /// Leave out the code mappings that are not in `only`, unless it is empty, or that are in
+    /// `skip`
+    pub fn select_code(&mut self, only: &[String], skip: &[String]) -> Result<()> {
+        for id in only {
+            if !self
+                .code_mappings
+                .iter()
+                .any(|code_mapping| code_mapping.id == *id)
+            {
+                bail!("cannot upload only `{id}', not found in code mappings");
+            }
+        }
+        self.omit_code(skip)?;
+        if only.is_empty() {
+            return Ok(());
+        }
+
+        let unselected_code = self
+            .code_mappings
+            .iter()
+            .map(|code_mapping| code_mapping.id.clone())
+            .filter(|id| !only.contains(id))
+            .collect::<Vec<_>>();
+        self.omit_code(&unselected_code)
+    }
+
+    /// Leave out the code mappings `ids`, which are recorded as omitted in the run context
+    pub fn omit_code(&mut self, ids: &[String]) -> Result<()> {
+        for id in ids {
+            if self.omitted_code.contains(id) {
+                continue;
+            }
+            if !self
+                .code_mappings
+                .iter()
+                .any(|code_mapping| code_mapping.id == *id)
+            {
+                bail!("cannot skip `{id}', not found in code mappings");
+            }
+            self.omitted_code.push(id.clone());
+        }
+        self.code_mappings
+            .retain(|code_mapping| !ids.contains(&code_mapping.id));
+
+        Ok(())
+    }
+}
+
+/// The build steps of a payload together with the environment they are run in, which exports
+/// the run context
+pub struct PayloadBuild<'p> {
+    pub steps: &'p [String],
+    pub environment: Vec<(String, String)>,
 }
 
-#[derive(serde::Serialize)]
+impl PayloadBuild<'_> {
+    /// Run the build steps in order in `prep_dir_path`, stopping at the first one that fails
+    pub fn run(&self, prep_dir_path: &Path) -> Result<()> {
+        for step in self.steps {
+            println!("Running build step `{step}'...");
+            let status = std::process::Command::new("bash")
+                .arg("-c")
+                .arg(step)
+                .current_dir(prep_dir_path)
+                .envs(self.environment.iter().map(|(name, value)| (name, value)))
+                .status()
+                .context(format!("failed to run build step `{step}'"))?;
+            if !status.success() {
+                bail!("build step `{step}' failed with {status}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
 pub struct PayloadInfo {
     code_revisions: HashMap<String, String>,
+    #[schemars(with = "String")]
     config_dir: PathBuf,
+    #[schemars(with = "Vec<String>")]
+    config_entrypoints: Vec<PathBuf>,
+    bundles: Vec<String>,
+    environment_lock: Option<EnvironmentLockInfo>,
+    /// Ids of the code mappings that were not uploaded for the run
+    omitted_code: Vec<String>,
+}
+
+/// Recorded dependency lock of a run, from which the run script can build the environment of the
+/// run, e.g. with `uv sync` or `pip install -r`
+#[derive(serde::Serialize, schemars::JsonSchema)]
+struct EnvironmentLockInfo {
+    kind: &'static str,
+    #[schemars(with = "String")]
+    path: PathBuf,
 }
 
 impl PayloadInfo {
-    pub fn new(source: &PayloadMapping, config_dir_destination_path: &Path) -> PayloadInfo {
+    pub fn new(
+        source: &PayloadMapping,
+        config_dir_destination_path: &Path,
+        reproduce_info_path: &Path,
+    ) -> PayloadInfo {
         PayloadInfo {
             code_revisions: source
                 .code_mappings
@@ -72,22 +420,68 @@ impl PayloadInfo {
                 })
                 .collect::<HashMap<_, _>>(),
             config_dir: config_dir_destination_path.to_owned(),
+            config_entrypoints: source.config_source.rendered_entrypoint_paths(),
+            bundles: source.bundles.clone(),
+            omitted_code: source.omitted_code.clone(),
+            environment_lock: source
+                .environment_lock
+                .as_ref()
+                .map(|lock| EnvironmentLockInfo {
+                    kind: lock.kind,
+                    path: reproduce_info_path.join(lock.file_name),
+                }),
         }
     }
+
+    /// Revisions of the code sources checked out from git remotes, by code source id
+    pub fn code_versions(&self) -> BTreeMap<String, String> {
+        self.code_revisions.clone().into_iter().collect()
+    }
+
+    pub fn config_entrypoints(&self) -> &[PathBuf] {
+        &self.config_entrypoints
+    }
 }
 
 pub fn build_payload_mapping(
     payload_mapping_config: &PayloadMappingConfig,
     config_dir_override_path: Option<&Path>,
     ignore_revisions: &Vec<String>,
+    bundles: &[String],
 ) -> Result<PayloadMapping> {
-    assert!(payload_mapping_config.config.entrypoint.is_relative());
+    let mut code_mapping_configs = payload_mapping_config.code.iter().collect::<Vec<_>>();
+    let mut auxiliary_mapping_configs = payload_mapping_config
+        .auxiliary
+        .iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    for bundle_id in bundles {
+        let bundle = payload_mapping_config
+            .bundles
+            .as_ref()
+            .and_then(|bundles| bundles.get(bundle_id))
+            .ok_or(anyhow!(
+                "found no payload bundle `{bundle_id}' in the configuration"
+            ))?;
+        for (code_source_id, code_mapping_config) in bundle.code.iter().flatten() {
+            if code_mapping_configs
+                .iter()
+                .any(|(id, _)| *id == code_source_id)
+            {
+                return Err(anyhow!(
+                    "found code source id `{code_source_id}' of payload bundle `{bundle_id}' \
+                    twice in the selected payload"
+                ));
+            }
+            code_mapping_configs.push((code_source_id, code_mapping_config));
+        }
+        auxiliary_mapping_configs.extend(bundle.auxiliary.iter().flatten());
+    }
 
     for ignore_id in ignore_revisions.iter() {
-        if !payload_mapping_config
-            .code
-            .keys()
-            .any(|code_source_id| *code_source_id == *ignore_id)
+        if !code_mapping_configs
+            .iter()
+            .any(|(code_source_id, _)| *code_source_id == ignore_id)
         {
             return Err(anyhow!(
                 "cannot ignore revision of id `{ignore_id}', not found in code mappings",
@@ -118,9 +512,8 @@ pub fn build_payload_mapping(
         "failed to convert relative config override {config_dir_path} to an absolute path"
     ))?;
 
-    let code_mappings: Vec<CodeMapping> = payload_mapping_config
-        .code
-        .iter()
+    let code_mappings: Vec<CodeMapping> = code_mapping_configs
+        .into_iter()
         .map(|(code_source_id, code_mapping_config)| {
             assert!(code_mapping_config.target.is_relative());
 
@@ -131,6 +524,11 @@ pub fn build_payload_mapping(
             {
                 // we always exclude the git directory, since this is never needed for runs
                 let mut copy_excludes = vec![String::from("/.git/")];
+                copy_excludes.extend(
+                    SPARROW_STATE_EXCLUDES
+                        .iter()
+                        .map(|&exclude| exclude.to_owned()),
+                );
 
                 if !code_mapping_config.local.no_config_exclude {
                     copy_excludes.push(format!("/{}/", payload_mapping_config.config.dir));
@@ -144,10 +542,17 @@ pub fn build_payload_mapping(
                     );
                 }
 
-                copy_excludes.extend(
-                    read_excludes_from_gitignore(&code_mapping_config.local.path)
-                        .context("failed to add excludes from gitignore")?,
-                );
+                let exclude_sources = code_mapping_config
+                    .local
+                    .copy_excludes_from
+                    .clone()
+                    .unwrap_or(default_exclude_sources(&code_mapping_config.local.path));
+                for exclude_source in &exclude_sources {
+                    copy_excludes.extend(
+                        read_excludes(exclude_source, &code_mapping_config.local.path)
+                            .context(format!("failed to add excludes for `{code_source_id}'"))?,
+                    );
+                }
                 if let Some(exclude_additions) =
                     &code_mapping_config.local.gitignore_exclude_additions
                 {
@@ -162,11 +567,17 @@ pub fn build_payload_mapping(
                 CodeSource::Local {
                     path: code_mapping_config.local.path.clone(),
                     copy_excludes,
+                    snapshot: snapshot(
+                        &code_mapping_config.local.path,
+                        code_mapping_config.local.snapshot.unwrap_or_default(),
+                    )
+                    .context(format!("failed to snapshot `{code_source_id}'"))?,
                 }
             } else {
                 CodeSource::Remote {
                     url: code_mapping_config.remote.url.clone(),
                     git_revision: code_mapping_config.remote.revision.clone(),
+                    submodules: code_mapping_config.remote.submodules.unwrap_or_default(),
                 }
             };
 
@@ -174,46 +585,194 @@ pub fn build_payload_mapping(
                 id: code_source_id.clone(),
                 source,
                 target_path: code_mapping_config.target.clone(),
+                target_permissions: TargetPermissions {
+                    preserve: code_mapping_config.preserve_permissions,
+                    chmod: code_mapping_config.chmod.clone(),
+                },
             })
         })
         .collect::<Result<_>>()?;
 
-    let auxiliary_mappings = payload_mapping_config
-        .auxiliary
-        .clone()
-        .unwrap_or(vec![])
-        .iter()
-        .map(|mapping_config| AuxiliaryMapping {
-            source_path: mapping_config.path.clone(),
-            target_path: mapping_config.target.clone(),
-            copy_excludes: mapping_config.excludes.clone().unwrap_or(vec![]),
+    let auxiliary_mappings = auxiliary_mapping_configs
+        .into_iter()
+        .map(|mapping_config| {
+            Ok(AuxiliaryMapping {
+                source_path: mapping_config.path.clone(),
+                target_path: mapping_config.target.clone(),
+                copy_excludes: mapping_config
+                    .excludes
+                    .clone()
+                    .unwrap_or(vec![])
+                    .into_iter()
+                    .chain(read_sparrowignore(&mapping_config.path).context(format!(
+                        "failed to add excludes for auxiliary mapping {}",
+                        mapping_config.path
+                    ))?)
+                    .collect(),
+                target_permissions: TargetPermissions {
+                    preserve: mapping_config.preserve_permissions,
+                    chmod: mapping_config.chmod.clone(),
+                },
+            })
         })
-        .collect();
+        .collect::<Result<_>>()?;
 
     Ok(PayloadMapping {
         code_mappings,
         config_source: ConfigSource {
-            entrypoint_path: payload_mapping_config.config.entrypoint.clone(),
+            entrypoint_paths: payload_mapping_config.config.entrypoints.clone(),
             dir_path: config_dir_path,
         },
         auxiliary_mappings,
+        bundles: bundles.to_vec(),
+        build_steps: payload_mapping_config
+            .build_steps
+            .clone()
+            .unwrap_or_default(),
+        omitted_code: Vec::new(),
+        environment_lock: payload_mapping_config
+            .environment_lock
+            .map(capture_environment_lock)
+            .transpose()
+            .context("failed to capture the environment lock")?,
     })
 }
 
+/// Excludes used for the `builtin` exclude source, covering common caches and environments
+/// which are never needed for runs
+const BUILTIN_EXCLUDES: &[&str] = &[
+    "__pycache__/",
+    "*.pyc",
+    ".ipynb_checkpoints/",
+    ".mypy_cache/",
+    ".pytest_cache/",
+    ".ruff_cache/",
+    ".venv/",
+    ".DS_Store",
+];
+
+/// Local state of sparrow in a project, which is never copied along with a code source at the
+/// top of the project, since staged runs would otherwise be copied into themselves
+const SPARROW_STATE_EXCLUDES: &[&str] = &[
+    "/.sparrow/staged/",
+    "/.sparrow/cloud/",
+    "/.sparrow/queue/",
+    "/.sparrow/sweeps/",
+];
+
+const SPARROWIGNORE_FILE_NAME: &str = ".sparrowignore";
+
+/// Use the `.sparrowignore` of a code source instead of its `.gitignore` if it has one, so that
+/// uploads can be controlled independently of what is tracked by git
+fn default_exclude_sources(repository_path: &Path) -> Vec<ExcludeSource> {
+    if repository_path.join(SPARROWIGNORE_FILE_NAME).exists() {
+        vec![ExcludeSource::Sparrowignore]
+    } else {
+        vec![ExcludeSource::Gitignore]
+    }
+}
+
+/// Read the `.sparrowignore` at the top of `dir_path`, if there is one
+fn read_sparrowignore(dir_path: &Path) -> Result<Vec<String>> {
+    let sparrowignore_path = dir_path.join(SPARROWIGNORE_FILE_NAME);
+    if !sparrowignore_path.is_file() {
+        return Ok(Vec::new());
+    }
+
+    read_ignore_file(&sparrowignore_path).context(format!("failed to read {sparrowignore_path}"))
+}
+
+fn read_excludes(exclude_source: &ExcludeSource, repository_path: &Path) -> Result<Vec<String>> {
+    match exclude_source {
+        ExcludeSource::Gitignore => read_excludes_from_gitignore(repository_path),
+        ExcludeSource::Sparrowignore => {
+            let sparrowignore_path = repository_path.join(SPARROWIGNORE_FILE_NAME);
+            if !sparrowignore_path.exists() {
+                println!("warning: {sparrowignore_path} does not exist, using no excludes from it");
+            }
+            read_sparrowignore(repository_path)
+        }
+        ExcludeSource::Builtin => Ok(BUILTIN_EXCLUDES
+            .iter()
+            .map(|&exclude| exclude.to_owned())
+            .collect()),
+        ExcludeSource::File(path) => {
+            let path = repository_path.join(path);
+            read_ignore_file(&path).context(format!("failed to read excludes from {path}"))
+        }
+    }
+}
+
+fn read_ignore_file(path: &Path) -> Result<Vec<String>, std::io::Error> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.starts_with("#") && !line.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Read the excludes of all `.gitignore` files in the repository and of `.git/info/exclude`,
+/// where patterns of nested `.gitignore` files are anchored to their directory; since rsync
+/// cannot re-include files, negated patterns of nested `.gitignore` files are skipped
 fn read_excludes_from_gitignore(repository_path: &Path) -> Result<Vec<String>> {
-    let read_ignores = |path: &Path| -> Result<Vec<_>, std::io::Error> {
-        Ok(std::fs::read_to_string(repository_path.join(path))?
-            .lines()
-            .filter(|line| !line.starts_with("#") && !line.is_empty())
-            .map(String::from)
-            .collect())
-    };
+    let mut excludes = Vec::new();
+
+    let root_gitignore_path = repository_path.join(".gitignore");
+    if root_gitignore_path.exists() {
+        excludes.extend(
+            read_ignore_file(&root_gitignore_path)
+                .context(format!("failed to read {root_gitignore_path}"))?,
+        );
+    } else {
+        println!("warning: {root_gitignore_path} does not exist, using no excludes from it");
+    }
+
+    let nested_gitignore_paths = walkdir::WalkDir::new(repository_path)
+        .min_depth(2)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && entry.file_name() == ".gitignore")
+        .map(|entry| entry.utf8_path().to_owned());
+    for gitignore_path in nested_gitignore_paths {
+        let dir_path = gitignore_path
+            .parent()
+            .expect("expected gitignore path to have a parent")
+            .strip_prefix(repository_path)
+            .expect("expected walked path to be below the repository");
+        for pattern in read_ignore_file(&gitignore_path)
+            .context(format!("failed to read {gitignore_path}"))?
+            .iter()
+            .filter(|pattern| !pattern.starts_with('!'))
+        {
+            excludes.extend(anchor_gitignore_pattern(pattern, dir_path));
+        }
+    }
+
+    let info_exclude_path = repository_path.join(".git/info/exclude");
+    if std::fs::exists(&info_exclude_path).context(format!(
+        "failed to check for existence of {info_exclude_path}"
+    ))? {
+        excludes.extend(
+            read_ignore_file(&info_exclude_path)
+                .context(format!("failed to read {info_exclude_path}"))?,
+        );
+    }
+
+    Ok(excludes)
+}
+
+/// Translate a pattern of the `.gitignore` in `dir_path` into rsync excludes relative to the
+/// repository root, following the gitignore rule that patterns containing a non-trailing slash
+/// are relative to their `.gitignore`, while all others match at any depth below it
+fn anchor_gitignore_pattern(pattern: &str, dir_path: &Path) -> Vec<String> {
+    if pattern.trim_end_matches('/').contains('/') {
+        return vec![format!("/{dir_path}/{}", pattern.trim_start_matches('/'))];
+    }
 
-    Ok(Iterator::chain(
-        read_ignores(&Path::new(".gitignore")).context(format!("failed to open {repository_path}/.gitignore"))?.into_iter(),
-        if std::fs::exists(".git/info/exclude").context(format!("failed to check for existence of `{repository_path}.git/info/exclude`"))? {
-            read_ignores(&Path::new(".git/info/exclude")).context(format!("failed to open {repository_path}/.git/info/exclude"))?
-        } else { vec![] }.into_iter(),
-    )
-    .collect())
+    vec![
+        format!("/{dir_path}/{pattern}"),
+        format!("/{dir_path}/**/{pattern}"),
+    ]
 }
@@ -0,0 +1,64 @@
+use crate::host::rsync::SyncOptions;
+use crate::host::{Host, RunID};
+use crate::utils::Utf8Path;
+use anyhow::{Context, Result};
+use camino::Utf8PathBuf as PathBuf;
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+pub const PINNED_MARKER_FILE_NAME: &str = ".pinned";
+
+fn pinned_marker_path(host: &dyn Host, run_id: &RunID) -> PathBuf {
+    run_id
+        .path(host.output_base_dir_path())
+        .join(PINNED_MARKER_FILE_NAME)
+}
+
+pub fn is_pinned(host: &dyn Host, run_id: &RunID) -> Result<bool> {
+    host.exists(&pinned_marker_path(host, run_id))
+}
+
+/// Place the pinned marker into the run output on the host and, if the run output was already
+/// synced, into the local copy as well, so that sparrow refuses to overwrite the run
+pub fn pin_run(host: &dyn Host, local_host: &dyn Host, run_id: &RunID) -> Result<()> {
+    let mut marker_file =
+        NamedTempFile::new().context("failed to create temporary pinned marker file")?;
+    writeln!(
+        marker_file,
+        "{}",
+        humantime::format_rfc3339_seconds(std::time::SystemTime::now())
+    )
+    .context("failed to write pinned marker")?;
+
+    for host in marker_hosts(host, local_host, run_id)? {
+        host.put(
+            marker_file.utf8_path(),
+            &pinned_marker_path(host, run_id),
+            SyncOptions::default(),
+        );
+    }
+
+    Ok(())
+}
+
+pub fn unpin_run(host: &dyn Host, local_host: &dyn Host, run_id: &RunID) -> Result<()> {
+    for host in marker_hosts(host, local_host, run_id)? {
+        host.remove_file(&pinned_marker_path(host, run_id))
+            .context(format!("failed to remove pinned marker from {}", host.id()))?;
+    }
+
+    Ok(())
+}
+
+fn marker_hosts<'h>(
+    host: &'h dyn Host,
+    local_host: &'h dyn Host,
+    run_id: &RunID,
+) -> Result<Vec<&'h dyn Host>> {
+    let mut hosts = vec![host];
+    if !host.is_local() && local_host.exists(&run_id.path(local_host.output_base_dir_path()))? {
+        hosts.push(local_host);
+    }
+
+    Ok(hosts)
+}
@@ -0,0 +1,197 @@
+use crate::host::{Host, RunID};
+use anyhow::{anyhow, Context, Result};
+use camino::Utf8PathBuf as PathBuf;
+use std::collections::BTreeMap;
+
+const MAX_RUN_DIR_FILE_SIZE: u64 = 4096;
+const MAX_LOG_FILE_SIZE: u64 = 64 * 1024 * 1024;
+const SNAKEMAKE_LOG_DIR_PATH: &str = ".snakemake/log";
+const SHOWN_FAILURE_COUNT: usize = 5;
+
+struct JobFailure {
+    rule: String,
+    job_id: u64,
+    log: Option<String>,
+}
+
+/// Progress of a snakemake workflow as reported in its log
+pub struct SnakemakeProgress {
+    completed_job_count: u64,
+    total_job_count: Option<u64>,
+    /// Rules of the started jobs which have neither finished nor failed yet, by job id
+    running_jobs: BTreeMap<u64, String>,
+    failures: Vec<JobFailure>,
+}
+
+/// Read the most recent snakemake log of `run_id`, which is looked for in the run directory
+/// first and in the run output second, since either can be the working directory of snakemake
+pub fn read_snakemake_log(host: &dyn Host, run_id: &RunID) -> Result<String> {
+    let run_dir_path = host
+        .read_file_head(
+            &host.run_dir_file_destination_path(run_id),
+            MAX_RUN_DIR_FILE_SIZE,
+        )
+        .ok()
+        .map(|content| PathBuf::from(String::from_utf8_lossy(&content).trim()));
+    let log_dir_path = run_dir_path
+        .into_iter()
+        .chain(std::iter::once(run_id.path(host.output_base_dir_path())))
+        .map(|path| path.join(SNAKEMAKE_LOG_DIR_PATH))
+        .find(|path| host.list_dir(path).is_ok())
+        .ok_or(anyhow!(
+            "found no {SNAKEMAKE_LOG_DIR_PATH} in the run directory or the run output of {run_id}"
+        ))?;
+
+    // log files are named by their creation time, so the greatest name is the most recent
+    let log_file_name = host
+        .list_dir(&log_dir_path)?
+        .into_iter()
+        .filter(|entry| !entry.is_dir && entry.name.ends_with(".snakemake.log"))
+        .map(|entry| entry.name)
+        .max()
+        .ok_or(anyhow!("found no snakemake log in {log_dir_path}"))?;
+    let log_file_path = log_dir_path.join(log_file_name);
+
+    let content = host
+        .read_file_head(&log_file_path, MAX_LOG_FILE_SIZE)
+        .context(format!("failed to read {log_file_path}"))?;
+    Ok(String::from_utf8_lossy(&content).into_owned())
+}
+
+impl SnakemakeProgress {
+    pub fn parse(log: &str) -> Self {
+        let mut progress = SnakemakeProgress {
+            completed_job_count: 0,
+            total_job_count: None,
+            running_jobs: BTreeMap::new(),
+            failures: Vec::new(),
+        };
+
+        // rule of the job block currently being read and whether the block reports an error
+        let mut current_rule: Option<(String, bool)> = None;
+        // whether the lines being read belong to the error block of the last failure
+        let mut in_failure = false;
+        for line in log.lines() {
+            let line = line.trim();
+
+            // error blocks end with a blank line, while job blocks start with a timestamp
+            if line.is_empty() || line.starts_with('[') {
+                in_failure = false;
+                continue;
+            }
+
+            if let Some(log_path) = line.strip_prefix("log: ") {
+                if let (true, Some(failure)) = (in_failure, progress.failures.last_mut()) {
+                    failure.log = Some(
+                        log_path
+                            .trim_end_matches(" (check log file(s) for error details)")
+                            .to_owned(),
+                    );
+                }
+            } else if let Some(rule) = line
+                .strip_prefix("rule ")
+                .or_else(|| line.strip_prefix("localrule "))
+                .or_else(|| line.strip_prefix("checkpoint "))
+                .and_then(|rule| rule.strip_suffix(':'))
+            {
+                current_rule = Some((rule.to_owned(), false));
+            } else if let Some(rule) = line
+                .strip_prefix("Error in rule ")
+                .and_then(|rule| rule.strip_suffix(':'))
+            {
+                current_rule = Some((rule.to_owned(), true));
+            } else if let Some(job_id) = line.strip_prefix("jobid: ") {
+                let (Some((rule, failed)), Ok(job_id)) = (current_rule.take(), job_id.parse())
+                else {
+                    continue;
+                };
+                if failed {
+                    progress.running_jobs.remove(&job_id);
+                    progress.failures.push(JobFailure {
+                        rule,
+                        job_id,
+                        log: None,
+                    });
+                    in_failure = true;
+                } else {
+                    progress.running_jobs.insert(job_id, rule);
+                }
+            } else if let Some(job_id) = finished_job_id(line) {
+                progress.running_jobs.remove(&job_id);
+            } else if let Some((completed, total)) = parse_step_count(line) {
+                progress.completed_job_count = completed;
+                progress.total_job_count = Some(total);
+            } else if let Some(total) = line.strip_prefix("total") {
+                // the last row of the job stats table, which is printed before the first job
+                if progress.total_job_count.is_none() {
+                    progress.total_job_count = total.trim().parse().ok();
+                }
+            }
+        }
+
+        progress
+    }
+}
+
+/// Job id of `Finished job 3.` or, since snakemake 8, `Finished jobid: 3 (Rule: train)`
+fn finished_job_id(line: &str) -> Option<u64> {
+    let job_id = line
+        .strip_prefix("Finished job ")
+        .map(|rest| rest.trim_end_matches('.'))
+        .or_else(|| line.strip_prefix("Finished jobid: "))?;
+    job_id.split_whitespace().next()?.parse().ok()
+}
+
+/// Completed and total jobs of `5 of 10 steps (50%) done`
+fn parse_step_count(line: &str) -> Option<(u64, u64)> {
+    let (completed, rest) = line.split_once(" of ")?;
+    let (total, rest) = rest.split_once(" steps")?;
+    if !rest.ends_with("done") {
+        return None;
+    }
+    Some((completed.parse().ok()?, total.parse().ok()?))
+}
+
+impl std::fmt::Display for SnakemakeProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.total_job_count {
+            Some(total) if total > 0 => writeln!(
+                f,
+                "progress: {} of {total} jobs ({:.0}%)",
+                self.completed_job_count,
+                self.completed_job_count as f64 / total as f64 * 100.0
+            )?,
+            _ => writeln!(
+                f,
+                "progress: {} jobs, total unknown",
+                self.completed_job_count
+            )?,
+        }
+
+        writeln!(f, "running:")?;
+        let mut running_rules = BTreeMap::<&str, usize>::new();
+        for rule in self.running_jobs.values() {
+            *running_rules.entry(rule).or_default() += 1;
+        }
+        if running_rules.is_empty() {
+            writeln!(f, "  -")?;
+        }
+        for (rule, count) in running_rules {
+            writeln!(f, "  {rule} ({count} jobs)")?;
+        }
+
+        if !self.failures.is_empty() {
+            writeln!(f, "recent failures ({} in total):", self.failures.len())?;
+        }
+        let shown_failures = self.failures.len().saturating_sub(SHOWN_FAILURE_COUNT);
+        for failure in &self.failures[shown_failures..] {
+            write!(f, "  {} (job {})", failure.rule, failure.job_id)?;
+            match &failure.log {
+                Some(log) => writeln!(f, ", log: {log}")?,
+                None => writeln!(f)?,
+            }
+        }
+
+        Ok(())
+    }
+}
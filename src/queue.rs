@@ -0,0 +1,386 @@
+use crate::cfg::{Cli, RunArgs, RunnerCommandConfig};
+use crate::host::{build_host, Host, HostPurpose, RunID};
+use crate::report::{CompletionReport, CompletionReportFormat};
+use crate::run::{expand_run_name, free_run_slots};
+use crate::GlobalConfig;
+use anyhow::{bail, Context, Result};
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use clap::Parser;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const QUEUE_DIR_PATH: &str = ".sparrow/queue";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum QueueEntryState {
+    Pending,
+    Submitted,
+    Failed,
+}
+
+impl std::fmt::Display for QueueEntryState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueueEntryState::Pending => write!(f, "pending"),
+            QueueEntryState::Submitted => write!(f, "submitted"),
+            QueueEntryState::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+/// A run waiting to be submitted with the arguments of `sparrow run`, where the run name has
+/// already been expanded when the run was queued
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct QueueEntry {
+    pub run_id: RunID,
+    pub host: String,
+    pub args: Vec<String>,
+    pub state: QueueEntryState,
+}
+
+impl QueueEntry {
+    fn log_path(&self) -> PathBuf {
+        PathBuf::from(QUEUE_DIR_PATH)
+            .join("logs")
+            .join(format!("{}-{}.log", self.run_id.group, self.run_id.name))
+    }
+}
+
+/// Load the queue, let `modify` change it and store it again, while holding a lock, so that
+/// runs can be queued while the queue is processed
+fn modify_queue<T, F: FnOnce(&mut Vec<QueueEntry>) -> Result<T>>(modify: F) -> Result<T> {
+    std::fs::create_dir_all(QUEUE_DIR_PATH)
+        .context(format!("failed to create {QUEUE_DIR_PATH}"))?;
+
+    let lock_path = PathBuf::from(QUEUE_DIR_PATH).join(".lock");
+    let lock_file = std::fs::File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .context(format!("failed to open {lock_path}"))?;
+    lock_file
+        .lock()
+        .context(format!("failed to lock {lock_path}"))?;
+
+    let queue_path = PathBuf::from(QUEUE_DIR_PATH).join("queue.json");
+    let mut entries = if queue_path.exists() {
+        let content =
+            std::fs::read_to_string(&queue_path).context(format!("failed to read {queue_path}"))?;
+        serde_json::from_str(&content).context(format!("failed to parse {queue_path}"))?
+    } else {
+        Vec::new()
+    };
+
+    let result = modify(&mut entries)?;
+
+    std::fs::write(
+        &queue_path,
+        serde_json::to_string_pretty(&entries).expect("expected queue to be serializable"),
+    )
+    .context(format!("failed to write {queue_path}"))?;
+
+    Ok(result)
+}
+
+/// Queue a run given by the arguments of `sparrow run`, expanding its run name right away
+pub fn add(mut args: Vec<String>, config: &GlobalConfig) -> Result<()> {
+    let cli = Cli::try_parse_from(
+        ["sparrow", "run"]
+            .into_iter()
+            .map(String::from)
+            .chain(args.iter().cloned()),
+    )
+    .context("found invalid arguments for `sparrow run'")?;
+    let Some(RunnerCommandConfig::Run(run_args)) = cli.command else {
+        unreachable!("expected arguments to be parsed as a run command");
+    };
+    let RunArgs {
+        run_name,
+        run_group,
+        config_dir,
+        use_previous_config,
+        host,
+        only_print_run_script,
+        reuse_run_dir,
+        ..
+    } = *run_args;
+
+    if only_print_run_script {
+        bail!("cannot queue a run that only prints its run script");
+    }
+    if reuse_run_dir.is_some() {
+        bail!("cannot queue a run that reuses a run directory");
+    }
+    let run_name = run_name.expect("expected a run name to be required without --reuse-run-dir");
+
+    let expanded_run_name = if use_previous_config {
+        if run_name.contains('{') {
+            bail!("cannot expand run name template `{run_name}' when using the previous config");
+        }
+        run_name.clone()
+    } else {
+        let config_entrypoint_path = config_dir
+            .as_deref()
+            .unwrap_or(&config.payload.config.dir)
+            .join(&config.payload.config.entrypoints[0]);
+        expand_run_name(&run_name, &config_entrypoint_path)
+            .context(format!("failed to expand run name template `{run_name}'"))?
+    };
+    replace_run_name_arg(&mut args, &run_name, &expanded_run_name);
+
+    let entry = QueueEntry {
+        run_id: RunID::new(
+            expanded_run_name,
+            run_group.unwrap_or(config.run_group.clone()),
+        ),
+        host: host
+            .or(config.default_host.clone())
+            .unwrap_or(String::from("local")),
+        args,
+        state: QueueEntryState::Pending,
+    };
+
+    modify_queue(|entries| {
+        if entries.iter().any(|queued| {
+            queued.state == QueueEntryState::Pending
+                && queued.run_id == entry.run_id
+                && queued.host == entry.host
+        }) {
+            bail!("{} is already queued for {}", entry.run_id, entry.host);
+        }
+
+        println!("Queued {} for {}", entry.run_id, entry.host);
+        entries.push(entry);
+        Ok(())
+    })
+}
+
+/// Replace the value of the run name argument in `args` by its expansion, so that templates
+/// are not expanded a second time on submission
+fn replace_run_name_arg(args: &mut [String], run_name: &str, expanded_run_name: &str) {
+    let mut previous_arg: Option<String> = None;
+    for arg in args.iter_mut() {
+        let is_value =
+            arg == run_name && matches!(previous_arg.as_deref(), Some("-n") | Some("--run-name"));
+        let replacement = if is_value {
+            Some(expanded_run_name.to_owned())
+        } else if *arg == format!("--run-name={run_name}") {
+            Some(format!("--run-name={expanded_run_name}"))
+        } else if *arg == format!("-n{run_name}") {
+            Some(format!("-n{expanded_run_name}"))
+        } else {
+            None
+        };
+
+        previous_arg = Some(arg.clone());
+        if let Some(replacement) = replacement {
+            *arg = replacement;
+            return;
+        }
+    }
+}
+
+pub fn list() -> Result<()> {
+    modify_queue(|entries| {
+        for entry in entries.iter() {
+            println!("{:<9}  {:<12}  {}", entry.state, entry.host, entry.run_id);
+        }
+        Ok(())
+    })
+}
+
+/// Submit pending runs in the order they were queued, such that no more than `max_parallel` of
+/// the queued runs are running at the same time and no host exceeds its `max_concurrent_runs`,
+/// where runs for a host at its limit wait while later runs for other hosts go ahead, checking
+/// again every `interval`; once all runs submitted this way finished, report their outcome and
+/// also write it to `report_path`
+pub fn start(
+    max_parallel: usize,
+    interval: Duration,
+    report_path: Option<&Path>,
+    config: GlobalConfig,
+) -> Result<()> {
+    let sparrow_path =
+        std::env::current_exe().context("failed to locate the sparrow executable")?;
+    let report_format = report_path
+        .map(CompletionReportFormat::from_path)
+        .transpose()?;
+
+    let mut hosts = HashMap::<String, Box<dyn Host>>::new();
+    // runs on the local host execute in the foreground of their submission process
+    // together with the index of their entry in the batch
+    let mut local_runs = Vec::<(std::process::Child, usize)>::new();
+    // entries processed by this invocation with their submission log and whether submitting, or
+    // for local runs submitting and running, failed
+    let mut batch = Vec::<(RunID, String, PathBuf, bool)>::new();
+    loop {
+        local_runs.retain_mut(|(child, batch_index)| match child.try_wait() {
+            Ok(None) => true,
+            Ok(Some(status)) => {
+                batch[*batch_index].3 = !status.success();
+                false
+            }
+            Err(_) => false,
+        });
+
+        let (pending_entries, submitted_entries) = modify_queue(|entries| {
+            let select = |state| {
+                entries
+                    .iter()
+                    .filter(|entry| entry.state == state)
+                    .map(|entry| (entry.run_id.clone(), entry.host.clone(), entry.args.clone()))
+                    .collect::<Vec<_>>()
+            };
+            Ok((
+                select(QueueEntryState::Pending),
+                select(QueueEntryState::Submitted),
+            ))
+        })?;
+
+        let mut running_count = local_runs.len();
+        let mut batch_is_running = false;
+        for (run_id, host_id, _) in &submitted_entries {
+            let host = get_host(&mut hosts, host_id, &config)?;
+            // sessions are kept open after runs exit, so a recorded exit code tells they finished
+            if !host.is_local()
+                && host.running_runs().contains(run_id)
+                && host.recorded_exit_code(run_id).is_none()
+            {
+                running_count += 1;
+                batch_is_running |= batch.iter().any(|(batch_run_id, batch_host_id, _, _)| {
+                    batch_run_id == run_id && batch_host_id == host_id
+                });
+            }
+        }
+
+        if pending_entries.is_empty() && local_runs.is_empty() && !batch_is_running {
+            println!("Queue is empty");
+            if batch.is_empty() {
+                return Ok(());
+            }
+
+            let mut report = CompletionReport::default();
+            for (run_id, host_id, submission_log_path, submission_failed) in &batch {
+                report.add(
+                    get_host(&mut hosts, host_id, &config)?,
+                    run_id,
+                    submission_failed.then_some(submission_log_path.as_path()),
+                );
+            }
+            print!("\n{}", report.render(CompletionReportFormat::Table));
+            if let (Some(path), Some(format)) = (report_path, report_format) {
+                std::fs::write(path, report.render(format))
+                    .context(format!("failed to write {path}"))?;
+            }
+            return Ok(());
+        }
+
+        let mut free_slots = max_parallel.saturating_sub(running_count);
+        // free slots of hosts with a `max_concurrent_runs`, which are looked up once per round
+        let mut free_host_slots = HashMap::<String, Option<usize>>::new();
+        for (run_id, host_id, args) in pending_entries {
+            if free_slots == 0 {
+                break;
+            }
+            let host = get_host(&mut hosts, &host_id, &config)?;
+            let free_host_slots = free_host_slots
+                .entry(host_id.clone())
+                .or_insert_with(|| free_run_slots(host, &config.remote_hosts));
+            if *free_host_slots == Some(0) {
+                continue;
+            }
+            free_slots -= 1;
+            if let Some(free_host_slots) = free_host_slots {
+                *free_host_slots -= 1;
+            }
+
+            let entry = QueueEntry {
+                run_id,
+                host: host_id,
+                args,
+                state: QueueEntryState::Pending,
+            };
+
+            let log_path = entry.log_path();
+            let log_parent_path = log_path
+                .parent()
+                .expect("expected log path to have a parent");
+            std::fs::create_dir_all(log_parent_path)
+                .context(format!("failed to create {log_parent_path}"))?;
+            let log_file =
+                std::fs::File::create(&log_path).context(format!("failed to create {log_path}"))?;
+
+            println!("Submitting {} to {}...", entry.run_id, entry.host);
+            let mut submission_command = std::process::Command::new(&sparrow_path);
+            submission_command
+                .arg("run")
+                .arg("--no-config-review")
+                .arg("--detach")
+                .arg("--yes")
+                .args(&entry.args)
+                .stdin(std::process::Stdio::null())
+                .stdout(
+                    log_file
+                        .try_clone()
+                        .context(format!("failed to open {log_path}"))?,
+                )
+                .stderr(log_file);
+            let mut child = submission_command
+                .spawn()
+                .context(format!("failed to spawn `{submission_command:?}`"))?;
+
+            let state = if host.is_local() {
+                local_runs.push((child, batch.len()));
+                QueueEntryState::Submitted
+            } else {
+                let status = child
+                    .wait()
+                    .context(format!("failed to wait for `{submission_command:?}`"))?;
+                if status.success() {
+                    QueueEntryState::Submitted
+                } else {
+                    eprintln!(
+                        "warning: submitting {} failed, see {log_path}",
+                        entry.run_id
+                    );
+                    QueueEntryState::Failed
+                }
+            };
+
+            batch.push((
+                entry.run_id.clone(),
+                entry.host.clone(),
+                log_path,
+                state == QueueEntryState::Failed,
+            ));
+            modify_queue(|entries| {
+                if let Some(queued) = entries.iter_mut().find(|queued| {
+                    queued.state == QueueEntryState::Pending
+                        && queued.run_id == entry.run_id
+                        && queued.host == entry.host
+                }) {
+                    queued.state = state;
+                }
+                Ok(())
+            })?;
+        }
+
+        std::thread::sleep(interval);
+    }
+}
+
+fn get_host<'h>(
+    hosts: &'h mut HashMap<String, Box<dyn Host>>,
+    host_id: &str,
+    config: &GlobalConfig,
+) -> Result<&'h dyn Host> {
+    if !hosts.contains_key(host_id) {
+        let host = build_host(host_id, config, HostPurpose::Submission, false)
+            .context(format!("failed to build {host_id} as host"))?;
+        hosts.insert(host_id.to_owned(), host);
+    }
+
+    Ok(&*hosts[host_id])
+}
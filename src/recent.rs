@@ -0,0 +1,136 @@
+use crate::host::local::cache_dir_path;
+use crate::host::RunID;
+use crate::utils::{select_interactively, select_interactively_multiple};
+use anyhow::{Context, Result};
+use camino::Utf8PathBuf as PathBuf;
+use std::time::SystemTime;
+
+/// Number of runs per host that are remembered as recently used
+const MAX_RECENT_RUNS: usize = 100;
+
+/// A run that was submitted or selected on a host, remembered to offer it first in selections
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct RecentRun {
+    pub run_id: RunID,
+    pub time: String,
+}
+
+fn recent_runs_file_path(host_id: &str) -> PathBuf {
+    cache_dir_path()
+        .join("recent")
+        .join(format!("{host_id}.json"))
+}
+
+/// Runs recently used on the host `host_id`, most recent first
+pub fn recent_runs(host_id: &str) -> Result<Vec<RecentRun>> {
+    let recent_runs_path = recent_runs_file_path(host_id);
+    if !recent_runs_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = std::fs::read_to_string(&recent_runs_path)
+        .context(format!("failed to read {recent_runs_path}"))?;
+    serde_json::from_str(&content).context(format!("failed to parse {recent_runs_path}"))
+}
+
+/// Hosts with recently used runs
+pub fn hosts_with_recent_runs() -> Result<Vec<String>> {
+    let recent_dir_path = cache_dir_path().join("recent");
+    if !recent_dir_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut host_ids = std::fs::read_dir(&recent_dir_path)
+        .context(format!("failed to read {recent_dir_path}"))?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|file_name| file_name.strip_suffix(".json"))
+                .map(str::to_owned)
+        })
+        .collect::<Vec<_>>();
+    host_ids.sort();
+    Ok(host_ids)
+}
+
+/// Remember `run_ids` as the most recently used runs on the host `host_id`, which only affects
+/// the order of selections, so failures are reported as warnings
+pub fn record_recent_runs(host_id: &str, run_ids: &[&RunID]) {
+    let time = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+    let mut recent = recent_runs(host_id).unwrap_or_default();
+    recent.retain(|recent_run| !run_ids.contains(&&recent_run.run_id));
+    recent.splice(
+        0..0,
+        run_ids.iter().map(|run_id| RecentRun {
+            run_id: (*run_id).clone(),
+            time: time.clone(),
+        }),
+    );
+    recent.truncate(MAX_RECENT_RUNS);
+    write_recent_runs(host_id, &recent);
+}
+
+/// Replace `run_id` by `new_run_id` in the recently used runs on the host `host_id`, keeping its
+/// position, after the run was renamed
+pub fn rename_recent_run(host_id: &str, run_id: &RunID, new_run_id: &RunID) {
+    let mut recent = recent_runs(host_id).unwrap_or_default();
+    if !recent.iter().any(|recent_run| recent_run.run_id == *run_id) {
+        return;
+    }
+    for recent_run in recent.iter_mut() {
+        if recent_run.run_id == *run_id {
+            recent_run.run_id = new_run_id.clone();
+        }
+    }
+    write_recent_runs(host_id, &recent);
+}
+
+fn write_recent_runs(host_id: &str, recent: &[RecentRun]) {
+    let recent_runs_path = recent_runs_file_path(host_id);
+    let result = recent_runs_path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| {
+            std::fs::write(
+                &recent_runs_path,
+                serde_json::to_string(recent).expect("expected recent runs to be serializable"),
+            )
+        });
+    if let Err(err) = result {
+        eprintln!("warning: failed to record recent runs in {recent_runs_path}: {err}");
+    }
+}
+
+/// `run_ids` with the runs recently used on the host `host_id` first, most recent first, followed
+/// by all other runs in their original order
+fn sort_by_recency(host_id: &str, run_ids: &[RunID]) -> Vec<RunID> {
+    let recent = recent_runs(host_id).unwrap_or_default();
+    let mut sorted_run_ids = run_ids.to_vec();
+    sorted_run_ids.sort_by_key(|run_id| {
+        recent
+            .iter()
+            .position(|recent_run| recent_run.run_id == *run_id)
+            .unwrap_or(usize::MAX)
+    });
+    sorted_run_ids
+}
+
+/// Select one of `run_ids` of the host `host_id` interactively, offering recently used runs
+/// first, and remember the selection as recently used
+pub fn select_run(host_id: &str, run_ids: &[RunID], prompt: &str) -> Result<RunID> {
+    let run_id = select_interactively(&sort_by_recency(host_id, run_ids), prompt)?.clone();
+    record_recent_runs(host_id, &[&run_id]);
+    Ok(run_id)
+}
+
+/// Select any number of `run_ids` of the host `host_id` like [`select_run`]
+pub fn select_runs(host_id: &str, run_ids: &[RunID], prompt: &str) -> Result<Vec<RunID>> {
+    let run_ids = select_interactively_multiple(&sort_by_recency(host_id, run_ids), prompt)?
+        .into_iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    record_recent_runs(host_id, &run_ids.iter().collect::<Vec<_>>());
+    Ok(run_ids)
+}
@@ -0,0 +1,98 @@
+use crate::host::{Host, RunID};
+use crate::notes::append_note;
+use crate::recent::rename_recent_run;
+use crate::run::record_parent;
+use anyhow::{bail, Context, Result};
+
+/// Name of `run_id` after renaming it to `name` and moving it to `group`, where either defaults
+/// to the current one
+pub fn renamed_run_id(run_id: &RunID, name: Option<&str>, group: Option<&str>) -> Result<RunID> {
+    let new_run_id = RunID::new(
+        name.unwrap_or(run_id.name.as_str()),
+        group.unwrap_or(run_id.group.as_str()),
+    );
+    for part in [&new_run_id.name, &new_run_id.group] {
+        if part.is_empty() || part.contains('/') || part.starts_with('.') {
+            bail!("invalid run name or group `{part}', expected no `/' and no leading `.'");
+        }
+    }
+    if new_run_id == *run_id {
+        bail!("{run_id} already has this name");
+    }
+
+    Ok(new_run_id)
+}
+
+/// Rename `run_id` to `new_run_id` on `host`, which moves its output, renames its session if it
+/// is still open and updates the children that recorded it as their parent, and do the same for
+/// the synced copy on `local_host` if there is one
+pub fn rename_run(
+    host: &dyn Host,
+    local_host: &dyn Host,
+    run_id: &RunID,
+    new_run_id: &RunID,
+) -> Result<()> {
+    // running runs can only be told for remote hosts
+    if !host.is_local()
+        && host.running_runs().contains(run_id)
+        && host.recorded_exit_code(run_id).is_none()
+    {
+        bail!("refusing to rename {run_id}, since it is still running");
+    }
+    let has_local_copy =
+        !host.is_local() && local_host.exists(&run_id.path(local_host.output_base_dir_path()))?;
+    let mut hosts = vec![host];
+    if has_local_copy {
+        hosts.push(local_host);
+    }
+    for host in &hosts {
+        if host.exists(&new_run_id.path(host.output_base_dir_path()))? {
+            bail!(
+                "{new_run_id} exists already on {}, refusing to overwrite it",
+                host.id()
+            );
+        }
+    }
+
+    for host in hosts {
+        move_run_output(host, run_id, new_run_id)
+            .context(format!("failed to move {run_id} on {}", host.id()))?;
+        rename_recent_run(host.id(), run_id, new_run_id);
+        println!("Renamed {run_id} to {new_run_id} on {}", host.id());
+    }
+    if !host.is_local() {
+        host.rename_session(run_id, new_run_id)?;
+    }
+    if let Err(err) = append_note(host, new_run_id, &format!("renamed from {run_id}")) {
+        eprintln!("warning: failed to note the rename in the notes of {new_run_id}: {err:#}");
+    }
+
+    Ok(())
+}
+
+fn move_run_output(host: &dyn Host, run_id: &RunID, new_run_id: &RunID) -> Result<()> {
+    let children = host
+        .run_parents()?
+        .into_iter()
+        .filter(|(_, parent_id)| parent_id == run_id)
+        .map(|(child_id, _)| child_id)
+        .collect::<Vec<_>>();
+
+    host.create_dir_all(&host.output_base_dir_path().join(&new_run_id.group));
+    host.move_path(
+        &run_id.path(host.output_base_dir_path()),
+        &new_run_id.path(host.output_base_dir_path()),
+    )?;
+    // moving the last run out of a group leaves nothing in it that would be listed as a run
+    let group_path = host.output_base_dir_path().join(&run_id.group);
+    if new_run_id.group != run_id.group && host.list_dir(&group_path)?.is_empty() {
+        host.remove_dir_all(&group_path)?;
+    }
+
+    for child_id in children {
+        record_parent(host, new_run_id, &child_id)
+            .context(format!("failed to update the parent of {child_id}"))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,389 @@
+use crate::host::local::result_cache_dir_path;
+use crate::host::rsync::SyncOptions;
+use crate::host::{ensure_run_group_exists, Host, RunID};
+use anyhow::{bail, Context, Result};
+use camino::Utf8Path as Path;
+use std::collections::BTreeSet;
+
+pub enum SweepReportFormat {
+    Table,
+    Markdown,
+    Csv,
+}
+
+impl SweepReportFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension() {
+            Some("csv") => Ok(SweepReportFormat::Csv),
+            Some("md") => Ok(SweepReportFormat::Markdown),
+            _ => bail!("cannot infer report format from {path}, expected a .csv or .md file"),
+        }
+    }
+}
+
+pub struct SweepReport {
+    metric_names: Vec<String>,
+    rows: Vec<(RunID, serde_json::Map<String, serde_json::Value>)>,
+}
+
+impl SweepReport {
+    /// Collect the metrics file of every run in `run_group` on `host`, downloading only the
+    /// metrics files into the local result cache for remote hosts
+    pub fn collect(host: &dyn Host, run_group: &str, metrics_path: &Path) -> Result<Self> {
+        let run_ids = host
+            .runs()
+            .context(format!("failed to obtain runs from {}", host.id()))?;
+        ensure_run_group_exists(run_group, &run_ids)
+            .context(format!("failed to find runs on {}", host.id()))?;
+        let run_ids = run_ids
+            .into_iter()
+            .filter(|run_id| run_id.group == run_group)
+            .collect::<Vec<_>>();
+
+        let mut rows = Vec::new();
+        for run_id in run_ids {
+            let local_metrics_path = if host.is_local() {
+                run_id.path(host.output_base_dir_path()).join(metrics_path)
+            } else {
+                let local_metrics_path = run_id
+                    .path(result_cache_dir_path(host.id()))
+                    .join(metrics_path);
+                let local_parent_path = local_metrics_path
+                    .parent()
+                    .expect("expected metrics path to have a parent");
+                std::fs::create_dir_all(local_parent_path)
+                    .context(format!("failed to create {local_parent_path}"))?;
+                let host_metrics_path = run_id.path(host.output_base_dir_path()).join(metrics_path);
+                if host.exists(&host_metrics_path)? {
+                    host.try_get(
+                        &host_metrics_path,
+                        local_parent_path,
+                        SyncOptions::default().quiet(),
+                    )
+                    .context(format!("failed to download {metrics_path} of {run_id}"))?;
+                }
+                local_metrics_path
+            };
+
+            if !local_metrics_path.exists() {
+                eprintln!("warning: {run_id} has no {metrics_path}, skipping");
+                continue;
+            }
+
+            rows.push((run_id, read_metrics(&local_metrics_path)?));
+        }
+
+        let metric_names = rows
+            .iter()
+            .flat_map(|(_, metrics)| metrics.keys().cloned())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        Ok(SweepReport { metric_names, rows })
+    }
+
+    pub fn sort_by(&mut self, metric_name: &str, descending: bool) -> Result<()> {
+        if !self.metric_names.iter().any(|name| name == metric_name) {
+            bail!(
+                "cannot sort by `{metric_name}', available metrics are {}",
+                self.metric_names.join(", ")
+            );
+        }
+
+        // runs without a numeric value for the metric always go last
+        self.rows.sort_by(|(_, lhs), (_, rhs)| {
+            let lhs = lhs.get(metric_name).and_then(|value| value.as_f64());
+            let rhs = rhs.get(metric_name).and_then(|value| value.as_f64());
+            match (lhs, rhs) {
+                (Some(lhs), Some(rhs)) if descending => rhs.total_cmp(&lhs),
+                (Some(lhs), Some(rhs)) => lhs.total_cmp(&rhs),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => std::cmp::Ordering::Equal,
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn render(&self, format: SweepReportFormat) -> String {
+        let header = std::iter::once(String::from("run"))
+            .chain(self.metric_names.iter().cloned())
+            .collect::<Vec<_>>();
+        let rows = self
+            .rows
+            .iter()
+            .map(|(run_id, metrics)| {
+                std::iter::once(run_id.name.clone())
+                    .chain(
+                        self.metric_names
+                            .iter()
+                            .map(|name| match metrics.get(name) {
+                                Some(serde_json::Value::String(value)) => value.clone(),
+                                Some(value) => value.to_string(),
+                                None => String::new(),
+                            }),
+                    )
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        match format {
+            SweepReportFormat::Csv => std::iter::once(&header)
+                .chain(rows.iter())
+                .map(|row| {
+                    row.iter()
+                        .map(|field| csv_field(field))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                        + "\n"
+                })
+                .collect(),
+            SweepReportFormat::Markdown => {
+                let separator = vec![String::from("---"); header.len()];
+                std::iter::once(&header)
+                    .chain(std::iter::once(&separator))
+                    .chain(rows.iter())
+                    .map(|row| format!("| {} |\n", row.join(" | ")))
+                    .collect()
+            }
+            SweepReportFormat::Table => {
+                let widths = (0..header.len())
+                    .map(|column| {
+                        std::iter::once(&header)
+                            .chain(rows.iter())
+                            .map(|row| row[column].len())
+                            .max()
+                            .unwrap_or(0)
+                    })
+                    .collect::<Vec<_>>();
+                std::iter::once(&header)
+                    .chain(rows.iter())
+                    .map(|row| {
+                        row.iter()
+                            .zip(widths.iter())
+                            .map(|(cell, width)| format!("{cell:<width$}"))
+                            .collect::<Vec<_>>()
+                            .join("  ")
+                            .trim_end()
+                            .to_owned()
+                            + "\n"
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// How a run of a batch ended, as far as it can be told from its submission and exit code
+pub enum RunOutcome {
+    Succeeded,
+    Failed(i32),
+    SubmissionFailed,
+    /// The run exited without recording its exit code, e.g. because it was killed
+    Unknown,
+}
+
+impl std::fmt::Display for RunOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunOutcome::Succeeded => write!(f, "succeeded"),
+            RunOutcome::Failed(exit_code) => write!(f, "failed with exit code {exit_code}"),
+            RunOutcome::SubmissionFailed => write!(f, "submission failed"),
+            RunOutcome::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+pub enum CompletionReportFormat {
+    Table,
+    Markdown,
+    Junit,
+}
+
+impl CompletionReportFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension() {
+            Some("md") => Ok(CompletionReportFormat::Markdown),
+            Some("xml") => Ok(CompletionReportFormat::Junit),
+            _ => bail!("cannot infer report format from {path}, expected a .md or .xml file"),
+        }
+    }
+}
+
+struct CompletionRow {
+    run_id: RunID,
+    host_id: String,
+    outcome: RunOutcome,
+    log_links: Vec<String>,
+}
+
+/// Which runs of a batch, e.g. a processed queue, succeeded or failed, with links to their logs
+#[derive(Default)]
+pub struct CompletionReport {
+    rows: Vec<CompletionRow>,
+}
+
+impl CompletionReport {
+    /// Add a finished run of `host`, whose outcome is read from the exit code it recorded, or
+    /// whose submission failed with the log at `failed_submission_log_path` before it could run
+    pub fn add(
+        &mut self,
+        host: &dyn Host,
+        run_id: &RunID,
+        failed_submission_log_path: Option<&Path>,
+    ) {
+        let outcome = match (host.recorded_exit_code(run_id), failed_submission_log_path) {
+            (Some(0), _) => RunOutcome::Succeeded,
+            (Some(exit_code), _) => RunOutcome::Failed(exit_code),
+            (None, Some(log_path)) => {
+                self.rows.push(CompletionRow {
+                    run_id: run_id.clone(),
+                    host_id: host.id().to_owned(),
+                    outcome: RunOutcome::SubmissionFailed,
+                    log_links: vec![log_path.to_string()],
+                });
+                return;
+            }
+            (None, None) => RunOutcome::Unknown,
+        };
+
+        let output_path = run_id.path(host.output_base_dir_path());
+        let log_links = host
+            .log_file_paths(run_id)
+            .into_iter()
+            .map(|log_file_path| {
+                let log_file_path = output_path.join(log_file_path);
+                if host.is_local() {
+                    log_file_path.to_string()
+                } else {
+                    format!("{}:{log_file_path}", host.hostname())
+                }
+            })
+            .collect();
+
+        self.rows.push(CompletionRow {
+            run_id: run_id.clone(),
+            host_id: host.id().to_owned(),
+            outcome,
+            log_links,
+        });
+    }
+
+    pub fn render(&self, format: CompletionReportFormat) -> String {
+        match format {
+            CompletionReportFormat::Table => {
+                let run_width = self
+                    .rows
+                    .iter()
+                    .map(|row| row.run_id.to_string().len())
+                    .chain(std::iter::once("run".len()))
+                    .max()
+                    .unwrap_or(0);
+                let host_width = self
+                    .rows
+                    .iter()
+                    .map(|row| row.host_id.len())
+                    .chain(std::iter::once("host".len()))
+                    .max()
+                    .unwrap_or(0);
+
+                std::iter::once(format!(
+                    "{:<run_width$}  {:<host_width$}  outcome\n",
+                    "run", "host"
+                ))
+                .chain(self.rows.iter().flat_map(|row| {
+                    std::iter::once(format!(
+                        "{:<run_width$}  {:<host_width$}  {}\n",
+                        row.run_id.to_string(),
+                        row.host_id,
+                        row.outcome
+                    ))
+                    .chain(row.log_links.iter().map(|link| format!("    {link}\n")))
+                }))
+                .collect()
+            }
+            CompletionReportFormat::Markdown => std::iter::once(String::from(
+                "| run | host | outcome | logs |\n| --- | --- | --- | --- |\n",
+            ))
+            .chain(self.rows.iter().map(|row| {
+                format!(
+                    "| {} | {} | {} | {} |\n",
+                    row.run_id,
+                    row.host_id,
+                    row.outcome,
+                    row.log_links
+                        .iter()
+                        .map(|link| format!("`{link}`"))
+                        .collect::<Vec<_>>()
+                        .join("<br>")
+                )
+            }))
+            .collect(),
+            CompletionReportFormat::Junit => {
+                let failure_count = self
+                    .rows
+                    .iter()
+                    .filter(|row| !matches!(row.outcome, RunOutcome::Succeeded))
+                    .count();
+                let test_cases = self.rows.iter().map(|row| {
+                    let name = format!(
+                        "    <testcase classname=\"{}\" name=\"{}\"",
+                        escape_xml(&row.run_id.group),
+                        escape_xml(&row.run_id.name)
+                    );
+                    let logs = escape_xml(&row.log_links.join("\n"));
+                    match row.outcome {
+                        RunOutcome::Succeeded => format!(
+                            "{name}>\n      <system-out>{logs}</system-out>\n    </testcase>\n"
+                        ),
+                        _ => format!(
+                            "{name}>\n      <failure message=\"{} on {}\">{logs}</failure>\n    \
+                            </testcase>\n",
+                            escape_xml(&row.outcome.to_string()),
+                            escape_xml(&row.host_id)
+                        ),
+                    }
+                });
+
+                std::iter::once(format!(
+                    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                    <testsuites>\n  \
+                    <testsuite name=\"sparrow\" tests=\"{}\" failures=\"{failure_count}\">\n",
+                    self.rows.len()
+                ))
+                .chain(test_cases)
+                .chain(std::iter::once(String::from(
+                    "  </testsuite>\n</testsuites>\n",
+                )))
+                .collect()
+            }
+        }
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Quote `field` for a CSV file as RFC 4180 describes, if it contains a separator, a quote or a
+/// line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn read_metrics(path: &Path) -> Result<serde_json::Map<String, serde_json::Value>> {
+    let content = std::fs::read_to_string(path).context(format!("failed to read {path}"))?;
+    match serde_json::from_str(&content).context(format!("failed to parse {path}"))? {
+        serde_json::Value::Object(metrics) => Ok(metrics),
+        _ => bail!("expected {path} to contain a json object of metrics"),
+    }
+}
@@ -0,0 +1,122 @@
+use crate::cfg::{MetadataSyncConfig, ResultsConfig, RunOutputSyncContent, RunOutputSyncOptions};
+use crate::utils::glob_matches;
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+
+#[derive(Clone, PartialEq)]
+pub struct RunResult {
+    pub name: String,
+    pub path: PathBuf,
+    pub open_with: Option<String>,
+}
+
+impl std::fmt::Display for RunResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.name == self.path.as_str() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} ({})", self.name, self.path)
+        }
+    }
+}
+
+pub fn build_run_results(results_config: &ResultsConfig) -> Vec<RunResult> {
+    match results_config {
+        ResultsConfig::Paths(paths) => paths
+            .iter()
+            .map(|path| RunResult {
+                name: path.to_string(),
+                path: path.clone(),
+                open_with: None,
+            })
+            .collect(),
+        ResultsConfig::Named(named_results) => {
+            let mut results = named_results
+                .iter()
+                .map(|(name, result_config)| RunResult {
+                    name: name.clone(),
+                    path: result_config.path.clone(),
+                    open_with: result_config.open_with.clone(),
+                })
+                .collect::<Vec<_>>();
+            results.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+            results
+        }
+    }
+}
+
+/// Build rsync include patterns that select only the subtrees of the given result paths,
+/// including all of their parent directories so that rsync descends into them
+pub fn result_sync_includes(result_paths: &Vec<&Path>) -> Vec<String> {
+    let mut includes = Vec::new();
+    for result_path in result_paths {
+        let result_path = result_path.as_str().trim_matches('/');
+
+        let mut parent_path = String::new();
+        let components = result_path.split('/').collect::<Vec<_>>();
+        for component in &components[..components.len() - 1] {
+            parent_path += &format!("/{component}");
+            let include = format!("{parent_path}/");
+            if !includes.contains(&include) {
+                includes.push(include);
+            }
+        }
+        includes.push(format!("/{result_path}"));
+        includes.push(format!("/{result_path}/***"));
+    }
+
+    includes
+}
+
+const DEFAULT_METADATA_MAX_SIZE: &str = "1M";
+const DEFAULT_METADATA_PATTERNS: &[&str] = &[
+    "*.json", "*.yaml", "*.yml", "*.toml", "*.log", "*.txt", "*.csv", "*.md",
+];
+
+/// Build rsync include patterns that select files matching one of the metadata patterns anywhere
+/// in the run output, including all directories so that rsync descends into them, together with
+/// the maximum size of the files to sync
+pub fn metadata_sync_includes(
+    metadata_config: Option<&MetadataSyncConfig>,
+) -> (Vec<String>, String) {
+    let patterns = match metadata_config.and_then(|config| config.patterns.as_ref()) {
+        Some(patterns) => patterns.clone(),
+        None => DEFAULT_METADATA_PATTERNS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect(),
+    };
+    let max_size = metadata_config
+        .and_then(|config| config.max_size.clone())
+        .unwrap_or(String::from(DEFAULT_METADATA_MAX_SIZE));
+
+    let includes = std::iter::once(String::from("*/"))
+        .chain(patterns)
+        .collect();
+    (includes, max_size)
+}
+
+/// Rsync exclude patterns when syncing `content` of a run of `group`, where the excludes of the
+/// longest group glob matching `group` take precedence over the global ones
+pub fn sync_excludes(
+    sync_options: &RunOutputSyncOptions,
+    group: &str,
+    content: &RunOutputSyncContent,
+) -> Vec<String> {
+    let group_options = sync_options
+        .groups
+        .iter()
+        .flatten()
+        .filter(|(pattern, _)| glob_matches(pattern, group))
+        .max_by_key(|(pattern, _)| (pattern.len(), *pattern))
+        .map(|(_, group_options)| group_options);
+
+    match content {
+        RunOutputSyncContent::Results => group_options
+            .and_then(|group_options| group_options.result_excludes.clone())
+            .unwrap_or(sync_options.result_excludes.clone()),
+        RunOutputSyncContent::NecessaryForReproduction => group_options
+            .and_then(|group_options| group_options.reproduce_excludes.clone())
+            .unwrap_or(sync_options.reproduce_excludes.clone()),
+        RunOutputSyncContent::Metadata => Vec::new(),
+    }
+}
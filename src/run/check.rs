@@ -0,0 +1,200 @@
+use super::default::{
+    template_context_schema, template_environment, RUN_POST_SCRIPT_TEMPLATE_PATH,
+    RUN_PRE_SCRIPT_TEMPLATE_PATH, RUN_SCRIPT_TEMPLATE_PATH,
+};
+use anyhow::{bail, Context, Result};
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use serde_json::Value;
+
+const TEMPLATES_DIR_PATH: &str = ".sparrow/templates";
+
+/// Variable paths which the template environment provides rather than the template context
+const ENVIRONMENT_PATHS: [&str; 1] = ["host.module_load"];
+
+/// Resolve references and optional values in the JSON schema `schema`, whose definitions are in
+/// `root`, so that its fields can be looked up
+fn resolve_schema<'s>(schema: &'s Value, root: &'s Value) -> &'s Value {
+    if let Some(name) = schema
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|reference| reference.strip_prefix("#/$defs/"))
+    {
+        if let Some(definition) = root.get("$defs").and_then(|defs| defs.get(name)) {
+            return resolve_schema(definition, root);
+        }
+    }
+    if let Some(variants) = schema.get("anyOf").and_then(Value::as_array) {
+        let variant = variants
+            .iter()
+            .find(|variant| variant.get("type").and_then(Value::as_str) != Some("null"));
+        if let Some(variant) = variant {
+            return resolve_schema(variant, root);
+        }
+    }
+
+    schema
+}
+
+/// Whether the dotted variable path `path` exists in the template context with the JSON schema
+/// `root`, where maps with arbitrary keys, like the tags of a run, and plain values, on which any
+/// method may be called, accept any further parts
+fn schema_contains(root: &Value, path: &str) -> bool {
+    let mut schema = resolve_schema(root, root);
+    for part in path.split('.') {
+        let Some(properties) = schema.get("properties") else {
+            return true;
+        };
+        match properties.get(part) {
+            Some(field_schema) => schema = resolve_schema(field_schema, root),
+            None => return false,
+        }
+    }
+
+    true
+}
+
+/// Check the run script templates and all templates in `.sparrow/templates` for syntax errors,
+/// variables that are not part of the template context and variables that are set but never
+/// used, printing one line per problem
+pub fn check_templates() -> Result<()> {
+    let mut template_paths = vec![PathBuf::from(RUN_SCRIPT_TEMPLATE_PATH)];
+    for path in [RUN_PRE_SCRIPT_TEMPLATE_PATH, RUN_POST_SCRIPT_TEMPLATE_PATH] {
+        if Path::new(path).exists() {
+            template_paths.push(PathBuf::from(path));
+        }
+    }
+    if Path::new(TEMPLATES_DIR_PATH).is_dir() {
+        for entry in walkdir::WalkDir::new(TEMPLATES_DIR_PATH).sort_by_file_name() {
+            let entry = entry.context(format!("failed to walk {TEMPLATES_DIR_PATH}"))?;
+            if entry.file_type().is_file() {
+                let path = PathBuf::from_path_buf(entry.into_path())
+                    .map_err(|path| anyhow::anyhow!("found non-utf8 path {}", path.display()))?;
+                template_paths.push(path);
+            }
+        }
+    }
+
+    let mut problem_count = 0;
+    for template_path in &template_paths {
+        let problems = check_template(template_path)?;
+        for problem in &problems {
+            println!("{problem}");
+        }
+        problem_count += problems.len();
+    }
+
+    if problem_count > 0 {
+        bail!(
+            "found {problem_count} problems in {} templates",
+            template_paths.len()
+        );
+    }
+    println!(
+        "checked {} templates, found no problems",
+        template_paths.len()
+    );
+
+    Ok(())
+}
+
+fn check_template(template_path: &Path) -> Result<Vec<String>> {
+    let template_content = std::fs::read_to_string(template_path)
+        .context(format!("failed to read {template_path}"))?;
+
+    let mut env = template_environment();
+    if let Err(err) = env.add_template(template_path.as_str(), &template_content) {
+        let line = err.line().unwrap_or(0);
+        let message = match err.detail() {
+            Some(detail) => format!("{}: {detail}", err.kind()),
+            None => err.kind().to_string(),
+        };
+        return Ok(vec![format!("{template_path}:{line}: {message}")]);
+    }
+    let template = env
+        .get_template(template_path.as_str())
+        .expect("expected template to be added before");
+
+    let globals = env
+        .globals()
+        .map(|(name, _)| name.to_owned())
+        .collect::<Vec<_>>();
+    let context_schema = template_context_schema();
+    let mut undefined_paths = template
+        .undeclared_variables(true)
+        .into_iter()
+        .filter(|path| {
+            let root = path.split('.').next().unwrap_or(path);
+            !globals.iter().any(|global| global == root)
+                && !ENVIRONMENT_PATHS.contains(&path.as_str())
+                && !schema_contains(context_schema.as_value(), path)
+        })
+        .collect::<Vec<_>>();
+    undefined_paths.sort();
+
+    let mut problems = undefined_paths
+        .iter()
+        .map(|path| {
+            let line = find_line(&template_content, path);
+            format!("{template_path}:{line}: undefined variable `{path}'")
+        })
+        .collect::<Vec<_>>();
+    problems.extend(
+        unused_set_variables(&template_content)
+            .into_iter()
+            .map(|(name, line)| format!("{template_path}:{line}: variable `{name}' is never used")),
+    );
+
+    Ok(problems)
+}
+
+/// Line of the first occurrence of `needle`, or zero if it does not occur literally
+fn find_line(content: &str, needle: &str) -> usize {
+    content
+        .lines()
+        .position(|line| line.contains(needle))
+        .map_or(0, |index| index + 1)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Variables assigned with `{% set name = ... %}` whose name does not occur anywhere else in
+/// the template, together with the line of their assignment
+fn unused_set_variables(content: &str) -> Vec<(String, usize)> {
+    let mut assignments = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let mut rest = line;
+        while let Some(start) = rest.find("{%") {
+            rest = rest[start + 2..]
+                .trim_start_matches(['-', '+'])
+                .trim_start();
+            if let Some(statement) = rest.strip_prefix("set ") {
+                let name = statement
+                    .trim_start()
+                    .split(|c: char| !is_identifier_char(c))
+                    .next()
+                    .unwrap_or("");
+                if !name.is_empty() {
+                    assignments.push((name.to_owned(), index + 1));
+                }
+            }
+        }
+    }
+
+    assignments
+        .into_iter()
+        .filter(|(name, _)| {
+            let occurrence_count = content
+                .match_indices(name.as_str())
+                .filter(|(position, _)| {
+                    let before = content[..*position].chars().next_back();
+                    let after = content[position + name.len()..].chars().next();
+                    !before.is_some_and(is_identifier_char)
+                        && !after.is_some_and(is_identifier_char)
+                })
+                .count();
+            occurrence_count <= 1
+        })
+        .collect()
+}
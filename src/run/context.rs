@@ -0,0 +1,99 @@
+use super::default::{build_template_context, template_context_schema};
+use super::render::{build_context_host, new_run_info};
+use super::{
+    ParentRunInfo, PreviousRunInfo, PreviousRunStatus, RunInfo, SweepInfo, PARENT_LINK_NAME,
+};
+use crate::cfg::ResourceProfileConfig;
+use crate::host::{Host, RunID};
+use crate::GlobalConfig;
+use anyhow::Result;
+use camino::Utf8PathBuf as PathBuf;
+use std::collections::BTreeMap;
+
+/// Fill in the parts of the context of `run_info` which a new run only has in some cases with
+/// examples, returning the names of the filled in parts
+fn fill_in_examples(run_info: &mut RunInfo, host: &dyn Host) -> Vec<&'static str> {
+    let mut examples = Vec::new();
+    if run_info.resources.is_none() {
+        run_info.resources = Some(ResourceProfileConfig {
+            gpus: Some(1),
+            cpus: Some(8),
+            mem: Some(String::from("32G")),
+            time: Some(String::from("04:00:00")),
+            partition: Some(String::from("gpu")),
+        });
+        examples.push("resources");
+    }
+    if run_info.sweep.is_none() {
+        run_info.sweep = Some(SweepInfo {
+            index: 0,
+            total: 4,
+            params: BTreeMap::from([(String::from("seed"), String::from("0"))]),
+        });
+        examples.push("sweep");
+    }
+    if run_info.previous_run.is_none() {
+        run_info.previous_run = Some(PreviousRunInfo {
+            code_versions: run_info.payload.code_versions(),
+            output_path: run_info.output_path.clone(),
+            status: PreviousRunStatus::Finished,
+        });
+        examples.push("previous_run");
+    }
+    if run_info.parent.is_none() {
+        let parent_id = RunID::new("parent", run_info.id.group.as_str());
+        run_info.parent = Some(ParentRunInfo {
+            output_path: parent_id.path(host.output_base_dir_path()),
+            config_dir: host.config_dir_destination_path(&parent_id),
+            config_entrypoints: run_info.payload.config_entrypoints().to_vec(),
+            code_versions: run_info.payload.code_versions(),
+            link_path: Some(PathBuf::from(PARENT_LINK_NAME)),
+            run_id: parent_id,
+        });
+        examples.push("parent");
+    }
+    if run_info.tags.is_empty() {
+        run_info.tags = [(String::from("purpose"), String::from("example"))].into();
+        examples.push("tags");
+    }
+
+    examples
+}
+
+/// Print the template context a new run named `run_name` using the resource profile `resources`
+/// would get on `host` as JSON, with examples for the parts it would not have, or the JSON schema
+/// of the template context if `schema` is set
+pub fn print_context(
+    run_name: String,
+    resources: Option<String>,
+    host: Option<String>,
+    schema: bool,
+    config: GlobalConfig,
+) -> Result<()> {
+    if schema {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&template_context_schema())
+                .expect("expected the template context schema to serialize")
+        );
+        return Ok(());
+    }
+
+    let host = build_context_host(host, &config)?;
+    let mut run_info = new_run_info(&*host, run_name, resources, config)?;
+    let examples = fill_in_examples(&mut run_info, &*host);
+    if !examples.is_empty() {
+        eprintln!(
+            "note: {} are examples, since a new run {} would not have them",
+            examples.join(", "),
+            run_info.id
+        );
+    }
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&build_template_context(&run_info))
+            .expect("expected the template context to serialize")
+    );
+
+    Ok(())
+}
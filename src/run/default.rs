@@ -1,46 +1,283 @@
-use super::{RunInfo, Runner};
-use crate::host::{Host, RunDirectory, RunID};
-use crate::utils::{escape_single_quotes, tmux_wrap};
-use std::collections::HashMap;
-use std::io::Write;
+use super::{ParentRunInfo, PreviousRunInfo, RunInfo, Runner, RunnerInfo, SweepInfo};
+use crate::cfg::ResourceProfileConfig;
+use crate::control::ControlContext;
+use crate::host::timings::TIMINGS_FILE_NAME;
+use crate::host::{
+    Host, HostInfo, RunDirectory, RunID, CONTROL_FILE_NAME, ENVIRONMENT_FILE_NAME,
+    EXIT_CODE_FILE_NAME, PROCESS_GROUP_FILE_NAME,
+};
+use crate::payload::PayloadInfo;
+use crate::utils::{shell_quote, Utf8Path};
+use crate::version::VersionContext;
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{IsTerminal, Write};
 use std::os::unix::process::CommandExt;
 use tempfile::NamedTempFile;
 
+pub const RUN_SCRIPT_TEMPLATE_PATH: &str = ".sparrow/run.sh.j2";
+/// Optional templates of the sections before and after the main command of the run script
+pub const RUN_PRE_SCRIPT_TEMPLATE_PATH: &str = ".sparrow/run.pre.sh.j2";
+pub const RUN_POST_SCRIPT_TEMPLATE_PATH: &str = ".sparrow/run.post.sh.j2";
+
+/// Container in which local runs are executed, with the run directory and the run output base
+/// directory bind-mounted at their host paths
+pub struct Container {
+    pub engine: String,
+    pub image: String,
+}
+
+impl Container {
+    fn wrap_command(
+        &self,
+        cmd: &str,
+        run_dir: &RunDirectory,
+        host: &dyn Host,
+        environment_variable_names: &[String],
+        limits: Option<&LocalLimits>,
+    ) -> String {
+        let run_dir_path = run_dir.path();
+        let output_base_dir_path = host.output_base_dir_path();
+        let interactive_flags = if std::io::stdin().is_terminal() {
+            " --interactive --tty"
+        } else {
+            ""
+        };
+        let environment_flags = environment_variable_names
+            .iter()
+            .map(|name| format!(" --env {}", shell_quote(name)))
+            .collect::<String>();
+        let limit_flags = limits
+            .map(|limits| {
+                let memory_flag = limits
+                    .memory
+                    .map(|memory| format!(" --memory {memory} --memory-swap {memory}"))
+                    .unwrap_or_default();
+                let cpus_flag = limits
+                    .cpus
+                    .map(|cpus| format!(" --cpus {cpus}"))
+                    .unwrap_or_default();
+                memory_flag + &cpus_flag
+            })
+            .unwrap_or_default();
+        format!(
+            "{engine} run --rm{interactive_flags}{environment_flags}{limit_flags} \
+                --volume {run_dir_volume} --volume {output_base_dir_volume} \
+                --workdir {run_dir_path} {image} bash -c {cmd}",
+            engine = self.engine,
+            run_dir_volume = shell_quote(&format!("{run_dir_path}:{run_dir_path}")),
+            output_base_dir_volume =
+                shell_quote(&format!("{output_base_dir_path}:{output_base_dir_path}")),
+            run_dir_path = shell_quote(run_dir_path.as_str()),
+            image = shell_quote(&self.image),
+            cmd = shell_quote(cmd),
+        )
+    }
+}
+
+/// How the resource limits of local runs are enforced
+enum LimitMechanism {
+    /// A transient scope of the systemd user manager, whose cgroup limits the resident memory
+    /// without swap and the cpu time of the run
+    SystemdScope,
+    /// `prlimit` on the address space and `taskset` on the usable cpus, for systems without a
+    /// systemd user manager
+    Prlimit,
+}
+
+/// Memory in bytes and cpus that local runs may use at most, so that a run gone wrong is killed
+/// or slowed down instead of freezing the machine
+pub struct LocalLimits {
+    pub memory: Option<u64>,
+    pub cpus: Option<u16>,
+    mechanism: LimitMechanism,
+}
+
+fn command_succeeds(program: &str, args: &[&str]) -> bool {
+    std::process::Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+impl LocalLimits {
+    /// Limits enforced by a systemd scope if the systemd user manager is reachable, and by
+    /// `prlimit` and `taskset` otherwise, which have to be installed then
+    pub fn new(memory: Option<u64>, cpus: Option<u16>) -> Result<Self> {
+        if cpus == Some(0) {
+            bail!("expected at least one cpu for the local run");
+        }
+
+        let systemd_scope_args = ["--user", "--scope", "--quiet", "true"];
+        let mechanism = if command_succeeds("systemd-run", &systemd_scope_args) {
+            LimitMechanism::SystemdScope
+        } else {
+            eprintln!(
+                "warning: cannot create a scope with the systemd user manager, falling back to \
+                limiting the address space with prlimit and pinning cpus with taskset"
+            );
+            if memory.is_some() && !command_succeeds("prlimit", &["--version"]) {
+                bail!("limiting the memory of local runs requires systemd-run or prlimit");
+            }
+            if cpus.is_some() && !command_succeeds("taskset", &["--version"]) {
+                bail!("limiting the cpus of local runs requires systemd-run or taskset");
+            }
+            LimitMechanism::Prlimit
+        };
+
+        Ok(LocalLimits {
+            memory,
+            cpus,
+            mechanism,
+        })
+    }
+
+    fn wrap_command(&self, cmd: &str) -> String {
+        let mut wrapper = match self.mechanism {
+            LimitMechanism::SystemdScope => {
+                let mut wrapper = String::from("systemd-run --user --scope --quiet");
+                if let Some(memory) = self.memory {
+                    wrapper += &format!(" -p MemoryMax={memory} -p MemorySwapMax=0");
+                }
+                if let Some(cpus) = self.cpus {
+                    wrapper += &format!(" -p CPUQuota={}%", 100 * u32::from(cpus));
+                }
+                wrapper + " --"
+            }
+            LimitMechanism::Prlimit => {
+                let mut wrapper = String::new();
+                if let Some(memory) = self.memory {
+                    wrapper += &format!("prlimit --as={memory} ");
+                }
+                if let Some(cpus) = self.cpus {
+                    wrapper += &format!("taskset --cpu-list 0-{} ", cpus - 1);
+                }
+                wrapper
+            }
+        };
+        if !wrapper.ends_with(' ') {
+            wrapper.push(' ');
+        }
+
+        format!("{wrapper}bash -c {}", shell_quote(cmd))
+    }
+}
+
+/// How the rendered run script is processed before it is uploaded
+pub struct RunScriptOptions {
+    /// Check the run script with shellcheck, or with `bash -n` if shellcheck is not installed
+    pub validate: bool,
+    /// Add the standard prologue of [`prologue`] before the staging and main sections
+    pub prologue: bool,
+}
+
 pub struct DefaultRunner {
     cmdline: Vec<String>,
     environment_variable_transfer_requests: Vec<String>,
+    environment: BTreeMap<String, String>,
     config: HashMap<String, String>,
+    run_script_options: RunScriptOptions,
+    container: Option<Container>,
+    local_limits: Option<LocalLimits>,
 }
 
 impl DefaultRunner {
     pub fn new(
         cmdline: &Vec<String>,
         environment_variable_transfer_requests: &Vec<String>,
+        environment: BTreeMap<String, String>,
         config: &HashMap<String, String>,
+        run_script_options: RunScriptOptions,
+        container: Option<Container>,
+        local_limits: Option<LocalLimits>,
     ) -> Self {
         return Self {
             cmdline: cmdline.clone(),
             environment_variable_transfer_requests: environment_variable_transfer_requests.clone(),
+            environment,
             config: config.clone(),
+            run_script_options,
+            container,
+            local_limits,
         };
     }
 }
 
 impl Runner for DefaultRunner {
-    fn create_run_script(&self, run_info: &RunInfo) -> NamedTempFile {
-        let context = build_template_context(run_info);
-
+    fn create_run_script_from_context(&self, context: minijinja::Value) -> NamedTempFile {
         // load file as string
-        let run_template_content = std::fs::read_to_string(".sparrow/run.sh.j2")
+        let run_template_content = std::fs::read_to_string(RUN_SCRIPT_TEMPLATE_PATH)
             .expect("couldn't find .sparrow/run.sh.j2 in current directory");
 
-        let mut env = minijinja::Environment::new();
-        env.add_template("run", run_template_content.as_str())
-            .unwrap();
-        let run_template = env.get_template("run").unwrap();
-        let run_script_content = run_template
-            .render(context)
+        let sweep_exports = sweep_exports(&context)
+            .expect("expected sweep info in the template context to be well-formed");
+        let scheduler_directives = scheduler_directives(&context)
+            .expect("expected resources in the template context to be well-formed");
+        let pre_section = render_optional_template(RUN_PRE_SCRIPT_TEMPLATE_PATH, context.clone())
+            .expect("expected rendering of .sparrow/run.pre.sh.j2 to work");
+        let post_section = render_optional_template(RUN_POST_SCRIPT_TEMPLATE_PATH, context.clone())
+            .expect("expected rendering of .sparrow/run.post.sh.j2 to work");
+        let status_traps = status_traps(
+            &context,
+            post_section.is_some(),
+            self.run_script_options.prologue,
+        )
+        .expect("expected the output path in the template context to be well-formed");
+        let prologue = self.run_script_options.prologue.then(|| {
+            prologue(&context)
+                .expect("expected the run id and host in the context to be well-formed")
+        });
+        let main_section = render_template(&run_template_content, context)
             .expect("expected run script template rendering to work");
+        let environment_source = if self.environment.is_empty() {
+            String::new()
+        } else {
+            // sbatch executes a copy of the run script, so `$0` is not in the run directory, but
+            // the run script is always started from within it
+            format!("source \"${{SLURM_SUBMIT_DIR:-$PWD}}/{ENVIRONMENT_FILE_NAME}\"\n")
+        };
+
+        // the shebang of the main template has to stay the first line of the run script
+        let (shebang, main_section) = match main_section.split_once('\n') {
+            Some((shebang, rest)) if shebang.starts_with("#!") => (Some(shebang), rest),
+            _ => (None, main_section.as_str()),
+        };
+        // sbatch only reads directives before the first command of the script, so the leading
+        // comments of the main template, like its `#SBATCH` lines, go before all other sections
+        let (template_header, main_section) = split_leading_comments(main_section);
+        let post_run_function = post_section
+            .map(|post_section| format!("sparrow_post_run() {{\n{post_section}\n:\n}}\n"));
+        let sections = [
+            (
+                "template header",
+                Some(template_header.to_owned()).filter(|header| !header.trim().is_empty()),
+            ),
+            ("scheduler directives", Some(scheduler_directives)),
+            // the status traps write into the run output, which the prologue creates
+            ("prologue", prologue),
+            ("environment", Some(environment_source + &sweep_exports)),
+            ("teardown", post_run_function),
+            ("status traps", Some(status_traps)),
+            ("staging", pre_section),
+            ("main", Some(main_section.to_owned())),
+        ];
+        let mut run_script_content = shebang
+            .map(|shebang| format!("{shebang}\n"))
+            .unwrap_or_default();
+        for (name, section) in sections {
+            match section {
+                Some(section) if !section.is_empty() => {
+                    run_script_content += &format!("# sparrow: {name}\n{section}");
+                    if !section.ends_with('\n') {
+                        run_script_content.push('\n');
+                    }
+                }
+                _ => {}
+            }
+        }
 
         let mut run_script =
             NamedTempFile::new().expect("could not create temporary run script file");
@@ -50,10 +287,56 @@ impl Runner for DefaultRunner {
         return run_script;
     }
 
-    fn run(&self, host: &dyn Host, run_dir: &RunDirectory, run_id: &RunID) {
+    fn validate_run_script(&self, run_script: &NamedTempFile) -> Result<()> {
+        if !self.run_script_options.validate {
+            return Ok(());
+        }
+
+        let run_script_path = run_script.utf8_path();
+        println!("Validating run script...");
+        // fall back to a plain syntax check if shellcheck is not installed
+        let status = match std::process::Command::new("shellcheck")
+            .arg("--shell=bash")
+            .arg("--severity=error")
+            .arg(run_script_path)
+            .status()
+        {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                std::process::Command::new("bash")
+                    .arg("-n")
+                    .arg(run_script_path)
+                    .status()
+                    .context("failed to run bash -n on the run script")?
+            }
+            result => result.context("failed to run shellcheck on the run script")?,
+        };
+        if !status.success() {
+            bail!("the run script did not pass validation, see the messages above");
+        }
+
+        Ok(())
+    }
+
+    fn create_environment_file(&self) -> Result<Option<NamedTempFile>> {
+        if self.environment.is_empty() {
+            return Ok(None);
+        }
+
+        // temporary files are only readable by their owner, which carries over to the run directory
+        let mut environment_file =
+            NamedTempFile::new().context("failed to create temporary environment file")?;
+        for (name, value) in &self.environment {
+            writeln!(environment_file, "export {name}={}", shell_quote(value))
+                .context("failed to write environment file")?;
+        }
+
+        Ok(Some(environment_file))
+    }
+
+    fn run(&self, host: &dyn Host, run_dir: &RunDirectory, run_id: &RunID, attach: bool) {
         let run_cmd = &format!(
             "cd {run_dir_path} && {script_run_command}",
-            run_dir_path = run_dir.path(),
+            run_dir_path = shell_quote(run_dir.path().as_str()),
             script_run_command = host.script_run_command("./run.sh")
         );
 
@@ -74,28 +357,57 @@ impl Runner for DefaultRunner {
             .collect::<Vec<_>>();
 
         if host.is_local() {
+            let run_cmd = match &self.container {
+                Some(container) => &container.wrap_command(
+                    &host.script_run_command("./run.sh"),
+                    run_dir,
+                    host,
+                    &self.environment_variable_transfer_requests,
+                    self.local_limits.as_ref(),
+                ),
+                None => match &self.local_limits {
+                    Some(local_limits) => &format!(
+                        "cd {run_dir_path} && {limited_script_run_command}",
+                        run_dir_path = shell_quote(run_dir.path().as_str()),
+                        limited_script_run_command =
+                            local_limits.wrap_command(&host.script_run_command("./run.sh"))
+                    ),
+                    None => run_cmd,
+                },
+            };
             let err = cmd.arg(run_cmd).exec();
             panic!("expected exec to never fail: {err}");
         }
 
         let hostname = host.hostname();
-        let tmux_session_name = &format!("{run_id}");
-        let run_cmd_wrapped = tmux_wrap(run_cmd, tmux_session_name);
-        let run_cmd_wrapped = escape_single_quotes(&run_cmd_wrapped);
-
-        let run_cmd_wrapped_with_variables = format!(
-            "{} {run_cmd_wrapped}",
-            environment_variables_to_transfer
-                .iter()
-                .map(|(name, value)| { escape_single_quotes(&format!("{name}='{value}'")) })
-                .collect::<Vec<_>>()
-                .join(" ")
+        let run_cmd_detached = host.detached_run_command(
+            run_cmd,
+            run_id,
+            &self.environment_variable_transfer_requests,
+            attach,
         );
-        let err = cmd.arg(&format!(
-            "ssh -qtt {hostname} 'cd {} && {run_cmd_wrapped_with_variables}'",
-            run_dir.path()
-        ))
-        .exec();
+        let run_cmd_detached = if environment_variables_to_transfer.is_empty() {
+            run_cmd_detached
+        } else {
+            format!(
+                "export {} && {run_cmd_detached}",
+                environment_variables_to_transfer
+                    .iter()
+                    .map(|(name, value)| format!("{name}={}", shell_quote(value)))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )
+        };
+        let run_cmd_wrapped_with_variables = shell_quote(&format!(
+            "cd {} && {run_cmd_detached}",
+            shell_quote(run_dir.path().as_str())
+        ));
+        let err = cmd
+            .arg(format!(
+                "ssh -qtt {} {run_cmd_wrapped_with_variables}",
+                shell_quote(hostname)
+            ))
+            .exec();
         panic!("expected exec to never fail: {err}");
     }
 
@@ -108,12 +420,255 @@ impl Runner for DefaultRunner {
     }
 }
 
-fn build_template_context(run_info: &RunInfo) -> minijinja::Value {
-    minijinja::context! {
-        run_id => run_info.id,
-        host => run_info.host,
-        runner => run_info.runner,
-        payload => run_info.payload,
-        output_path => run_info.output_path,
+/// Context templates are rendered with, which is recorded as `run_context.json` of each run
+#[derive(serde::Serialize, schemars::JsonSchema)]
+struct TemplateContext<'r> {
+    run_id: &'r RunID,
+    host: &'r HostInfo,
+    runner: &'r RunnerInfo,
+    payload: &'r PayloadInfo,
+    /// Resource profile selected with `--resources`, with overrides from the command line applied
+    resources: &'r Option<ResourceProfileConfig>,
+    tags: &'r HashMap<String, String>,
+    sweep: &'r Option<SweepInfo>,
+    /// Output of the run on the host
+    #[schemars(with = "String")]
+    output_path: &'r camino::Utf8PathBuf,
+    previous_run: &'r Option<PreviousRunInfo>,
+    /// Run given with `--parent`
+    parent: &'r Option<ParentRunInfo>,
+    control: ControlContext,
+    sparrow: VersionContext,
+}
+
+pub fn build_template_context(run_info: &RunInfo) -> minijinja::Value {
+    minijinja::Value::from_serialize(TemplateContext {
+        run_id: &run_info.id,
+        host: &run_info.host,
+        runner: &run_info.runner,
+        payload: &run_info.payload,
+        resources: &run_info.resources,
+        tags: &run_info.tags,
+        sweep: &run_info.sweep,
+        output_path: &run_info.output_path,
+        previous_run: &run_info.previous_run,
+        parent: &run_info.parent,
+        control: crate::control::template_context(&run_info.output_path),
+        sparrow: crate::version::template_context(),
+    })
+}
+
+/// JSON schema of the template context
+pub fn template_context_schema() -> schemars::Schema {
+    schemars::schema_for!(TemplateContext<'static>)
+}
+
+/// Build `#SBATCH` directives of the resources of the run, which slurm applies when the run
+/// script is submitted with sbatch and which are plain comments otherwise
+fn scheduler_directives(context: &minijinja::Value) -> Result<String> {
+    let resources = context.get_attr("resources")?;
+    if resources.is_undefined() || resources.is_none() {
+        return Ok(String::new());
+    }
+
+    let mut directives = String::new();
+    for (field, option) in [
+        ("gpus", "gpus"),
+        ("cpus", "cpus-per-task"),
+        ("mem", "mem"),
+        ("time", "time"),
+        ("partition", "partition"),
+    ] {
+        let value = resources.get_attr(field)?;
+        if !value.is_undefined() && !value.is_none() {
+            directives += &format!("#SBATCH --{option}={value}\n");
+        }
+    }
+
+    Ok(directives)
+}
+
+/// Split `script` after its leading comment and blank lines
+fn split_leading_comments(script: &str) -> (&str, &str) {
+    let header_length = script
+        .split_inclusive('\n')
+        .take_while(|line| line.trim().is_empty() || line.trim_start().starts_with('#'))
+        .map(str::len)
+        .sum();
+    script.split_at(header_length)
+}
+
+/// Build `export` statements of the sweep index, total and parameters of the run, which are
+/// named `SPARROW_SWEEP_INDEX`, `SPARROW_SWEEP_TOTAL` and `SPARROW_SWEEP_PARAM_<KEY>`
+fn sweep_exports(context: &minijinja::Value) -> Result<String> {
+    let sweep = context.get_attr("sweep")?;
+    if sweep.is_undefined() || sweep.is_none() {
+        return Ok(String::new());
     }
+
+    let mut exports = vec![
+        (
+            "SPARROW_SWEEP_INDEX".to_owned(),
+            sweep.get_attr("index")?.to_string(),
+        ),
+        (
+            "SPARROW_SWEEP_TOTAL".to_owned(),
+            sweep.get_attr("total")?.to_string(),
+        ),
+    ];
+    let params = sweep.get_attr("params")?;
+    for key in params.try_iter()? {
+        let variable_name = key
+            .to_string()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        exports.push((
+            format!("SPARROW_SWEEP_PARAM_{variable_name}"),
+            params.get_item(&key)?.to_string(),
+        ));
+    }
+
+    Ok(exports
+        .iter()
+        .map(|(name, value)| format!("export {name}={}\n", shell_quote(value)))
+        .collect())
+}
+
+/// Write the exit code of the run script into the reproduce info of the run output when it exits,
+/// so that the outcome of finished runs can be reported, after calling `sparrow_post_run` of the
+/// teardown section if there is one, which finds the exit code in `$sparrow_exit_code`; the
+/// process group of the run script is recorded on start for `sparrow run-signal --posix`, the
+/// start and end time and the slurm partition are added to the timings of the run, and a
+/// control file left over from an earlier execution in the same output is removed; with the
+/// prologue, the end of the run is also logged, and since the prologue enables `set -e` before,
+/// failures to record the status never abort the run or the exit handler
+fn status_traps(context: &minijinja::Value, post_run: bool, prologue: bool) -> Result<String> {
+    let output_path = camino::Utf8PathBuf::from(context.get_attr("output_path")?.to_string());
+    let exit_code_path = output_path.join("reproduce_info").join(EXIT_CODE_FILE_NAME);
+    let process_group_path = output_path
+        .join("reproduce_info")
+        .join(PROCESS_GROUP_FILE_NAME);
+    let timings_path = output_path.join("reproduce_info").join(TIMINGS_FILE_NAME);
+    let post_run_call = if post_run {
+        "    sparrow_post_run\n"
+    } else {
+        ""
+    };
+    let end_log = if prologue {
+        "    echo \"sparrow: ended with exit code $sparrow_exit_code at $(date -Iseconds)\"\n"
+    } else {
+        ""
+    };
+    Ok(format!(
+        "rm -f {}\n\
+        ps -o pgid= -p $$ | tr -d ' ' 2> /dev/null > {} || true\n\
+        sparrow_timings_path={}\n\
+        echo \"started=$(date +%s)\" 2> /dev/null >> \"$sparrow_timings_path\" || true\n\
+        if [ -n \"${{SLURM_JOB_PARTITION:-}}\" ]; then\n\
+        \x20   echo \"partition=$SLURM_JOB_PARTITION\" \\\n\
+        \x20       2> /dev/null >> \"$sparrow_timings_path\" || true\n\
+        fi\n\
+        sparrow_exit_code_path={}\n\
+        sparrow_on_exit() {{\n\
+        \x20   sparrow_exit_code=$?\n\
+        \x20   set +e\n\
+        {post_run_call}\
+        \x20   echo \"ended=$(date +%s)\" 2> /dev/null >> \"$sparrow_timings_path\"\n\
+        \x20   echo \"$sparrow_exit_code\" 2> /dev/null > \"$sparrow_exit_code_path\"\n\
+        {end_log}\
+        }}\n\
+        trap sparrow_on_exit EXIT\n",
+        shell_quote(output_path.join(CONTROL_FILE_NAME).as_str()),
+        shell_quote(process_group_path.as_str()),
+        shell_quote(timings_path.as_str()),
+        shell_quote(exit_code_path.as_str())
+    ))
+}
+
+/// Standard prologue of the run script, which creates the run output, exports the run name and
+/// group, the output path and the host id as `SPARROW_RUN_NAME`, `SPARROW_RUN_GROUP`,
+/// `SPARROW_OUTPUT_PATH` and `SPARROW_HOST`, logs the start of the run and makes the rest of the
+/// run script exit on errors, unset variables and failing pipelines
+fn prologue(context: &minijinja::Value) -> Result<String> {
+    let run_id = context.get_attr("run_id")?;
+    let output_path = context.get_attr("output_path")?.to_string();
+    let exports = [
+        ("SPARROW_RUN_NAME", run_id.get_attr("name")?.to_string()),
+        ("SPARROW_RUN_GROUP", run_id.get_attr("group")?.to_string()),
+        ("SPARROW_OUTPUT_PATH", output_path.clone()),
+        (
+            "SPARROW_HOST",
+            context.get_attr("host")?.get_attr("id")?.to_string(),
+        ),
+    ];
+
+    let mut prologue = format!("mkdir -p {}\n", shell_quote(&output_path));
+    for (name, value) in exports {
+        prologue += &format!("export {name}={}\n", shell_quote(&value));
+    }
+    prologue += "echo \"sparrow: started $SPARROW_RUN_GROUP/$SPARROW_RUN_NAME on $(hostname) at \
+        $(date -Iseconds)\"\n";
+    prologue += "set -euo pipefail\n";
+
+    Ok(prologue)
+}
+
+/// Render the template at `path` if it exists
+fn render_optional_template(path: &str, context: minijinja::Value) -> Result<Option<String>> {
+    if !std::path::Path::new(path).exists() {
+        return Ok(None);
+    }
+
+    let template_content =
+        std::fs::read_to_string(path).context(format!("failed to read {path}"))?;
+    render_template(&template_content, context)
+        .context(format!("failed to render {path}"))
+        .map(Some)
+}
+
+/// Environment in which templates are rendered, which provides `host.module_load(names)` to
+/// render the `module load` line of the modules configured for the host under `names`
+pub fn template_environment<'s>() -> minijinja::Environment<'s> {
+    let mut env = minijinja::Environment::new();
+    env.set_unknown_method_callback(|_, value, method, args| {
+        let modules = match method {
+            "module_load" => value.get_attr("modules")?,
+            _ => minijinja::Value::UNDEFINED,
+        };
+        if modules.is_undefined() {
+            return Err(minijinja::Error::from(minijinja::ErrorKind::UnknownMethod));
+        }
+
+        let (names,): (Vec<String>,) = minijinja::value::from_args(args)?;
+        let mut line = String::from("module load");
+        for name in names {
+            let module = modules.get_attr(&name)?;
+            if module.is_undefined() {
+                return Err(minijinja::Error::new(
+                    minijinja::ErrorKind::InvalidOperation,
+                    format!("found no module `{name}' in the modules of the host"),
+                ));
+            }
+            line += &format!(" {module}");
+        }
+        Ok(minijinja::Value::from(line))
+    });
+    env
+}
+
+pub fn render_template(template_content: &str, context: minijinja::Value) -> Result<String> {
+    let mut env = template_environment();
+    env.add_template("template", template_content)
+        .context("failed to parse template")?;
+    env.get_template("template")
+        .expect("expected template to be added before")
+        .render(context)
+        .context("failed to render template")
 }
@@ -1,25 +1,67 @@
-use crate::cfg::RunnerConfig;
-use crate::host::{build_host, build_local_host, Host, HostInfo, RunDirectory, RunID};
-use crate::payload::{build_payload_mapping, CodeSource, PayloadInfo, PayloadMapping};
+use crate::cfg::{
+    CodeSelectionArgs, ConfigUploadConfig, ConfigUploadDelete, LocalLimitArgs,
+    OutputPermissionsConfig, RemoteHostConfig, ResourceOverrides, ResourceProfileConfig,
+    ReviewMode, RunnerConfig,
+};
+use crate::exit::{ExitStatus, WithExitStatus};
+use crate::host::local::LocalHost;
+use crate::host::maintenance::parse_slurm_time_limit;
+use crate::host::rsync::SyncOptions;
+use crate::host::status::record_operation;
+use crate::host::upload_summary::UploadSummary;
+use crate::host::{
+    build_host, build_local_host, configured_review_mode, format_tags, resolve_host_id,
+    resolve_run_id, review_config_directory, Host, HostInfo, HostPurpose, RunDirectory, RunID,
+    RunTag, ENVIRONMENT_FILE_NAME,
+};
+use crate::payload::{
+    build_payload_mapping, local_submodule_states, CodeSource, EnvironmentLock, PayloadBuild,
+    PayloadInfo, PayloadMapping, CONFIG_TEMPLATE_EXTENSION,
+};
+use crate::pin;
+use crate::recent::record_recent_runs;
+use crate::utils::{confirm, format_date, select_interactively, shell_quote, Utf8Path};
+use crate::version::migrate_run_context;
 use crate::GlobalConfig;
-use anyhow::{Context, Result};
-use camino::Utf8PathBuf as PathBuf;
-use default::DefaultRunner;
-use std::collections::HashMap;
-use tempfile::NamedTempFile;
+use anyhow::{anyhow, bail, Context, Result};
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use config::{Config, File, FileFormat};
+use default::{build_template_context, Container, DefaultRunner, LocalLimits, RunScriptOptions};
+use script::{record_run_script, warn_on_template_change};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use summary::SubmissionSummary;
+use tempfile::{NamedTempFile, TempDir};
 
+pub mod check;
+pub mod context;
 pub mod default;
+pub mod render;
+pub mod reproduce;
+pub mod reuse;
+pub mod script;
+pub mod stage;
+pub mod summary;
 
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, schemars::JsonSchema)]
 pub struct RunnerInfo {
     cmdline: String,
     config: HashMap<String, String>,
 }
 
 pub trait Runner {
-    fn create_run_script(&self, run_info: &RunInfo) -> NamedTempFile;
+    fn create_run_script(&self, run_info: &RunInfo) -> NamedTempFile {
+        self.create_run_script_from_context(build_template_context(run_info))
+    }
+    fn create_run_script_from_context(&self, context: minijinja::Value) -> NamedTempFile;
+
+    /// Check the run script for errors before it is uploaded, if enabled in the configuration
+    fn validate_run_script(&self, run_script: &NamedTempFile) -> Result<()>;
+
+    /// File with the environment variables the run script sources, if there are any
+    fn create_environment_file(&self) -> Result<Option<NamedTempFile>>;
 
-    fn run(&self, host: &dyn Host, run_dir: &RunDirectory, run_id: &RunID);
+    fn run(&self, host: &dyn Host, run_dir: &RunDirectory, run_id: &RunID, attach: bool);
 
     fn cmdline(&self) -> &Vec<String>;
     fn config(&self) -> &HashMap<String, String>;
@@ -32,7 +74,12 @@ pub trait Runner {
     }
 }
 
-pub fn build_runner(cmdline: &Vec<String>, config: Option<RunnerConfig>) -> Box<dyn Runner> {
+pub fn build_runner(
+    cmdline: &Vec<String>,
+    config: Option<RunnerConfig>,
+    container: Option<Container>,
+    local_limits: Option<LocalLimits>,
+) -> Box<dyn Runner> {
     let config = config.unwrap_or_default();
 
     let variable_transfer_requests = config
@@ -46,14 +93,43 @@ pub fn build_runner(cmdline: &Vec<String>, config: Option<RunnerConfig>) -> Box<
                     expected {variable_name} to be retreivable from \
                     the local environment because of a transfer request: {err}"
             );
-            std::process::exit(1);
+            ExitStatus::Config.exit();
         }
     });
 
+    // explicitly transferred variables take precedence over the ones from environment files
+    let env_files = config.env_files.unwrap_or_default();
+    let mut environment = BTreeMap::new();
+    for env_file_path in &env_files {
+        match read_environment_file(env_file_path) {
+            Ok(variables) => environment.extend(variables),
+            Err(err) => {
+                eprintln!(
+                    "refusing to run; failed to read environment file {env_file_path}: {err:#}"
+                );
+                ExitStatus::Config.exit();
+            }
+        }
+    }
+    if !env_files.is_empty() {
+        environment.extend(variable_transfer_requests.iter().map(|variable_name| {
+            let variable_value = std::env::var(variable_name)
+                .expect("expected variable to be retreivable from the environment");
+            (variable_name.clone(), variable_value)
+        }));
+    }
+
     Box::new(DefaultRunner::new(
         cmdline,
         &variable_transfer_requests,
+        environment,
         &config.config.unwrap_or(HashMap::new()),
+        RunScriptOptions {
+            validate: config.validate_run_script.unwrap_or(false),
+            prologue: config.prologue.unwrap_or(false),
+        },
+        container,
+        local_limits,
     ))
 }
 
@@ -62,7 +138,139 @@ pub struct RunInfo {
     pub host: HostInfo,
     pub runner: RunnerInfo,
     pub payload: PayloadInfo,
+    pub resources: Option<ResourceProfileConfig>,
+    pub tags: HashMap<String, String>,
+    pub sweep: Option<SweepInfo>,
+    pub output_path: PathBuf,
+    pub previous_run: Option<PreviousRunInfo>,
+    pub parent: Option<ParentRunInfo>,
+}
+
+/// Position of a run within a sweep or job array, which run scripts can use to partition
+/// datasets or select seeds deterministically
+#[derive(serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone)]
+pub struct SweepInfo {
+    pub index: usize,
+    pub total: usize,
+    pub params: BTreeMap<String, String>,
+}
+
+/// A run with the same id which already exists on the host, which run scripts can use for
+/// warm starts, e.g. by resuming from its checkpoints
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct PreviousRunInfo {
+    pub code_versions: BTreeMap<String, String>,
+    #[schemars(with = "String")]
     pub output_path: PathBuf,
+    pub status: PreviousRunStatus,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviousRunStatus {
+    Running,
+    Finished,
+}
+
+const MAX_CODE_VERSIONS_SIZE: u64 = 1024 * 1024;
+
+/// Code versions recorded for `run_id` on `host`, which are empty if it recorded none
+pub fn read_recorded_code_versions(
+    host: &dyn Host,
+    run_id: &RunID,
+) -> Result<BTreeMap<String, String>> {
+    let code_versions_path = host.code_versions_file_destination_path(run_id);
+    if !host.exists(&code_versions_path)? {
+        return Ok(BTreeMap::new());
+    }
+
+    let content = host
+        .read_file_head(&code_versions_path, MAX_CODE_VERSIONS_SIZE)
+        .context(format!("failed to read {code_versions_path}"))?;
+    Ok(String::from_utf8_lossy(&content)
+        .lines()
+        .filter_map(|line| line.split_once(" = "))
+        .map(|(code_source_id, version)| (code_source_id.to_owned(), version.to_owned()))
+        .collect())
+}
+
+impl PreviousRunInfo {
+    /// Look up the run `run_id` on the host, before a new submission overwrites what it recorded
+    pub fn read(host: &dyn Host, run_id: &RunID) -> Result<Option<PreviousRunInfo>> {
+        let output_path = run_id.path(host.output_base_dir_path());
+        if !host.exists(&output_path)? {
+            return Ok(None);
+        }
+
+        let code_versions = read_recorded_code_versions(host, run_id)?;
+
+        // runs on the local host are executed in the foreground, so they are never tracked, and a
+        // recorded exit code takes precedence, since sessions kept open after the run exited still
+        // count as running
+        let status = match host.recorded_exit_code(run_id) {
+            None if !host.is_local() && host.running_runs().contains(run_id) => {
+                PreviousRunStatus::Running
+            }
+            _ => PreviousRunStatus::Finished,
+        };
+
+        Ok(Some(PreviousRunInfo {
+            code_versions,
+            output_path,
+            status,
+        }))
+    }
+}
+
+/// Name of the link to the output of the parent run in the run directory
+pub const PARENT_LINK_NAME: &str = "parent";
+
+/// A run on the same host whose outputs the run builds on, e.g. the training run of an
+/// evaluation run, which is recorded as lineage of the run
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct ParentRunInfo {
+    pub run_id: RunID,
+    #[schemars(with = "String")]
+    pub output_path: PathBuf,
+    #[schemars(with = "String")]
+    pub config_dir: PathBuf,
+    #[schemars(with = "Vec<String>")]
+    pub config_entrypoints: Vec<PathBuf>,
+    pub code_versions: BTreeMap<String, String>,
+    /// Path of the link to `output_path` relative to the run directory, if the run gets one
+    #[schemars(with = "Option<String>")]
+    pub link_path: Option<PathBuf>,
+}
+
+impl ParentRunInfo {
+    /// Look up the run `run_id` on the host, whose output is linked into the run directory as
+    /// [`PARENT_LINK_NAME`] if `link` is set
+    pub fn read(host: &dyn Host, run_id: &RunID, link: bool) -> Result<ParentRunInfo> {
+        let output_path = run_id.path(host.output_base_dir_path());
+        if !host.exists(&output_path)? {
+            bail!("found no output of {run_id} on {}", host.id());
+        }
+
+        let config_entrypoints = match read_recorded_run_context(host, run_id) {
+            Ok(context) => context
+                .pointer("/payload/config_entrypoints")
+                .and_then(|entrypoints| serde_json::from_value(entrypoints.clone()).ok())
+                .unwrap_or_default(),
+            Err(err) => {
+                eprintln!("warning: exposing no config entrypoints of {run_id}: {err:#}");
+                Vec::new()
+            }
+        };
+
+        Ok(ParentRunInfo {
+            run_id: run_id.clone(),
+            output_path,
+            config_dir: host.config_dir_destination_path(run_id),
+            config_entrypoints,
+            code_versions: read_recorded_code_versions(host, run_id)?,
+            link_path: link.then(|| PathBuf::from(PARENT_LINK_NAME)),
+        })
+    }
 }
 
 impl RunInfo {
@@ -70,16 +278,660 @@ impl RunInfo {
         host: &dyn Host,
         runner: &dyn Runner,
         payload_mapping: &PayloadMapping,
+        resources: Option<&ResourceProfileConfig>,
+        tags: &[RunTag],
+        sweep: Option<SweepInfo>,
         run_id: &RunID,
     ) -> RunInfo {
+        RunInfo::for_host_info(
+            host.info(),
+            runner,
+            payload_mapping,
+            resources,
+            tags,
+            sweep,
+            run_id,
+        )
+    }
+
+    /// Like [`RunInfo::new`], but for a host that is only known by `host_info`, like a host that
+    /// runs are staged for while it is out of reach
+    pub fn for_host_info(
+        host_info: HostInfo,
+        runner: &dyn Runner,
+        payload_mapping: &PayloadMapping,
+        resources: Option<&ResourceProfileConfig>,
+        tags: &[RunTag],
+        sweep: Option<SweepInfo>,
+        run_id: &RunID,
+    ) -> RunInfo {
+        let output_path = run_id.path(&host_info.run_output_base_dir_path);
+        let reproduce_info_path = output_path.join("reproduce_info");
         RunInfo {
             id: run_id.clone(),
-            host: host.info(),
+            host: host_info,
             runner: runner.info(),
-            payload: PayloadInfo::new(payload_mapping, &host.config_dir_destination_path(&run_id)),
-            output_path: run_id.path(host.output_base_dir_path()),
+            payload: PayloadInfo::new(
+                payload_mapping,
+                &reproduce_info_path.join("config"),
+                &reproduce_info_path,
+            ),
+            resources: resources.cloned(),
+            tags: tags.iter().cloned().collect(),
+            sweep,
+            output_path,
+            previous_run: None,
+            parent: None,
         }
     }
+
+    pub fn with_previous_run(mut self, previous_run: Option<PreviousRunInfo>) -> RunInfo {
+        self.previous_run = previous_run;
+        self
+    }
+
+    pub fn with_parent(mut self, parent: Option<ParentRunInfo>) -> RunInfo {
+        self.parent = parent;
+        self
+    }
+}
+
+/// Read `KEY=VALUE` lines of an environment file, which may be prefixed with `export` and have
+/// quoted values, while empty lines and lines starting with `#` are ignored
+fn read_environment_file(path: &Path) -> Result<Vec<(String, String)>> {
+    let content = std::fs::read_to_string(path).context(format!("failed to read {path}"))?;
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (name, value) = line
+                .split_once('=')
+                .ok_or(anyhow!("found malformed line `{line}' in {path}"))?;
+            let name = name.trim();
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                bail!("found invalid variable name `{name}' in {path}");
+            }
+
+            let value = value.trim();
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|quote| value.strip_prefix(*quote)?.strip_suffix(*quote))
+                .unwrap_or(value);
+            Ok((name.to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+/// Name of the resource profile runs get when no profile is selected, if it is defined
+pub const DEFAULT_RESOURCE_PROFILE_NAME: &str = "default";
+
+/// Look up the resource profile `name` for the host `host_id`, preferring profiles defined by
+/// the host over the global ones
+pub fn resolve_resource_profile<'c>(
+    name: &str,
+    host_id: &str,
+    global_profiles: &'c Option<HashMap<String, ResourceProfileConfig>>,
+    remote_configs: &'c HashMap<String, RemoteHostConfig>,
+) -> Result<&'c ResourceProfileConfig> {
+    find_resource_profile(name, host_id, global_profiles, remote_configs).ok_or(anyhow!(
+        "found no resource profile `{name}' for {host_id} in the configuration"
+    ))
+}
+
+fn find_resource_profile<'c>(
+    name: &str,
+    host_id: &str,
+    global_profiles: &'c Option<HashMap<String, ResourceProfileConfig>>,
+    remote_configs: &'c HashMap<String, RemoteHostConfig>,
+) -> Option<&'c ResourceProfileConfig> {
+    let host_profiles = remote_configs
+        .get(host_id)
+        .and_then(|remote_config| remote_config.resources.as_ref());
+    host_profiles
+        .and_then(|profiles| profiles.get(name))
+        .or(global_profiles
+            .as_ref()
+            .and_then(|profiles| profiles.get(name)))
+}
+
+/// Resources of a run from the profile `name`, or from the profile
+/// [`DEFAULT_RESOURCE_PROFILE_NAME`] if no name is given and it is defined, where the fields of
+/// `overrides` take precedence
+pub fn resolve_resources(
+    name: Option<&str>,
+    overrides: &ResourceOverrides,
+    host_id: &str,
+    global_profiles: &Option<HashMap<String, ResourceProfileConfig>>,
+    remote_configs: &HashMap<String, RemoteHostConfig>,
+) -> Result<Option<ResourceProfileConfig>> {
+    let profile = match name {
+        Some(name) => Some(resolve_resource_profile(
+            name,
+            host_id,
+            global_profiles,
+            remote_configs,
+        )?),
+        None => find_resource_profile(
+            DEFAULT_RESOURCE_PROFILE_NAME,
+            host_id,
+            global_profiles,
+            remote_configs,
+        ),
+    };
+
+    let has_overrides = overrides.gpus.is_some()
+        || overrides.cpus.is_some()
+        || overrides.mem.is_some()
+        || overrides.time.is_some();
+    if profile.is_none() && !has_overrides {
+        return Ok(None);
+    }
+
+    let profile = profile.cloned().unwrap_or_default();
+    Ok(Some(ResourceProfileConfig {
+        gpus: overrides.gpus.or(profile.gpus),
+        cpus: overrides.cpus.or(profile.cpus),
+        mem: overrides.mem.clone().or(profile.mem),
+        time: overrides.time.clone().or(profile.time),
+        partition: profile.partition,
+    }))
+}
+
+/// Number of runs which can still be submitted to `host` before reaching its
+/// `max_concurrent_runs`, or `None` if the host has no such limit
+pub fn free_run_slots(
+    host: &dyn Host,
+    remote_configs: &HashMap<String, RemoteHostConfig>,
+) -> Option<usize> {
+    let max_concurrent_runs = remote_configs.get(host.id())?.max_concurrent_runs?;
+    Some(max_concurrent_runs.saturating_sub(host.running_runs().len()))
+}
+
+/// Refuse to submit `run_id` to `host` while it runs as many runs as it may
+pub fn enforce_concurrency_limit(
+    host: &dyn Host,
+    remote_configs: &HashMap<String, RemoteHostConfig>,
+    run_id: &RunID,
+) -> Result<()> {
+    if free_run_slots(host, remote_configs) == Some(0) {
+        bail!(
+            "refusing to submit {run_id}, since {} reached its max_concurrent_runs, queue it with \
+            `sparrow queue add' to submit it once a run finished",
+            host.id()
+        );
+    }
+    Ok(())
+}
+
+/// Refuse to submit `run_id` to `host` while a run with the same id is running there, whose tmux
+/// session or registration the new run would clash with
+pub fn enforce_unique_running_run(host: &dyn Host, run_id: &RunID) -> Result<()> {
+    // runs on the local host are executed in the foreground, so they are never tracked
+    if host.is_local() || !host.running_runs().contains(run_id) {
+        return Ok(());
+    }
+
+    // sessions kept open after the run script exited still count as running
+    match host.recorded_exit_code(run_id) {
+        Some(0) => bail!(
+            "refusing to submit {run_id}, since its session is still open on {0} after it \
+            finished, close it with `sparrow host clean-sessions {0}` or choose another run name",
+            host.id()
+        ),
+        Some(_) => bail!(
+            "refusing to submit {run_id}, since its session is still open on {} after it failed, \
+            attach with `sparrow run-attach` and exit the shell or choose another run name",
+            host.id()
+        ),
+        None => bail!(
+            "refusing to submit {run_id}, since it is already running on {}, wait for it to \
+            finish or choose another run name",
+            host.id()
+        ),
+    }
+}
+
+/// What to do with a submission that would run into a maintenance window
+enum MaintenanceChoice {
+    Submit,
+    Abort,
+    SwitchHost(String),
+}
+
+impl std::fmt::Display for MaintenanceChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MaintenanceChoice::Submit => write!(f, "submit anyway"),
+            MaintenanceChoice::Abort => write!(f, "abort"),
+            MaintenanceChoice::SwitchHost(host_id) => write!(f, "submit to {host_id} instead"),
+        }
+    }
+}
+
+/// Warn if `run_id` would run into a maintenance reservation of `host` within its time limit,
+/// or within the runtime of its previous execution if it has no time limit, and let the user
+/// submit anyway, abort or pick another remote host, whose id is returned
+pub fn check_maintenance_windows(
+    host: &dyn Host,
+    run_id: &RunID,
+    resources: Option<&ResourceProfileConfig>,
+    yes: bool,
+    remote_configs: &HashMap<String, RemoteHostConfig>,
+) -> Result<Option<String>> {
+    let maintenance = match host.maintenance() {
+        Ok(maintenance) => maintenance,
+        Err(err) => {
+            eprintln!(
+                "warning: failed to check for maintenance on {}: {err:#}",
+                host.id()
+            );
+            return Ok(None);
+        }
+    };
+
+    let time_limit = resources.and_then(|resources| resources.time.as_deref());
+    let walltime = match time_limit {
+        Some(time_limit) => parse_slurm_time_limit(time_limit),
+        None => host
+            .recorded_timings(run_id)
+            .and_then(|timings| timings.runtime()),
+    };
+    let partition = resources.and_then(|resources| resources.partition.as_deref());
+    let windows = maintenance
+        .windows
+        .iter()
+        .filter(|window| window.overlaps(maintenance.now, walltime))
+        .filter(|window| match (&window.partition, partition) {
+            (Some(window_partition), Some(partition)) => window_partition == partition,
+            _ => true,
+        })
+        .collect::<Vec<_>>();
+    if windows.is_empty() {
+        return Ok(None);
+    }
+
+    let walltime_description = match (time_limit, walltime) {
+        (Some(time_limit), _) => format!("its time limit of {time_limit}"),
+        (None, Some(walltime)) => format!(
+            "the runtime of its previous execution of {}",
+            humantime::format_duration(walltime)
+        ),
+        (None, None) => String::from("its unknown runtime"),
+    };
+    eprintln!(
+        "warning: {run_id} might not finish on {} within {walltime_description} before \
+        maintenance, slurm holds it until the maintenance is over:",
+        host.id()
+    );
+    for window in windows {
+        eprintln!("    {window}");
+    }
+    if yes {
+        return Ok(None);
+    }
+
+    let mut other_host_ids = remote_configs
+        .keys()
+        .filter(|host_id| *host_id != host.id())
+        .collect::<Vec<_>>();
+    other_host_ids.sort();
+    let mut choices = vec![MaintenanceChoice::Submit, MaintenanceChoice::Abort];
+    choices.extend(
+        other_host_ids
+            .into_iter()
+            .map(|host_id| MaintenanceChoice::SwitchHost(host_id.clone())),
+    );
+    match select_interactively(&choices, "maintenance: ")
+        .context("failed to select how to handle the maintenance")?
+    {
+        MaintenanceChoice::Submit => Ok(None),
+        MaintenanceChoice::Abort => {
+            bail!(
+                "aborted the submission of {run_id} because of maintenance on {}",
+                host.id()
+            )
+        }
+        MaintenanceChoice::SwitchHost(host_id) => Ok(Some(host_id.clone())),
+    }
+}
+
+const SUBMISSION_LOCK_PATH: &str = ".sparrow/.lock";
+
+/// Lock the project against concurrent submissions until the returned file is dropped, which
+/// also happens when the runner replaces the process with the run command
+pub fn lock_submission() -> Result<std::fs::File> {
+    let lock_file = std::fs::File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(SUBMISSION_LOCK_PATH)
+        .context(format!("failed to open {SUBMISSION_LOCK_PATH}"))?;
+
+    match lock_file.try_lock() {
+        Ok(()) => Ok(lock_file),
+        Err(std::fs::TryLockError::WouldBlock) => bail!(
+            "another submission is in progress in this project ({SUBMISSION_LOCK_PATH} is \
+            locked), wait for it to finish or pass --no-lock"
+        ),
+        Err(std::fs::TryLockError::Error(err)) => {
+            Err(err).context(format!("failed to lock {SUBMISSION_LOCK_PATH}"))
+        }
+    }
+}
+
+/// Expand `{config.<key>}` placeholders in a run name with values from the config entrypoint
+/// and `{date}` with the current date, e.g. `{config.model}-{config.lr}-{date}`
+pub fn expand_run_name(template: &str, config_entrypoint_path: &Path) -> Result<String> {
+    let mut entrypoint_config = None;
+    let mut run_name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        run_name += &rest[..start];
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .context(format!("found unclosed placeholder in `{template}'"))?;
+        let placeholder = &rest[start + 1..end];
+
+        let value = if placeholder == "date" {
+            format_date(std::time::SystemTime::now())
+        } else if let Some(key) = placeholder.strip_prefix("config.") {
+            if config_entrypoint_path.extension() == Some(CONFIG_TEMPLATE_EXTENSION) {
+                bail!(
+                    "cannot expand `{{{placeholder}}}' from the config template \
+                    {config_entrypoint_path}, since it is only rendered for the named run"
+                );
+            }
+            if entrypoint_config.is_none() {
+                entrypoint_config = Some(
+                    Config::builder()
+                        .add_source(
+                            File::from(config_entrypoint_path.as_std_path())
+                                .format(FileFormat::Yaml),
+                        )
+                        .build()
+                        .context(format!("failed to read {config_entrypoint_path}"))?,
+                );
+            }
+            entrypoint_config
+                .as_ref()
+                .expect("expected entrypoint config to be loaded")
+                .get_string(key)
+                .context(format!(
+                    "failed to obtain `{key}' from {config_entrypoint_path}"
+                ))?
+        } else {
+            bail!(
+                "unknown placeholder `{{{placeholder}}}', expected `{{date}}' or \
+                `{{config.<key>}}'"
+            );
+        };
+
+        run_name += &value;
+        rest = &rest[end + 1..];
+    }
+    run_name += rest;
+
+    if run_name.contains('/') {
+        bail!("expanded run name `{run_name}' must not contain `/'");
+    }
+
+    Ok(run_name)
+}
+
+/// Remote artifacts created during a submission, which are removed again when the submission
+/// fails, either by returning an error or by panicking, before the rollback is disarmed
+struct SubmissionRollback<'h> {
+    host: &'h dyn Host,
+    paths: Vec<PathBuf>,
+    armed: bool,
+}
+
+impl<'h> SubmissionRollback<'h> {
+    fn new(host: &'h dyn Host) -> Self {
+        Self {
+            host,
+            paths: Vec::new(),
+            armed: true,
+        }
+    }
+
+    fn track(&mut self, path: PathBuf) {
+        self.paths.push(path);
+    }
+
+    fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for SubmissionRollback<'_> {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+
+        for path in self.paths.iter().rev() {
+            eprintln!(
+                "Submission failed, removing {path} from {}...",
+                self.host.id()
+            );
+            if let Err(err) = self.host.remove_dir_all(path) {
+                eprintln!("warning: failed to remove {path}: {err:#}");
+            }
+        }
+    }
+}
+
+/// Environment of the payload build steps, which exposes the run id, host and output path
+/// directly and the complete template context as json in `SPARROW_RUN_CONTEXT`
+fn build_step_environment(run_info: &RunInfo) -> Result<Vec<(String, String)>> {
+    let run_context = serde_json::to_string(&build_template_context(run_info))
+        .context("failed to serialize the run context")?;
+    Ok(vec![
+        (String::from("SPARROW_RUN_NAME"), run_info.id.name.clone()),
+        (String::from("SPARROW_RUN_GROUP"), run_info.id.group.clone()),
+        (String::from("SPARROW_HOST"), run_info.host.id.clone()),
+        (
+            String::from("SPARROW_OUTPUT_PATH"),
+            run_info.output_path.to_string(),
+        ),
+        (String::from("SPARROW_RUN_CONTEXT"), run_context),
+    ])
+}
+
+/// Store the template context of the run in its reproduce info, so that templates can later be
+/// rendered against it
+fn record_run_context(host: &dyn Host, run_info: &RunInfo, run_id: &RunID) -> Result<()> {
+    let mut context_file =
+        NamedTempFile::new().context("failed to create temporary run context file")?;
+    serde_json::to_writer_pretty(&mut context_file, &build_template_context(run_info))
+        .context("failed to write run context")?;
+
+    host.put(
+        context_file.utf8_path(),
+        &host.run_context_file_destination_path(run_id),
+        SyncOptions::default(),
+    );
+
+    Ok(())
+}
+
+/// Read the template context recorded for the run `run_id` when it was submitted
+pub fn read_recorded_run_context(host: &dyn Host, run_id: &RunID) -> Result<serde_json::Value> {
+    let context_dir = TempDir::new().context("failed to create temporary run context directory")?;
+    let context_path = context_dir.utf8_path().join("run_context.json");
+    let recorded_context_path = host.run_context_file_destination_path(run_id);
+    if !host.exists(&recorded_context_path)? {
+        bail!(
+            "{run_id} on {} has no recorded run context, it was most likely \
+            submitted by an older version of sparrow",
+            host.id()
+        );
+    }
+    host.try_get(
+        &recorded_context_path,
+        &context_path,
+        SyncOptions::default().quiet(),
+    )
+    .context(format!("failed to download the run context of {run_id}"))?;
+
+    let context_content = std::fs::read_to_string(&context_path)
+        .context(format!("failed to read run context of {run_id}"))?;
+    let mut context = serde_json::from_str(&context_content)
+        .context(format!("failed to parse run context of {run_id}"))?;
+    migrate_run_context(&mut context, run_id);
+
+    Ok(context)
+}
+
+/// Start the timings of `run_id` with the current time of the host, so that the start time the
+/// run script adds is comparable to it regardless of the clock skew of the host
+pub fn record_submission_time(host: &dyn Host, run_id: &RunID) -> Result<()> {
+    let command = format!(
+        "echo \"submitted=$(date +%s)\" > {}",
+        shell_quote(host.timings_file_destination_path(run_id).as_str())
+    );
+    let status = host.execute(&command, None)?;
+    if !status.success() {
+        bail!("`{command}` failed on {}", host.id());
+    }
+
+    Ok(())
+}
+
+pub fn record_parent(host: &dyn Host, parent_id: &RunID, run_id: &RunID) -> Result<()> {
+    let mut parent_file = NamedTempFile::new().context("failed to create temporary parent file")?;
+    writeln!(parent_file, "{parent_id}").context("failed to write parent run")?;
+
+    host.put(
+        parent_file.utf8_path(),
+        &host.parent_file_destination_path(run_id),
+        SyncOptions::default(),
+    );
+
+    Ok(())
+}
+
+/// Link the output of the parent run `parent` into the run directory at `run_dir_path`
+fn link_parent_output(host: &dyn Host, parent: &ParentRunInfo, run_dir_path: &Path) -> Result<()> {
+    let Some(link_path) = &parent.link_path else {
+        return Ok(());
+    };
+    let command = format!(
+        "ln -sfn {} {}",
+        shell_quote(parent.output_path.as_str()),
+        shell_quote(run_dir_path.join(link_path).as_str())
+    );
+    let status = host.execute(&command, None)?;
+    if !status.success() {
+        bail!("`{command}` failed on {}", host.id());
+    }
+
+    Ok(())
+}
+
+fn record_run_dir(host: &dyn Host, run_dir_path: &Path, run_id: &RunID) -> Result<()> {
+    let mut run_dir_file =
+        NamedTempFile::new().context("failed to create temporary run directory file")?;
+    writeln!(run_dir_file, "{run_dir_path}").context("failed to write run directory path")?;
+
+    host.put(
+        run_dir_file.utf8_path(),
+        &host.run_dir_file_destination_path(run_id),
+        SyncOptions::default(),
+    );
+
+    Ok(())
+}
+
+fn record_upload_summary(
+    host: &dyn Host,
+    upload_summary: &UploadSummary,
+    run_id: &RunID,
+) -> Result<()> {
+    let mut summary_file =
+        NamedTempFile::new().context("failed to create temporary upload summary file")?;
+    summary_file
+        .write_all((crate::version::to_yaml() + &upload_summary.to_yaml()).as_bytes())
+        .context("failed to write upload summary")?;
+
+    host.put(
+        summary_file.utf8_path(),
+        &host.upload_summary_file_destination_path(run_id),
+        SyncOptions::default(),
+    );
+
+    Ok(())
+}
+
+fn record_environment_lock(
+    host: &dyn Host,
+    environment_lock: &EnvironmentLock,
+    run_id: &RunID,
+) -> Result<()> {
+    let mut lock_file = NamedTempFile::new().context("failed to create temporary lock file")?;
+    lock_file
+        .write_all(&environment_lock.content)
+        .context("failed to write environment lock")?;
+
+    host.put(
+        lock_file.utf8_path(),
+        &host
+            .reproduce_info_path(run_id)
+            .join(environment_lock.file_name),
+        SyncOptions::default(),
+    );
+
+    Ok(())
+}
+
+fn record_tags(host: &dyn Host, tags: &HashMap<String, String>, run_id: &RunID) -> Result<()> {
+    let mut tags = tags.clone().into_iter().collect::<Vec<_>>();
+    tags.sort();
+
+    let mut tags_file = NamedTempFile::new().context("failed to create temporary tags file")?;
+    tags_file
+        .write_all(format_tags(&tags).as_bytes())
+        .context("failed to write run tags")?;
+
+    host.put(
+        tags_file.utf8_path(),
+        &host.tags_file_destination_path(run_id),
+        SyncOptions::default(),
+    );
+
+    Ok(())
+}
+
+/// Store the uncommitted changes of submodules as `<id>/<submodule path>.diff` in the reproduce
+/// info of the run
+fn record_submodule_diffs(
+    host: &dyn Host,
+    submodule_diffs: &[(String, String)],
+    run_id: &RunID,
+) -> Result<()> {
+    let diffs_dir =
+        TempDir::new().context("failed to create temporary submodule diffs directory")?;
+    for (key, diff) in submodule_diffs {
+        let diff_path = diffs_dir.utf8_path().join(format!("{key}.diff"));
+        let diff_parent_path = diff_path
+            .parent()
+            .expect("expected diff path to have a parent");
+        std::fs::create_dir_all(diff_parent_path)
+            .context(format!("failed to create {diff_parent_path}"))?;
+        std::fs::write(&diff_path, diff).context(format!("failed to write {diff_path}"))?;
+    }
+
+    host.put(
+        diffs_dir.utf8_path(),
+        &host.submodule_diffs_destination_path(run_id),
+        SyncOptions::default().copy_contents(),
+    );
+
+    Ok(())
 }
 
 fn print_run_script(run_script: tempfile::NamedTempFile) {
@@ -89,34 +941,204 @@ fn print_run_script(run_script: tempfile::NamedTempFile) {
     println!();
     println!("------- run_script end -------");
 }
-pub fn run(
-    run_name: String,
-    run_group: Option<String>,
-    config_dir: Option<PathBuf>,
-    use_previous_config: bool,
-    ignore_revisions: Vec<String>,
-    host: String,
-    enforce_quick: bool,
-    no_config_review: bool,
-    remainder: Vec<String>,
-    only_print_run_script: bool,
-    config: GlobalConfig,
-) -> Result<()> {
-    let run_group = run_group.unwrap_or(config.run_group);
+/// What to run where and how to submit it, as given on the command line
+pub struct RunOptions {
+    pub run_name: String,
+    pub run_group: Option<String>,
+    pub config_dir: Option<PathBuf>,
+    pub use_previous_config: bool,
+    pub ignore_revisions: Vec<String>,
+    pub host: Option<String>,
+    pub enforce_quick: bool,
+    pub no_config_review: bool,
+    pub remainder: Vec<String>,
+    pub resources: Option<String>,
+    pub resource_overrides: ResourceOverrides,
+    pub payload_bundles: Vec<String>,
+    pub code_selection: CodeSelectionArgs,
+    pub tags: Vec<RunTag>,
+    pub sweep: Option<SweepInfo>,
+    pub only_print_run_script: bool,
+    pub stage_only: bool,
+    pub detach: bool,
+    pub no_lock: bool,
+    pub container: Option<String>,
+    pub local_limits: LocalLimitArgs,
+    pub parent: Option<String>,
+    pub link_parent: bool,
+    pub unpin_first: bool,
+    pub yes: bool,
+}
+
+pub fn run(options: RunOptions, config: GlobalConfig) -> Result<()> {
+    let RunOptions {
+        run_name,
+        run_group,
+        config_dir,
+        use_previous_config,
+        ignore_revisions,
+        host,
+        enforce_quick,
+        no_config_review,
+        remainder,
+        resources,
+        resource_overrides,
+        payload_bundles,
+        code_selection,
+        tags,
+        sweep,
+        only_print_run_script,
+        stage_only,
+        detach,
+        no_lock,
+        container,
+        local_limits,
+        parent,
+        link_parent,
+        unpin_first,
+        yes,
+    } = options;
+
+    let run_name = if use_previous_config {
+        if run_name.contains('{') {
+            bail!("cannot expand run name template `{run_name}' when using the previous config");
+        }
+        run_name
+    } else {
+        let config_entrypoint_path = config_dir
+            .as_deref()
+            .unwrap_or(&config.payload.config.dir)
+            .join(&config.payload.config.entrypoints[0]);
+        expand_run_name(&run_name, &config_entrypoint_path)
+            .context(format!("failed to expand run name template `{run_name}'"))
+            .exit_status(ExitStatus::Config)?
+    };
+
+    let run_group = run_group.unwrap_or_else(|| config.run_group.clone());
     let run_id = RunID::new(&run_name, &run_group);
 
     let local_host = build_local_host(&config.local_host);
 
-    println!("Connect to host...");
-    let host = build_host(
-        &host,
-        &config.local_host,
-        &config.remote_hosts,
-        enforce_quick,
-    )
-    .context(format!("failed to build {host} as host"))?;
+    let mut host_id = host
+        .or(config.default_host.clone())
+        .unwrap_or(String::from("local"));
+
+    let _submission_lock = (!no_lock && !only_print_run_script)
+        .then(lock_submission)
+        .transpose()?;
+
+    if stage_only {
+        let host_id = resolve_host_id(&host_id, &config.remote_hosts)
+            .context("can only stage runs for remote hosts")
+            .exit_status(ExitStatus::Config)?;
+        let host_info = stage::offline_host_info(host_id, &config.remote_hosts[host_id])
+            .exit_status(ExitStatus::Config)?;
+        let resources = resolve_resources(
+            resources.as_deref(),
+            &resource_overrides,
+            host_id,
+            &config.resources,
+            &config.remote_hosts,
+        )
+        .exit_status(ExitStatus::Config)?;
+        let runner = build_runner(&remainder, config.runner, None, None);
+        let mut payload_mapping = build_payload_mapping(
+            &config.payload,
+            config_dir.as_deref(),
+            &ignore_revisions,
+            &payload_bundles,
+        )
+        .context("failed to build payload mapping")
+        .exit_status(ExitStatus::Config)?;
+        payload_mapping
+            .select_code(&code_selection.only_code, &code_selection.skip_code)
+            .context("failed to select code mappings")
+            .exit_status(ExitStatus::Config)?;
+        let run_info = RunInfo::for_host_info(
+            host_info,
+            &*runner,
+            &payload_mapping,
+            resources.as_ref(),
+            &tags,
+            sweep,
+            &run_id,
+        );
+        return stage::stage(
+            &*runner,
+            &payload_mapping,
+            &run_info,
+            configured_review_mode(config.review.as_ref(), no_config_review),
+            !yes,
+        );
+    }
+
+    // another host may be picked to avoid a maintenance window of the first one
+    let (host, resources) = loop {
+        println!("Connect to host...");
+        let host = build_host(&host_id, &config, HostPurpose::Submission, enforce_quick)
+            .context(format!("failed to build {host_id} as host"))?;
+
+        if !only_print_run_script {
+            enforce_unique_running_run(&*host, &run_id)?;
+            enforce_concurrency_limit(&*host, &config.remote_hosts, &run_id)?;
+        }
+
+        let resources = resolve_resources(
+            resources.as_deref(),
+            &resource_overrides,
+            host.id(),
+            &config.resources,
+            &config.remote_hosts,
+        )
+        .exit_status(ExitStatus::Config)?;
+
+        if !only_print_run_script && config.remote_hosts.contains_key(host.id()) {
+            let other_host_id = check_maintenance_windows(
+                &*host,
+                &run_id,
+                resources.as_ref(),
+                yes,
+                &config.remote_hosts,
+            )?;
+            if let Some(other_host_id) = other_host_id {
+                host_id = other_host_id;
+                continue;
+            }
+        }
 
-    let runner = build_runner(&remainder, config.runner);
+        break (host, resources);
+    };
+
+    let is_pinned = !only_print_run_script && pin::is_pinned(&*host, &run_id)?;
+    if is_pinned && !unpin_first {
+        bail!("refusing to overwrite {run_id}, since it is pinned, pass --unpin-first to do so");
+    }
+
+    if container.is_some() && !host.is_local() {
+        bail!("running in a container is only supported on the local host");
+    }
+    let container = container.map(|image| Container {
+        engine: config
+            .local_host
+            .container_engine
+            .clone()
+            .unwrap_or(String::from("docker")),
+        image,
+    });
+
+    let local_limits = if local_limits.local_mem.is_some() || local_limits.local_cpus.is_some() {
+        if !host.is_local() {
+            bail!("limiting resources with --local-mem or --local-cpus is only supported locally");
+        }
+        Some(
+            LocalLimits::new(local_limits.local_mem, local_limits.local_cpus)
+                .context("failed to set up resource limits for the local run")?,
+        )
+    } else {
+        None
+    };
+
+    let runner = build_runner(&remainder, config.runner, container, local_limits);
 
     let config_dir = use_previous_config
         .then(|| {
@@ -130,60 +1152,316 @@ pub fn run(
         })
         .transpose()?
         .or(config_dir);
-    let payload_mapping =
-        build_payload_mapping(&config.payload, config_dir.as_deref(), &ignore_revisions)
-            .context("failed to build payload mapping")?;
+    let mut payload_mapping = build_payload_mapping(
+        &config.payload,
+        config_dir.as_deref(),
+        &ignore_revisions,
+        &payload_bundles,
+    )
+    .context("failed to build payload mapping")
+    .exit_status(ExitStatus::Config)?;
+    payload_mapping
+        .select_code(&code_selection.only_code, &code_selection.skip_code)
+        .context("failed to select code mappings")
+        .exit_status(ExitStatus::Config)?;
+
+    let previous_run = PreviousRunInfo::read(&*host, &run_id)
+        .context(format!("failed to look up previous run {run_id}"))?;
+    let parent = parent
+        .map(|parent| {
+            let run_ids = host
+                .runs()
+                .context(format!("failed to obtain runs from {}", host.id()))?;
+            let parent_id = resolve_run_id(&parent, &run_ids)?;
+            if parent_id == run_id {
+                bail!("{run_id} cannot be its own parent");
+            }
+            ParentRunInfo::read(&*host, &parent_id, link_parent)
+                .context(format!("failed to look up parent run {parent_id}"))
+        })
+        .transpose()?;
+    let run_info = RunInfo::new(
+        &*host,
+        &*runner,
+        &payload_mapping,
+        resources.as_ref(),
+        &tags,
+        sweep,
+        &run_id,
+    )
+    .with_previous_run(previous_run)
+    .with_parent(parent);
+    submit(
+        &*host,
+        &*runner,
+        &payload_mapping,
+        &run_info,
+        &SubmitOptions {
+            review_mode: configured_review_mode(config.review.as_ref(), no_config_review),
+            only_print_run_script,
+            detach,
+            keep_payload: config.run_output.keep_payload.unwrap_or(false),
+            output_permissions: config.run_output.permissions.clone(),
+            confirm: !yes,
+            config_upload_delete: config_upload_delete(config.config_upload.as_ref()),
+            unpin: is_pinned.then(|| build_local_host(&config.local_host)),
+        },
+    )
+}
+
+pub fn config_upload_delete(config_upload: Option<&ConfigUploadConfig>) -> ConfigUploadDelete {
+    config_upload
+        .and_then(|config_upload| config_upload.delete)
+        .unwrap_or_default()
+}
 
-    let run_info = RunInfo::new(&*host, &*runner, &payload_mapping, &run_id);
-    let run_script = runner.create_run_script(&run_info);
-    if only_print_run_script {
+#[derive(Default)]
+pub struct SubmitOptions {
+    pub review_mode: ReviewMode,
+    pub only_print_run_script: bool,
+    pub detach: bool,
+    /// Copy the run directory into the run output after uploading it
+    pub keep_payload: bool,
+    pub output_permissions: Option<OutputPermissionsConfig>,
+    /// Show a summary of the submission and ask for confirmation before creating anything, which
+    /// is asked after the config review if there is one
+    pub confirm: bool,
+    pub config_upload_delete: ConfigUploadDelete,
+    /// Unpin the run on the host, and its synced copy on the given local host, once the
+    /// submission is confirmed
+    pub unpin: Option<LocalHost>,
+}
+
+pub fn submit(
+    host: &dyn Host,
+    runner: &dyn Runner,
+    payload_mapping: &PayloadMapping,
+    run_info: &RunInfo,
+    options: &SubmitOptions,
+) -> Result<()> {
+    let run_id = &run_info.id;
+    let run_script = runner.create_run_script(run_info);
+    if options.only_print_run_script {
         print_run_script(run_script);
         return Ok(());
     }
+    runner
+        .validate_run_script(&run_script)
+        .context("found an invalid run script")?;
+    host.check_execution().exit_status(ExitStatus::Config)?;
+    if run_info.previous_run.is_some() {
+        warn_on_template_change(host, run_id);
+    }
 
+    if options.confirm {
+        let summary = SubmissionSummary::new(host, payload_mapping, run_info);
+        print!("{summary}");
+    }
     println!(
         "Copying config to run directory from `{}'...",
         payload_mapping.config_source.dir_path
     );
-    host.prepare_config_directory(
+    // with a review, the question after it is the only confirmation of the submission
+    let review_dir = TempDir::new().context("failed to create temporary review directory")?;
+    review_config_directory(
         &payload_mapping.config_source,
-        &run_id,
-        payload_mapping
-            .code_mappings
-            .iter()
-            .filter_map(|code_mapping| {
-                code_mapping
-                    .source
-                    .git_revision()
-                    .map(|revision| (code_mapping.id.clone(), revision.clone()))
-            })
-            .collect(),
-        !no_config_review,
-    );
+        review_dir.utf8_path(),
+        options.review_mode,
+        options.confirm.then_some("Submit?"),
+        build_template_context(run_info),
+    )?;
+    if options.confirm && options.review_mode == ReviewMode::None && !confirm("Submit?")? {
+        return Err(anyhow::Error::new(ExitStatus::Aborted).context("submission was not confirmed"));
+    }
+    if let Some(local_host) = &options.unpin {
+        println!("Unpin {run_id}...");
+        pin::unpin_run(host, local_host, run_id).context(format!("failed to unpin {run_id}"))?;
+    }
+
+    let mut rollback = SubmissionRollback::new(host);
+    let run_output_path = run_id.path(host.output_base_dir_path());
+    if !host.exists(&run_output_path)? {
+        rollback.track(run_output_path.clone());
+    }
+    let creates_group = !host.exists(&host.output_base_dir_path().join(&run_id.group))?;
+
+    let code_versions = collect_code_versions(payload_mapping)?;
+    host.prepare_config_directory(
+        review_dir.utf8_path(),
+        run_id,
+        code_versions.versions,
+        options.config_upload_delete,
+    )?;
+    if !code_versions.submodule_diffs.is_empty() {
+        record_submodule_diffs(host, &code_versions.submodule_diffs, run_id)
+            .context("failed to record the submodule diffs")?;
+    }
+    record_run_context(host, run_info, run_id).context("failed to record the run context")?;
+    record_run_script(host, &run_script, run_info, run_id)
+        .context("failed to record the run script")?;
+    if let Some(environment_lock) = &payload_mapping.environment_lock {
+        record_environment_lock(host, environment_lock, run_id)
+            .context("failed to record the environment lock")?;
+    }
+    if !run_info.tags.is_empty() {
+        record_tags(host, &run_info.tags, run_id).context("failed to record the run tags")?;
+    }
+    if let Some(parent) = &run_info.parent {
+        record_parent(host, &parent.run_id, run_id).context("failed to record the parent run")?;
+    }
 
     println!("Copying code to run directory from...");
     payload_mapping
         .code_mappings
         .iter()
         .for_each(|code_mapping| {
-            println!(
-                "    {}: {}",
-                code_mapping.id,
-                match code_mapping.source {
-                    CodeSource::Local { ref path, .. } => format!("{}", path),
-                    CodeSource::Remote {
-                        ref url,
-                        ref git_revision,
-                    } => format!("{}@{}", url, git_revision),
-                }
-            );
+            println!("    {}: {}", code_mapping.id, code_mapping.source);
         });
-    let run_dir = host.prepare_run_directory(
+    let environment_file = runner
+        .create_environment_file()
+        .context("failed to create the environment file of the run")?;
+    let payload_build = PayloadBuild {
+        steps: &payload_mapping.build_steps,
+        environment: build_step_environment(run_info)?,
+    };
+    let (run_dir, upload_summary) = host.prepare_run_directory(
         &payload_mapping.code_mappings,
         &payload_mapping.auxiliary_mappings,
+        &payload_build,
         run_script,
-    );
+        environment_file,
+        run_id,
+    )?;
+
+    if let RunDirectory::Remote(run_dir_path) = &run_dir {
+        rollback.track(run_dir_path.clone());
+    }
+    if let Some(parent) = &run_info.parent {
+        link_parent_output(host, parent, run_dir.path()).context(format!(
+            "failed to link the output of {} into the run directory",
+            parent.run_id
+        ))?;
+    }
+
+    launch_run(
+        host,
+        runner,
+        &run_dir,
+        &upload_summary,
+        run_id,
+        creates_group,
+        options,
+    )?;
+    rollback.disarm();
+
+    Ok(())
+}
+
+/// Code versions of the code sources of a payload as recorded in the reproduce info, together
+/// with the uncommitted changes of the submodules of local code sources by submodule
+struct CodeVersions {
+    versions: HashMap<String, String>,
+    submodule_diffs: Vec<(String, String)>,
+}
+
+fn collect_code_versions(payload_mapping: &PayloadMapping) -> Result<CodeVersions> {
+    let mut code_versions = payload_mapping
+        .code_mappings
+        .iter()
+        .filter_map(|code_mapping| {
+            code_mapping
+                .source
+                .git_revision()
+                .map(|revision| (code_mapping.id.clone(), revision.clone()))
+        })
+        .collect::<HashMap<_, _>>();
+    // submodules of local code sources are recorded as `<id>/<submodule path>`, which does not
+    // clash with the ids of code sources
+    let mut submodule_diffs = Vec::new();
+    for code_mapping in &payload_mapping.code_mappings {
+        let CodeSource::Local { path, snapshot, .. } = &code_mapping.source else {
+            continue;
+        };
+        if let Some(snapshot) = snapshot {
+            code_versions.insert(
+                format!("{}:tree", code_mapping.id),
+                snapshot.tree_id.clone(),
+            );
+            // the exported tree leaves submodules empty, so the state of their working trees does
+            // not describe what was uploaded
+            continue;
+        }
+        let submodule_states = local_submodule_states(path).context(format!(
+            "failed to capture the submodules of `{}'",
+            code_mapping.id
+        ))?;
+        for state in submodule_states {
+            let key = format!("{}/{}", code_mapping.id, state.path);
+            let dirty_suffix = if state.diff.is_some() { "-dirty" } else { "" };
+            code_versions.insert(key.clone(), format!("{}{dirty_suffix}", state.revision));
+            if let Some(diff) = state.diff {
+                submodule_diffs.push((key, diff));
+            }
+        }
+    }
+
+    Ok(CodeVersions {
+        versions: code_versions,
+        submodule_diffs,
+    })
+}
+
+/// Record the uploaded run directory `run_dir` of `run_id` and execute the run, which replaces
+/// the process when attaching to it; `creates_group` tells whether the submission created the
+/// run group directory, whose permissions are then set as well
+fn launch_run(
+    host: &dyn Host,
+    runner: &dyn Runner,
+    run_dir: &RunDirectory,
+    upload_summary: &UploadSummary,
+    run_id: &RunID,
+    creates_group: bool,
+    options: &SubmitOptions,
+) -> Result<()> {
+    if let RunDirectory::Remote(run_dir_path) = run_dir {
+        record_run_dir(host, run_dir_path, run_id).context("failed to record the run directory")?;
+    }
+
+    println!("{upload_summary}");
+    record_upload_summary(host, upload_summary, run_id)
+        .context("failed to record the upload summary")?;
+
+    if options.keep_payload {
+        println!("Copying run directory to the run output...");
+        // the environment file may hold secrets, which should not outlive the run directory
+        host.copy_dir(
+            run_dir.path(),
+            &host.payload_destination_path(run_id),
+            &[format!("/{ENVIRONMENT_FILE_NAME}")],
+        )
+        .context("failed to keep a copy of the run directory")?;
+    }
+
+    if let Some(permissions) = &options.output_permissions {
+        let group_path = host.output_base_dir_path().join(&run_id.group);
+        if creates_group {
+            host.set_permissions(&group_path, permissions, false)
+                .context(format!("failed to set the permissions of {group_path}"))?;
+        }
+        let run_output_path = run_id.path(host.output_base_dir_path());
+        host.set_permissions(&run_output_path, permissions, true)
+            .context(format!(
+                "failed to set the permissions of {run_output_path}"
+            ))?;
+    }
+
+    // executing the run replaces the process, so the submission is recorded beforehand
+    record_operation(host.id(), &format!("submitted {run_id}"));
+    record_recent_runs(host.id(), &[run_id]);
+    record_submission_time(host, run_id).context("failed to record the submission time")?;
 
     println!("Execute run...");
-    Ok(runner.run(&*host, &run_dir, &run_id))
+    runner.run(host, run_dir, run_id, !options.detach);
+
+    Ok(())
 }
@@ -0,0 +1,87 @@
+use super::default::{build_template_context, render_template};
+use super::RunInfo;
+use super::{build_runner, read_recorded_run_context, resolve_resources};
+use crate::cfg::ResourceOverrides;
+use crate::host::{resolve_run_id, Host, RunID};
+use crate::payload::build_payload_mapping;
+use crate::GlobalConfig;
+use anyhow::{Context, Result};
+use camino::Utf8PathBuf as PathBuf;
+
+/// Build `host`, which defaults to `default_host` from the configuration or the local host, to
+/// obtain a template context for
+pub fn build_context_host(host: Option<String>, config: &GlobalConfig) -> Result<Box<dyn Host>> {
+    let host = host
+        .or(config.default_host.clone())
+        .unwrap_or(String::from("local"));
+    config
+        .build_host(&host)
+        .context(format!("failed to build {host} as host"))
+}
+
+/// Run info a new run named `run_name` using the resource profile `resources` would get on `host`
+pub fn new_run_info(
+    host: &dyn Host,
+    run_name: String,
+    resources: Option<String>,
+    config: GlobalConfig,
+) -> Result<RunInfo> {
+    let run_id = RunID::new(run_name, config.run_group);
+    let resources = resolve_resources(
+        resources.as_deref(),
+        &ResourceOverrides::default(),
+        host.id(),
+        &config.resources,
+        &config.remote_hosts,
+    )?;
+    let runner = build_runner(&Vec::new(), config.runner, None, None);
+    let payload_mapping = build_payload_mapping(&config.payload, None, &Vec::new(), &[])
+        .context("failed to build payload mapping")?;
+    Ok(RunInfo::new(
+        host,
+        &*runner,
+        &payload_mapping,
+        resources.as_ref(),
+        &[],
+        None,
+        &run_id,
+    ))
+}
+
+/// Render the template at `template_path` to stdout, either against the context a new run
+/// named `run_name` using the resource profile `resources` would get or against the recorded
+/// context of the run `run`, given as `<group>/<name>`
+pub fn render(
+    template_path: PathBuf,
+    run: Option<String>,
+    run_name: String,
+    resources: Option<String>,
+    host: Option<String>,
+    config: GlobalConfig,
+) -> Result<()> {
+    let template_content = std::fs::read_to_string(&template_path)
+        .context(format!("failed to read {template_path}"))?;
+
+    let host = build_context_host(host, &config)?;
+    let context = match run {
+        Some(run) => {
+            let run_id = resolve_run_id(
+                &run,
+                &host
+                    .runs()
+                    .context(format!("failed to obtain runs from {}", host.id()))?,
+            )?;
+            minijinja::Value::from_serialize(read_recorded_run_context(&*host, &run_id)?)
+        }
+        None => build_template_context(&new_run_info(&*host, run_name, resources, config)?),
+    };
+
+    // minijinja drops the trailing newline of templates
+    println!(
+        "{}",
+        render_template(&template_content, context)
+            .context(format!("failed to render {template_path}"))?
+    );
+
+    Ok(())
+}
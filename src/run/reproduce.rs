@@ -0,0 +1,225 @@
+use super::script::warn_on_template_change;
+use super::{
+    build_runner, config_upload_delete, enforce_concurrency_limit, lock_submission, submit,
+    ParentRunInfo, RunInfo, SubmitOptions,
+};
+use crate::cfg::ResourceProfileConfig;
+use crate::exit::{ExitStatus, WithExitStatus};
+use crate::host::rsync::SyncOptions;
+use crate::host::{build_host, HostPurpose, RunID, RunTag};
+use crate::payload::{build_payload_mapping, CodeSource};
+use crate::recent::select_run;
+use crate::utils::Utf8Path;
+use crate::version::migrate_run_context;
+use crate::GlobalConfig;
+use anyhow::{anyhow, bail, Context, Result};
+use camino::Utf8Path as Path;
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+/// Relaunch a previous run with the exact code revisions and config recorded in its
+/// `reproduce_info`, as a new run named `<name>-repro-<n>` in the same group
+pub fn reproduce(
+    host: Option<String>,
+    enforce_quick: bool,
+    no_lock: bool,
+    remainder: Vec<String>,
+    config: GlobalConfig,
+) -> Result<()> {
+    let host = host
+        .or(config.default_host.clone())
+        .unwrap_or(String::from("local"));
+
+    let _submission_lock = (!no_lock).then(lock_submission).transpose()?;
+
+    println!("Connect to host...");
+    let host = build_host(&host, &config, HostPurpose::Submission, enforce_quick)
+        .context(format!("failed to build {host} as host"))?;
+
+    let run_ids = host
+        .runs()
+        .context(format!("failed to obtain runs from {}", host.id()))?;
+    let original_run_id =
+        select_run(host.id(), &run_ids, "run: ").context("failed to select a run to reproduce")?;
+    let run_id = reproduction_run_id(&original_run_id, &run_ids);
+    enforce_concurrency_limit(&*host, &config.remote_hosts, &run_id)?;
+
+    let reproduce_info_dir =
+        TempDir::new().context("failed to create temporary reproduce info directory")?;
+    host.try_get(
+        &host.reproduce_info_path(&original_run_id),
+        reproduce_info_dir.utf8_path(),
+        SyncOptions::default().copy_contents(),
+    )
+    .context(format!(
+        "failed to download the reproduce info of {original_run_id}"
+    ))?;
+
+    let code_versions =
+        read_code_versions(&reproduce_info_dir.utf8_path().join("code_versions.txt"))
+            .context(format!("failed to read code versions of {original_run_id}"))?;
+
+    let run_context = read_run_context(
+        &reproduce_info_dir.utf8_path().join("run_context.json"),
+        &original_run_id,
+    )
+    .context(format!(
+        "failed to read the run context of {original_run_id}"
+    ))?;
+    let payload_bundles = match run_context.pointer("/payload/bundles") {
+        Some(bundles) => serde_json::from_value(bundles.clone()).context(format!(
+            "found malformed payload bundles in the run context of {original_run_id}"
+        ))?,
+        None => Vec::new(),
+    };
+    let omitted_code: Vec<String> = match run_context.pointer("/payload/omitted_code") {
+        Some(omitted_code) => serde_json::from_value(omitted_code.clone()).context(format!(
+            "found malformed omitted code mappings in the run context of {original_run_id}"
+        ))?,
+        None => Vec::new(),
+    };
+    let sweep = match run_context.get("sweep") {
+        Some(sweep) if !sweep.is_null() => Some(serde_json::from_value(sweep.clone()).context(
+            format!("found a malformed sweep position in the run context of {original_run_id}"),
+        )?),
+        _ => None,
+    };
+    let resources: Option<ResourceProfileConfig> = match run_context.get("resources") {
+        Some(resources) if !resources.is_null() => {
+            Some(serde_json::from_value(resources.clone()).context(format!(
+                "found malformed resources in the run context of {original_run_id}"
+            ))?)
+        }
+        _ => None,
+    };
+    let tags: Vec<RunTag> = match run_context.get("tags") {
+        Some(tags) if !tags.is_null() => {
+            serde_json::from_value::<HashMap<String, String>>(tags.clone())
+                .context(format!(
+                    "found malformed tags in the run context of {original_run_id}"
+                ))?
+                .into_iter()
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+    let parent = run_context
+        .get("parent")
+        .filter(|parent| !parent.is_null())
+        .map(|parent| {
+            let parent_id = serde_json::from_value::<RunID>(parent["run_id"].clone()).context(
+                format!("found a malformed parent in the run context of {original_run_id}"),
+            )?;
+            let link = parent
+                .get("link_path")
+                .is_some_and(|link_path| !link_path.is_null());
+            ParentRunInfo::read(&*host, &parent_id, link)
+                .context(format!("failed to look up parent run {parent_id}"))
+        })
+        .transpose()?;
+
+    let mut payload_mapping = build_payload_mapping(
+        &config.payload,
+        Some(&reproduce_info_dir.utf8_path().join("config")),
+        &Vec::new(),
+        &payload_bundles,
+    )
+    .context("failed to build payload mapping")
+    .exit_status(ExitStatus::Config)?;
+    payload_mapping
+        .omit_code(&omitted_code)
+        .context(format!(
+            "failed to leave out the code mappings {original_run_id} omitted"
+        ))
+        .exit_status(ExitStatus::Config)?;
+    for code_mapping in payload_mapping.code_mappings.iter_mut() {
+        let revision = code_versions.get(&code_mapping.id).ok_or(anyhow!(
+            "{original_run_id} used the local state of `{}' instead of a pinned revision, \
+            so it cannot be reproduced",
+            code_mapping.id
+        ))?;
+        match &mut code_mapping.source {
+            CodeSource::Remote { git_revision, .. } => *git_revision = revision.clone(),
+            CodeSource::Local { .. } => {
+                unreachable!("expected code sources to be remote without ignored revisions")
+            }
+        }
+    }
+
+    warn_on_template_change(&*host, &original_run_id);
+    println!("Reproducing {original_run_id} as {run_id}...");
+    let runner = build_runner(&remainder, config.runner, None, None);
+    let run_info = RunInfo::new(
+        &*host,
+        &*runner,
+        &payload_mapping,
+        resources.as_ref(),
+        &tags,
+        sweep,
+        &run_id,
+    )
+    .with_parent(parent);
+    submit(
+        &*host,
+        &*runner,
+        &payload_mapping,
+        &run_info,
+        &SubmitOptions {
+            keep_payload: config.run_output.keep_payload.unwrap_or(false),
+            output_permissions: config.run_output.permissions.clone(),
+            config_upload_delete: config_upload_delete(config.config_upload.as_ref()),
+            ..Default::default()
+        },
+    )
+}
+
+fn reproduction_run_id(original_run_id: &RunID, existing_run_ids: &[RunID]) -> RunID {
+    (1..)
+        .map(|index| {
+            RunID::new(
+                format!("{}-repro-{index}", original_run_id.name),
+                original_run_id.group.clone(),
+            )
+        })
+        .find(|run_id| !existing_run_ids.contains(run_id))
+        .expect("expected to find an unused reproduction run name")
+}
+
+fn read_code_versions(path: &Path) -> Result<HashMap<String, String>> {
+    if !path.exists() {
+        bail!("{path} does not exist");
+    }
+
+    std::fs::read_to_string(path)
+        .context(format!("failed to read {path}"))?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (code_source_id, version) = line
+                .split_once(" = ")
+                .ok_or(anyhow!("found malformed line `{line}' in {path}"))?;
+            Ok((code_source_id.to_owned(), version.to_owned()))
+        })
+        .collect()
+}
+
+/// Read the run context recorded for the run `run_id`, where runs without a recorded context are
+/// assumed to have used no payload bundles and to have no parent
+fn read_run_context(run_context_path: &Path, run_id: &RunID) -> Result<serde_json::Value> {
+    if !run_context_path.exists() {
+        eprintln!(
+            "warning: {run_id} has no recorded run context, it was most likely submitted by an \
+            older version of sparrow"
+        );
+        return Ok(serde_json::Value::Null);
+    }
+
+    let mut run_context: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(run_context_path)
+            .context(format!("failed to read {run_context_path}"))?,
+    )
+    .context(format!("failed to parse {run_context_path}"))?;
+    migrate_run_context(&mut run_context, run_id);
+
+    Ok(run_context)
+}
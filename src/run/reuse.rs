@@ -0,0 +1,105 @@
+use super::{
+    build_runner, enforce_unique_running_run, read_recorded_run_context, record_submission_time,
+};
+use crate::host::rsync::SyncOptions;
+use crate::host::{build_host, resolve_run_id, HostPurpose, RunDirectory, ENVIRONMENT_FILE_NAME};
+use crate::utils::Utf8Path;
+use crate::GlobalConfig;
+use anyhow::{bail, Context, Result};
+use camino::Utf8PathBuf as PathBuf;
+
+/// Execute the run script again in the existing run directory of the run `run`, given as
+/// `<group>/<name>`, without uploading any code or config; with `update_run_script`, the run
+/// script is first re-rendered against the recorded run context and uploaded in place of the
+/// previous one
+pub fn reuse_run_directory(
+    run: String,
+    host: Option<String>,
+    enforce_quick: bool,
+    remainder: Vec<String>,
+    update_run_script: bool,
+    detach: bool,
+    config: GlobalConfig,
+) -> Result<()> {
+    let host = host
+        .or(config.default_host.clone())
+        .unwrap_or(String::from("local"));
+
+    println!("Connect to host...");
+    let host = build_host(&host, &config, HostPurpose::Submission, enforce_quick)
+        .context(format!("failed to build {host} as host"))?;
+    if host.is_local() {
+        bail!("cannot reuse run directories on the local host, since they are temporary");
+    }
+    let run_id = resolve_run_id(
+        &run,
+        &host
+            .runs()
+            .context(format!("failed to obtain runs from {}", host.id()))?,
+    )?;
+
+    let run_dir_record = host
+        .read_file_head(&host.run_dir_file_destination_path(&run_id), 4096)
+        .context(format!(
+            "{run_id} on {} has no recorded run directory, it was most likely \
+            submitted by an older version of sparrow",
+            host.id()
+        ))?;
+    let run_dir_path = PathBuf::from(
+        String::from_utf8(run_dir_record)
+            .context(format!(
+                "found non-valid utf8 in run directory record of {run_id}"
+            ))?
+            .trim(),
+    );
+    if !host.exists(&run_dir_path)? {
+        bail!(
+            "run directory {run_dir_path} of {run_id} does not exist anymore on {}",
+            host.id()
+        );
+    }
+
+    enforce_unique_running_run(&*host, &run_id)?;
+
+    let runner = build_runner(&remainder, config.runner, None, None);
+
+    if update_run_script {
+        let mut context = read_recorded_run_context(&*host, &run_id)?;
+        // the runner reflects the current command line, so that arguments can be changed
+        context["runner"] =
+            serde_json::to_value(runner.info()).context("failed to serialize runner info")?;
+
+        println!("Updating run script in {run_dir_path}...");
+        let run_script =
+            runner.create_run_script_from_context(minijinja::Value::from_serialize(context));
+        runner
+            .validate_run_script(&run_script)
+            .context("found an invalid run script")?;
+        host.put(
+            run_script.utf8_path(),
+            &run_dir_path.join("run.sh"),
+            SyncOptions::default(),
+        );
+        if let Some(environment_file) = runner
+            .create_environment_file()
+            .context("failed to create the environment file of the run")?
+        {
+            host.put(
+                environment_file.utf8_path(),
+                &run_dir_path.join(ENVIRONMENT_FILE_NAME),
+                SyncOptions::default(),
+            );
+        }
+    }
+
+    record_submission_time(&*host, &run_id).context("failed to record the submission time")?;
+    println!("Execute run in {run_dir_path}...");
+    runner.run(
+        &*host,
+        &RunDirectory::Remote(run_dir_path),
+        &run_id,
+        !detach,
+    );
+
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use super::default::{
+    build_template_context, RUN_POST_SCRIPT_TEMPLATE_PATH, RUN_PRE_SCRIPT_TEMPLATE_PATH,
+    RUN_SCRIPT_TEMPLATE_PATH,
+};
+use super::RunInfo;
+use crate::host::rsync::SyncOptions;
+use crate::host::{Host, RunID};
+use crate::utils::Utf8Path;
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+const MAX_RUN_SCRIPT_SIZE: u64 = 16 * 1024 * 1024;
+const MAX_FINGERPRINTS_FILE_SIZE: u64 = 4096;
+
+/// Checksums recorded with the run script of a run, by which a later submission can tell whether
+/// the run script templates changed in the meantime
+pub struct RunScriptFingerprints {
+    /// Checksum of the rendered run script
+    script: String,
+    /// Checksum of the run script templates
+    template: String,
+    /// Checksum of the run script templates together with the template context
+    template_context: String,
+}
+
+fn sha256(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Checksum of the run script templates of the current project, where each existing template
+/// contributes its path and content
+pub fn template_fingerprint() -> Result<String> {
+    let mut template_content = Vec::new();
+    for path in [
+        RUN_SCRIPT_TEMPLATE_PATH,
+        RUN_PRE_SCRIPT_TEMPLATE_PATH,
+        RUN_POST_SCRIPT_TEMPLATE_PATH,
+    ] {
+        if !std::path::Path::new(path).exists() {
+            continue;
+        }
+        template_content.extend_from_slice(format!("{path}\n").as_bytes());
+        template_content.extend(std::fs::read(path).context(format!("failed to read {path}"))?);
+    }
+
+    Ok(sha256(&template_content))
+}
+
+impl RunScriptFingerprints {
+    pub fn new(run_script: &NamedTempFile, run_info: &RunInfo) -> Result<Self> {
+        let script = std::fs::read(run_script.path()).context("failed to read the run script")?;
+        let template = template_fingerprint()?;
+        let context = serde_json::to_string(&build_template_context(run_info))
+            .context("failed to serialize the run context")?;
+
+        Ok(RunScriptFingerprints {
+            script: sha256(&script),
+            template_context: sha256(format!("{template}\n{context}").as_bytes()),
+            template,
+        })
+    }
+
+    /// Parse the `<key> = <checksum>` lines of a fingerprints file
+    fn parse(content: &str) -> Option<Self> {
+        let field = |key: &str| {
+            content
+                .lines()
+                .filter_map(|line| line.split_once(" = "))
+                .find(|(line_key, _)| *line_key == key)
+                .map(|(_, checksum)| checksum.trim().to_owned())
+        };
+
+        Some(RunScriptFingerprints {
+            script: field("script")?,
+            template: field("template")?,
+            template_context: field("template_context")?,
+        })
+    }
+
+    fn to_text(&self) -> String {
+        format!(
+            "script = {}\ntemplate = {}\ntemplate_context = {}\n",
+            self.script, self.template, self.template_context
+        )
+    }
+}
+
+/// Store a copy of the rendered run script of `run_info` together with its fingerprints in the
+/// reproduce info of the run, so that the exact script of a past run can be looked up
+pub fn record_run_script(
+    host: &dyn Host,
+    run_script: &NamedTempFile,
+    run_info: &RunInfo,
+    run_id: &RunID,
+) -> Result<()> {
+    let fingerprints = RunScriptFingerprints::new(run_script, run_info)?;
+    let mut fingerprints_file =
+        NamedTempFile::new().context("failed to create temporary fingerprints file")?;
+    fingerprints_file
+        .write_all(fingerprints.to_text().as_bytes())
+        .context("failed to write run script fingerprints")?;
+
+    host.put(
+        run_script.utf8_path(),
+        &host.run_script_file_destination_path(run_id),
+        SyncOptions::default(),
+    );
+    host.put(
+        fingerprints_file.utf8_path(),
+        &host.run_script_fingerprints_file_destination_path(run_id),
+        SyncOptions::default(),
+    );
+
+    Ok(())
+}
+
+/// Fingerprints recorded with the run script of `run_id`, or `None` if it was submitted by an
+/// older version of sparrow
+pub fn read_recorded_fingerprints(
+    host: &dyn Host,
+    run_id: &RunID,
+) -> Result<Option<RunScriptFingerprints>> {
+    let fingerprints_path = host.run_script_fingerprints_file_destination_path(run_id);
+    if !host.exists(&fingerprints_path)? {
+        return Ok(None);
+    }
+
+    let content = host
+        .read_file_head(&fingerprints_path, MAX_FINGERPRINTS_FILE_SIZE)
+        .context(format!("failed to read {fingerprints_path}"))?;
+    Ok(RunScriptFingerprints::parse(&String::from_utf8_lossy(
+        &content,
+    )))
+}
+
+/// Warn if the run script templates of the project changed since `run_id` was submitted, since a
+/// resubmission of it would then run a different script
+pub fn warn_on_template_change(host: &dyn Host, run_id: &RunID) {
+    let recorded = match read_recorded_fingerprints(host, run_id) {
+        Ok(Some(recorded)) => recorded,
+        Ok(None) => return,
+        Err(err) => {
+            eprintln!("warning: failed to compare the run script templates with {run_id}: {err:#}");
+            return;
+        }
+    };
+    match template_fingerprint() {
+        Ok(template) if template != recorded.template => eprintln!(
+            "warning: the run script templates changed since {run_id} was submitted, see \
+            its run script with `sparrow run-script show --host {}'",
+            host.id()
+        ),
+        Ok(_) => {}
+        Err(err) => {
+            eprintln!("warning: failed to compare the run script templates with {run_id}: {err:#}")
+        }
+    }
+}
+
+/// Print the run script recorded for `run_id` on `host`
+pub fn show_run_script(host: &dyn Host, run_id: &RunID) -> Result<()> {
+    let run_script_path = host.run_script_file_destination_path(run_id);
+    if !host.exists(&run_script_path)? {
+        bail!(
+            "{run_id} on {} has no recorded run script, it was most likely \
+            submitted by an older version of sparrow",
+            host.id()
+        );
+    }
+
+    let content = host
+        .read_file_head(&run_script_path, MAX_RUN_SCRIPT_SIZE)
+        .context(format!("failed to read {run_script_path}"))?;
+    print!("{}", String::from_utf8_lossy(&content));
+
+    Ok(())
+}
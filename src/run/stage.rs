@@ -0,0 +1,329 @@
+use super::default::build_template_context;
+use super::script::record_run_script;
+use super::{
+    build_step_environment, collect_code_versions, enforce_concurrency_limit,
+    enforce_unique_running_run, launch_run, record_environment_lock, record_run_context,
+    record_submodule_diffs, record_tags, RunInfo, Runner, SubmissionRollback, SubmitOptions,
+};
+use crate::cfg::{ConfigUploadDelete, RemoteHostConfig, ReviewMode};
+use crate::exit::{ExitStatus, WithExitStatus};
+use crate::host::facts::HostFacts;
+use crate::host::local::LocalHost;
+use crate::host::manifest::{Manifest, MANIFEST_FILE_NAME};
+use crate::host::rsync::{copy_directory, SyncOptions};
+use crate::host::{
+    payload_mapping_targets, prepare_payload_directory, review_config_directory, Host, HostInfo,
+    RunDirectory, RunID, ENVIRONMENT_FILE_NAME,
+};
+use crate::payload::{PayloadBuild, PayloadMapping};
+use crate::pin;
+use crate::utils::Utf8Path;
+use anyhow::{bail, Context, Result};
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use std::collections::{BTreeMap, HashMap};
+use tempfile::{NamedTempFile, TempDir};
+
+/// Runs are staged below this directory as `<host-id>/<group>/<name>`, where the reproduce info
+/// is laid out like in a run output, so that it can be uploaded as it is
+const STAGED_DIR_PATH: &str = ".sparrow/staged";
+const STAGED_RUN_FILE_NAME: &str = "staged.json";
+const STAGED_PAYLOAD_DIR_NAME: &str = "payload";
+
+/// What is needed to upload a staged payload besides the payload itself
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StagedRun {
+    staged: String,
+    /// Names and target paths of the code and auxiliary mappings in the payload
+    mappings: Vec<(String, PathBuf)>,
+    /// Target paths of the auxiliary mappings, whose files may be deduplicated by the host
+    auxiliary_target_paths: Vec<PathBuf>,
+}
+
+/// The staging area of `host_id` as a local host, so that the reproduce info of staged runs is
+/// written by the same code that writes it on hosts
+fn staging_host(host_id: &str) -> LocalHost {
+    LocalHost::new(
+        &PathBuf::from(STAGED_DIR_PATH).join(host_id),
+        String::from("bash {}"),
+        BTreeMap::new(),
+    )
+}
+
+/// Runs staged for the host `host_id`
+pub fn staged_runs(host_id: &str) -> Result<Vec<RunID>> {
+    if !PathBuf::from(STAGED_DIR_PATH).join(host_id).exists() {
+        return Ok(Vec::new());
+    }
+    staging_host(host_id).runs()
+}
+
+/// Describe the remote host `host_id` by its configuration and the facts cached when it was last
+/// reached, so that run scripts can be rendered for it without connecting to it
+pub fn offline_host_info(host_id: &str, remote_config: &RemoteHostConfig) -> Result<HostInfo> {
+    let output_base_dir_path = &remote_config.run_output_base_dir;
+    if output_base_dir_path.as_str().starts_with('~') || output_base_dir_path.as_str().contains('$')
+    {
+        bail!(
+            "cannot stage runs for {host_id}, since its run_output_base_dir \
+            {output_base_dir_path} can only be expanded on the host"
+        );
+    }
+
+    Ok(HostInfo {
+        id: host_id.to_owned(),
+        hostname: remote_config.hostname.clone(),
+        run_output_base_dir_path: output_base_dir_path.clone(),
+        is_local: false,
+        is_configured_for_quick_run: false,
+        modules: remote_config
+            .modules
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .collect(),
+        facts: HostFacts::last_cached(&remote_config.hostname).unwrap_or_else(|| {
+            eprintln!("warning: exposing no facts of {host_id} to templates, none are cached yet");
+            HostFacts::default()
+        }),
+    })
+}
+
+/// Prepare the payload of `run_info`, render its run script and review its config into the
+/// staging area of its host, without connecting to the host; `confirm` asks whether to stage the
+/// reviewed config
+pub fn stage(
+    runner: &dyn Runner,
+    payload_mapping: &PayloadMapping,
+    run_info: &RunInfo,
+    review_mode: ReviewMode,
+    confirm: bool,
+) -> Result<()> {
+    let run_id = &run_info.id;
+    let host_id = &run_info.host.id;
+    let staging = staging_host(host_id);
+    let staged_path = run_id.path(staging.output_base_dir_path());
+    if staged_path.exists() {
+        bail!(
+            "{run_id} is already staged for {host_id} in {staged_path}, push it with \
+            `sparrow push-staged --host {host_id}' or remove it first"
+        );
+    }
+
+    let run_script = runner.create_run_script(run_info);
+    runner
+        .validate_run_script(&run_script)
+        .context("found an invalid run script")?;
+
+    std::fs::create_dir_all(&staged_path).context(format!("failed to create {staged_path}"))?;
+    let result = (|| -> Result<()> {
+        println!(
+            "Copying config to staging area from `{}'...",
+            payload_mapping.config_source.dir_path
+        );
+        let review_dir = TempDir::new().context("failed to create temporary review directory")?;
+        review_config_directory(
+            &payload_mapping.config_source,
+            review_dir.utf8_path(),
+            review_mode,
+            confirm.then_some("stage reviewed config?"),
+            build_template_context(run_info),
+        )?;
+        let code_versions = collect_code_versions(payload_mapping)?;
+        staging.prepare_config_directory(
+            review_dir.utf8_path(),
+            run_id,
+            code_versions.versions,
+            ConfigUploadDelete::Keep,
+        )?;
+        if !code_versions.submodule_diffs.is_empty() {
+            record_submodule_diffs(&staging, &code_versions.submodule_diffs, run_id)
+                .context("failed to record the submodule diffs")?;
+        }
+        record_run_context(&staging, run_info, run_id)
+            .context("failed to record the run context")?;
+        record_run_script(&staging, &run_script, run_info, run_id)
+            .context("failed to record the run script")?;
+        if let Some(environment_lock) = &payload_mapping.environment_lock {
+            record_environment_lock(&staging, environment_lock, run_id)
+                .context("failed to record the environment lock")?;
+        }
+        if !run_info.tags.is_empty() {
+            record_tags(&staging, &run_info.tags, run_id)
+                .context("failed to record the run tags")?;
+        }
+
+        println!("Copying code to staging area from...");
+        for code_mapping in &payload_mapping.code_mappings {
+            println!("    {}: {}", code_mapping.id, code_mapping.source);
+        }
+        let payload_build = PayloadBuild {
+            steps: &payload_mapping.build_steps,
+            environment: build_step_environment(run_info)?,
+        };
+        let staged_payload_path = staged_path.join(STAGED_PAYLOAD_DIR_NAME);
+        std::fs::create_dir_all(&staged_payload_path)
+            .context(format!("failed to create {staged_payload_path}"))?;
+        prepare_payload_directory(
+            &payload_mapping.code_mappings,
+            &payload_mapping.auxiliary_mappings,
+            &payload_build,
+            run_script.utf8_path(),
+            &staged_payload_path,
+        )?;
+        // captured now, so that the pushed run gets the environment it was staged with
+        if let Some(environment_file) = runner
+            .create_environment_file()
+            .context("failed to create the environment file of the run")?
+        {
+            let staged_environment_path = staged_path.join(ENVIRONMENT_FILE_NAME);
+            std::fs::copy(&environment_file, &staged_environment_path)
+                .context(format!("failed to write {staged_environment_path}"))?;
+        }
+
+        let staged_run = StagedRun {
+            staged: humantime::format_rfc3339_seconds(std::time::SystemTime::now()).to_string(),
+            mappings: payload_mapping_targets(
+                &payload_mapping.code_mappings,
+                &payload_mapping.auxiliary_mappings,
+            ),
+            auxiliary_target_paths: payload_mapping
+                .auxiliary_mappings
+                .iter()
+                .map(|auxiliary_mapping| auxiliary_mapping.target_path.clone())
+                .collect(),
+        };
+        let staged_run_path = staged_path.join(STAGED_RUN_FILE_NAME);
+        std::fs::write(
+            &staged_run_path,
+            serde_json::to_string_pretty(&staged_run).expect("expected staged run to serialize"),
+        )
+        .context(format!("failed to write {staged_run_path}"))
+    })();
+    if let Err(err) = result {
+        std::fs::remove_dir_all(&staged_path).context(format!("failed to remove {staged_path}"))?;
+        return Err(err);
+    }
+
+    println!(
+        "Staged {run_id} in {staged_path}, push it with `sparrow push-staged --host {host_id}'"
+    );
+    Ok(())
+}
+
+/// Upload the staged run `run_id` to `host` and launch it, removing it from the staging area
+/// once it is launched, where `options` are expected to detach from the run, so that the staging
+/// area is cleaned up before attaching to it
+pub fn push_staged(
+    host: &dyn Host,
+    runner: &dyn Runner,
+    run_id: &RunID,
+    options: &SubmitOptions,
+) -> Result<()> {
+    let staging = staging_host(host.id());
+    let staged_path = run_id.path(staging.output_base_dir_path());
+    let staged_run_path = staged_path.join(STAGED_RUN_FILE_NAME);
+    let staged_run = std::fs::read_to_string(&staged_run_path)
+        .context(format!("failed to read {staged_run_path}"))?;
+    let staged_run: StagedRun =
+        serde_json::from_str(&staged_run).context(format!("failed to parse {staged_run_path}"))?;
+    println!("Push {run_id}, staged at {}...", staged_run.staged);
+
+    host.check_execution().exit_status(ExitStatus::Config)?;
+    enforce_unique_running_run(host, run_id)?;
+    if pin::is_pinned(host, run_id)? {
+        bail!(
+            "refusing to overwrite {run_id}, since it is pinned, unpin it with `sparrow run-pin'"
+        );
+    }
+
+    let mut rollback = SubmissionRollback::new(host);
+    let run_output_path = run_id.path(host.output_base_dir_path());
+    if !host.exists(&run_output_path)? {
+        rollback.track(run_output_path.clone());
+    }
+    let creates_group = !host.exists(&host.output_base_dir_path().join(&run_id.group))?;
+
+    println!("Uploading staged config and reproduce info...");
+    host.upload_config_directory(
+        &staging.config_dir_destination_path(run_id),
+        run_id,
+        options.config_upload_delete,
+    );
+    host.put(
+        &staging.reproduce_info_path(run_id),
+        &host.reproduce_info_path(run_id),
+        SyncOptions::default()
+            .copy_contents()
+            .exclude(&vec![String::from("/config")]),
+    );
+
+    // the staged payload is kept until the run is launched, in case the upload fails
+    let payload_prep_dir =
+        TempDir::new().context("failed to create temporary payload directory")?;
+    let staged_payload_path = staged_path.join(STAGED_PAYLOAD_DIR_NAME);
+    copy_directory(
+        &staged_payload_path,
+        payload_prep_dir.utf8_path(),
+        SyncOptions::default().copy_contents(),
+    );
+    let manifest = Manifest::read(&staged_payload_path.join(MANIFEST_FILE_NAME))?;
+    let environment_file = read_staged_environment_file(&staged_path)?;
+    let (run_dir, upload_summary) = host.upload_prepared_run_directory(
+        payload_prep_dir,
+        &manifest,
+        &staged_run.mappings,
+        &staged_run.auxiliary_target_paths,
+        environment_file,
+        run_id,
+    )?;
+    if let RunDirectory::Remote(run_dir_path) = &run_dir {
+        rollback.track(run_dir_path.clone());
+    }
+
+    launch_run(
+        host,
+        runner,
+        &run_dir,
+        &upload_summary,
+        run_id,
+        creates_group,
+        options,
+    )?;
+    rollback.disarm();
+
+    std::fs::remove_dir_all(&staged_path).context(format!("failed to remove {staged_path}"))
+}
+
+/// Environment file written when the run at `staged_path` was staged, if it had an environment
+fn read_staged_environment_file(staged_path: &Path) -> Result<Option<NamedTempFile>> {
+    let staged_environment_path = staged_path.join(ENVIRONMENT_FILE_NAME);
+    if !staged_environment_path.exists() {
+        return Ok(None);
+    }
+
+    // copying keeps the permissions, so the file stays only readable by its owner
+    let environment_file =
+        NamedTempFile::new().context("failed to create temporary environment file")?;
+    std::fs::copy(&staged_environment_path, &environment_file)
+        .context(format!("failed to read {staged_environment_path}"))?;
+
+    Ok(Some(environment_file))
+}
+
+/// Push the staged runs `run_ids` to `host` one after another, checking the concurrency limit
+/// of the host before each of them
+pub fn push_staged_runs(
+    host: &dyn Host,
+    runner: &dyn Runner,
+    run_ids: &[RunID],
+    remote_configs: &HashMap<String, RemoteHostConfig>,
+    options: &SubmitOptions,
+) -> Result<()> {
+    for run_id in run_ids {
+        enforce_concurrency_limit(host, remote_configs, run_id)?;
+        push_staged(host, runner, run_id, options)
+            .context(format!("failed to push the staged run {run_id}"))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use super::RunInfo;
+use crate::host::Host;
+use crate::payload::{CodeSource, PayloadMapping};
+use crate::utils::format_size;
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+
+/// Condensed overview of a submission, which is confirmed before anything is created on the host
+pub struct SubmissionSummary {
+    host: String,
+    run_id: String,
+    parent_id: Option<String>,
+    output_path: PathBuf,
+    code_sources: Vec<(String, String)>,
+    config_dir_path: PathBuf,
+    sweep: Option<(usize, usize)>,
+    /// Size of the local sources to upload and whether remote sources add to it
+    upload_size: (u64, bool),
+}
+
+impl SubmissionSummary {
+    pub fn new(host: &dyn Host, payload_mapping: &PayloadMapping, run_info: &RunInfo) -> Self {
+        let code_sources = payload_mapping
+            .code_mappings
+            .iter()
+            .map(|code_mapping| {
+                let source = match &code_mapping.source {
+                    CodeSource::Local { path, .. } => describe_local_source(path),
+                    CodeSource::Remote {
+                        url, git_revision, ..
+                    } => format!("{url}@{git_revision}"),
+                };
+                (code_mapping.id.clone(), source)
+            })
+            .collect();
+
+        let mut upload_size = directory_size(&payload_mapping.config_source.dir_path, &[]);
+        let mut has_remote_sources = false;
+        for code_mapping in &payload_mapping.code_mappings {
+            match &code_mapping.source {
+                CodeSource::Local {
+                    path,
+                    copy_excludes,
+                    ..
+                } => upload_size += directory_size(path, copy_excludes),
+                CodeSource::Remote { .. } => has_remote_sources = true,
+            }
+        }
+        for auxiliary_mapping in &payload_mapping.auxiliary_mappings {
+            upload_size += directory_size(
+                &auxiliary_mapping.source_path,
+                &auxiliary_mapping.copy_excludes,
+            );
+        }
+
+        SubmissionSummary {
+            host: format!("{} ({})", host.id(), host.hostname()),
+            run_id: run_info.id.to_string(),
+            parent_id: run_info
+                .parent
+                .as_ref()
+                .map(|parent| parent.run_id.to_string()),
+            output_path: run_info.id.path(host.output_base_dir_path()),
+            code_sources,
+            config_dir_path: payload_mapping.config_source.dir_path.clone(),
+            sweep: run_info
+                .sweep
+                .as_ref()
+                .map(|sweep| (sweep.index, sweep.total)),
+            upload_size: (upload_size, has_remote_sources),
+        }
+    }
+}
+
+impl std::fmt::Display for SubmissionSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "host:       {}", self.host)?;
+        writeln!(f, "run:        {}", self.run_id)?;
+        if let Some(parent_id) = &self.parent_id {
+            writeln!(f, "parent:     {parent_id}")?;
+        }
+        writeln!(f, "output:     {}", self.output_path)?;
+        writeln!(f, "config dir: {}", self.config_dir_path)?;
+        match self.sweep {
+            Some((index, total)) => writeln!(f, "sweep:      run {} of {total}", index + 1)?,
+            None => writeln!(f, "sweep:      -")?,
+        }
+        let (upload_size, has_remote_sources) = self.upload_size;
+        writeln!(
+            f,
+            "upload:     ~{}{}",
+            format_size(upload_size),
+            if has_remote_sources {
+                " plus remote sources"
+            } else {
+                ""
+            }
+        )?;
+        writeln!(f, "code:")?;
+        for (id, source) in &self.code_sources {
+            writeln!(f, "  {id}: {source}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Path of a local code source with the revision of its repository and whether the working tree
+/// differs from it
+fn describe_local_source(path: &Path) -> String {
+    let Ok(repository) = git2::Repository::discover(path) else {
+        return format!("{path} (no git repository)");
+    };
+    let revision = repository
+        .head()
+        .ok()
+        .and_then(|head| head.peel_to_commit().ok())
+        .map(|commit| commit.id().to_string()[..12].to_owned())
+        .unwrap_or(String::from("no commits"));
+    let is_dirty = repository
+        .statuses(Some(git2::StatusOptions::new().include_untracked(false)))
+        .map(|statuses| !statuses.is_empty())
+        .unwrap_or(false);
+
+    format!(
+        "{path}@{revision}{}",
+        if is_dirty { " (dirty)" } else { "" }
+    )
+}
+
+/// Total size of the files below `path`, skipping entries whose name is one of `excludes`, which
+/// approximates the rsync exclude patterns of the upload
+fn directory_size(path: &Path, excludes: &[String]) -> u64 {
+    let is_excluded = |name: &str| {
+        excludes
+            .iter()
+            .any(|exclude| exclude.trim_matches('/') == name)
+    };
+
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0 || !is_excluded(&entry.file_name().to_string_lossy())
+        })
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
@@ -0,0 +1,90 @@
+use crate::cfg::{ConfigSourceConfig, ReviewMode};
+use crate::host::rsync::{copy_directory, SyncOptions};
+use crate::host::{check_config_syntax, review_config};
+use anyhow::{bail, Context, Result};
+use camino::Utf8PathBuf as PathBuf;
+
+const CONFIG_SNAPSHOTS_DIR_PATH: &str = ".sparrow/config_snapshots";
+
+/// Path of the snapshot `name`, which has to exist
+pub fn snapshot_path(name: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(CONFIG_SNAPSHOTS_DIR_PATH).join(name);
+    if !path.is_dir() {
+        bail!("found no config snapshot `{name}' in {CONFIG_SNAPSHOTS_DIR_PATH}");
+    }
+
+    Ok(path)
+}
+
+/// Store the current payload config directory as snapshot `name`, reviewing the snapshot right
+/// away so that runs using it later do not have to be reviewed again
+pub fn save(name: &str, review_mode: ReviewMode, config_source: &ConfigSourceConfig) -> Result<()> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        bail!(
+            "invalid snapshot name `{name}', it must not be empty, contain `/' or start with `.'"
+        );
+    }
+
+    let path = PathBuf::from(CONFIG_SNAPSHOTS_DIR_PATH).join(name);
+    if path.exists() {
+        bail!("refusing to save config snapshot `{name}', since {path} already exists");
+    }
+    std::fs::create_dir_all(&path).context(format!("failed to create {path}"))?;
+
+    copy_directory(
+        &config_source.dir,
+        &path,
+        SyncOptions::default().copy_contents().resolve_symlinks(),
+    );
+
+    if let Err(err) = review_config(
+        &path,
+        &config_source.entrypoints,
+        review_mode,
+        Some("save reviewed config snapshot?"),
+    ) {
+        std::fs::remove_dir_all(&path).context(format!("failed to remove {path}"))?;
+        return Err(err);
+    }
+    for entrypoint_path in &config_source.entrypoints {
+        check_config_syntax(&path.join(entrypoint_path)).context(format!(
+            "found a syntax error in the config entrypoint {entrypoint_path} of snapshot `{name}'"
+        ))?;
+    }
+
+    Ok(())
+}
+
+pub fn list() -> Result<()> {
+    let snapshots_path = PathBuf::from(CONFIG_SNAPSHOTS_DIR_PATH);
+    if !snapshots_path.exists() {
+        return Ok(());
+    }
+
+    let mut names = std::fs::read_dir(&snapshots_path)
+        .context(format!("failed to read {snapshots_path}"))?
+        .map(|entry| {
+            let entry = entry.context(format!("failed to read {snapshots_path}"))?;
+            Ok(entry.file_name().to_string_lossy().into_owned())
+        })
+        .collect::<Result<Vec<_>>>()?;
+    names.sort();
+
+    for name in names {
+        println!("{name}");
+    }
+
+    Ok(())
+}
+
+/// Copy the snapshot `name` back into the payload config directory, so that it becomes the
+/// working config again
+pub fn restore(name: &str, config_source: &ConfigSourceConfig) -> Result<()> {
+    copy_directory(
+        &snapshot_path(name)?,
+        &config_source.dir,
+        SyncOptions::default().copy_contents(),
+    );
+
+    Ok(())
+}
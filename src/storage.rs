@@ -0,0 +1,220 @@
+use crate::host::disk_usage::FilesystemUsage;
+use crate::host::local::cache_dir_path;
+use crate::host::{ensure_run_group_exists, Host, RunID};
+use crate::utils::format_size;
+use anyhow::{Context, Result};
+use camino::Utf8PathBuf as PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::time::{Duration, SystemTime};
+
+/// Cached run sizes are measured again once they are older than this
+const DISK_USAGE_CACHE_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Size of a run output in bytes as measured at `time`
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CachedRunSize {
+    size: u64,
+    time: String,
+}
+
+impl CachedRunSize {
+    fn is_fresh(&self) -> bool {
+        humantime::parse_rfc3339(&self.time)
+            .ok()
+            .and_then(|time| time.elapsed().ok())
+            .is_some_and(|age| age < DISK_USAGE_CACHE_MAX_AGE)
+    }
+}
+
+fn disk_usage_cache_path(host_id: &str) -> PathBuf {
+    cache_dir_path()
+        .join("disk_usage")
+        .join(format!("{host_id}.json"))
+}
+
+/// Run sizes of the host `host_id` by run, as measured by previous reports
+fn read_cached_run_sizes(host_id: &str) -> BTreeMap<String, CachedRunSize> {
+    std::fs::read_to_string(disk_usage_cache_path(host_id))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn write_cached_run_sizes(
+    host_id: &str,
+    run_sizes: &BTreeMap<String, CachedRunSize>,
+) -> Result<()> {
+    let cache_path = disk_usage_cache_path(host_id);
+    let cache_parent_path = cache_path
+        .parent()
+        .expect("expected disk usage cache path to have a parent");
+    std::fs::create_dir_all(cache_parent_path)
+        .context(format!("failed to create {cache_parent_path}"))?;
+    std::fs::write(
+        &cache_path,
+        serde_json::to_string_pretty(run_sizes).expect("expected run sizes to be serializable"),
+    )
+    .context(format!("failed to write {cache_path}"))
+}
+
+/// Disk usage of the run outputs on a host, by run and by group, together with the usage of the
+/// file system they are stored on
+pub struct StorageReport {
+    /// Sizes of the runs in bytes, largest first
+    run_sizes: Vec<(RunID, u64)>,
+    /// Number of runs whose size was taken from the cache
+    cached_count: usize,
+    filesystem: Option<FilesystemUsage>,
+}
+
+impl StorageReport {
+    /// Measure the runs of `host`, or only those in `group`, where sizes measured within the last
+    /// hour are taken from the cache unless `refresh` is set
+    pub fn collect(host: &dyn Host, group: Option<&str>, refresh: bool) -> Result<Self> {
+        let runs = host
+            .runs()
+            .context(format!("failed to obtain runs from {}", host.id()))?;
+        if let Some(group) = group {
+            ensure_run_group_exists(group, &runs)?;
+        }
+        let selected_runs = runs
+            .iter()
+            .filter(|run_id| group.is_none_or(|group| run_id.group == group))
+            .collect::<Vec<_>>();
+
+        let mut cached_run_sizes = read_cached_run_sizes(host.id());
+        let stale_runs = selected_runs
+            .iter()
+            .filter(|run_id| {
+                refresh
+                    || !cached_run_sizes
+                        .get(&run_id.to_string())
+                        .is_some_and(CachedRunSize::is_fresh)
+            })
+            .map(|run_id| (*run_id).clone())
+            .collect::<Vec<_>>();
+
+        let disk_usage = host
+            .disk_usage(&stale_runs)
+            .context(format!("failed to measure disk usage on {}", host.id()))?;
+        let time = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+        for (run_id, size) in &disk_usage.run_sizes {
+            cached_run_sizes.insert(
+                run_id.to_string(),
+                CachedRunSize {
+                    size: *size,
+                    time: time.clone(),
+                },
+            );
+        }
+        let existing_runs = runs.iter().map(RunID::to_string).collect::<HashSet<_>>();
+        cached_run_sizes.retain(|run, _| existing_runs.contains(run));
+        if let Err(err) = write_cached_run_sizes(host.id(), &cached_run_sizes) {
+            eprintln!("warning: failed to cache disk usage: {err:#}");
+        }
+
+        let mut run_sizes = selected_runs
+            .iter()
+            .filter_map(|run_id| {
+                cached_run_sizes
+                    .get(&run_id.to_string())
+                    .map(|cached| ((*run_id).clone(), cached.size))
+            })
+            .collect::<Vec<_>>();
+        run_sizes.sort_by(|(_, size), (_, other_size)| other_size.cmp(size));
+
+        Ok(StorageReport {
+            cached_count: run_sizes
+                .iter()
+                .filter(|(run_id, _)| !stale_runs.contains(run_id))
+                .count(),
+            run_sizes,
+            filesystem: disk_usage.filesystem,
+        })
+    }
+}
+
+impl std::fmt::Display for StorageReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut group_sizes = BTreeMap::<&str, (usize, u64)>::new();
+        for (run_id, size) in &self.run_sizes {
+            let group_size = group_sizes.entry(&run_id.group).or_default();
+            group_size.0 += 1;
+            group_size.1 += size;
+        }
+        let mut group_sizes = group_sizes.into_iter().collect::<Vec<_>>();
+        group_sizes.sort_by(|(_, (_, size)), (_, (_, other_size))| other_size.cmp(size));
+
+        let run_width = self
+            .run_sizes
+            .iter()
+            .map(|(run_id, _)| run_id.to_string().len())
+            .chain(std::iter::once("run".len()))
+            .max()
+            .unwrap_or(0);
+        writeln!(f, "{:<run_width$}  {:>10}", "run", "size")?;
+        for (run_id, size) in &self.run_sizes {
+            writeln!(
+                f,
+                "{:<run_width$}  {:>10}",
+                run_id.to_string(),
+                format_size(*size)
+            )?;
+        }
+        writeln!(f)?;
+
+        let group_width = group_sizes
+            .iter()
+            .map(|(group, _)| group.len())
+            .chain(std::iter::once("group".len()))
+            .max()
+            .unwrap_or(0);
+        writeln!(
+            f,
+            "{:<group_width$}  {:>5}  {:>10}",
+            "group", "runs", "size"
+        )?;
+        for (group, (run_count, size)) in &group_sizes {
+            writeln!(
+                f,
+                "{group:<group_width$}  {run_count:>5}  {:>10}",
+                format_size(*size)
+            )?;
+        }
+        writeln!(f)?;
+
+        let total_size = self.run_sizes.iter().map(|(_, size)| size).sum::<u64>();
+        write!(
+            f,
+            "total: {} in {} runs",
+            format_size(total_size),
+            self.run_sizes.len()
+        )?;
+        if self.cached_count > 0 {
+            write!(
+                f,
+                " ({} sizes cached, pass --refresh to remeasure)",
+                self.cached_count
+            )?;
+        }
+        writeln!(f)?;
+        if let Some(filesystem) = self.filesystem {
+            let label = if filesystem.is_quota {
+                "quota"
+            } else {
+                "file system"
+            };
+            writeln!(
+                f,
+                "{label}: {} of {} used ({:.0}%), {} available, runs take {:.1}%",
+                format_size(filesystem.used),
+                format_size(filesystem.size),
+                100.0 * filesystem.used as f64 / filesystem.size.max(1) as f64,
+                format_size(filesystem.available),
+                100.0 * total_size as f64 / filesystem.size.max(1) as f64,
+            )?;
+        }
+
+        Ok(())
+    }
+}
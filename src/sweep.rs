@@ -0,0 +1,328 @@
+use crate::exit::ExitStatus;
+use crate::queue;
+use crate::utils::confirm;
+use crate::GlobalConfig;
+use anyhow::{bail, Context, Result};
+use camino::Utf8PathBuf as PathBuf;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+
+const SWEEPS_DIR_PATH: &str = ".sparrow/sweeps";
+
+/// Parameter values of one run of a sweep by parameter name
+type SweepPoint = BTreeMap<String, String>;
+
+/// A sweep read from `.sparrow/sweeps/<name>.yaml`, whose runs are the cartesian product of the
+/// points in `list`, the combinations of `grid`, the samples of `random` and the `seeds`, where
+/// each part that is not given contributes a single empty point
+#[derive(Deserialize)]
+struct SweepSpec {
+    /// Run name of each run, in which `{<param>}` is replaced by the value of the parameter and
+    /// `{index}` by the index of the run within the sweep
+    name: Option<String>,
+    /// Additional arguments of `sparrow run` for every run of the sweep
+    args: Option<Vec<String>>,
+    list: Option<Vec<BTreeMap<String, serde_json::Value>>>,
+    grid: Option<BTreeMap<String, Vec<serde_json::Value>>>,
+    random: Option<RandomSpec>,
+    /// Values of the parameter `seed`, with which every other point is repeated
+    seeds: Option<Vec<serde_json::Value>>,
+}
+
+#[derive(Deserialize)]
+struct RandomSpec {
+    samples: usize,
+    /// Seed of the sampling, which defaults to zero, so that a sweep plans the same runs every
+    /// time it is launched
+    seed: Option<u64>,
+    params: BTreeMap<String, Distribution>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Distribution {
+    Uniform([f64; 2]),
+    LogUniform([f64; 2]),
+    IntUniform([i64; 2]),
+    Choice(Vec<serde_json::Value>),
+}
+
+impl Distribution {
+    fn validate(&self, param: &str) -> Result<()> {
+        match self {
+            Distribution::Uniform([low, high]) if low > high => {
+                bail!("found empty range [{low}, {high}] for `{param}'")
+            }
+            Distribution::LogUniform([low, high]) if *low <= 0.0 || low > high => {
+                bail!("found invalid range [{low}, {high}] for `{param}', expected 0 < low <= high")
+            }
+            Distribution::IntUniform([low, high]) if low > high => {
+                bail!("found empty range [{low}, {high}] for `{param}'")
+            }
+            Distribution::Choice(values) if values.is_empty() => {
+                bail!("found no values to choose from for `{param}'")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn sample(&self, rng: &mut fastrand::Rng) -> String {
+        match self {
+            Distribution::Uniform([low, high]) => format_float(low + rng.f64() * (high - low)),
+            Distribution::LogUniform([low, high]) => {
+                let (low, high) = (low.ln(), high.ln());
+                format_float((low + rng.f64() * (high - low)).exp())
+            }
+            Distribution::IntUniform([low, high]) => rng.i64(*low..=*high).to_string(),
+            Distribution::Choice(values) => format_value(&values[rng.usize(..values.len())]),
+        }
+    }
+}
+
+/// Format sampled floats with four significant digits, in scientific notation if they are far
+/// from one, to keep them readable in run names
+fn format_float(value: f64) -> String {
+    if value != 0.0 && !(1e-3..1e4).contains(&value.abs()) {
+        return format!("{value:.3e}");
+    }
+
+    let digits = (3 - value.abs().log10().floor() as i32).max(0) as usize;
+    let formatted = format!("{value:.digits$}");
+    if formatted.contains('.') {
+        formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_owned()
+    } else {
+        formatted
+    }
+}
+
+fn format_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Extend each of `points` by each of `values` of `param`
+fn cross(points: Vec<SweepPoint>, param: &str, values: &[String]) -> Vec<SweepPoint> {
+    points
+        .into_iter()
+        .flat_map(|point| {
+            values.iter().map(move |value| {
+                let mut point = point.clone();
+                point.insert(param.to_owned(), value.clone());
+                point
+            })
+        })
+        .collect()
+}
+
+impl SweepSpec {
+    fn read(name: &str) -> Result<Self> {
+        let path = PathBuf::from(SWEEPS_DIR_PATH).join(format!("{name}.yaml"));
+        if !path.exists() {
+            bail!("found no sweep `{name}', expected it at {path}");
+        }
+
+        // parsed directly, since the config crate would lowercase the names of the parameters, and
+        // through JSON, since serde_yaml expects distributions as YAML tags instead of keys
+        let content = std::fs::read_to_string(&path).context(format!("failed to read {path}"))?;
+        serde_yaml::from_str(&content)
+            .and_then(|spec: serde_json::Value| {
+                serde_json::from_value(spec).map_err(serde::de::Error::custom)
+            })
+            .context(format!("failed to parse {path}"))
+    }
+
+    fn points(&self) -> Result<Vec<SweepPoint>> {
+        let mut points = match &self.list {
+            Some(list) => list
+                .iter()
+                .map(|point| {
+                    point
+                        .iter()
+                        .map(|(param, value)| (param.clone(), format_value(value)))
+                        .collect()
+                })
+                .collect(),
+            None => vec![SweepPoint::new()],
+        };
+
+        for (param, values) in self.grid.iter().flatten() {
+            points = cross(
+                points,
+                param,
+                &values.iter().map(format_value).collect::<Vec<_>>(),
+            );
+        }
+
+        if let Some(random) = &self.random {
+            for (param, distribution) in &random.params {
+                distribution.validate(param)?;
+            }
+
+            let mut rng = fastrand::Rng::with_seed(random.seed.unwrap_or(0));
+            let samples = (0..random.samples)
+                .map(|_| {
+                    random
+                        .params
+                        .iter()
+                        .map(|(param, distribution)| (param.clone(), distribution.sample(&mut rng)))
+                        .collect::<SweepPoint>()
+                })
+                .collect::<Vec<_>>();
+            points = points
+                .into_iter()
+                .flat_map(|point| {
+                    samples.iter().map(move |sample| {
+                        let mut point = point.clone();
+                        point.extend(sample.clone());
+                        point
+                    })
+                })
+                .collect();
+        }
+
+        if let Some(seeds) = &self.seeds {
+            points = cross(
+                points,
+                "seed",
+                &seeds.iter().map(format_value).collect::<Vec<_>>(),
+            );
+        }
+
+        Ok(points)
+    }
+
+    fn run_name(&self, sweep_name: &str, index: usize, point: &SweepPoint) -> String {
+        let mut run_name = self
+            .name
+            .clone()
+            .unwrap_or(format!("{sweep_name}-{{index}}"))
+            .replace("{index}", &index.to_string());
+        for (param, value) in point {
+            run_name = run_name.replace(&format!("{{{param}}}"), value);
+        }
+        run_name
+    }
+}
+
+/// A run of a sweep, planned before anything is submitted
+struct PlannedRun {
+    name: String,
+    point: SweepPoint,
+}
+
+/// Plan the runs of the sweep `name`, show them and, once confirmed, submit them one after
+/// another to `host`, or add them to the queue if `use_queue` is set
+pub fn launch(
+    name: &str,
+    host: Option<String>,
+    use_queue: bool,
+    yes: bool,
+    config: &GlobalConfig,
+) -> Result<()> {
+    let spec = SweepSpec::read(name)?;
+    let planned_runs = spec
+        .points()
+        .context(format!("failed to plan the runs of `{name}'"))?
+        .into_iter()
+        .enumerate()
+        .map(|(index, point)| PlannedRun {
+            name: spec.run_name(name, index, &point),
+            point,
+        })
+        .collect::<Vec<_>>();
+    if planned_runs.is_empty() {
+        bail!("sweep `{name}' plans no runs");
+    }
+
+    let mut run_names = HashSet::new();
+    for planned_run in &planned_runs {
+        if !run_names.insert(&planned_run.name) {
+            bail!(
+                "sweep `{name}' plans the run name `{}' more than once, add parameters to its \
+                `name' to tell the runs apart",
+                planned_run.name
+            );
+        }
+    }
+
+    let name_width = planned_runs
+        .iter()
+        .map(|run| run.name.len())
+        .max()
+        .unwrap_or(0);
+    println!("{:>5}  {:<name_width$}  params", "index", "run");
+    for (index, planned_run) in planned_runs.iter().enumerate() {
+        let params = planned_run
+            .point
+            .iter()
+            .map(|(param, value)| format!("{param}={value}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!("{index:>5}  {:<name_width$}  {params}", planned_run.name);
+    }
+
+    let action = if use_queue { "Queue" } else { "Submit" };
+    if !yes && !confirm(&format!("{action} {} runs?", planned_runs.len()))? {
+        return Err(anyhow::Error::new(ExitStatus::Aborted).context("sweep was not confirmed"));
+    }
+
+    let total = planned_runs.len();
+    let mut failed_runs = Vec::new();
+    for (index, planned_run) in planned_runs.into_iter().enumerate() {
+        let mut args = vec![
+            String::from("--run-name"),
+            planned_run.name.clone(),
+            String::from("--sweep-index"),
+            index.to_string(),
+            String::from("--sweep-total"),
+            total.to_string(),
+        ];
+        if let Some(host) = &host {
+            args.extend([String::from("--host"), host.clone()]);
+        }
+        for (param, value) in &planned_run.point {
+            args.extend([String::from("--sweep-param"), format!("{param}={value}")]);
+        }
+        args.extend(spec.args.iter().flatten().cloned());
+
+        if use_queue {
+            queue::add(args, config).context(format!("failed to queue {}", planned_run.name))?;
+            continue;
+        }
+
+        println!(
+            "Submitting {} ({} of {total})...",
+            planned_run.name,
+            index + 1
+        );
+        let sparrow_path =
+            std::env::current_exe().context("failed to locate the sparrow executable")?;
+        let status = std::process::Command::new(&sparrow_path)
+            .arg("run")
+            .arg("--no-config-review")
+            .arg("--detach")
+            .arg("--yes")
+            .args(&args)
+            .status()
+            .context(format!("failed to run {}", sparrow_path.display()))?;
+        if !status.success() {
+            eprintln!("warning: submitting {} failed", planned_run.name);
+            failed_runs.push(planned_run.name);
+        }
+    }
+
+    if !failed_runs.is_empty() {
+        bail!(
+            "failed to submit {} of {total} runs: {}",
+            failed_runs.len(),
+            failed_runs.join(", ")
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use crate::cfg::UserOverlayConfig;
+use anyhow::{bail, Context, Result};
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use config::builder::DefaultState;
+use config::{Config, ConfigBuilder, File, FileFormat};
+use std::collections::HashMap;
+
+const TEAM_CONFIG_PATH: &str = ".sparrow/team.yaml";
+const USER_CONFIGS_DIR_PATH: &str = ".sparrow/users";
+
+/// Add the shared team configuration and the overlay of the user to `builder`, where the user is
+/// `as_user` or otherwise `$USER`; both files are optional unless the user was given explicitly
+pub fn add_team_sources(
+    builder: ConfigBuilder<DefaultState>,
+    as_user: Option<&str>,
+) -> Result<ConfigBuilder<DefaultState>> {
+    let user = match as_user {
+        Some(user) => Some(user.to_owned()),
+        None => std::env::var("USER").ok(),
+    };
+    let overlay_path = user
+        .as_ref()
+        .map(|user| PathBuf::from(USER_CONFIGS_DIR_PATH).join(format!("{user}.yaml")));
+    let overlay_path = overlay_path.filter(|path| path.exists());
+    if let (Some(user), None) = (as_user, &overlay_path) {
+        bail!("found no configuration of user `{user}' in {USER_CONFIGS_DIR_PATH}");
+    }
+
+    let mut variables = match &overlay_path {
+        Some(path) => read_overlay(path)?.variables.unwrap_or_default(),
+        None => HashMap::new(),
+    };
+    if let Some(user) = user {
+        variables.entry("user".to_owned()).or_insert(user);
+    }
+
+    let mut builder = builder;
+    if has_team_config() {
+        let mut team_config: serde_json::Value = Config::builder()
+            .add_source(File::new(TEAM_CONFIG_PATH, FileFormat::Yaml))
+            .build()
+            .and_then(|config| config.try_deserialize())
+            .context(format!("failed to read {TEAM_CONFIG_PATH}"))?;
+        substitute_variables(&mut team_config, &variables);
+
+        builder = builder.add_source(
+            Config::try_from(&team_config).context(format!("failed to read {TEAM_CONFIG_PATH}"))?,
+        );
+    }
+    if let Some(path) = overlay_path {
+        builder = builder.add_source(File::new(path.as_str(), FileFormat::Yaml));
+    }
+
+    Ok(builder)
+}
+
+/// Whether the project shares a team configuration, in which case the team configuration and the
+/// overlay of the user can take the place of `.sparrow/private.yaml`
+pub fn has_team_config() -> bool {
+    Path::new(TEAM_CONFIG_PATH).exists()
+}
+
+fn read_overlay(path: &Path) -> Result<UserOverlayConfig> {
+    Config::builder()
+        .add_source(File::new(path.as_str(), FileFormat::Yaml))
+        .build()
+        .and_then(|config| config.try_deserialize())
+        .context(format!("failed to read {path}"))
+}
+
+/// Replace `{<name>}` in all strings of `value` by the variable `name`, leaving placeholders of
+/// unknown variables, like `{rand}` of run directory templates, untouched
+fn substitute_variables(value: &mut serde_json::Value, variables: &HashMap<String, String>) {
+    match value {
+        serde_json::Value::String(string) => {
+            *string = substitute_placeholders(string, variables);
+        }
+        serde_json::Value::Array(values) => {
+            for value in values {
+                substitute_variables(value, variables);
+            }
+        }
+        serde_json::Value::Object(values) => {
+            for value in values.values_mut() {
+                substitute_variables(value, variables);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn substitute_placeholders(string: &str, variables: &HashMap<String, String>) -> String {
+    let mut substituted = String::new();
+    let mut rest = string;
+    while let Some(start) = rest.find('{') {
+        substituted.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+
+        // the configuration lowercases all keys, so the variable names are lowercase as well
+        match variables.get(&rest[1..end].to_lowercase()) {
+            Some(variable) => substituted.push_str(variable),
+            None => substituted.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    substituted.push_str(rest);
+
+    substituted
+}
@@ -0,0 +1,101 @@
+use crate::host::rsync::SyncOptions;
+use crate::host::{Host, RunID};
+use crate::utils::Utf8Path;
+use anyhow::{bail, Context, Result};
+use tempfile::TempDir;
+
+/// Copy the output of `run_id` from `source` to `destination`, directly from the source host if
+/// it reaches the destination host via ssh without a password prompt, and relayed through the
+/// local machine otherwise or if `relay` is set
+pub fn transfer_run_output(
+    source: &dyn Host,
+    destination: &dyn Host,
+    run_id: &RunID,
+    relay: bool,
+) -> Result<()> {
+    // a recorded exit code means the run is done even if its session is kept open
+    if source.running_runs().contains(run_id) && source.recorded_exit_code(run_id).is_none() {
+        bail!("{run_id} is still running on {}", source.id());
+    }
+    let source_path = run_id.path(source.output_base_dir_path());
+    let destination_path = run_id.path(destination.output_base_dir_path());
+    if destination.exists(&destination_path)? {
+        bail!(
+            "{run_id} exists already on {}, refusing to overwrite it",
+            destination.id()
+        );
+    }
+    destination.create_dir_all(&destination.output_base_dir_path().join(&run_id.group));
+
+    if !relay {
+        println!(
+            "Copy {run_id} from {} to {} directly...",
+            source.id(),
+            destination.id()
+        );
+        match source.copy_dir_to_host(&source_path, destination.hostname(), &destination_path) {
+            Ok(()) => return Ok(()),
+            Err(err) => eprintln!(
+                "warning: failed to copy {run_id} directly, relaying it through the local \
+                machine instead: {err:#}"
+            ),
+        }
+    }
+
+    println!(
+        "Relay {run_id} from {} to {} through the local machine...",
+        source.id(),
+        destination.id()
+    );
+    let relay_dir = TempDir::new().context("failed to create temporary relay directory")?;
+    source.try_get(
+        &source_path,
+        relay_dir.utf8_path(),
+        SyncOptions::default().copy_contents().progress(),
+    )?;
+    destination.try_put(
+        relay_dir.utf8_path(),
+        &destination_path,
+        SyncOptions::default().copy_contents().progress(),
+    )?;
+
+    Ok(())
+}
+
+/// Fail unless the output of `run_id` on `destination` has all files of the output on `source`
+/// with the same sizes, so that the output on `source` can be removed
+pub fn verify_transferred_run_output(
+    source: &dyn Host,
+    destination: &dyn Host,
+    run_id: &RunID,
+) -> Result<()> {
+    let source_sizes = source
+        .file_sizes(&run_id.path(source.output_base_dir_path()))
+        .context(format!(
+            "failed to list the output of {run_id} on {}",
+            source.id()
+        ))?;
+    let destination_sizes = destination
+        .file_sizes(&run_id.path(destination.output_base_dir_path()))
+        .context(format!(
+            "failed to list the output of {run_id} on {}",
+            destination.id()
+        ))?;
+
+    let mismatched_paths = source_sizes
+        .iter()
+        .filter(|(path, size)| destination_sizes.get(*path) != Some(size))
+        .map(|(path, _)| path.as_str())
+        .collect::<Vec<_>>();
+    if !mismatched_paths.is_empty() {
+        bail!(
+            "found {} of {} files of {run_id} missing or with another size on {}, e.g. {}",
+            mismatched_paths.len(),
+            source_sizes.len(),
+            destination.id(),
+            mismatched_paths[0]
+        );
+    }
+
+    Ok(())
+}
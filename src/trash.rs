@@ -0,0 +1,150 @@
+use crate::host::{Host, RunID};
+use crate::pin::is_pinned;
+use anyhow::{bail, Context, Result};
+use camino::Utf8PathBuf as PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// Directory below the run output base directory of a host, which holds deleted runs in one
+/// directory per deletion named by its time
+const TRASH_DIR_NAME: &str = ".sparrow_trash";
+pub const DEFAULT_TRASH_RETENTION: &str = "30d";
+
+/// A deleted run in the trash of a host
+pub struct TrashedRun {
+    deletion_time: SystemTime,
+    run_id: RunID,
+    path: PathBuf,
+}
+
+impl std::fmt::Display for TrashedRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}  {}",
+            humantime::format_rfc3339_seconds(self.deletion_time),
+            self.run_id
+        )
+    }
+}
+
+fn trash_path(host: &dyn Host) -> PathBuf {
+    host.output_base_dir_path().join(TRASH_DIR_NAME)
+}
+
+/// Move the outputs of `run_ids` into the trash of `host`, refusing to delete pinned or
+/// running runs
+pub fn trash_runs(host: &dyn Host, run_ids: &[&RunID]) -> Result<()> {
+    // running runs can only be told for remote hosts
+    let running_runs = if host.is_local() {
+        Vec::new()
+    } else {
+        host.running_runs()
+    };
+    for run_id in run_ids {
+        if is_pinned(host, run_id)? {
+            bail!("refusing to delete {run_id}, since it is pinned, see `sparrow run-pin --unpin'");
+        }
+        // a recorded exit code means the run is done even if its session is kept open
+        if running_runs.contains(run_id) && host.recorded_exit_code(run_id).is_none() {
+            bail!("refusing to delete {run_id}, since it is still running");
+        }
+    }
+
+    let deletion_path =
+        trash_path(host).join(humantime::format_rfc3339_seconds(SystemTime::now()).to_string());
+    for run_id in run_ids {
+        let trashed_path = run_id.path(&deletion_path);
+        host.create_dir_all(
+            trashed_path
+                .parent()
+                .expect("expected trashed run path to have a parent"),
+        );
+        host.move_path(&run_id.path(host.output_base_dir_path()), &trashed_path)
+            .context(format!("failed to move {run_id} into the trash"))?;
+        println!("Moved {run_id} to {trashed_path}");
+    }
+
+    Ok(())
+}
+
+/// List the runs in the trash of `host`, oldest deletion first
+pub fn trashed_runs(host: &dyn Host) -> Result<Vec<TrashedRun>> {
+    let trash_path = trash_path(host);
+    if !host.exists(&trash_path)? {
+        return Ok(Vec::new());
+    }
+
+    let mut trashed_runs = Vec::new();
+    for deletion in host.list_dir(&trash_path)? {
+        let Ok(deletion_time) = humantime::parse_rfc3339(&deletion.name) else {
+            eprintln!("warning: ignoring {} in {trash_path}", deletion.name);
+            continue;
+        };
+        let deletion_path = trash_path.join(&deletion.name);
+        for group in host.list_dir(&deletion_path)? {
+            for name in host.list_dir(&deletion_path.join(&group.name))? {
+                let run_id = RunID::new(name.name, group.name.clone());
+                trashed_runs.push(TrashedRun {
+                    deletion_time,
+                    path: run_id.path(&deletion_path),
+                    run_id,
+                });
+            }
+        }
+    }
+
+    Ok(trashed_runs)
+}
+
+/// Move a trashed run back to its original place, unless a run of the same id exists by now
+pub fn restore_run(host: &dyn Host, trashed_run: &TrashedRun) -> Result<()> {
+    let run_output_path = trashed_run.run_id.path(host.output_base_dir_path());
+    if host.exists(&run_output_path)? {
+        bail!(
+            "refusing to restore {}, since {run_output_path} exists",
+            trashed_run.run_id
+        );
+    }
+
+    host.create_dir_all(
+        run_output_path
+            .parent()
+            .expect("expected run output path to have a parent"),
+    );
+    host.move_path(&trashed_run.path, &run_output_path)
+        .context(format!("failed to restore {}", trashed_run.run_id))?;
+    remove_empty_parents(host, trashed_run)
+}
+
+/// Permanently delete the trashed runs of `host` which were deleted longer than `retention` ago
+pub fn empty_trash(host: &dyn Host, retention: Duration) -> Result<()> {
+    let now = SystemTime::now();
+    for trashed_run in trashed_runs(host)? {
+        let age = now
+            .duration_since(trashed_run.deletion_time)
+            .unwrap_or(Duration::ZERO);
+        if age < retention {
+            continue;
+        }
+
+        host.remove_dir_all(&trashed_run.path)
+            .context(format!("failed to remove {}", trashed_run.path))?;
+        remove_empty_parents(host, &trashed_run)?;
+        println!("Removed {trashed_run}");
+    }
+
+    Ok(())
+}
+
+/// Remove the group and deletion directories of a trashed run once they became empty
+fn remove_empty_parents(host: &dyn Host, trashed_run: &TrashedRun) -> Result<()> {
+    for path in trashed_run.path.ancestors().skip(1).take(2) {
+        if !host.list_dir(path)?.is_empty() {
+            break;
+        }
+        host.remove_dir_all(path)
+            .context(format!("failed to remove {path}"))?;
+    }
+
+    Ok(())
+}
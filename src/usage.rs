@@ -0,0 +1,121 @@
+use crate::host::accounting::AccountedJob;
+use crate::host::{Host, RunID};
+use anyhow::{Context, Result};
+use camino::Utf8PathBuf as PathBuf;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+const MAX_RUN_DIR_FILE_SIZE: u64 = 4096;
+
+#[derive(Default)]
+struct GroupUsage {
+    runs: BTreeSet<String>,
+    job_count: usize,
+    cpu_hours: f64,
+    gpu_hours: f64,
+    /// Consumed energy in joules of the jobs for which it was gathered
+    energy: u64,
+}
+
+/// Resources used by the slurm jobs of sparrow runs, summed per run group
+pub struct UsageReport {
+    groups: BTreeMap<String, GroupUsage>,
+    unmatched_job_count: usize,
+}
+
+impl UsageReport {
+    /// Query the slurm accounting of `host` for jobs that started within `since` and attribute
+    /// them to runs by their working directory, which has to lie within the run output or the
+    /// recorded run directory of a run
+    pub fn collect(host: &dyn Host, since: Duration) -> Result<Self> {
+        let jobs = host
+            .accounted_jobs(since)
+            .context(format!("failed to query slurm accounting on {}", host.id()))?;
+
+        let run_paths = host
+            .runs()
+            .context(format!("failed to obtain runs from {}", host.id()))?
+            .into_iter()
+            .flat_map(|run_id| {
+                let output_path = run_id.path(host.output_base_dir_path());
+                // runs submitted by older versions of sparrow have no recorded run directory
+                let run_dir_path = host
+                    .read_file_head(
+                        &host.run_dir_file_destination_path(&run_id),
+                        MAX_RUN_DIR_FILE_SIZE,
+                    )
+                    .ok()
+                    .map(|content| PathBuf::from(String::from_utf8_lossy(&content).trim()));
+                std::iter::once((output_path, run_id.clone()))
+                    .chain(run_dir_path.map(|run_dir_path| (run_dir_path, run_id)))
+            })
+            .collect::<Vec<(PathBuf, RunID)>>();
+
+        let mut report = UsageReport {
+            groups: BTreeMap::new(),
+            unmatched_job_count: 0,
+        };
+        for job in jobs {
+            let Some((_, run_id)) = run_paths
+                .iter()
+                .find(|(path, _)| job.work_dir.starts_with(path))
+            else {
+                report.unmatched_job_count += 1;
+                continue;
+            };
+
+            report.add(run_id, &job);
+        }
+
+        Ok(report)
+    }
+
+    fn add(&mut self, run_id: &RunID, job: &AccountedJob) {
+        let hours = job.elapsed.as_secs_f64() / 3600.0;
+        let usage = self.groups.entry(run_id.group.clone()).or_default();
+        usage.runs.insert(run_id.name.clone());
+        usage.job_count += 1;
+        usage.cpu_hours += job.cpu_count as f64 * hours;
+        usage.gpu_hours += job.gpu_count as f64 * hours;
+        usage.energy += job.energy.unwrap_or(0);
+    }
+}
+
+impl std::fmt::Display for UsageReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let group_width = self
+            .groups
+            .keys()
+            .map(|group| group.len())
+            .chain(std::iter::once("group".len()))
+            .max()
+            .unwrap_or(0);
+
+        writeln!(
+            f,
+            "{:<group_width$}  {:>5}  {:>5}  {:>10}  {:>10}  {:>10}",
+            "group", "runs", "jobs", "CPU-hours", "GPU-hours", "energy/kWh"
+        )?;
+        for (group, usage) in &self.groups {
+            writeln!(
+                f,
+                "{group:<group_width$}  {:>5}  {:>5}  {:>10.1}  {:>10.1}  {:>10.1}",
+                usage.runs.len(),
+                usage.job_count,
+                usage.cpu_hours,
+                usage.gpu_hours,
+                usage.energy as f64 / 3.6e6,
+            )?;
+        }
+
+        if self.unmatched_job_count > 0 {
+            writeln!(
+                f,
+                "{} jobs could not be attributed to any run",
+                self.unmatched_job_count
+            )?;
+        }
+
+        Ok(())
+    }
+}
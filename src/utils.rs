@@ -1,6 +1,9 @@
+use crate::cfg::TmuxKeepOpen;
+use crate::exit::ExitStatus;
 use anyhow::{bail, Context, Result};
 use camino::Utf8Path as Path;
-use std::io::Write;
+use std::collections::HashSet;
+use std::io::{BufRead, IsTerminal, Write};
 use tempfile::{NamedTempFile, TempDir};
 use walkdir::DirEntry;
 
@@ -56,16 +59,39 @@ pub fn select_interactively<'d, D: std::fmt::Display>(
     options: &'d Vec<D>,
     prompt: &str,
 ) -> Result<&'d D> {
+    return Ok(run_interactive_selection(options, prompt, false)?
+        .into_iter()
+        .next()
+        .expect("expected interactive selection to yield at least one option"));
+}
+
+/// Select any number of `options` interactively with fzf, where the selections are returned in
+/// the order of `options` rather than in the order they were selected in
+pub fn select_interactively_multiple<'d, D: std::fmt::Display>(
+    options: &'d Vec<D>,
+    prompt: &str,
+) -> Result<Vec<&'d D>> {
+    run_interactive_selection(options, prompt, true)
+}
+
+fn run_interactive_selection<'d, D: std::fmt::Display>(
+    options: &'d Vec<D>,
+    prompt: &str,
+    multiple: bool,
+) -> Result<Vec<&'d D>> {
     let mut fzf_command = std::process::Command::new("fzf");
     fzf_command
         .arg("--prompt")
         .arg(prompt)
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped());
+    if multiple {
+        fzf_command.arg("--multi");
+    }
 
-    let mut child = fzf_command
-        .spawn()
-        .context(format!("failed to spawn interactive selection command `{fzf_command:?}`"))?;
+    let mut child = fzf_command.spawn().context(format!(
+        "failed to spawn interactive selection command `{fzf_command:?}`"
+    ))?;
 
     let options_input = options
         .iter()
@@ -78,33 +104,199 @@ pub fn select_interactively<'d, D: std::fmt::Display>(
         .as_mut()
         .expect("expected stdin of fzf to be piped before")
         .write_all(options_input.as_bytes())
-        .context(format!("failed to write to stdin of interactive selection `{fzf_command:?}`"))?;
+        .context(format!(
+            "failed to write to stdin of interactive selection `{fzf_command:?}`"
+        ))?;
 
-    let output = child
-        .wait_with_output()
-        .context(format!("failed to wait for output of interactive selection `{fzf_command:?}`"))?;
+    let output = child.wait_with_output().context(format!(
+        "failed to wait for output of interactive selection `{fzf_command:?}`"
+    ))?;
     if !output.status.success() {
-        bail!("interactive selection failed to exit successfully, most likely because nothing was selected");
+        return Err(anyhow::Error::new(ExitStatus::Aborted).context(
+            "interactive selection failed to exit successfully, most likely because nothing was \
+            selected",
+        ));
     }
 
     let output = String::from_utf8(output.stdout).context(format!(
         "found non-valid utf8 in output of `{fzf_command:?}` "
     ))?;
-    let output = output.trim();
 
-    return Ok(
-        options
-            .iter()
-            .find(|x| x.to_string() == output)
-            .expect("expected rofi output to be one of the options"),
+    let selected_lines = output.lines().map(str::trim).collect::<HashSet<_>>();
+    let selections = options
+        .iter()
+        .filter(|option| selected_lines.contains(option.to_string().as_str()))
+        .collect::<Vec<_>>();
+    if selections.len() < selected_lines.len() {
+        bail!("expected fzf output to be one of the options");
+    }
+
+    Ok(selections)
+}
+
+/// Ask `question` with a `[y/N]` prompt, which fails if stdin is no terminal, since
+/// non-interactive invocations have to pass `--yes'
+pub fn confirm(question: &str) -> Result<bool> {
+    let answer = ask(&format!("{question} [y/N]"))?;
+    Ok(matches!(answer.as_str(), "y" | "yes"))
+}
+
+/// Ask `question` and return the trimmed, lowercase answer, which fails if stdin is no terminal
+pub fn ask(question: &str) -> Result<String> {
+    if !std::io::stdin().is_terminal() {
+        bail!("cannot ask for confirmation without a terminal, pass --yes to skip it");
+    }
+
+    print!("{question} ");
+    std::io::stdout()
+        .flush()
+        .context("failed to flush stdout")?;
+    let mut answer = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("failed to read confirmation")?;
+
+    Ok(answer.trim().to_lowercase())
+}
+
+pub fn tmux_wrap(
+    cmd: &str,
+    session_name: &str,
+    attach: bool,
+    keep_open_on: TmuxKeepOpen,
+) -> String {
+    let detach_flag = if attach { "" } else { " -d" };
+    return format!(
+        "exec tmux new-session{detach_flag} -s {} {}",
+        shell_quote(session_name),
+        shell_quote(&keep_open_wrap(cmd, keep_open_on))
     );
 }
 
-pub fn tmux_wrap(cmd: &str, session_name: &str) -> String {
-    let cmd = escape_single_quotes(cmd);
-    return format!("exec tmux new-session -s {session_name} '{cmd}; bash'");
+/// Wrap `cmd` so that its session continues with a shell after it exits as `keep_open_on` says
+pub fn keep_open_wrap(cmd: &str, keep_open_on: TmuxKeepOpen) -> String {
+    match keep_open_on {
+        TmuxKeepOpen::Always => format!("{cmd}; bash"),
+        TmuxKeepOpen::Failure => format!("{cmd} || bash"),
+        TmuxKeepOpen::Never => cmd.to_owned(),
+    }
+}
+
+/// Show a desktop notification, which is only reported on failure since a missing notification
+/// daemon should not fail the command that just finished
+pub fn notify_desktop(summary: &str, body: &str) {
+    let result = notify_rust::Notification::new()
+        .appname("sparrow")
+        .summary(summary)
+        .body(body)
+        .show();
+    if let Err(err) = result {
+        eprintln!("warning: failed to show desktop notification: {err}");
+    }
+}
+
+/// Quote `arg` so that a POSIX shell reads it as a single word with its literal content, which
+/// leaves words without special characters untouched to keep commands readable
+pub fn shell_quote(arg: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "_-./:@,+%=".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_plain) {
+        return arg.to_owned();
+    }
+    return format!("'{}'", arg.replace('\'', "'\"'\"'"));
 }
 
-pub fn escape_single_quotes(cmd: &str) -> String {
-    return cmd.replace("'", "'\"'\"'");
+/// Format `size` in bytes with a binary unit, e.g. as `1.5 MiB`
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    if size < 1024 {
+        return format!("{size} B");
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next_unit;
+    }
+    format!("{value:.1} {unit}")
+}
+
+/// Date of `time` as `YYYY-MM-DD` in UTC, i.e. the date part of the RFC 3339 timestamps sparrow
+/// records elsewhere
+pub fn format_date(time: std::time::SystemTime) -> String {
+    let timestamp = humantime::format_rfc3339_seconds(time).to_string();
+    let (date, _) = timestamp
+        .split_once('T')
+        .expect("expected RFC 3339 timestamp to separate date and time by `T'");
+    date.to_owned()
+}
+
+/// Number of inserted, removed or substituted characters that turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut distances = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = distances[0];
+        distances[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let above = distances[j + 1];
+            distances[j + 1] = (diagonal + usize::from(a_char != *b_char))
+                .min(above + 1)
+                .min(distances[j] + 1);
+            diagonal = above;
+        }
+    }
+    distances[b.len()]
+}
+
+/// ` (did you mean `<candidate>'?)` for the candidate closest to `value`, if any is close enough
+/// to make `value` a likely typo of it, and an empty string otherwise
+pub fn suggest_closest<'c, I: IntoIterator<Item = &'c str>>(value: &str, candidates: I) -> String {
+    let max_distance = (value.chars().count() / 3).max(2);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(value, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| format!(" (did you mean `{candidate}'?)"))
+        .unwrap_or_default()
+}
+
+/// Whether `text` matches the shell-style glob `pattern`, where `*` matches any sequence of
+/// characters and `?` matches any single character
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    // position after the last `*` in the pattern and the text position it was tried at, to
+    // backtrack to when the rest of the pattern does not match
+    let mut backtrack = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
 }
@@ -0,0 +1,102 @@
+use crate::control;
+use crate::host::RunID;
+use camino::Utf8Path as Path;
+
+/// Version of the sparrow crate
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// `git describe` output of the sources sparrow was built from, which is unknown for builds
+/// outside of a git checkout
+pub const GIT_DESCRIBE: Option<&str> = option_env!("SPARROW_GIT_DESCRIBE");
+
+/// Layout of the metadata in `reproduce_info`, which is increased whenever recorded files are
+/// renamed or change their structure; runs without a recorded layout have layout 1
+///
+/// Additions that older runs lack, like `host.modules`, `payload.environment_lock` and `parent` in
+/// the run context or the timings file, keep the layout, since their readers treat them as absent.
+///
+/// * 2: the run context records `sparrow` and `control`
+pub const METADATA_LAYOUT: u64 = 2;
+
+/// Sparrow part of the template context, where `version` and `git_describe` are null in the
+/// recorded context of runs submitted before they were recorded
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct VersionContext {
+    version: &'static str,
+    git_describe: Option<&'static str>,
+    layout: u64,
+}
+
+/// Sparrow part of the template context, with the version of sparrow and the metadata layout
+pub fn template_context() -> VersionContext {
+    VersionContext {
+        version: VERSION,
+        git_describe: GIT_DESCRIBE,
+        layout: METADATA_LAYOUT,
+    }
+}
+
+/// Sparrow part of `run_meta.yaml`
+pub fn to_yaml() -> String {
+    let quote =
+        |value: &str| serde_json::to_string(value).expect("expected strings to be serializable");
+
+    let mut yaml = format!("sparrow:\n  version: {}\n", quote(VERSION));
+    if let Some(git_describe) = GIT_DESCRIBE {
+        yaml += &format!("  git_describe: {}\n", quote(git_describe));
+    }
+    yaml += &format!("  layout: {METADATA_LAYOUT}\n");
+
+    yaml
+}
+
+/// Warn if the recorded run context `context` of `run_id` has a metadata layout different from
+/// the current one and migrate it to the current layout if it is older
+pub fn migrate_run_context(context: &mut serde_json::Value, run_id: &RunID) {
+    let layout = context
+        .pointer("/sparrow/layout")
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(1);
+    if layout == METADATA_LAYOUT {
+        return;
+    }
+
+    let writer = match context
+        .pointer("/sparrow/version")
+        .and_then(|value| value.as_str())
+    {
+        Some(version) => format!("sparrow {version}"),
+        None => String::from("an older version of sparrow"),
+    };
+    if layout > METADATA_LAYOUT {
+        eprintln!(
+            "warning: {run_id} was submitted by {writer} with metadata layout {layout}, which \
+            is newer than layout {METADATA_LAYOUT} of sparrow {VERSION}, so its metadata might \
+            be misread"
+        );
+        return;
+    }
+
+    eprintln!(
+        "warning: {run_id} was submitted by {writer} with metadata layout {layout}, migrating \
+        it to layout {METADATA_LAYOUT}"
+    );
+    let Some(context) = context.as_object_mut() else {
+        return;
+    };
+    if layout < 2 {
+        if let Some(output_path) = context.get("output_path").and_then(|value| value.as_str()) {
+            let control = serde_json::to_value(control::template_context(Path::new(output_path)))
+                .expect("expected the control context to be serializable");
+            context.entry("control").or_insert(control);
+        }
+        context.insert(
+            String::from("sparrow"),
+            serde_json::json!({
+                "version": null,
+                "git_describe": null,
+                "layout": METADATA_LAYOUT,
+            }),
+        );
+    }
+}